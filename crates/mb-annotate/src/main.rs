@@ -19,6 +19,9 @@ struct Args {
     model: String,
     #[arg(long)]
     system_prompt: Option<String>,
+    /// Send `stream: false` and print each reply once it is complete.
+    #[arg(long)]
+    no_stream: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -49,6 +52,21 @@ struct StreamDelta {
     content: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    choices: Vec<CompletionChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionMessage {
+    content: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ErrorEnvelope {
     error: ErrorDetail,
@@ -194,6 +212,105 @@ async fn maybe_annotate_turn(
     }
 }
 
+/// Prints SSE content deltas as they arrive and returns the full reply.
+async fn read_streamed_reply(response: reqwest::Response) -> String {
+    let mut stream = response.bytes_stream();
+    let mut buffer = String::new();
+    let mut assistant_text = String::new();
+    let mut printed_prefix = false;
+    let mut done = false;
+
+    while let Some(item) = stream.next().await {
+        let bytes = match item {
+            Ok(b) => b,
+            Err(err) => {
+                eprintln!("{}", format!("\nStream error: {err}").red());
+                break;
+            }
+        };
+
+        buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+        while let Some(pos) = buffer.find('\n') {
+            let mut line = buffer[..pos].to_owned();
+            buffer.drain(..=pos);
+
+            if line.ends_with('\r') {
+                line.pop();
+            }
+            if !line.starts_with("data: ") {
+                continue;
+            }
+
+            let data = &line[6..];
+            if data == "[DONE]" {
+                done = true;
+                break;
+            }
+            if data.is_empty() {
+                continue;
+            }
+
+            if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) {
+                for choice in chunk.choices {
+                    if let Some(content) = choice.delta.content {
+                        if !printed_prefix {
+                            print!("{}", "Assistant: ".bright_green());
+                            printed_prefix = true;
+                        }
+                        print!("{}", content.bright_green());
+                        let _ = io::stdout().flush();
+                        assistant_text.push_str(&content);
+                    }
+                }
+            }
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    if printed_prefix {
+        println!();
+    } else {
+        println!("{}", "Assistant: <empty response>".bright_green());
+    }
+    assistant_text
+}
+
+/// Prints the `choices[0].message.content` of a non-streaming response and
+/// returns it.
+async fn read_full_reply(response: reqwest::Response) -> String {
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(err) => {
+            eprintln!("{}", format!("Failed to read response: {err}").red());
+            return String::new();
+        }
+    };
+
+    let content = match serde_json::from_str::<CompletionResponse>(&body) {
+        Ok(parsed) => parsed
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .unwrap_or_default(),
+        Err(err) => {
+            eprintln!("{}", format!("Malformed response: {err}").red());
+            return String::new();
+        }
+    };
+
+    if content.is_empty() {
+        println!("{}", "Assistant: <empty response>".bright_green());
+    } else {
+        println!("{}{}", "Assistant: ".bright_green(), content.bright_green());
+    }
+    content
+}
+
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
@@ -251,7 +368,7 @@ async fn main() {
         let request = ChatCompletionRequest {
             model: args.model.clone(),
             messages: history.clone(),
-            stream: true,
+            stream: !args.no_stream,
         };
 
         let client_turn_id = Uuid::new_v4().to_string();
@@ -295,68 +412,11 @@ async fn main() {
             .map(ToOwned::to_owned)
             .unwrap_or(client_turn_id);
 
-        let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
-        let mut assistant_text = String::new();
-        let mut printed_prefix = false;
-        let mut done = false;
-
-        while let Some(item) = stream.next().await {
-            let bytes = match item {
-                Ok(b) => b,
-                Err(err) => {
-                    eprintln!("{}", format!("\nStream error: {err}").red());
-                    break;
-                }
-            };
-
-            buffer.push_str(&String::from_utf8_lossy(&bytes));
-
-            while let Some(pos) = buffer.find('\n') {
-                let mut line = buffer[..pos].to_owned();
-                buffer.drain(..=pos);
-
-                if line.ends_with('\r') {
-                    line.pop();
-                }
-                if !line.starts_with("data: ") {
-                    continue;
-                }
-
-                let data = &line[6..];
-                if data == "[DONE]" {
-                    done = true;
-                    break;
-                }
-                if data.is_empty() {
-                    continue;
-                }
-
-                if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) {
-                    for choice in chunk.choices {
-                        if let Some(content) = choice.delta.content {
-                            if !printed_prefix {
-                                print!("{}", "Assistant: ".bright_green());
-                                printed_prefix = true;
-                            }
-                            print!("{}", content.bright_green());
-                            let _ = io::stdout().flush();
-                            assistant_text.push_str(&content);
-                        }
-                    }
-                }
-            }
-
-            if done {
-                break;
-            }
-        }
-
-        if printed_prefix {
-            println!();
+        let assistant_text = if args.no_stream {
+            read_full_reply(response).await
         } else {
-            println!("{}", "Assistant: <empty response>".bright_green());
-        }
+            read_streamed_reply(response).await
+        };
 
         if !assistant_text.is_empty() {
             history.push(ChatMessage {
@@ -1,4 +1,7 @@
-use crate::core::{ApiKey, AuthError, ClientId, ModelId};
+use std::collections::HashSet;
+use std::sync::{PoisonError, RwLock};
+
+use crate::core::{ApiKey, AuthError, ClientId, ModelId, ParamPolicy};
 
 // ---------------------------------------------------------------------------
 // Client permission types
@@ -8,6 +11,8 @@ use crate::core::{ApiKey, AuthError, ClientId, ModelId};
 pub enum AllowedModels {
     All,
     Specific(Vec<ModelId>),
+    /// Every model except the listed ones.
+    AllExcept(Vec<ModelId>),
 }
 
 #[derive(Clone, Debug)]
@@ -27,6 +32,11 @@ pub struct ClientInfo {
     pub allowed_models: AllowedModels,
     pub rate_limit: RateLimit,
     pub quota: QuotaConfig,
+    pub param_policy: ParamPolicy,
+    /// Higher values take freed backend capacity first when requests queue.
+    pub priority: u8,
+    /// Most tool definitions one request may carry; `None` is unlimited.
+    pub max_tools: Option<u32>,
 }
 
 // ---------------------------------------------------------------------------
@@ -39,13 +49,20 @@ pub struct ClientInfo {
 /// intentionally does not implement `Hash` (constant-time `PartialEq` only).
 /// Linear scan is acceptable: the number of clients is small, and iterating
 /// all entries prevents early-exit timing leaks across keys.
+///
+/// Keys can be revoked at runtime; a revoked client's key stops
+/// authenticating immediately and stays revoked until restart.
 pub struct AuthService {
     clients: Vec<(ApiKey, ClientInfo)>,
+    revoked: RwLock<HashSet<ClientId>>,
 }
 
 impl AuthService {
     pub fn new(clients: Vec<(ApiKey, ClientInfo)>) -> Self {
-        Self { clients }
+        Self {
+            clients,
+            revoked: RwLock::new(HashSet::new()),
+        }
     }
 
     /// Authenticate an API key, returning the associated `ClientInfo`.
@@ -60,7 +77,40 @@ impl AuthService {
                 matched = Some(info);
             }
         }
-        matched.ok_or(AuthError::InvalidApiKey)
+        matched
+            .filter(|info| !self.is_revoked(&info.id))
+            .ok_or(AuthError::InvalidApiKey)
+    }
+
+    /// Revoke the key of client `id`. Returns `false` when no such client is
+    /// configured; revoking twice is harmless.
+    pub fn revoke(&self, id: &ClientId) -> bool {
+        if self.client(id).is_none() {
+            return false;
+        }
+        self.revoked
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id.clone());
+        true
+    }
+
+    /// Whether the key of client `id` has been revoked.
+    pub fn is_revoked(&self, id: &ClientId) -> bool {
+        self.revoked
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(id)
+    }
+
+    /// All configured clients, revoked ones included, in config order.
+    pub fn clients(&self) -> impl Iterator<Item = &ClientInfo> {
+        self.clients.iter().map(|(_, info)| info)
+    }
+
+    /// Look up a configured client by id.
+    pub fn client(&self, id: &ClientId) -> Option<&ClientInfo> {
+        self.clients().find(|info| &info.id == id)
     }
 
     /// Check whether `client` is permitted to access `model`.
@@ -77,6 +127,16 @@ impl AuthService {
                     })
                 }
             }
+            AllowedModels::AllExcept(denied) => {
+                if denied.contains(model) {
+                    Err(AuthError::ModelNotPermitted {
+                        model: model.clone(),
+                        client: client.id.clone(),
+                    })
+                } else {
+                    Ok(())
+                }
+            }
         }
     }
 }
@@ -100,6 +160,9 @@ mod tests {
             quota: QuotaConfig {
                 monthly_token_limit: None,
             },
+            param_policy: ParamPolicy::default(),
+            priority: 0,
+            max_tools: None,
         }
     }
 
@@ -125,6 +188,39 @@ mod tests {
         assert!(matches!(result.unwrap_err(), AuthError::InvalidApiKey));
     }
 
+    #[test]
+    fn test_revoked_key_rejected() {
+        let alpha = ApiKey::new("mb-sk-alpha000000000000000000000000");
+        let beta = ApiKey::new("mb-sk-beta0000000000000000000000000");
+        let svc = AuthService::new(vec![
+            (alpha.clone(), make_client("team-alpha", AllowedModels::All)),
+            (beta.clone(), make_client("team-beta", AllowedModels::All)),
+        ]);
+
+        assert!(svc.revoke(&ClientId::new("team-alpha")));
+        assert!(svc.revoke(&ClientId::new("team-alpha")));
+        assert!(!svc.revoke(&ClientId::new("team-gamma")));
+
+        assert!(matches!(
+            svc.validate(&alpha).unwrap_err(),
+            AuthError::InvalidApiKey
+        ));
+        assert_eq!(svc.validate(&beta).unwrap().id.as_str(), "team-beta");
+        assert!(svc.is_revoked(&ClientId::new("team-alpha")));
+        assert!(!svc.is_revoked(&ClientId::new("team-beta")));
+    }
+
+    #[test]
+    fn test_client_lookup_by_id() {
+        let svc = AuthService::new(vec![(
+            ApiKey::new("mb-sk-valid000000000000000000000000"),
+            make_client("team-alpha", AllowedModels::All),
+        )]);
+
+        assert!(svc.client(&ClientId::new("team-alpha")).is_some());
+        assert!(svc.client(&ClientId::new("team-beta")).is_none());
+    }
+
     #[test]
     fn test_model_permitted_specific() {
         let client = make_client(
@@ -159,4 +255,29 @@ mod tests {
             AuthService::check_model_permission(&client, &ModelId::new("any-model-at-all"));
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_all_except_denies_listed_model() {
+        let client = make_client(
+            "team-alpha",
+            AllowedModels::AllExcept(vec![ModelId::new("gpt-4")]),
+        );
+
+        let result = AuthService::check_model_permission(&client, &ModelId::new("gpt-4"));
+        assert!(matches!(
+            result.unwrap_err(),
+            AuthError::ModelNotPermitted { .. }
+        ));
+    }
+
+    #[test]
+    fn test_all_except_allows_unlisted_model() {
+        let client = make_client(
+            "team-alpha",
+            AllowedModels::AllExcept(vec![ModelId::new("gpt-4")]),
+        );
+
+        let result = AuthService::check_model_permission(&client, &ModelId::new("llama3-70b"));
+        assert!(result.is_ok());
+    }
 }
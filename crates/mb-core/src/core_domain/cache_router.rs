@@ -1,5 +1,8 @@
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
 
 use crate::core::{BackendId, ContentPart, Message, MessageContent, ModelId, PrefixHash, Role};
 
@@ -30,16 +33,11 @@ impl CacheAffinityMap {
     }
 
     pub fn get(&mut self, model: &ModelId, prefix: PrefixHash) -> Option<&BackendId> {
-        let key = (model.clone(), prefix);
-        if self.entries.contains_key(&key) {
-            self.counter += 1;
-            let entry = self.entries.get_mut(&key).expect("checked above");
-            entry.last_used = self.counter;
-            entry.hit_count += 1;
-            Some(&entry.backend)
-        } else {
-            None
-        }
+        let entry = self.entries.get_mut(&(model.clone(), prefix))?;
+        self.counter += 1;
+        entry.last_used = self.counter;
+        entry.hit_count += 1;
+        Some(&entry.backend)
     }
 
     pub fn record(&mut self, model: &ModelId, prefix: PrefixHash, backend: &BackendId) {
@@ -79,12 +77,204 @@ impl CacheAffinityMap {
     }
 }
 
+// ---------------------------------------------------------------------------
+// ShardedAffinityMap — CacheAffinityMap split across independently locked shards
+// ---------------------------------------------------------------------------
+
+pub const DEFAULT_AFFINITY_SHARDS: usize = 16;
+
+/// Concurrency-safe affinity map for the request hot path.
+///
+/// Entries are distributed by a hash of `(ModelId, PrefixHash)` so requests
+/// touching different keys lock different shards. Each shard is its own
+/// `CacheAffinityMap` with `max_entries / shards` capacity (rounded up), so
+/// LRU ordering and eviction hold per shard rather than globally.
+pub struct ShardedAffinityMap {
+    shards: Vec<Mutex<CacheAffinityMap>>,
+    /// Lookups and records dropped by the `try_` methods on a busy shard.
+    skipped: AtomicU64,
+}
+
+impl ShardedAffinityMap {
+    pub fn new(max_entries: usize) -> Self {
+        Self::with_shard_count(max_entries, DEFAULT_AFFINITY_SHARDS)
+    }
+
+    pub fn with_shard_count(max_entries: usize, shard_count: usize) -> Self {
+        let shard_count = shard_count.max(1);
+        let per_shard = max_entries.div_ceil(shard_count).max(1);
+        Self {
+            shards: (0..shard_count)
+                .map(|_| Mutex::new(CacheAffinityMap::new(per_shard)))
+                .collect(),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, model: &ModelId, prefix: PrefixHash) -> Option<BackendId> {
+        self.lock_shard(self.shard_index(model, prefix))
+            .get(model, prefix)
+            .cloned()
+    }
+
+    pub fn record(&self, model: &ModelId, prefix: PrefixHash, backend: &BackendId) {
+        self.lock_shard(self.shard_index(model, prefix))
+            .record(model, prefix, backend);
+    }
+
+    /// Like [`get`](Self::get), but answers `None` instead of waiting when
+    /// the shard is locked elsewhere, counting the skip.
+    pub fn try_get(&self, model: &ModelId, prefix: PrefixHash) -> Option<BackendId> {
+        self.try_lock_shard(self.shard_index(model, prefix))?
+            .get(model, prefix)
+            .cloned()
+    }
+
+    /// Like [`record`](Self::record), but drops the entry instead of waiting
+    /// when the shard is locked elsewhere, counting the skip.
+    pub fn try_record(&self, model: &ModelId, prefix: PrefixHash, backend: &BackendId) {
+        if let Some(mut shard) = self.try_lock_shard(self.shard_index(model, prefix)) {
+            shard.record(model, prefix, backend);
+        }
+    }
+
+    /// Operations the `try_` methods skipped because a shard was busy.
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Locks the shard `(model, prefix)` belongs to until the guard drops.
+    /// Other callers on that shard wait meanwhile, or skip via the `try_`
+    /// methods.
+    pub fn lock_shard_of(
+        &self,
+        model: &ModelId,
+        prefix: PrefixHash,
+    ) -> MutexGuard<'_, CacheAffinityMap> {
+        self.lock_shard(self.shard_index(model, prefix))
+    }
+
+    pub fn evict_backend(&self, backend: &BackendId) {
+        for index in 0..self.shards.len() {
+            self.lock_shard(index).evict_backend(backend);
+        }
+    }
+
+    fn shard_index(&self, model: &ModelId, prefix: PrefixHash) -> usize {
+        let mut hasher = DefaultHasher::new();
+        model.hash(&mut hasher);
+        prefix.hash(&mut hasher);
+        // Truncation is fine: only the low bits pick the shard.
+        (hasher.finish() as usize) % self.shards.len()
+    }
+
+    /// A poisoned shard still holds a consistent map (every mutation is
+    /// a single HashMap operation), so recover it instead of panicking.
+    fn lock_shard(&self, index: usize) -> MutexGuard<'_, CacheAffinityMap> {
+        self.shards[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn try_lock_shard(&self, index: usize) -> Option<MutexGuard<'_, CacheAffinityMap>> {
+        match self.shards[index].try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PrefixDepthTracker — per-model shared-prefix diagnostics for prefix_depth
+// ---------------------------------------------------------------------------
+
+/// Number of recent shared-prefix samples kept per model.
+const PREFIX_SAMPLE_WINDOW: usize = 64;
+
+#[derive(Default)]
+struct ModelPrefixHistory {
+    last_hashes: Vec<u64>,
+    shared_lengths: VecDeque<usize>,
+}
+
+/// Tracks how many leading prefix messages consecutive requests to the same
+/// model have in common, to suggest a `prefix_depth`.
+///
+/// Only system/user messages count, matching [`compute_prefix_hash`], so a
+/// suggested depth can be used as `prefix_depth` directly.
+#[derive(Default)]
+pub struct PrefixDepthTracker {
+    models: HashMap<ModelId, ModelPrefixHistory>,
+}
+
+impl PrefixDepthTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compares `messages` against the previous request for `model` and
+    /// records the shared prefix length.
+    pub fn observe(&mut self, model: &ModelId, messages: &[Message]) {
+        let hashes: Vec<u64> = messages
+            .iter()
+            .filter(|msg| matches!(msg.role, Role::System | Role::User))
+            .map(|msg| {
+                let mut hasher = DefaultHasher::new();
+                hash_message_content(&msg.content, &mut hasher);
+                hasher.finish()
+            })
+            .collect();
+
+        let history = self.models.entry(model.clone()).or_default();
+        if !history.last_hashes.is_empty() {
+            let shared = history
+                .last_hashes
+                .iter()
+                .zip(&hashes)
+                .take_while(|(prev, next)| prev == next)
+                .count();
+            if history.shared_lengths.len() == PREFIX_SAMPLE_WINDOW {
+                history.shared_lengths.pop_front();
+            }
+            history.shared_lengths.push_back(shared);
+        }
+        history.last_hashes = hashes;
+    }
+
+    /// Median shared-prefix length over the recent window, or `None` until
+    /// at least two requests for `model` have been observed.
+    pub fn suggested_depth(&self, model: &ModelId) -> Option<usize> {
+        let history = self.models.get(model)?;
+        if history.shared_lengths.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<usize> = history.shared_lengths.iter().copied().collect();
+        sorted.sort_unstable();
+        Some(sorted[(sorted.len() - 1) / 2])
+    }
+
+    /// Number of shared-prefix samples currently held for `model`.
+    pub fn sample_count(&self, model: &ModelId) -> usize {
+        self.models
+            .get(model)
+            .map_or(0, |history| history.shared_lengths.len())
+    }
+
+    pub fn models(&self) -> impl Iterator<Item = &ModelId> {
+        self.models.keys()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Prefix hash computation
 // ---------------------------------------------------------------------------
 
 pub fn compute_prefix_hash(messages: &[Message], prefix_depth: usize) -> PrefixHash {
-    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut hasher = DefaultHasher::new();
     let mut count = 0;
 
     for msg in messages {
@@ -263,4 +453,244 @@ mod tests {
 
         assert_eq!(hash_text, hash_mixed);
     }
+
+    #[test]
+    fn test_prefix_hash_skips_audio() {
+        let text_messages = vec![msg_parts(
+            Role::User,
+            vec![ContentPart::Text {
+                text: "Transcribe this.".to_owned(),
+            }],
+        )];
+        let audio_messages = |data: &str| {
+            vec![msg_parts(
+                Role::User,
+                vec![
+                    ContentPart::Text {
+                        text: "Transcribe this.".to_owned(),
+                    },
+                    ContentPart::InputAudio {
+                        data: data.to_owned(),
+                        format: "wav".to_owned(),
+                    },
+                ],
+            )]
+        };
+
+        let hash_text = compute_prefix_hash(&text_messages, 1);
+        assert_eq!(
+            hash_text,
+            compute_prefix_hash(&audio_messages("UklGRg=="), 1)
+        );
+        assert_eq!(
+            hash_text,
+            compute_prefix_hash(&audio_messages("SUQzBA=="), 1)
+        );
+    }
+
+    #[test]
+    fn test_sharded_map_get_and_record() {
+        let map = ShardedAffinityMap::new(64);
+        let model = ModelId::new("llama3-70b");
+        let backend = BackendId::new("gpu-1");
+
+        assert_eq!(map.get(&model, PrefixHash::new(7)), None);
+        map.record(&model, PrefixHash::new(7), &backend);
+        assert_eq!(map.get(&model, PrefixHash::new(7)), Some(backend));
+    }
+
+    #[test]
+    fn test_sharded_map_preserves_lru_per_shard() {
+        let map = ShardedAffinityMap::with_shard_count(8, 4);
+        let model = ModelId::new("llama3-70b");
+
+        // Collect three prefixes that land in the same shard (capacity 2).
+        let target = map.shard_index(&model, PrefixHash::new(0));
+        let same_shard: Vec<PrefixHash> = (0..)
+            .map(PrefixHash::new)
+            .filter(|p| map.shard_index(&model, *p) == target)
+            .take(3)
+            .collect();
+
+        map.record(&model, same_shard[0], &BackendId::new("gpu-1"));
+        map.record(&model, same_shard[1], &BackendId::new("gpu-2"));
+        // Touch the oldest entry so the second one becomes LRU.
+        assert!(map.get(&model, same_shard[0]).is_some());
+        map.record(&model, same_shard[2], &BackendId::new("gpu-3"));
+
+        assert_eq!(
+            map.get(&model, same_shard[0]),
+            Some(BackendId::new("gpu-1"))
+        );
+        assert_eq!(map.get(&model, same_shard[1]), None);
+        assert_eq!(
+            map.get(&model, same_shard[2]),
+            Some(BackendId::new("gpu-3"))
+        );
+    }
+
+    #[test]
+    fn test_sharded_map_evict_backend_across_shards() {
+        let map = ShardedAffinityMap::with_shard_count(256, 8);
+        let model = ModelId::new("llama3-70b");
+        let doomed = BackendId::new("gpu-1");
+        let survivor = BackendId::new("gpu-2");
+
+        for i in 0..32 {
+            let backend = if i % 2 == 0 { &doomed } else { &survivor };
+            map.record(&model, PrefixHash::new(i), backend);
+        }
+        map.evict_backend(&doomed);
+
+        for i in 0..32 {
+            let expected = (i % 2 == 1).then(|| survivor.clone());
+            assert_eq!(map.get(&model, PrefixHash::new(i)), expected);
+        }
+    }
+
+    #[test]
+    fn test_sharded_map_other_shards_not_blocked() {
+        let map = ShardedAffinityMap::with_shard_count(64, 16);
+        let busy_model = ModelId::new("llama3-70b");
+        let busy_index = map.shard_index(&busy_model, PrefixHash::new(1));
+        let (other_model, other_prefix) = (0..)
+            .map(|i| (ModelId::new(format!("model-{i}")), PrefixHash::new(1)))
+            .find(|(m, p)| map.shard_index(m, *p) != busy_index)
+            .expect("some key maps to another shard");
+
+        // Hold one shard's lock for the whole scope; operations on a
+        // different shard must still complete from another thread.
+        let _held = map.lock_shard(busy_index);
+        let recorded = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    map.record(&other_model, other_prefix, &BackendId::new("gpu-2"));
+                    map.get(&other_model, other_prefix)
+                })
+                .join()
+                .expect("worker thread")
+        });
+        assert_eq!(recorded, Some(BackendId::new("gpu-2")));
+    }
+
+    #[test]
+    fn test_sharded_map_try_ops_skip_busy_shard() {
+        let map = ShardedAffinityMap::with_shard_count(64, 4);
+        let model = ModelId::new("llama3-70b");
+        let prefix = PrefixHash::new(9);
+        map.record(&model, prefix, &BackendId::new("gpu-1"));
+
+        let held = map.lock_shard_of(&model, prefix);
+        let found = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    map.try_record(&model, prefix, &BackendId::new("gpu-2"));
+                    map.try_get(&model, prefix)
+                })
+                .join()
+                .expect("worker thread")
+        });
+        drop(held);
+
+        assert_eq!(found, None);
+        assert_eq!(map.skipped(), 2);
+        // The skipped record left the existing entry alone.
+        assert_eq!(map.try_get(&model, prefix), Some(BackendId::new("gpu-1")));
+        assert_eq!(map.skipped(), 2);
+    }
+
+    #[test]
+    fn test_sharded_map_concurrent_models() {
+        let map = ShardedAffinityMap::with_shard_count(4096, 16);
+
+        std::thread::scope(|scope| {
+            for t in 0..8 {
+                let map = &map;
+                scope.spawn(move || {
+                    let model = ModelId::new(format!("model-{t}"));
+                    let backend = BackendId::new(format!("gpu-{t}"));
+                    for i in 0..2_000 {
+                        map.record(&model, PrefixHash::new(i % 256), &backend);
+                        assert_eq!(
+                            map.get(&model, PrefixHash::new(i % 256)),
+                            Some(backend.clone())
+                        );
+                    }
+                });
+            }
+        });
+
+        for t in 0..8 {
+            let model = ModelId::new(format!("model-{t}"));
+            assert_eq!(
+                map.get(&model, PrefixHash::new(0)),
+                Some(BackendId::new(format!("gpu-{t}")))
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefix_tracker_needs_two_requests() {
+        let mut tracker = PrefixDepthTracker::new();
+        let model = ModelId::new("llama3-70b");
+
+        assert_eq!(tracker.suggested_depth(&model), None);
+        tracker.observe(&model, &[msg(Role::User, "hi")]);
+        assert_eq!(tracker.suggested_depth(&model), None);
+        assert_eq!(tracker.sample_count(&model), 0);
+    }
+
+    #[test]
+    fn test_prefix_tracker_suggests_shared_system_prompt() {
+        let mut tracker = PrefixDepthTracker::new();
+        let model = ModelId::new("llama3-70b");
+
+        for question in ["a", "b", "c", "d", "e"] {
+            tracker.observe(
+                &model,
+                &[
+                    msg(Role::System, "You are a helpful assistant."),
+                    msg(Role::User, question),
+                ],
+            );
+        }
+
+        assert_eq!(tracker.suggested_depth(&model), Some(1));
+        assert_eq!(tracker.sample_count(&model), 4);
+    }
+
+    #[test]
+    fn test_prefix_tracker_reflects_growing_conversation() {
+        let mut tracker = PrefixDepthTracker::new();
+        let model = ModelId::new("llama3-70b");
+        let mut history = vec![
+            msg(Role::System, "You are a helpful assistant."),
+            msg(Role::User, "First question"),
+        ];
+
+        tracker.observe(&model, &history);
+        for turn in 0..3 {
+            history.push(msg(Role::Assistant, "answer"));
+            history.push(msg(Role::User, &format!("follow-up {turn}")));
+            tracker.observe(&model, &history);
+        }
+
+        // Shared lengths are 2, 3, 4 system/user messages; median is 3.
+        assert_eq!(tracker.suggested_depth(&model), Some(3));
+    }
+
+    #[test]
+    fn test_prefix_tracker_is_per_model() {
+        let mut tracker = PrefixDepthTracker::new();
+        let shared = ModelId::new("llama3-70b");
+        let unrelated = ModelId::new("mistral-7b");
+
+        tracker.observe(&shared, &[msg(Role::System, "sys"), msg(Role::User, "a")]);
+        tracker.observe(&unrelated, &[msg(Role::User, "x")]);
+        tracker.observe(&shared, &[msg(Role::System, "sys"), msg(Role::User, "b")]);
+        tracker.observe(&unrelated, &[msg(Role::User, "y")]);
+
+        assert_eq!(tracker.suggested_depth(&shared), Some(1));
+        assert_eq!(tracker.suggested_depth(&unrelated), Some(0));
+    }
 }
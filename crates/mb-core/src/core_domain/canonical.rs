@@ -1,6 +1,8 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-use crate::core::{ClientId, ModelId, PrefixHash, RequestId};
+use crate::core::{BackendSpec, ClientId, ModelId, PrefixHash, RequestId};
 
 // ---------------------------------------------------------------------------
 // Message types
@@ -34,6 +36,11 @@ pub enum ContentPart {
         #[serde(skip_serializing_if = "Option::is_none")]
         detail: Option<ImageDetail>,
     },
+    /// Base64-encoded audio clip; `format` is e.g. `wav` or `mp3`.
+    InputAudio {
+        data: String,
+        format: String,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -73,6 +80,12 @@ pub struct GenerationParams {
     pub presence_penalty: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<u64>,
+    /// Sample only from the `top_k` most likely tokens (open-weight backends).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    /// Drop tokens below `min_p` times the top token's probability.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f64>,
 }
 
 // ---------------------------------------------------------------------------
@@ -100,6 +113,21 @@ pub enum ToolChoice {
 // Request types
 // ---------------------------------------------------------------------------
 
+/// Output shape requested through `response_format`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    /// JSON matching `schema`; `name` and `strict` are passed to the backend.
+    JsonSchema {
+        name: String,
+        schema: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RequestMetadata {
     pub request_id: RequestId,
@@ -107,6 +135,26 @@ pub struct RequestMetadata {
     pub estimated_input_tokens: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prefix_hash: Option<PrefixHash>,
+    /// Client app name from an OpenRouter-style `X-Title` header, when the
+    /// gateway captures attribution headers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_title: Option<String>,
+    /// Client app URL from an `HTTP-Referer` header, captured alongside
+    /// `app_title`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_referer: Option<String>,
+    /// The client's OpenAI-style `metadata` map, kept for the gateway's own
+    /// records and never forwarded to backends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<BTreeMap<String, String>>,
+    /// The client sent `store: true`, asking for this exchange to be kept
+    /// regardless of feedback sampling.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub store: bool,
+    /// Backend spec named by a `provider/` model prefix, preferred when
+    /// routing (`routing.provider_prefix`).
+    #[serde(skip)]
+    pub provider_hint: Option<BackendSpec>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -118,6 +166,27 @@ pub struct CanonicalRequest {
     pub tools: Option<Vec<ToolDefinition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<ToolChoice>,
+    /// Whether the model may call several tools in one turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    /// Ask the backend for per-token log probabilities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// Number of alternatives to report per token alongside `logprobs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Legacy completions param: prepend the prompt to the generated text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub echo: Option<bool>,
+    /// Legacy completions param: text that follows the completion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    /// Number of choices to generate. Backends are always asked for one;
+    /// more are served by fanning out when `routing.fanout` allows it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
     pub stream: bool,
     pub metadata: RequestMetadata,
 }
@@ -147,6 +216,9 @@ pub struct Choice {
     pub index: u32,
     pub message: Message,
     pub finish_reason: FinishReason,
+    /// The backend's `logprobs` object, passed through unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -176,9 +248,23 @@ pub enum DeltaContent {
 pub struct StreamChoice {
     pub index: u32,
     pub delta: DeltaContent,
+    /// The backend's `logprobs` object for this delta, passed through
+    /// unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<serde_json::Value>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CanonicalStreamChunk {
     pub choices: Vec<StreamChoice>,
 }
+
+/// Per-stream values stamped on every chunk sent to the client, so chunks
+/// share one completion id and name the requested model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamContext {
+    pub id: String,
+    pub model: ModelId,
+    /// Unix seconds at which the stream started.
+    pub created: u64,
+}
@@ -0,0 +1,199 @@
+use std::fmt;
+
+use crate::core::{CanonicalRequest, ContentPart, MessageContent, ResponseFormat};
+
+// ---------------------------------------------------------------------------
+// ModelCapabilities — which request features a model can handle
+// ---------------------------------------------------------------------------
+
+/// A request feature that not every model supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// Tool definitions, `tool_choice` or `tool` role messages.
+    Tools,
+    /// Image content parts.
+    Vision,
+    /// A `json_object` or `json_schema` response format.
+    JsonMode,
+}
+
+impl Capability {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Tools => "tools",
+            Self::Vision => "vision",
+            Self::JsonMode => "json_mode",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The features a model supports. Models without an entry are assumed to
+/// support everything, so the default is fully capable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    pub tools: bool,
+    pub vision: bool,
+    pub json_mode: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            vision: true,
+            json_mode: true,
+        }
+    }
+}
+
+impl ModelCapabilities {
+    /// The first feature `req` uses that this model lacks, if any.
+    pub fn first_missing(&self, req: &CanonicalRequest) -> Option<Capability> {
+        if !self.tools && super::tool_support::uses_tools(req) {
+            return Some(Capability::Tools);
+        }
+        if !self.vision && uses_images(req) {
+            return Some(Capability::Vision);
+        }
+        if !self.json_mode && uses_json_mode(req) {
+            return Some(Capability::JsonMode);
+        }
+        None
+    }
+}
+
+fn uses_images(req: &CanonicalRequest) -> bool {
+    req.messages.iter().any(|m| match &m.content {
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .any(|part| matches!(part, ContentPart::ImageUrl { .. })),
+        MessageContent::Text(_) => false,
+    })
+}
+
+fn uses_json_mode(req: &CanonicalRequest) -> bool {
+    matches!(
+        req.response_format,
+        Some(ResponseFormat::JsonObject | ResponseFormat::JsonSchema { .. })
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{
+        ClientId, GenerationParams, Message, ModelId, RequestId, RequestMetadata, Role,
+        ToolDefinition,
+    };
+
+    use super::*;
+
+    fn request(content: MessageContent) -> CanonicalRequest {
+        CanonicalRequest {
+            model: ModelId::new("llama3-8b"),
+            messages: vec![Message {
+                role: Role::User,
+                content,
+                name: None,
+                tool_call_id: None,
+            }],
+            params: GenerationParams::default(),
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            logprobs: None,
+            top_logprobs: None,
+            response_format: None,
+            echo: None,
+            suffix: None,
+            n: None,
+            stream: false,
+            metadata: RequestMetadata {
+                request_id: RequestId::new("req-test"),
+                client_id: ClientId::new("client-a"),
+                estimated_input_tokens: 0,
+                prefix_hash: None,
+                app_title: None,
+                app_referer: None,
+                client_metadata: None,
+                store: false,
+                provider_hint: None,
+            },
+        }
+    }
+
+    fn text_request() -> CanonicalRequest {
+        request(MessageContent::Text("hi".to_owned()))
+    }
+
+    #[test]
+    fn test_default_supports_everything() {
+        let mut req = request(MessageContent::Parts(vec![ContentPart::ImageUrl {
+            url: "https://example.com/cat.png".to_owned(),
+            detail: None,
+        }]));
+        req.response_format = Some(ResponseFormat::JsonObject);
+        assert_eq!(ModelCapabilities::default().first_missing(&req), None);
+    }
+
+    #[test]
+    fn test_plain_request_needs_nothing() {
+        let none = ModelCapabilities {
+            tools: false,
+            vision: false,
+            json_mode: false,
+        };
+        assert_eq!(none.first_missing(&text_request()), None);
+    }
+
+    #[test]
+    fn test_each_missing_capability_detected() {
+        let mut with_tools = text_request();
+        with_tools.tools = Some(vec![ToolDefinition {
+            name: "lookup".to_owned(),
+            description: None,
+            parameters: serde_json::json!({"type": "object"}),
+        }]);
+        let no_tools = ModelCapabilities {
+            tools: false,
+            ..ModelCapabilities::default()
+        };
+        assert_eq!(no_tools.first_missing(&with_tools), Some(Capability::Tools));
+
+        let with_image = request(MessageContent::Parts(vec![ContentPart::ImageUrl {
+            url: "https://example.com/cat.png".to_owned(),
+            detail: None,
+        }]));
+        let no_vision = ModelCapabilities {
+            vision: false,
+            ..ModelCapabilities::default()
+        };
+        assert_eq!(
+            no_vision.first_missing(&with_image),
+            Some(Capability::Vision)
+        );
+
+        let mut with_json = text_request();
+        with_json.response_format = Some(ResponseFormat::JsonObject);
+        let no_json = ModelCapabilities {
+            json_mode: false,
+            ..ModelCapabilities::default()
+        };
+        assert_eq!(
+            no_json.first_missing(&with_json),
+            Some(Capability::JsonMode)
+        );
+
+        with_json.response_format = Some(ResponseFormat::Text);
+        assert_eq!(no_json.first_missing(&with_json), None);
+    }
+}
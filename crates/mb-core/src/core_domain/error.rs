@@ -14,16 +14,36 @@ pub enum AuthError {
 
 #[derive(Debug, thiserror::Error)]
 pub enum RoutingError {
-    #[error("no healthy backend for model {model}")]
-    NoHealthyBackend { model: ModelId },
-    #[error("model {model} not found")]
+    #[error("no healthy backend for model {model}: {serving} serving backend(s), none healthy")]
+    NoHealthyBackend { model: ModelId, serving: usize },
+    #[error("model {model} not found: no backend serves it")]
     ModelNotFound { model: ModelId },
+    #[error("no capacity for model {model}: gave up after waiting {waited_ms}ms")]
+    QueueTimeout { model: ModelId, waited_ms: u64 },
+    #[error("no capacity for model {model}: every healthy backend is at its max_rpm")]
+    BackendsThrottled { model: ModelId },
+}
+
+impl RoutingError {
+    /// Stable, machine-readable cause for logs and metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::NoHealthyBackend { .. } => "all_unhealthy",
+            Self::ModelNotFound { .. } => "not_served",
+            Self::QueueTimeout { .. } => "queue_timeout",
+            Self::BackendsThrottled { .. } => "backend_rpm_exhausted",
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum AdapterError {
     #[error("failed to parse request: {0}")]
     ParseRequest(String),
+    /// A well-formed JSON request with a missing or mistyped field; `param`
+    /// names the field (e.g. `messages[0].role`).
+    #[error("{message}")]
+    InvalidField { param: String, message: String },
     #[error("failed to format response: {0}")]
     FormatResponse(String),
     #[error("unsupported feature: {0}")]
@@ -38,6 +58,16 @@ pub enum BackendError {
     Connection(String),
     #[error("backend {backend} timed out after {timeout_ms}ms")]
     Timeout { backend: BackendId, timeout_ms: u64 },
+    #[error("backend {backend} answered with model {returned}, expected {requested}")]
+    ModelMismatch {
+        backend: BackendId,
+        requested: ModelId,
+        returned: ModelId,
+    },
+    #[error("backend {backend} sent a malformed response: {reason}")]
+    MalformedResponse { backend: BackendId, reason: String },
+    #[error("backend {backend} output does not match the requested json_schema: {reason}")]
+    SchemaViolation { backend: BackendId, reason: String },
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -48,6 +78,8 @@ pub enum HealthError {
     Timeout,
     #[error("health check returned unexpected status: {0}")]
     UnexpectedStatus(u16),
+    #[error("backend does not list configured models: {}", .0.join(", "))]
+    MissingModels(Vec<String>),
 }
 
 // ---------------------------------------------------------------------------
@@ -83,6 +115,10 @@ pub enum GatewayError {
     Adapter(#[from] AdapterError),
     #[error(transparent)]
     Backend(#[from] BackendError),
+    /// A gateway bug rather than a client or backend fault, e.g. a missing
+    /// adapter registration. The detail is logged, never sent to clients.
+    #[error("internal error: {0}")]
+    Internal(String),
 }
 
 // ---------------------------------------------------------------------------
@@ -104,7 +140,7 @@ mod tests {
     #[test]
     fn test_from_routing_error_to_gateway_error() {
         let model = ModelId::new("llama3-70b");
-        let err: GatewayError = RoutingError::NoHealthyBackend { model }.into();
+        let err: GatewayError = RoutingError::NoHealthyBackend { model, serving: 2 }.into();
         assert!(matches!(
             err,
             GatewayError::Routing(RoutingError::NoHealthyBackend { .. })
@@ -153,8 +189,38 @@ mod tests {
     fn test_display_routing_no_healthy_backend() {
         let err = RoutingError::NoHealthyBackend {
             model: ModelId::new("llama3-70b"),
+            serving: 2,
+        };
+        assert_eq!(
+            err.to_string(),
+            "no healthy backend for model llama3-70b: 2 serving backend(s), none healthy"
+        );
+        assert_eq!(err.reason(), "all_unhealthy");
+    }
+
+    #[test]
+    fn test_display_routing_queue_timeout() {
+        let err = RoutingError::QueueTimeout {
+            model: ModelId::new("llama3-70b"),
+            waited_ms: 30_000,
+        };
+        assert_eq!(
+            err.to_string(),
+            "no capacity for model llama3-70b: gave up after waiting 30000ms"
+        );
+        assert_eq!(err.reason(), "queue_timeout");
+    }
+
+    #[test]
+    fn test_display_routing_backends_throttled() {
+        let err = RoutingError::BackendsThrottled {
+            model: ModelId::new("llama3-70b"),
         };
-        assert_eq!(err.to_string(), "no healthy backend for model llama3-70b");
+        assert_eq!(
+            err.to_string(),
+            "no capacity for model llama3-70b: every healthy backend is at its max_rpm"
+        );
+        assert_eq!(err.reason(), "backend_rpm_exhausted");
     }
 
     #[test]
@@ -162,7 +228,11 @@ mod tests {
         let err = RoutingError::ModelNotFound {
             model: ModelId::new("nonexistent"),
         };
-        assert_eq!(err.to_string(), "model nonexistent not found");
+        assert_eq!(
+            err.to_string(),
+            "model nonexistent not found: no backend serves it"
+        );
+        assert_eq!(err.reason(), "not_served");
     }
 
     #[test]
@@ -171,6 +241,15 @@ mod tests {
         assert_eq!(err.to_string(), "failed to parse request: unexpected EOF");
     }
 
+    #[test]
+    fn test_display_adapter_invalid_field() {
+        let err = AdapterError::InvalidField {
+            param: "model".into(),
+            message: "model must be a string".into(),
+        };
+        assert_eq!(err.to_string(), "model must be a string");
+    }
+
     #[test]
     fn test_display_adapter_format_response() {
         let err = AdapterError::FormatResponse("serialization failed".into());
@@ -204,6 +283,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display_backend_schema_violation() {
+        let err = BackendError::SchemaViolation {
+            backend: BackendId::new("gpu-1"),
+            reason: "/age: expected integer, got string".into(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "backend gpu-1 output does not match the requested json_schema: \
+             /age: expected integer, got string"
+        );
+    }
+
     #[test]
     fn test_display_backend_timeout() {
         let err = BackendError::Timeout {
@@ -213,6 +305,31 @@ mod tests {
         assert_eq!(err.to_string(), "backend gpu-1 timed out after 5000ms");
     }
 
+    #[test]
+    fn test_display_backend_model_mismatch() {
+        let err = BackendError::ModelMismatch {
+            backend: BackendId::new("gpu-1"),
+            requested: ModelId::new("llama3-70b"),
+            returned: ModelId::new("llama3-8b"),
+        };
+        assert_eq!(
+            err.to_string(),
+            "backend gpu-1 answered with model llama3-8b, expected llama3-70b"
+        );
+    }
+
+    #[test]
+    fn test_display_backend_malformed_response() {
+        let err = BackendError::MalformedResponse {
+            backend: BackendId::new("gpu-1"),
+            reason: "body is not valid UTF-8".into(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "backend gpu-1 sent a malformed response: body is not valid UTF-8"
+        );
+    }
+
     #[test]
     fn test_display_health_connection_failed() {
         let err = HealthError::ConnectionFailed("dns lookup failed".into());
@@ -254,6 +371,15 @@ mod tests {
         assert_eq!(err.to_string(), "quota exceeded: 100001/100000");
     }
 
+    #[test]
+    fn test_display_gateway_internal() {
+        let err = GatewayError::Internal("no outbound adapter for ollama".to_owned());
+        assert_eq!(
+            err.to_string(),
+            "internal error: no outbound adapter for ollama"
+        );
+    }
+
     #[test]
     fn test_display_gateway_transparent_auth() {
         let err: GatewayError = AuthError::InvalidApiKey.into();
@@ -266,6 +392,9 @@ mod tests {
             model: ModelId::new("llama3-70b"),
         }
         .into();
-        assert_eq!(err.to_string(), "model llama3-70b not found");
+        assert_eq!(
+            err.to_string(),
+            "model llama3-70b not found: no backend serves it"
+        );
     }
 }
@@ -0,0 +1,207 @@
+use crate::core::{CanonicalResponse, GatewayError, TokenUsage};
+
+// ---------------------------------------------------------------------------
+// Fan-out merging — combine per-backend responses into one
+// ---------------------------------------------------------------------------
+
+/// A merged fan-out response plus the calls that did not contribute to it.
+#[derive(Debug)]
+pub struct MergedResponse {
+    pub response: CanonicalResponse,
+    /// Errors from failed calls; non-empty only when partial results were
+    /// accepted.
+    pub failures: Vec<GatewayError>,
+}
+
+impl MergedResponse {
+    pub fn is_partial(&self) -> bool {
+        !self.failures.is_empty()
+    }
+}
+
+/// Merges the results of sending one request to several backends (e.g. to
+/// serve `n > 1`).
+///
+/// Choices from successful calls are concatenated in order and renumbered
+/// from 0; usage is summed, since every call was billed for its prompt. The
+/// id, model and timestamp come from the first success.
+///
+/// When any call failed, `allow_partial` decides between returning the
+/// successful choices (with the failures attached) and failing with the
+/// first error. If every call failed the first error is returned either way,
+/// and an empty `results` is an internal error.
+pub fn merge_responses(
+    results: Vec<Result<CanonicalResponse, GatewayError>>,
+    allow_partial: bool,
+) -> Result<MergedResponse, GatewayError> {
+    if results.is_empty() {
+        return Err(GatewayError::Internal(
+            "fan-out produced no results to merge".to_owned(),
+        ));
+    }
+
+    let mut successes = Vec::new();
+    let mut failures = Vec::new();
+    for result in results {
+        match result {
+            Ok(response) => successes.push(response),
+            Err(e) => failures.push(e),
+        }
+    }
+
+    if successes.is_empty() || (!failures.is_empty() && !allow_partial) {
+        return Err(failures.swap_remove(0));
+    }
+
+    let mut successes = successes.into_iter();
+    let mut merged = successes.next().expect("checked non-empty above");
+    for response in successes {
+        merged.choices.extend(response.choices);
+        merged.usage = TokenUsage {
+            prompt_tokens: merged
+                .usage
+                .prompt_tokens
+                .saturating_add(response.usage.prompt_tokens),
+            completion_tokens: merged
+                .usage
+                .completion_tokens
+                .saturating_add(response.usage.completion_tokens),
+            total_tokens: merged
+                .usage
+                .total_tokens
+                .saturating_add(response.usage.total_tokens),
+        };
+    }
+    for (index, choice) in merged.choices.iter_mut().enumerate() {
+        choice.index = index as u32;
+    }
+
+    Ok(MergedResponse {
+        response: merged,
+        failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{BackendError, Choice, FinishReason, Message, MessageContent, ModelId, Role};
+
+    fn response(id: &str, text: &str, prompt: u64, completion: u64) -> CanonicalResponse {
+        CanonicalResponse {
+            id: id.to_owned(),
+            model: ModelId::new("llama3-70b"),
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: Role::Assistant,
+                    content: MessageContent::Text(text.to_owned()),
+                    name: None,
+                    tool_call_id: None,
+                },
+                finish_reason: FinishReason::Stop,
+                logprobs: None,
+            }],
+            usage: TokenUsage {
+                prompt_tokens: prompt,
+                completion_tokens: completion,
+                total_tokens: prompt.saturating_add(completion),
+            },
+            created: 1_700_000_000,
+        }
+    }
+
+    fn backend_down() -> GatewayError {
+        GatewayError::Backend(BackendError::Connection("refused".to_owned()))
+    }
+
+    #[test]
+    fn test_merge_concatenates_choices_and_sums_usage() {
+        let merged = merge_responses(
+            vec![
+                Ok(response("resp-a", "first", 10, 5)),
+                Ok(response("resp-b", "second", 10, 7)),
+            ],
+            false,
+        )
+        .expect("all calls succeeded");
+
+        assert!(!merged.is_partial());
+        assert_eq!(merged.response.id, "resp-a");
+        let indices: Vec<u32> = merged.response.choices.iter().map(|c| c.index).collect();
+        assert_eq!(indices, [0, 1]);
+        assert_eq!(merged.response.usage.prompt_tokens, 20);
+        assert_eq!(merged.response.usage.completion_tokens, 12);
+        assert_eq!(merged.response.usage.total_tokens, 32);
+    }
+
+    #[test]
+    fn test_partial_failure_keeps_successful_choice() {
+        let merged = merge_responses(
+            vec![Err(backend_down()), Ok(response("resp-b", "second", 10, 7))],
+            true,
+        )
+        .expect("partial results allowed");
+
+        assert!(merged.is_partial());
+        assert_eq!(merged.failures.len(), 1);
+        assert_eq!(merged.response.id, "resp-b");
+        assert_eq!(merged.response.choices.len(), 1);
+        assert_eq!(merged.response.choices[0].index, 0);
+        assert_eq!(
+            merged.response.choices[0].message.content,
+            MessageContent::Text("second".to_owned())
+        );
+        assert_eq!(merged.response.usage.total_tokens, 17);
+    }
+
+    #[test]
+    fn test_partial_failure_rejected_when_not_allowed() {
+        let result = merge_responses(
+            vec![Ok(response("resp-a", "first", 10, 5)), Err(backend_down())],
+            false,
+        );
+        assert!(matches!(
+            result,
+            Err(GatewayError::Backend(BackendError::Connection(_)))
+        ));
+    }
+
+    #[test]
+    fn test_empty_results_are_an_internal_error() {
+        let result = merge_responses(Vec::new(), true);
+        assert!(matches!(result, Err(GatewayError::Internal(_))));
+    }
+
+    #[test]
+    fn test_usage_sum_saturates() {
+        let merged = merge_responses(
+            vec![
+                Ok(response("resp-a", "first", u64::MAX, 1)),
+                Ok(response("resp-b", "second", 10, 7)),
+            ],
+            false,
+        )
+        .expect("all calls succeeded");
+        assert_eq!(merged.response.usage.prompt_tokens, u64::MAX);
+        assert_eq!(merged.response.usage.completion_tokens, 8);
+    }
+
+    #[test]
+    fn test_all_failed_returns_first_error() {
+        let result = merge_responses(
+            vec![
+                Err(backend_down()),
+                Err(GatewayError::Backend(BackendError::HttpStatus {
+                    status: 500,
+                    body: String::new(),
+                })),
+            ],
+            true,
+        );
+        assert!(matches!(
+            result,
+            Err(GatewayError::Backend(BackendError::Connection(_)))
+        ));
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::core::{BackendId, LatencyMs, ModelId};
 
 // ---------------------------------------------------------------------------
@@ -25,6 +27,9 @@ pub struct BackendState {
     pub max_concurrent: u32,
     pub last_latency: Option<LatencyMs>,
     pub consecutive_failures: u32,
+    /// Relative cost of serving a request here; lower is preferred by
+    /// `RoutingStrategy::CheapestFirst`.
+    pub cost_weight: u32,
 }
 
 impl BackendState {
@@ -37,6 +42,14 @@ impl BackendState {
             max_concurrent,
             last_latency: None,
             consecutive_failures: 0,
+            cost_weight: 0,
+        }
+    }
+
+    pub fn with_cost_weight(self, cost_weight: u32) -> Self {
+        Self {
+            cost_weight,
+            ..self
         }
     }
 
@@ -101,6 +114,43 @@ impl BackendState {
     }
 }
 
+// ---------------------------------------------------------------------------
+// BackendLoad — combined in-flight requests versus capacity for a model
+// ---------------------------------------------------------------------------
+
+/// In-flight requests and `max_concurrent` summed over the healthy backends
+/// serving one model. Displays as `active/max`.
+///
+/// `active` gives each backend's in-flight count, which callers track
+/// themselves so that reporting load never feeds back into routing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BackendLoad {
+    pub active: u32,
+    pub max: u32,
+}
+
+impl BackendLoad {
+    pub fn for_model<'a>(
+        states: impl IntoIterator<Item = &'a BackendState>,
+        model: &ModelId,
+        active: impl Fn(&BackendId) -> u32,
+    ) -> Self {
+        states
+            .into_iter()
+            .filter(|s| s.is_healthy() && s.serves_model(model))
+            .fold(Self::default(), |load, s| Self {
+                active: load.active.saturating_add(active(&s.id)),
+                max: load.max.saturating_add(s.max_concurrent),
+            })
+    }
+}
+
+impl fmt::Display for BackendLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.active, self.max)
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -205,4 +255,25 @@ mod tests {
         assert_eq!(state.consecutive_failures, 0);
         assert_eq!(state.last_latency, Some(LatencyMs::new(100)));
     }
+
+    #[test]
+    fn test_backend_load_for_model() {
+        let model = ModelId::new("llama3-70b");
+        let busy = make_backend().with_healthy(LatencyMs::new(10));
+        let idle = BackendState::new(BackendId::new("gpu-1"), vec![model.clone()], 8)
+            .with_healthy(LatencyMs::new(10));
+        // Neither down backends nor other models count.
+        let down =
+            BackendState::new(BackendId::new("gpu-2"), vec![model.clone()], 8).with_unhealthy();
+        let other = BackendState::new(BackendId::new("gpu-3"), vec![ModelId::new("qwen")], 8)
+            .with_healthy(LatencyMs::new(10));
+
+        let active = |id: &BackendId| match id.as_str() {
+            "gpu-1" => 0,
+            _ => 2,
+        };
+        let load = BackendLoad::for_model([&busy, &idle, &down, &other], &model, active);
+        assert_eq!(load, BackendLoad { active: 2, max: 12 });
+        assert_eq!(load.to_string(), "2/12");
+    }
 }
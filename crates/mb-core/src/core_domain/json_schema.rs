@@ -0,0 +1,277 @@
+use serde_json::Value;
+
+// ---------------------------------------------------------------------------
+// JSON Schema — structural check of model output
+// ---------------------------------------------------------------------------
+
+/// Where and why an instance fails a schema; `path` is a JSON pointer
+/// (empty for the root).
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("{}: {reason}", display_path(.path))]
+pub struct SchemaViolation {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Validates `instance` against the subset of JSON Schema used by
+/// `response_format: json_schema`: `type`, `enum`, `const`, `properties`,
+/// `required`, `additionalProperties`, `items`, `anyOf`, and the length and
+/// range bounds. Other keywords (including `$ref`) are accepted unchecked.
+pub fn validate_json_schema(instance: &Value, schema: &Value) -> Result<(), SchemaViolation> {
+    validate_at(instance, schema, "")
+}
+
+fn validate_at(instance: &Value, schema: &Value, path: &str) -> Result<(), SchemaViolation> {
+    let violation = |reason: String| SchemaViolation {
+        path: path.to_owned(),
+        reason,
+    };
+
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(violation("no value is allowed here".to_owned())),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| has_type(instance, name)) {
+            return Err(violation(format!(
+                "expected {}, got {}",
+                allowed.join(" or "),
+                type_name(instance)
+            )));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(instance) {
+            return Err(violation(
+                "value is not one of the allowed enum values".to_owned(),
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if instance != expected {
+            return Err(violation(format!("expected constant {expected}")));
+        }
+    }
+
+    if let Some(Value::Array(branches)) = schema.get("anyOf") {
+        if !branches
+            .iter()
+            .any(|branch| validate_at(instance, branch, path).is_ok())
+        {
+            return Err(violation(
+                "value matches none of the anyOf schemas".to_owned(),
+            ));
+        }
+    }
+
+    match instance {
+        Value::Object(fields) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        return Err(violation(format!("missing required property `{name}`")));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, value) in fields {
+                let child = format!("{path}/{}", escape_pointer(name));
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => validate_at(value, property, &child)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(violation(format!("unexpected property `{name}`")));
+                        }
+                        Some(additional @ Value::Object(_)) => {
+                            validate_at(value, additional, &child)?
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bound(schema, "minItems", items.len(), |len, min| len >= min)
+                .map_err(|min| violation(format!("expected at least {min} items")))?;
+            check_bound(schema, "maxItems", items.len(), |len, max| len <= max)
+                .map_err(|max| violation(format!("expected at most {max} items")))?;
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, &format!("{path}/{index}"))?;
+                }
+            }
+        }
+        Value::String(text) => {
+            let len = text.chars().count();
+            check_bound(schema, "minLength", len, |len, min| len >= min)
+                .map_err(|min| violation(format!("expected at least {min} characters")))?;
+            check_bound(schema, "maxLength", len, |len, max| len <= max)
+                .map_err(|max| violation(format!("expected at most {max} characters")))?;
+        }
+        Value::Number(number) => {
+            let value = number.as_f64().unwrap_or(f64::NAN);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if value < min {
+                    return Err(violation(format!("expected a value >= {min}")));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if value > max {
+                    return Err(violation(format!("expected a value <= {max}")));
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+
+    Ok(())
+}
+
+/// `Err(bound)` when `schema[keyword]` is set and `ok(len, bound)` fails.
+fn check_bound(
+    schema: &serde_json::Map<String, Value>,
+    keyword: &str,
+    len: usize,
+    ok: impl Fn(usize, usize) -> bool,
+) -> Result<(), usize> {
+    match schema.get(keyword).and_then(Value::as_u64) {
+        Some(bound) => {
+            let bound = usize::try_from(bound).unwrap_or(usize::MAX);
+            if ok(len, bound) {
+                Ok(())
+            } else {
+                Err(bound)
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|value| value.fract() == 0.0)
+        }
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        // Unknown type names are not ours to reject.
+        _ => true,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+                "role": {"enum": ["admin", "user"]}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_instance_passes() {
+        let instance = json!({"name": "Ada", "age": 36, "tags": ["math"], "role": "admin"});
+        assert_eq!(validate_json_schema(&instance, &person_schema()), Ok(()));
+    }
+
+    #[test]
+    fn test_violations_name_the_path() {
+        let cases = [
+            (
+                json!({"name": "Ada"}),
+                "/",
+                "missing required property `age`",
+            ),
+            (
+                json!({"name": "Ada", "age": "36"}),
+                "/age",
+                "expected integer, got string",
+            ),
+            (
+                json!({"name": "Ada", "age": 1, "tags": ["a", 2]}),
+                "/tags/1",
+                "expected string, got number",
+            ),
+            (
+                json!({"name": "Ada", "age": 1, "extra": true}),
+                "/",
+                "unexpected property `extra`",
+            ),
+            (
+                json!({"name": "Ada", "age": 1, "role": "root"}),
+                "/role",
+                "value is not one of the allowed enum values",
+            ),
+            (json!([1]), "/", "expected object, got array"),
+        ];
+        for (instance, path, reason) in cases {
+            let err = validate_json_schema(&instance, &person_schema()).unwrap_err();
+            assert_eq!(err.to_string(), format!("{path}: {reason}"), "{instance}");
+        }
+    }
+
+    #[test]
+    fn test_any_of_and_nullable_types() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "value": {"anyOf": [{"type": "string"}, {"type": "number"}]},
+                "note": {"type": ["string", "null"]}
+            }
+        });
+        assert!(validate_json_schema(&json!({"value": 1, "note": null}), &schema).is_ok());
+        assert!(validate_json_schema(&json!({"value": true}), &schema).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::core::LatencyMs;
+
+// ---------------------------------------------------------------------------
+// LatencyReservoir — rolling window of recent request latencies
+// ---------------------------------------------------------------------------
+
+/// Latency percentiles over the samples a [`LatencyReservoir`] holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatencyPercentiles {
+    pub p50: LatencyMs,
+    pub p95: LatencyMs,
+    pub p99: LatencyMs,
+    /// Samples the percentiles were computed from.
+    pub samples: usize,
+}
+
+/// Keeps the most recent `capacity` latency samples in a ring, so memory
+/// stays fixed however long the gateway runs and old traffic ages out.
+#[derive(Clone, Debug)]
+pub struct LatencyReservoir {
+    samples: Vec<LatencyMs>,
+    capacity: usize,
+    /// Slot the next sample overwrites once the ring is full.
+    next: usize,
+    /// Every sample ever recorded, including those since overwritten.
+    recorded: u64,
+}
+
+impl LatencyReservoir {
+    /// # Panics
+    /// When `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "latency reservoir needs room for a sample");
+        Self {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+            recorded: 0,
+        }
+    }
+
+    pub fn record(&mut self, latency: LatencyMs) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(latency);
+        } else {
+            self.samples[self.next] = latency;
+        }
+        self.next = (self.next + 1) % self.capacity;
+        self.recorded = self.recorded.saturating_add(1);
+    }
+
+    /// Samples recorded over the reservoir's lifetime.
+    pub fn recorded(&self) -> u64 {
+        self.recorded
+    }
+
+    /// Nearest-rank p50/p95/p99 of the held samples; `None` before the first
+    /// sample.
+    pub fn percentiles(&self) -> Option<LatencyPercentiles> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let rank = |p: usize| {
+            let index = (p * sorted.len()).div_ceil(100).saturating_sub(1);
+            sorted[index]
+        };
+        Some(LatencyPercentiles {
+            p50: rank(50),
+            p95: rank(95),
+            p99: rank(99),
+            samples: sorted.len(),
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(capacity: usize, values: impl IntoIterator<Item = u64>) -> LatencyReservoir {
+        let mut reservoir = LatencyReservoir::new(capacity);
+        for value in values {
+            reservoir.record(LatencyMs::new(value));
+        }
+        reservoir
+    }
+
+    fn assert_near(actual: LatencyMs, expected: u64, tolerance: u64) {
+        assert!(
+            actual.value().abs_diff(expected) <= tolerance,
+            "{} not within {tolerance} of {expected}",
+            actual.value()
+        );
+    }
+
+    #[test]
+    fn test_empty_has_no_percentiles() {
+        assert_eq!(LatencyReservoir::new(8).percentiles(), None);
+    }
+
+    #[test]
+    fn test_uniform_distribution_percentiles() {
+        // 1..=1000ms, shuffled so insertion order cannot help.
+        let values = (1..=1000u64).map(|i| (i * 389) % 1000 + 1);
+        let p = filled(1000, values).percentiles().expect("samples");
+
+        assert_eq!(p.samples, 1000);
+        assert_near(p.p50, 500, 5);
+        assert_near(p.p95, 950, 5);
+        assert_near(p.p99, 990, 5);
+    }
+
+    #[test]
+    fn test_skewed_distribution_percentiles() {
+        // 90% fast replies around 100ms, 10% slow ones at 2s.
+        let values = (0..500u64).map(|i| if i % 10 == 0 { 2_000 } else { 95 + i % 10 });
+        let p = filled(500, values).percentiles().expect("samples");
+
+        assert_near(p.p50, 100, 5);
+        assert_eq!(p.p95, LatencyMs::new(2_000));
+        assert_eq!(p.p99, LatencyMs::new(2_000));
+    }
+
+    #[test]
+    fn test_old_samples_age_out() {
+        let mut reservoir = filled(100, std::iter::repeat_n(5_000, 100));
+        for _ in 0..100 {
+            reservoir.record(LatencyMs::new(10));
+        }
+        let p = reservoir.percentiles().expect("samples");
+
+        assert_eq!(p.samples, 100);
+        assert_eq!(p.p99, LatencyMs::new(10));
+        assert_eq!(reservoir.recorded(), 200);
+    }
+}
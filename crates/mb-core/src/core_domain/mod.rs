@@ -1,19 +1,35 @@
 mod auth;
 pub mod cache_router;
 mod canonical;
+mod capabilities;
 mod error;
+mod fanout;
 mod health;
+mod json_schema;
+mod latency;
+mod normalize;
+mod param_policy;
 mod ports;
 mod quota;
 mod router;
+mod tokens;
+mod tool_support;
 mod types;
 
 pub use auth::*;
 pub use cache_router::*;
 pub use canonical::*;
+pub use capabilities::*;
 pub use error::*;
+pub use fanout::*;
 pub use health::*;
+pub use json_schema::*;
+pub use latency::*;
+pub use normalize::*;
+pub use param_policy::*;
 pub use ports::*;
 pub use quota::*;
 pub use router::*;
+pub use tokens::*;
+pub use tool_support::*;
 pub use types::*;
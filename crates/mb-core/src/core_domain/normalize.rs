@@ -0,0 +1,134 @@
+use crate::core::{ContentPart, Message, MessageContent, Role};
+
+// ---------------------------------------------------------------------------
+// Request normalization — rewrites applied before routing
+// ---------------------------------------------------------------------------
+
+/// Folds each run of consecutive system messages into the first of the run,
+/// joining their text with newlines; some backends honour only the first
+/// system message or reject several. The merged message keeps the first
+/// one's `name`. A system message carrying non-text parts is left alone and
+/// ends the run.
+pub fn merge_system_messages(messages: &mut Vec<Message>) {
+    let mut merged: Vec<Message> = Vec::with_capacity(messages.len());
+    for message in messages.drain(..) {
+        if let Some(previous) = merged.last_mut() {
+            if let (Some(head), Some(tail)) = (system_text(previous), system_text(&message)) {
+                previous.content = MessageContent::Text(format!("{head}\n{tail}"));
+                continue;
+            }
+        }
+        merged.push(message);
+    }
+    *messages = merged;
+}
+
+/// The text of a system message made only of text, if `message` is one.
+fn system_text(message: &Message) -> Option<String> {
+    if message.role != Role::System {
+        return None;
+    }
+    match &message.content {
+        MessageContent::Text(text) => Some(text.clone()),
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, text: &str) -> Message {
+        Message {
+            role,
+            content: MessageContent::Text(text.to_owned()),
+            name: None,
+            tool_call_id: None,
+        }
+    }
+
+    fn texts(messages: &[Message]) -> Vec<(Role, &str)> {
+        messages
+            .iter()
+            .map(|m| match &m.content {
+                MessageContent::Text(text) => (m.role.clone(), text.as_str()),
+                MessageContent::Parts(_) => (m.role.clone(), "<parts>"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_consecutive_system_messages_merged() {
+        let mut messages = vec![
+            message(Role::System, "Be terse."),
+            message(Role::System, "Answer in French."),
+            message(Role::User, "hi"),
+            message(Role::System, "late one"),
+            message(Role::System, "and another"),
+        ];
+        merge_system_messages(&mut messages);
+        assert_eq!(
+            texts(&messages),
+            [
+                (Role::System, "Be terse.\nAnswer in French."),
+                (Role::User, "hi"),
+                (Role::System, "late one\nand another"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_separated_or_non_text_system_messages_kept() {
+        let image = Message {
+            role: Role::System,
+            content: MessageContent::Parts(vec![ContentPart::ImageUrl {
+                url: "https://example.com/logo.png".to_owned(),
+                detail: None,
+            }]),
+            name: None,
+            tool_call_id: None,
+        };
+        let text_parts = Message {
+            role: Role::System,
+            content: MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "part ".to_owned(),
+                },
+                ContentPart::Text {
+                    text: "one".to_owned(),
+                },
+            ]),
+            name: None,
+            tool_call_id: None,
+        };
+        let mut messages = vec![
+            message(Role::System, "a"),
+            message(Role::User, "hi"),
+            message(Role::System, "b"),
+            image,
+            text_parts,
+            message(Role::System, "c"),
+        ];
+        merge_system_messages(&mut messages);
+        assert_eq!(
+            texts(&messages),
+            [
+                (Role::System, "a"),
+                (Role::User, "hi"),
+                (Role::System, "b"),
+                (Role::System, "<parts>"),
+                (Role::System, "part one\nc"),
+            ]
+        );
+    }
+}
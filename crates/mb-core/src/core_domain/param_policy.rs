@@ -0,0 +1,165 @@
+use crate::core::{AdapterError, GenerationParams};
+
+// ---------------------------------------------------------------------------
+// ParamPolicy — per-client restrictions on generation parameters
+// ---------------------------------------------------------------------------
+
+/// Names accepted in `forbidden_params`, matching the OpenAI request fields.
+pub const GENERATION_PARAM_NAMES: &[&str] = &[
+    "temperature",
+    "top_p",
+    "max_tokens",
+    "stop",
+    "frequency_penalty",
+    "presence_penalty",
+    "seed",
+    "top_k",
+    "min_p",
+];
+
+/// What happens when a client sets a parameter it may not set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ForbiddenParamAction {
+    /// Silently drop the parameter and let the backend use its default.
+    #[default]
+    Strip,
+    /// Fail the request with [`AdapterError::InvalidField`].
+    Reject,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParamPolicy {
+    /// Entries of [`GENERATION_PARAM_NAMES`]; unknown names are ignored.
+    pub forbidden: Vec<String>,
+    pub action: ForbiddenParamAction,
+}
+
+impl ParamPolicy {
+    /// Enforces the policy on `params`, returning the names that were
+    /// stripped.
+    pub fn apply(&self, params: &mut GenerationParams) -> Result<Vec<String>, AdapterError> {
+        let mut stripped = Vec::new();
+        for name in &self.forbidden {
+            if !is_set(params, name) {
+                continue;
+            }
+            if self.action == ForbiddenParamAction::Reject {
+                return Err(AdapterError::InvalidField {
+                    param: name.clone(),
+                    message: format!("{name} may not be set by this client"),
+                });
+            }
+            clear(params, name);
+            stripped.push(name.clone());
+        }
+        Ok(stripped)
+    }
+}
+
+fn is_set(params: &GenerationParams, name: &str) -> bool {
+    match name {
+        "temperature" => params.temperature.is_some(),
+        "top_p" => params.top_p.is_some(),
+        "max_tokens" => params.max_tokens.is_some(),
+        "stop" => params.stop.is_some(),
+        "frequency_penalty" => params.frequency_penalty.is_some(),
+        "presence_penalty" => params.presence_penalty.is_some(),
+        "seed" => params.seed.is_some(),
+        "top_k" => params.top_k.is_some(),
+        "min_p" => params.min_p.is_some(),
+        _ => false,
+    }
+}
+
+fn clear(params: &mut GenerationParams, name: &str) {
+    match name {
+        "temperature" => params.temperature = None,
+        "top_p" => params.top_p = None,
+        "max_tokens" => params.max_tokens = None,
+        "stop" => params.stop = None,
+        "frequency_penalty" => params.frequency_penalty = None,
+        "presence_penalty" => params.presence_penalty = None,
+        "seed" => params.seed = None,
+        "top_k" => params.top_k = None,
+        "min_p" => params.min_p = None,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> GenerationParams {
+        GenerationParams {
+            temperature: Some(1.5),
+            max_tokens: Some(100),
+            ..GenerationParams::default()
+        }
+    }
+
+    fn policy(action: ForbiddenParamAction) -> ParamPolicy {
+        ParamPolicy {
+            forbidden: vec!["temperature".to_owned(), "seed".to_owned()],
+            action,
+        }
+    }
+
+    #[test]
+    fn test_strip_clears_only_forbidden_params_that_are_set() {
+        let mut p = params();
+        let stripped = policy(ForbiddenParamAction::Strip).apply(&mut p).unwrap();
+        assert_eq!(stripped, ["temperature"]);
+        assert_eq!(p.temperature, None);
+        assert_eq!(p.max_tokens, Some(100));
+    }
+
+    #[test]
+    fn test_reject_names_the_param() {
+        let mut p = params();
+        let err = policy(ForbiddenParamAction::Reject)
+            .apply(&mut p)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AdapterError::InvalidField { ref param, .. } if param == "temperature"
+        ));
+
+        // Requests that leave forbidden params unset pass untouched.
+        let mut p = GenerationParams {
+            max_tokens: Some(100),
+            ..GenerationParams::default()
+        };
+        assert!(policy(ForbiddenParamAction::Reject)
+            .apply(&mut p)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_every_listed_name_is_recognised() {
+        let mut p = GenerationParams {
+            temperature: Some(1.0),
+            top_p: Some(1.0),
+            max_tokens: Some(1),
+            stop: Some(vec![]),
+            frequency_penalty: Some(0.0),
+            presence_penalty: Some(0.0),
+            seed: Some(1),
+            top_k: Some(1),
+            min_p: Some(0.1),
+        };
+        let all = ParamPolicy {
+            forbidden: GENERATION_PARAM_NAMES
+                .iter()
+                .map(|n| n.to_string())
+                .collect(),
+            action: ForbiddenParamAction::Strip,
+        };
+        assert_eq!(
+            all.apply(&mut p).unwrap().len(),
+            GENERATION_PARAM_NAMES.len()
+        );
+        assert_eq!(p, GenerationParams::default());
+    }
+}
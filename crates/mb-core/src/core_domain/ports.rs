@@ -1,9 +1,10 @@
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 
 use crate::core::{
     AdapterError, BackendId, CanonicalRequest, CanonicalResponse, CanonicalStreamChunk,
-    HealthError, LatencyMs, ModelId,
+    HealthError, LatencyMs, ModelId, StreamContext, ToolSupport,
 };
 
 // ---------------------------------------------------------------------------
@@ -23,6 +24,25 @@ pub enum BackendSpec {
     Ollama,
 }
 
+/// Wire framing for streamed chunks sent to the client.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StreamFraming {
+    /// `data: <chunk>` events, terminated by the adapter's done sentinel.
+    #[default]
+    Sse,
+    /// One chunk JSON per line, no sentinel.
+    Ndjson,
+}
+
+impl StreamFraming {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Sse => "text/event-stream",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // BackendInfo — core's abstract view of a backend (converted from config)
 // ---------------------------------------------------------------------------
@@ -34,6 +54,22 @@ pub struct BackendInfo {
     pub models: Vec<ModelId>,
     pub max_concurrent: u32,
     pub base_url: String,
+    /// Canonical model id → name this backend expects on the wire.
+    pub model_map: HashMap<ModelId, String>,
+    pub tool_support: ToolSupport,
+    /// Relative cost of serving a request here; lower is preferred by
+    /// `RoutingStrategy::CheapestFirst`.
+    pub cost_weight: u32,
+}
+
+impl BackendInfo {
+    /// The name to send this backend for `model`: its `model_map` entry, or
+    /// the canonical id when there is none.
+    pub fn wire_model<'a>(&'a self, model: &'a ModelId) -> &'a str {
+        self.model_map
+            .get(model)
+            .map_or(model.as_str(), String::as_str)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -43,16 +79,44 @@ pub struct BackendInfo {
 pub trait InboundAdapter: Send + Sync {
     fn api_spec(&self) -> ApiSpec;
 
-    fn parse_request(&self, body: &[u8]) -> Result<CanonicalRequest, AdapterError>;
+    /// Parses `body`, using `default_model` when the request names none.
+    fn parse_request(
+        &self,
+        body: &[u8],
+        default_model: Option<&ModelId>,
+    ) -> Result<CanonicalRequest, AdapterError>;
 
     fn format_response(&self, response: &CanonicalResponse) -> Result<Vec<u8>, AdapterError>;
 
+    /// Like [`format_response`](Self::format_response), but indented for
+    /// reading while debugging.
+    fn format_response_pretty(&self, response: &CanonicalResponse)
+        -> Result<Vec<u8>, AdapterError>;
+
     fn format_stream_chunk(
         &self,
         chunk: &CanonicalStreamChunk,
+        context: &StreamContext,
     ) -> Result<Option<String>, AdapterError>;
 
     fn done_sentinel(&self) -> &str;
+
+    /// Wraps a [`format_stream_chunk`](Self::format_stream_chunk) payload in
+    /// `framing`.
+    fn frame_stream_chunk(&self, payload: &str, framing: StreamFraming) -> String {
+        match framing {
+            StreamFraming::Sse => format!("data: {payload}\n\n"),
+            StreamFraming::Ndjson => format!("{payload}\n"),
+        }
+    }
+
+    /// Frame sent after the last chunk, if `framing` has one.
+    fn stream_trailer(&self, framing: StreamFraming) -> Option<String> {
+        match framing {
+            StreamFraming::Sse => Some(self.frame_stream_chunk(self.done_sentinel(), framing)),
+            StreamFraming::Ndjson => None,
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -62,7 +126,13 @@ pub trait InboundAdapter: Send + Sync {
 pub trait OutboundAdapter: Send + Sync {
     fn backend_spec(&self) -> BackendSpec;
 
-    fn build_request_body(&self, req: &CanonicalRequest) -> Result<Vec<u8>, AdapterError>;
+    /// Serializes `req` for `backend`, naming the model as the backend
+    /// expects (see [`BackendInfo::wire_model`]).
+    fn build_request_body(
+        &self,
+        req: &CanonicalRequest,
+        backend: &BackendInfo,
+    ) -> Result<Vec<u8>, AdapterError>;
 
     fn parse_response(&self, body: &[u8]) -> Result<CanonicalResponse, AdapterError>;
 
@@ -71,6 +141,19 @@ pub trait OutboundAdapter: Send + Sync {
     fn extra_headers(&self, backend: &BackendInfo) -> Vec<(String, String)>;
 
     fn inference_path(&self) -> &str;
+
+    /// Path for streaming calls, for specs that split generation across two
+    /// endpoints (e.g. TGI's `/generate` and `/generate_stream`).
+    fn streaming_inference_path(&self) -> &str {
+        self.inference_path()
+    }
+
+    /// Whether the backend honours `stream: true`. When it does not, the
+    /// gateway makes a non-streaming call and replays the whole response to
+    /// streaming clients as a single burst of chunks.
+    fn supports_streaming(&self) -> bool {
+        true
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -47,6 +47,131 @@ impl RateLimiter {
         self.timestamps.push_back(now_ms);
         Ok(())
     }
+
+    /// Like [`check`](Self::check), but never records the request.
+    pub fn peek(&self, now_ms: u64) -> Result<(), RateLimitInfo> {
+        if self.remaining(now_ms) > 0 {
+            Ok(())
+        } else {
+            Err(RateLimitInfo {
+                retry_after_ms: self.reset_after_ms(now_ms),
+            })
+        }
+    }
+
+    /// Maximum number of requests allowed per window.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Number of requests still allowed in the window ending at `now_ms`.
+    pub fn remaining(&self, now_ms: u64) -> u32 {
+        let used = self.in_window(now_ms).count();
+        let used = u32::try_from(used).unwrap_or(u32::MAX);
+        self.limit.saturating_sub(used)
+    }
+
+    /// Milliseconds from `now_ms` until the oldest recorded request leaves
+    /// the window and frees a slot. Returns 0 when no requests are recorded.
+    pub fn reset_after_ms(&self, now_ms: u64) -> u64 {
+        self.in_window(now_ms).next().map_or(0, |&oldest| {
+            oldest.saturating_add(self.window_ms).saturating_sub(now_ms)
+        })
+    }
+
+    fn in_window(&self, now_ms: u64) -> impl Iterator<Item = &u64> {
+        let window_start = now_ms.saturating_sub(self.window_ms);
+        self.timestamps
+            .iter()
+            .filter(move |&&ts| ts >= window_start)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TokenRateLimiter — sliding-window token budget (pure, no system clock)
+// ---------------------------------------------------------------------------
+
+pub struct TokenRateLimiter {
+    window_ms: u64,
+    limit: u64,
+    /// `(timestamp, tokens)` in arrival order.
+    usage: VecDeque<(u64, u64)>,
+}
+
+impl TokenRateLimiter {
+    pub fn new(window_ms: u64, limit: u64) -> Self {
+        Self {
+            window_ms,
+            limit,
+            usage: VecDeque::new(),
+        }
+    }
+
+    /// Check whether `tokens` more fit in the window ending at `now_ms`.
+    ///
+    /// On success, records them and returns `Ok(())`. A request larger than
+    /// the whole budget is only admitted into an empty window.
+    pub fn check(&mut self, now_ms: u64, tokens: u64) -> Result<(), RateLimitInfo> {
+        self.prune(now_ms);
+        self.peek(now_ms, tokens)?;
+        self.usage.push_back((now_ms, tokens));
+        Ok(())
+    }
+
+    /// Like [`check`](Self::check), but never records the tokens.
+    pub fn peek(&self, now_ms: u64, tokens: u64) -> Result<(), RateLimitInfo> {
+        let mut used = self.used(now_ms);
+        if used == 0 || used.saturating_add(tokens) <= self.limit {
+            return Ok(());
+        }
+        // Wait until enough of the oldest usage leaves the window.
+        let mut retry_after_ms = 0;
+        for (ts, spent) in self.in_window(now_ms) {
+            used -= spent;
+            retry_after_ms = ts.saturating_add(self.window_ms).saturating_sub(now_ms);
+            if used == 0 || used.saturating_add(tokens) <= self.limit {
+                break;
+            }
+        }
+        Err(RateLimitInfo { retry_after_ms })
+    }
+
+    /// Adds `tokens` consumed at `now_ms` without checking the budget, e.g.
+    /// completion tokens known only once the backend has answered.
+    pub fn record(&mut self, now_ms: u64, tokens: u64) {
+        self.prune(now_ms);
+        self.usage.push_back((now_ms, tokens));
+    }
+
+    /// Maximum number of tokens allowed per window.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Tokens still available in the window ending at `now_ms`.
+    pub fn remaining(&self, now_ms: u64) -> u64 {
+        self.limit.saturating_sub(self.used(now_ms))
+    }
+
+    fn used(&self, now_ms: u64) -> u64 {
+        self.in_window(now_ms)
+            .fold(0, |used, (_, tokens)| used.saturating_add(tokens))
+    }
+
+    fn prune(&mut self, now_ms: u64) {
+        let window_start = now_ms.saturating_sub(self.window_ms);
+        while self.usage.front().is_some_and(|&(ts, _)| ts < window_start) {
+            self.usage.pop_front();
+        }
+    }
+
+    fn in_window(&self, now_ms: u64) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let window_start = now_ms.saturating_sub(self.window_ms);
+        self.usage
+            .iter()
+            .copied()
+            .filter(move |&(ts, _)| ts >= window_start)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -128,6 +253,19 @@ impl QuotaTracker {
 
         entry.tokens_used = entry.tokens_used.saturating_add(actual_tokens);
     }
+
+    /// Zero `client`'s usage for `current_period`, returning the tokens cleared.
+    ///
+    /// Usage recorded for an earlier period is already stale and reports 0.
+    pub fn reset(&mut self, client: &ClientId, current_period: YearMonth) -> u64 {
+        let cleared = self
+            .usage
+            .get(client)
+            .filter(|u| u.period == current_period)
+            .map_or(0, |u| u.tokens_used);
+        self.usage.remove(client);
+        cleared
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -169,6 +307,72 @@ mod tests {
         assert!(limiter.check(12_000).is_ok());
     }
 
+    #[test]
+    fn test_rate_limiter_remaining_and_reset() {
+        let mut limiter = RateLimiter::new(60_000, 3);
+        assert_eq!(limiter.limit(), 3);
+        assert_eq!(limiter.remaining(0), 3);
+        assert_eq!(limiter.reset_after_ms(0), 0);
+
+        limiter.check(1000).unwrap();
+        assert_eq!(limiter.remaining(1000), 2);
+        assert_eq!(limiter.reset_after_ms(1000), 60_000);
+
+        limiter.check(2000).unwrap();
+        assert_eq!(limiter.remaining(2000), 1);
+        // Oldest request (t=1000) frees its slot at t=61000.
+        assert_eq!(limiter.reset_after_ms(2000), 59_000);
+
+        // Once the first request slides out, its slot is available again.
+        assert_eq!(limiter.remaining(61_500), 2);
+        assert_eq!(limiter.reset_after_ms(61_500), 500);
+    }
+
+    #[test]
+    fn test_rate_limiter_peek_does_not_consume() {
+        let mut limiter = RateLimiter::new(60_000, 1);
+        assert!(limiter.peek(1000).is_ok());
+        assert!(limiter.peek(1000).is_ok());
+        assert_eq!(limiter.remaining(1000), 1);
+
+        limiter.check(1000).unwrap();
+        let err = limiter.peek(2000).unwrap_err();
+        assert_eq!(err.retry_after_ms, 59_000);
+    }
+
+    // -- TokenRateLimiter --
+
+    #[test]
+    fn test_token_rate_limiter_budget() {
+        let mut limiter = TokenRateLimiter::new(60_000, 1_000);
+        assert!(limiter.check(1_000, 600).is_ok());
+        assert!(limiter.check(2_000, 300).is_ok());
+        assert_eq!(limiter.remaining(2_000), 100);
+
+        // 900 used; 200 more would overshoot until the first 600 expire.
+        let err = limiter.check(3_000, 200).unwrap_err();
+        assert_eq!(err.retry_after_ms, 58_000);
+        assert!(limiter.peek(61_001, 200).is_ok());
+        assert!(limiter.check(61_001, 200).is_ok());
+    }
+
+    #[test]
+    fn test_token_rate_limiter_record_counts_against_budget() {
+        let mut limiter = TokenRateLimiter::new(60_000, 1_000);
+        assert!(limiter.check(1_000, 100).is_ok());
+        limiter.record(1_500, 900);
+        assert_eq!(limiter.remaining(2_000), 0);
+        assert!(limiter.check(2_000, 1).is_err());
+    }
+
+    #[test]
+    fn test_token_rate_limiter_oversized_request_needs_empty_window() {
+        let mut limiter = TokenRateLimiter::new(60_000, 1_000);
+        assert!(limiter.check(1_000, 5_000).is_ok());
+        assert!(limiter.check(2_000, 5_000).is_err());
+        assert!(limiter.check(61_001, 5_000).is_ok());
+    }
+
     // -- QuotaTracker --
 
     #[test]
@@ -214,6 +418,30 @@ mod tests {
         assert!(tracker.check(&client, 50_000, &config, july).is_ok());
     }
 
+    #[test]
+    fn test_quota_reset_clears_current_period() {
+        let mut tracker = QuotaTracker::new();
+        let client = ClientId::new("team-alpha");
+        let other = ClientId::new("team-beta");
+        let period = YearMonth::new(2025, 6);
+        let config = QuotaConfig {
+            monthly_token_limit: Some(100_000),
+        };
+
+        tracker.record(&client, 100_000, period);
+        tracker.record(&other, 100_000, period);
+        assert!(tracker.check(&client, 1, &config, period).is_err());
+
+        assert_eq!(tracker.reset(&client, period), 100_000);
+        assert!(tracker.check(&client, 1, &config, period).is_ok());
+        // Other clients keep their usage.
+        assert!(tracker.check(&other, 1, &config, period).is_err());
+
+        // Usage from a previous month is not reported as cleared.
+        tracker.record(&client, 500, YearMonth::new(2025, 5));
+        assert_eq!(tracker.reset(&client, period), 0);
+    }
+
     #[test]
     fn test_quota_unlimited() {
         let tracker = QuotaTracker::new();
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use crate::core::{BackendId, BackendState, ModelId, RoutingError};
 
 // ---------------------------------------------------------------------------
@@ -8,38 +11,167 @@ use crate::core::{BackendId, BackendState, ModelId, RoutingError};
 pub enum RoutingStrategy {
     LeastLoaded,
     RoundRobin,
+    /// Lowest `cost_weight` first, least-loaded among equally cheap ones.
+    CheapestFirst,
+}
+
+impl RoutingStrategy {
+    /// The config spelling, e.g. `least-loaded`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LeastLoaded => "least-loaded",
+            Self::RoundRobin => "round-robin",
+            Self::CheapestFirst => "cheapest-first",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// RoutingPolicy — default strategy plus per-model overrides
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct RoutingPolicy {
+    default: RoutingStrategy,
+    per_model: HashMap<ModelId, RoutingStrategy>,
+}
+
+impl RoutingPolicy {
+    pub fn new(default: RoutingStrategy, per_model: HashMap<ModelId, RoutingStrategy>) -> Self {
+        Self { default, per_model }
+    }
+
+    /// The strategy configured for `model`, or the default when it has none.
+    pub fn strategy_for(&self, model: &ModelId) -> &RoutingStrategy {
+        self.per_model.get(model).unwrap_or(&self.default)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// RoundCounters — independent round-robin position per model
+// ---------------------------------------------------------------------------
+
+/// One round-robin counter per model, so traffic to one model does not skew
+/// the rotation of another. The model set is fixed at construction, which
+/// keeps lookups lock-free; unknown models share a fallback counter.
+#[derive(Debug, Default)]
+pub struct RoundCounters {
+    per_model: HashMap<ModelId, AtomicUsize>,
+    fallback: AtomicUsize,
+}
+
+impl RoundCounters {
+    pub fn new(models: impl IntoIterator<Item = ModelId>) -> Self {
+        Self {
+            per_model: models
+                .into_iter()
+                .map(|model| (model, AtomicUsize::new(0)))
+                .collect(),
+            fallback: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the round for this request to `model` and advances it.
+    pub fn next(&self, model: &ModelId) -> usize {
+        self.counter(model).fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the round the next request to `model` would get, without
+    /// advancing it.
+    pub fn peek(&self, model: &ModelId) -> usize {
+        self.counter(model).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, model: &ModelId) -> &AtomicUsize {
+        self.per_model.get(model).unwrap_or(&self.fallback)
+    }
 }
 
 // ---------------------------------------------------------------------------
 // select_backend — pure routing function (no IO, no side effects)
 // ---------------------------------------------------------------------------
 
+/// A routing decision together with how it was reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub backend: BackendId,
+    /// Every healthy backend was at capacity, so this is an overload pick.
+    pub saturated: bool,
+}
+
 /// Selects a backend for the given model using the specified strategy.
 ///
 /// Selection priority:
 /// 1. Cache affinity hint (if healthy + has capacity)
 /// 2. Strategy-based selection among backends with capacity
 /// 3. Overload fallback: strategy-based among all healthy backends
-pub fn select_backend(
-    backends: &[BackendState],
+///
+/// `backends` may be any iterator of borrowed states (e.g. a map's
+/// `values()`), so callers need not clone them into a slice.
+pub fn select_backend<'a>(
+    backends: impl IntoIterator<Item = &'a BackendState>,
     model: &ModelId,
     strategy: &RoutingStrategy,
     round: usize,
     affinity_hint: Option<&BackendId>,
 ) -> Result<BackendId, RoutingError> {
+    select_backend_detailed(backends, model, strategy, round, affinity_hint).map(|s| s.backend)
+}
+
+/// Like [`select_backend`], but also reports whether the pick was an
+/// overload fallback. Errors distinguish a model no backend serves from one
+/// whose serving backends are all unhealthy.
+pub fn select_backend_detailed<'a>(
+    backends: impl IntoIterator<Item = &'a BackendState>,
+    model: &ModelId,
+    strategy: &RoutingStrategy,
+    round: usize,
+    affinity_hint: Option<&BackendId>,
+) -> Result<Selection, RoutingError> {
     // Step 1: filter backends that serve the model
-    let serving: Vec<&BackendState> = backends.iter().filter(|b| b.serves_model(model)).collect();
-    if serving.is_empty() {
-        return Err(RoutingError::ModelNotFound {
+    let serving: Vec<&BackendState> = backends
+        .into_iter()
+        .filter(|b| b.serves_model(model))
+        .collect();
+    match serving.as_slice() {
+        [] => Err(RoutingError::ModelNotFound {
+            model: model.clone(),
+        }),
+        // With one candidate neither the hint nor the strategy can change
+        // the outcome, so skip them.
+        [only] => select_only(only, model),
+        _ => select_among(&serving, model, strategy, round, affinity_hint),
+    }
+}
+
+/// Fast path for a model served by exactly one backend. Must agree with
+/// [`select_among`] given that single backend.
+fn select_only(backend: &BackendState, model: &ModelId) -> Result<Selection, RoutingError> {
+    if !backend.is_healthy() {
+        return Err(RoutingError::NoHealthyBackend {
             model: model.clone(),
+            serving: 1,
         });
     }
+    Ok(Selection {
+        backend: backend.id.clone(),
+        saturated: !backend.has_capacity(),
+    })
+}
 
+fn select_among(
+    serving: &[&BackendState],
+    model: &ModelId,
+    strategy: &RoutingStrategy,
+    round: usize,
+    affinity_hint: Option<&BackendId>,
+) -> Result<Selection, RoutingError> {
     // Step 2: filter healthy backends
     let healthy: Vec<&BackendState> = serving.iter().filter(|b| b.is_healthy()).copied().collect();
     if healthy.is_empty() {
         return Err(RoutingError::NoHealthyBackend {
             model: model.clone(),
+            serving: serving.len(),
         });
     }
 
@@ -47,7 +179,10 @@ pub fn select_backend(
     if let Some(hint) = affinity_hint {
         if let Some(backend) = healthy.iter().find(|b| &b.id == hint) {
             if backend.has_capacity() {
-                return Ok(backend.id.clone());
+                return Ok(Selection {
+                    backend: backend.id.clone(),
+                    saturated: false,
+                });
             }
         }
     }
@@ -66,22 +201,34 @@ pub fn select_backend(
         &with_capacity
     };
 
-    let selected = apply_strategy(candidates, strategy, round);
-    Ok(selected.id.clone())
+    // `candidates` is never empty here; the error only guards the invariant.
+    let selected = apply_strategy(candidates, strategy, round).ok_or_else(|| {
+        RoutingError::NoHealthyBackend {
+            model: model.clone(),
+            serving: serving.len(),
+        }
+    })?;
+    Ok(Selection {
+        backend: selected.id.clone(),
+        saturated: with_capacity.is_empty(),
+    })
 }
 
 fn apply_strategy<'a>(
     candidates: &[&'a BackendState],
     strategy: &RoutingStrategy,
     round: usize,
-) -> &'a BackendState {
+) -> Option<&'a BackendState> {
     match strategy {
-        RoutingStrategy::LeastLoaded => candidates
+        RoutingStrategy::LeastLoaded => candidates.iter().min_by_key(|b| b.active_requests),
+        RoutingStrategy::RoundRobin => candidates.get(round.checked_rem(candidates.len())?),
+        // Saturated backends are already filtered out of `candidates` unless
+        // every one is full, so traffic spills to the next-cheapest.
+        RoutingStrategy::CheapestFirst => candidates
             .iter()
-            .min_by_key(|b| b.active_requests)
-            .expect("candidates must be non-empty"),
-        RoutingStrategy::RoundRobin => candidates[round % candidates.len()],
+            .min_by_key(|b| (b.cost_weight, b.active_requests)),
     }
+    .copied()
 }
 
 // ---------------------------------------------------------------------------
@@ -114,6 +261,57 @@ mod tests {
         state
     }
 
+    #[test]
+    fn test_round_counters_are_independent_per_model() {
+        let llama = ModelId::new("llama3");
+        let qwen = ModelId::new("qwen");
+        let counters = RoundCounters::new([llama.clone(), qwen.clone()]);
+
+        assert_eq!(counters.next(&llama), 0);
+        assert_eq!(counters.next(&qwen), 0);
+        assert_eq!(counters.next(&llama), 1);
+        assert_eq!(counters.peek(&llama), 2);
+        assert_eq!(counters.peek(&qwen), 1);
+
+        // Unknown models share the fallback counter.
+        let other = ModelId::new("gpt-4");
+        assert_eq!(counters.next(&other), 0);
+        assert_eq!(counters.next(&ModelId::new("gpt-3.5")), 1);
+    }
+
+    #[test]
+    fn test_policy_uses_model_override() {
+        let policy = RoutingPolicy::new(
+            RoutingStrategy::LeastLoaded,
+            HashMap::from([(ModelId::new("qwen-cache"), RoutingStrategy::RoundRobin)]),
+        );
+        assert_eq!(
+            policy.strategy_for(&ModelId::new("qwen-cache")),
+            &RoutingStrategy::RoundRobin
+        );
+        assert_eq!(
+            policy.strategy_for(&ModelId::new("llama3")),
+            &RoutingStrategy::LeastLoaded
+        );
+
+        // The override changes which backend wins for the same state.
+        let backends = vec![
+            make_backend("gpu-0", &["qwen-cache", "llama3"], true, 0, 4),
+            make_backend("gpu-1", &["qwen-cache", "llama3"], true, 2, 4),
+        ];
+        for model in ["qwen-cache", "llama3"] {
+            let model = ModelId::new(model);
+            let picked =
+                select_backend(&backends, &model, policy.strategy_for(&model), 1, None).unwrap();
+            let expected = if model.as_str() == "qwen-cache" {
+                "gpu-1"
+            } else {
+                "gpu-0"
+            };
+            assert_eq!(picked, BackendId::new(expected));
+        }
+    }
+
     #[test]
     fn test_affinity_hit() {
         let backends = vec![
@@ -133,6 +331,59 @@ mod tests {
         assert_eq!(result.unwrap(), BackendId::new("gpu-0"));
     }
 
+    #[test]
+    fn test_cheapest_first_picks_cheapest_healthy() {
+        let backends = vec![
+            make_backend("paid-api", &["llama3"], true, 0, 4).with_cost_weight(10),
+            make_backend("local-down", &["llama3"], false, 0, 4).with_cost_weight(0),
+            make_backend("local-box", &["llama3"], true, 3, 4).with_cost_weight(1),
+        ];
+        let result = select_backend(
+            &backends,
+            &ModelId::new("llama3"),
+            &RoutingStrategy::CheapestFirst,
+            0,
+            None,
+        );
+        assert_eq!(result.unwrap(), BackendId::new("local-box"));
+    }
+
+    #[test]
+    fn test_cheapest_first_spills_when_saturated() {
+        let backends = vec![
+            make_backend("local-box", &["llama3"], true, 4, 4).with_cost_weight(0),
+            make_backend("paid-api", &["llama3"], true, 0, 4).with_cost_weight(10),
+            make_backend("spot-gpu", &["llama3"], true, 2, 4).with_cost_weight(5),
+        ];
+        let selection = select_backend_detailed(
+            &backends,
+            &ModelId::new("llama3"),
+            &RoutingStrategy::CheapestFirst,
+            0,
+            None,
+        )
+        .unwrap();
+        assert_eq!(selection.backend, BackendId::new("spot-gpu"));
+        assert!(!selection.saturated);
+    }
+
+    #[test]
+    fn test_cheapest_first_ties_go_to_least_loaded() {
+        let backends = vec![
+            make_backend("gpu-0", &["llama3"], true, 3, 4).with_cost_weight(1),
+            make_backend("gpu-1", &["llama3"], true, 1, 4).with_cost_weight(1),
+            make_backend("gpu-2", &["llama3"], true, 0, 4).with_cost_weight(2),
+        ];
+        let result = select_backend(
+            &backends,
+            &ModelId::new("llama3"),
+            &RoutingStrategy::CheapestFirst,
+            0,
+            None,
+        );
+        assert_eq!(result.unwrap(), BackendId::new("gpu-1"));
+    }
+
     #[test]
     fn test_affinity_miss_unhealthy() {
         let backends = vec![
@@ -210,7 +461,9 @@ mod tests {
         let model = ModelId::new("gpt-4");
 
         let result = select_backend(&backends, &model, &RoutingStrategy::LeastLoaded, 0, None);
-        assert!(matches!(result, Err(RoutingError::ModelNotFound { .. })));
+        let err = result.expect_err("no backend serves gpt-4");
+        assert!(matches!(err, RoutingError::ModelNotFound { .. }));
+        assert_eq!(err.reason(), "not_served");
     }
 
     #[test]
@@ -222,7 +475,12 @@ mod tests {
         let model = ModelId::new("llama3");
 
         let result = select_backend(&backends, &model, &RoutingStrategy::LeastLoaded, 0, None);
-        assert!(matches!(result, Err(RoutingError::NoHealthyBackend { .. })));
+        let err = result.expect_err("both serving backends are unhealthy");
+        assert!(matches!(
+            err,
+            RoutingError::NoHealthyBackend { serving: 2, .. }
+        ));
+        assert_eq!(err.reason(), "all_unhealthy");
     }
 
     #[test]
@@ -237,4 +495,80 @@ mod tests {
         // Should still route even when all at capacity (overload)
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_detailed_reports_saturation() {
+        let model = ModelId::new("llama3");
+        let saturated = vec![
+            make_backend("gpu-0", &["llama3"], true, 4, 4),
+            make_backend("gpu-1", &["llama3"], false, 0, 4),
+        ];
+        let selection =
+            select_backend_detailed(&saturated, &model, &RoutingStrategy::LeastLoaded, 0, None)
+                .expect("overload still routes");
+        assert_eq!(selection.backend, BackendId::new("gpu-0"));
+        assert!(selection.saturated);
+
+        let spare = vec![
+            make_backend("gpu-0", &["llama3"], true, 4, 4),
+            make_backend("gpu-1", &["llama3"], true, 3, 4),
+        ];
+        let selection =
+            select_backend_detailed(&spare, &model, &RoutingStrategy::LeastLoaded, 0, None)
+                .expect("gpu-1 has capacity");
+        assert_eq!(selection.backend, BackendId::new("gpu-1"));
+        assert!(!selection.saturated);
+    }
+
+    #[test]
+    fn test_single_candidate_fast_path_matches_general_path() {
+        let model = ModelId::new("llama3");
+        let hints = [
+            None,
+            Some(BackendId::new("gpu-0")),
+            Some(BackendId::new("gpu-9")),
+        ];
+        let strategies = [RoutingStrategy::LeastLoaded, RoutingStrategy::RoundRobin];
+
+        for healthy in [true, false] {
+            for active in [0, 3, 4] {
+                let only = make_backend("gpu-0", &["llama3"], healthy, active, 4);
+                for hint in &hints {
+                    for strategy in &strategies {
+                        for round in 0..3 {
+                            let general =
+                                select_among(&[&only], &model, strategy, round, hint.as_ref());
+                            let fast = select_backend_detailed(
+                                [&only],
+                                &model,
+                                strategy,
+                                round,
+                                hint.as_ref(),
+                            );
+                            match (fast, general) {
+                                (Ok(fast), Ok(general)) => assert_eq!(fast, general),
+                                (Err(fast), Err(general)) => {
+                                    assert_eq!(fast.to_string(), general.to_string())
+                                }
+                                (fast, general) => panic!("{fast:?} != {general:?}"),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Several backends, one serving the model: the fast path still runs
+        // and agrees with the general path.
+        let fleet = vec![
+            make_backend("gpu-1", &["qwen"], true, 0, 4),
+            make_backend("gpu-0", &["llama3"], true, 2, 4),
+            make_backend("gpu-2", &["qwen"], true, 0, 4),
+        ];
+        let fast = select_backend_detailed(&fleet, &model, &RoutingStrategy::RoundRobin, 1, None)
+            .expect("gpu-0 is healthy");
+        let general = select_among(&[&fleet[1]], &model, &RoutingStrategy::RoundRobin, 1, None)
+            .expect("gpu-0 is healthy");
+        assert_eq!(fast, general);
+    }
 }
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use crate::core::{ContentPart, Message, MessageContent, ModelId};
+
+// ---------------------------------------------------------------------------
+// TokenCounter — pluggable input-token estimation
+// ---------------------------------------------------------------------------
+
+/// Decoded audio assumed per second of a clip: 16 kB, i.e. 8 kHz 16-bit
+/// mono PCM or a 128 kbit/s compressed stream.
+const AUDIO_BYTES_PER_SECOND: usize = 16_000;
+
+/// Tokens charged per second of input audio.
+const AUDIO_TOKENS_PER_SECOND: u64 = 10;
+
+/// Estimated tokens for a base64 audio clip, from its duration rather than
+/// its text length, since the encoded bytes never reach a text tokenizer.
+pub fn estimate_audio_tokens(base64_data: &str) -> u64 {
+    let decoded_bytes = base64_data.len() / 4 * 3;
+    decoded_bytes.div_ceil(AUDIO_BYTES_PER_SECOND) as u64 * AUDIO_TOKENS_PER_SECOND
+}
+
+/// Estimates how many tokens a model will see for a prompt.
+///
+/// The estimate feeds quota checks and `RequestMetadata::estimated_input_tokens`;
+/// implementations trade accuracy for cost.
+pub trait TokenCounter: Send + Sync {
+    fn count_text(&self, text: &str) -> u64;
+
+    fn count_messages(&self, messages: &[Message]) -> u64 {
+        messages
+            .iter()
+            .map(|m| match &m.content {
+                MessageContent::Text(t) => self.count_text(t),
+                MessageContent::Parts(parts) => parts
+                    .iter()
+                    .map(|p| match p {
+                        ContentPart::Text { text } => self.count_text(text),
+                        ContentPart::ImageUrl { url, .. } => self.count_text(url),
+                        ContentPart::InputAudio { data, .. } => estimate_audio_tokens(data),
+                    })
+                    .sum(),
+            })
+            .sum()
+    }
+}
+
+/// Byte length divided by four: cheap, and close enough for English prose.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeuristicTokenCounter;
+
+impl TokenCounter for HeuristicTokenCounter {
+    fn count_text(&self, text: &str) -> u64 {
+        (text.len() / 4) as u64
+    }
+
+    // Divides once over the whole prompt so short messages are not each
+    // rounded down to zero.
+    fn count_messages(&self, messages: &[Message]) -> u64 {
+        let mut total_bytes = 0;
+        let mut audio_tokens = 0;
+        for m in messages {
+            match &m.content {
+                MessageContent::Text(t) => total_bytes += t.len(),
+                MessageContent::Parts(parts) => {
+                    for p in parts {
+                        match p {
+                            ContentPart::Text { text } => total_bytes += text.len(),
+                            ContentPart::ImageUrl { url, .. } => total_bytes += url.len(),
+                            ContentPart::InputAudio { data, .. } => {
+                                audio_tokens += estimate_audio_tokens(data);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        (total_bytes / 4) as u64 + audio_tokens
+    }
+}
+
+// ---------------------------------------------------------------------------
+// TokenCounterRegistry — per-model-family counter selection
+// ---------------------------------------------------------------------------
+
+/// Picks a [`TokenCounter`] by model-name prefix (e.g. `"gpt-4"`), falling
+/// back to [`HeuristicTokenCounter`] for unknown families.
+#[derive(Clone)]
+pub struct TokenCounterRegistry {
+    families: Vec<(String, Arc<dyn TokenCounter>)>,
+    fallback: Arc<dyn TokenCounter>,
+}
+
+impl Default for TokenCounterRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenCounterRegistry {
+    pub fn new() -> Self {
+        Self {
+            families: Vec::new(),
+            fallback: Arc::new(HeuristicTokenCounter),
+        }
+    }
+
+    /// Uses `counter` for every model whose id starts with `prefix`.
+    /// When several prefixes match, the longest wins.
+    pub fn with_family(
+        mut self,
+        prefix: impl Into<String>,
+        counter: Arc<dyn TokenCounter>,
+    ) -> Self {
+        self.families.push((prefix.into(), counter));
+        self
+    }
+
+    pub fn counter_for(&self, model: &ModelId) -> &dyn TokenCounter {
+        self.families
+            .iter()
+            .filter(|(prefix, _)| model.as_str().starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.fallback.as_ref(), |(_, counter)| counter.as_ref())
+    }
+
+    pub fn count(&self, model: &ModelId, messages: &[Message]) -> u64 {
+        self.counter_for(model).count_messages(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Role;
+
+    /// Counts whitespace-separated words; stands in for a real tokenizer.
+    struct WordCounter;
+
+    impl TokenCounter for WordCounter {
+        fn count_text(&self, text: &str) -> u64 {
+            text.split_whitespace().count() as u64
+        }
+    }
+
+    fn user(text: &str) -> Message {
+        Message {
+            role: Role::User,
+            content: MessageContent::Text(text.to_owned()),
+            name: None,
+            tool_call_id: None,
+        }
+    }
+
+    #[test]
+    fn test_heuristic_counts_bytes_over_four() {
+        let messages = [user("Hello, world!"), user("abc")];
+        // 13 + 3 bytes, divided once.
+        assert_eq!(HeuristicTokenCounter.count_messages(&messages), 4);
+        // Multi-byte text is counted by UTF-8 length, not characters.
+        assert_eq!(HeuristicTokenCounter.count_text("日本語"), 2);
+    }
+
+    #[test]
+    fn test_heuristic_versus_word_counter() {
+        let messages = [user("the quick brown fox jumps over the lazy dog")];
+        assert_eq!(HeuristicTokenCounter.count_messages(&messages), 10);
+        assert_eq!(WordCounter.count_messages(&messages), 9);
+    }
+
+    #[test]
+    fn test_audio_counted_by_duration_not_length() {
+        // Three seconds of audio as base64: 48 kB decoded, 64 kB encoded.
+        let clip = "A".repeat(64_000);
+        let messages = [Message {
+            role: Role::User,
+            content: MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "transcribe".to_owned(),
+                },
+                ContentPart::InputAudio {
+                    data: clip.clone(),
+                    format: "wav".to_owned(),
+                },
+            ]),
+            name: None,
+            tool_call_id: None,
+        }];
+
+        assert_eq!(estimate_audio_tokens(&clip), 30);
+        assert_eq!(HeuristicTokenCounter.count_messages(&messages), 32);
+        assert_eq!(WordCounter.count_messages(&messages), 31);
+    }
+
+    #[test]
+    fn test_registry_picks_counter_by_family() {
+        let registry = TokenCounterRegistry::new()
+            .with_family("gpt-", Arc::new(WordCounter))
+            .with_family("gpt-4o", Arc::new(HeuristicTokenCounter));
+        let messages = [user("one two three four five six seven eight")];
+
+        // "gpt-" family uses the word counter.
+        assert_eq!(registry.count(&ModelId::new("gpt-3.5-turbo"), &messages), 8);
+        // Longest prefix wins.
+        assert_eq!(registry.count(&ModelId::new("gpt-4o-mini"), &messages), 9);
+        // Unknown families fall back to the heuristic.
+        assert_eq!(registry.count(&ModelId::new("llama3-70b"), &messages), 9);
+    }
+}
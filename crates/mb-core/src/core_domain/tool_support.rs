@@ -0,0 +1,166 @@
+use std::borrow::Cow;
+
+use crate::core::{AdapterError, CanonicalRequest, ContentPart, Message, MessageContent, Role};
+
+// ---------------------------------------------------------------------------
+// ToolSupport — per-backend handling of tool definitions and tool messages
+// ---------------------------------------------------------------------------
+
+/// How a backend copes with tool definitions and `tool` role messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToolSupport {
+    /// The backend understands tools; forward everything as-is.
+    #[default]
+    Native,
+    /// Reject tool-using requests with [`AdapterError::UnsupportedFeature`].
+    Reject,
+    /// Drop tool definitions and rewrite `tool` messages as plain user text.
+    Downgrade,
+}
+
+impl ToolSupport {
+    /// Returns the request to serialize for a backend with this support
+    /// level, borrowing it unchanged whenever possible.
+    pub fn apply(self, req: &CanonicalRequest) -> Result<Cow<'_, CanonicalRequest>, AdapterError> {
+        if self == Self::Native || !uses_tools(req) {
+            return Ok(Cow::Borrowed(req));
+        }
+        if self == Self::Reject {
+            return Err(AdapterError::UnsupportedFeature(
+                "tools are not supported by this backend".to_owned(),
+            ));
+        }
+
+        let mut downgraded = req.clone();
+        downgraded.tools = None;
+        downgraded.tool_choice = None;
+        downgraded.parallel_tool_calls = None;
+        for message in &mut downgraded.messages {
+            if message.role == Role::Tool {
+                downgrade_tool_message(message);
+            }
+        }
+        Ok(Cow::Owned(downgraded))
+    }
+}
+
+pub(super) fn uses_tools(req: &CanonicalRequest) -> bool {
+    req.tools.as_ref().is_some_and(|tools| !tools.is_empty())
+        || req.tool_choice.is_some()
+        || req.messages.iter().any(|m| m.role == Role::Tool)
+}
+
+/// Turns a tool result into a user turn, keeping the call id as a text label
+/// so the model can still tell which call it answers.
+fn downgrade_tool_message(message: &mut Message) {
+    let label = match message.tool_call_id.take() {
+        Some(id) => format!("[tool result {id}]"),
+        None => "[tool result]".to_owned(),
+    };
+    message.role = Role::User;
+    message.name = None;
+    message.content = match std::mem::replace(&mut message.content, MessageContent::Parts(vec![])) {
+        MessageContent::Text(text) => MessageContent::Text(format!("{label}\n{text}")),
+        MessageContent::Parts(mut parts) => {
+            parts.insert(0, ContentPart::Text { text: label });
+            MessageContent::Parts(parts)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        ClientId, GenerationParams, ModelId, RequestId, RequestMetadata, ToolDefinition,
+    };
+
+    fn message(role: Role, text: &str, tool_call_id: Option<&str>) -> Message {
+        Message {
+            role,
+            content: MessageContent::Text(text.to_owned()),
+            name: tool_call_id.map(|_| "get_weather".to_owned()),
+            tool_call_id: tool_call_id.map(str::to_owned),
+        }
+    }
+
+    fn tool_request() -> CanonicalRequest {
+        CanonicalRequest {
+            model: ModelId::new("llama3-70b"),
+            messages: vec![
+                message(Role::User, "Weather in Paris?", None),
+                message(Role::Assistant, "", None),
+                message(Role::Tool, "18C and sunny", Some("call_1")),
+            ],
+            params: GenerationParams::default(),
+            tools: Some(vec![ToolDefinition {
+                name: "get_weather".to_owned(),
+                description: None,
+                parameters: serde_json::json!({"type": "object"}),
+            }]),
+            tool_choice: None,
+            parallel_tool_calls: None,
+            logprobs: None,
+            top_logprobs: None,
+            response_format: None,
+            echo: None,
+            suffix: None,
+            n: None,
+            stream: false,
+            metadata: RequestMetadata {
+                request_id: RequestId::new("req-test"),
+                client_id: ClientId::new("client-test"),
+                estimated_input_tokens: 10,
+                prefix_hash: None,
+                app_title: None,
+                app_referer: None,
+                client_metadata: None,
+                store: false,
+                provider_hint: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_native_borrows_request() {
+        let req = tool_request();
+        let applied = ToolSupport::Native.apply(&req).unwrap();
+        assert!(matches!(applied, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_reject_only_tool_requests() {
+        let req = tool_request();
+        assert!(matches!(
+            ToolSupport::Reject.apply(&req),
+            Err(AdapterError::UnsupportedFeature(_))
+        ));
+
+        let mut plain = tool_request();
+        plain.tools = None;
+        plain.messages.truncate(1);
+        assert!(matches!(
+            ToolSupport::Reject.apply(&plain),
+            Ok(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_downgrade_rewrites_tool_messages() {
+        let req = tool_request();
+        let applied = ToolSupport::Downgrade.apply(&req).unwrap();
+
+        assert!(applied.tools.is_none());
+        assert!(applied.tool_choice.is_none());
+        let last = &applied.messages[2];
+        assert_eq!(last.role, Role::User);
+        assert_eq!(last.tool_call_id, None);
+        assert_eq!(last.name, None);
+        assert_eq!(
+            last.content,
+            MessageContent::Text("[tool result call_1]\n18C and sunny".to_owned())
+        );
+        // Other messages are untouched.
+        assert_eq!(applied.messages[0], req.messages[0]);
+    }
+}
@@ -1,16 +1,34 @@
+use std::collections::HashSet;
+
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use uuid::Uuid;
 
-use crate::models::{DpoMetadata, DpoPair, TurnRole, Verdict};
+use crate::models::{DpoMetadata, DpoPair, SftMessage, SftSample, TurnRole, Verdict};
 use crate::store::{FeedbackError, FeedbackStore};
 
 #[derive(Debug, Clone, Default)]
 pub struct DpoExportFilter {
     pub annotator_id: Option<String>,
     pub model_id: Option<String>,
+    /// Keep only pairs drawn from this conversation.
+    pub conversation_id: Option<Uuid>,
     pub verdict: Option<Verdict>,
     pub since: Option<DateTime<Utc>>,
     pub until: Option<DateTime<Utc>>,
+    /// Keep only annotations scored at least this high; unscored ones are dropped.
+    pub min_score: Option<u8>,
+    /// Collapse repeated pairs, keeping the most recent annotation.
+    pub dedup_by: Option<DpoDedup>,
+}
+
+/// What makes two exported DPO pairs duplicates of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DpoDedup {
+    /// Annotations of the same assistant turn, e.g. by several annotators.
+    TurnId,
+    /// Pairs with identical prompt and chosen response.
+    PromptChosen,
 }
 
 /// Export DPO pairs from stored annotations.
@@ -21,8 +39,14 @@ pub fn export_dpo_pairs(
     store: &dyn FeedbackStore,
     filter: &DpoExportFilter,
 ) -> Result<Vec<DpoPair>, FeedbackError> {
-    let annotations = store.list_annotations()?;
-    let mut pairs = Vec::new();
+    // A single conversation is looked up directly instead of scanning every
+    // annotation.
+    let annotations = match filter.conversation_id {
+        Some(conversation_id) => store.get_annotations_for_conversation(&conversation_id)?,
+        None => store.list_annotations()?,
+    };
+    // Paired with the annotated turn id for `DpoDedup::TurnId`.
+    let mut pairs: Vec<(Uuid, DpoPair)> = Vec::new();
 
     for annotation in annotations {
         if let Some(expected_annotator) = filter.annotator_id.as_deref() {
@@ -41,6 +65,12 @@ pub fn export_dpo_pairs(
             continue;
         }
 
+        if let Some(min_score) = filter.min_score {
+            if annotation.score.is_none_or(|score| score < min_score) {
+                continue;
+            }
+        }
+
         if let Some(since) = filter.since.as_ref() {
             if annotation.created_at < *since {
                 continue;
@@ -69,6 +99,13 @@ pub fn export_dpo_pairs(
             continue;
         }
 
+        if filter
+            .conversation_id
+            .is_some_and(|id| id != annotated_turn.conversation_id)
+        {
+            continue;
+        }
+
         let Some(conversation) = store.get_conversation_by_id(&annotated_turn.conversation_id)?
         else {
             continue;
@@ -93,21 +130,92 @@ pub fn export_dpo_pairs(
             continue;
         };
 
-        pairs.push(DpoPair {
-            prompt: prompt_turn.content.clone(),
-            chosen: chosen_response.to_string(),
-            rejected: annotated_turn.content,
-            metadata: DpoMetadata {
-                conversation_id: conversation.id,
-                model_id: conversation.model_id,
-                annotator_id: annotation.annotator_id,
-                verdict: annotation.verdict,
-                annotated_at: annotation.created_at,
+        pairs.push((
+            annotated_turn.id,
+            DpoPair {
+                prompt: prompt_turn.content.clone(),
+                chosen: chosen_response.to_string(),
+                rejected: annotated_turn.content,
+                metadata: DpoMetadata {
+                    conversation_id: conversation.id,
+                    model_id: conversation.model_id,
+                    annotator_id: annotation.annotator_id,
+                    verdict: annotation.verdict,
+                    annotated_at: annotation.created_at,
+                    temperature: annotated_turn.temperature,
+                    seed: annotated_turn.seed,
+                },
             },
-        });
+        ));
     }
 
-    Ok(pairs)
+    Ok(dedup_pairs(pairs, filter.dedup_by))
+}
+
+/// Drops all but the last of each group of duplicates. Annotations are
+/// listed oldest first, so the most recent one survives, in its place.
+fn dedup_pairs(pairs: Vec<(Uuid, DpoPair)>, dedup_by: Option<DpoDedup>) -> Vec<DpoPair> {
+    let Some(dedup_by) = dedup_by else {
+        return pairs.into_iter().map(|(_, pair)| pair).collect();
+    };
+    let mut seen_turns = HashSet::new();
+    let mut seen_texts = HashSet::new();
+    let mut kept: Vec<DpoPair> = pairs
+        .into_iter()
+        .rev()
+        .filter(|(turn_id, pair)| match dedup_by {
+            DpoDedup::TurnId => seen_turns.insert(*turn_id),
+            DpoDedup::PromptChosen => seen_texts.insert((pair.prompt.clone(), pair.chosen.clone())),
+        })
+        .map(|(_, pair)| pair)
+        .collect();
+    kept.reverse();
+    kept
+}
+
+/// Build a supervised fine-tuning sample from one conversation.
+///
+/// Turns are kept in order. An assistant turn annotated `Refused` or `Biased`
+/// with a non-empty `expected_response` is replaced by that response, so the
+/// sample teaches the corrected answer. Returns `None` for an unknown id.
+pub fn export_sft_sample(
+    store: &dyn FeedbackStore,
+    conversation_id: &Uuid,
+) -> Result<Option<SftSample>, FeedbackError> {
+    let Some(conversation) = store.get_conversation_by_id(conversation_id)? else {
+        return Ok(None);
+    };
+    let turns = store.get_turns_for_conversation(&conversation.id)?;
+    let annotations = store.get_annotations_for_conversation(&conversation.id)?;
+
+    let messages = turns
+        .into_iter()
+        .map(|turn| {
+            let correction = annotations
+                .iter()
+                .filter(|annotation| annotation.turn_id == turn.id)
+                .filter(|annotation| {
+                    matches!(annotation.verdict, Verdict::Refused | Verdict::Biased)
+                })
+                .filter_map(|annotation| annotation.expected_response.as_deref())
+                .map(str::trim)
+                .rfind(|value| !value.is_empty());
+            let content = match correction {
+                Some(expected) if turn.role == TurnRole::Assistant => expected.to_string(),
+                _ => turn.content,
+            };
+            SftMessage {
+                role: turn.role,
+                content,
+            }
+        })
+        .collect();
+
+    Ok(Some(SftSample {
+        conversation_id: conversation.id,
+        model_id: conversation.model_id,
+        messages,
+    }))
 }
 
 #[derive(Serialize)]
@@ -117,27 +225,52 @@ struct ExportJsonPair<'a> {
     rejected: &'a str,
 }
 
-pub fn export_to_json(pairs: &[DpoPair]) -> Result<String, FeedbackError> {
-    let export_pairs: Vec<ExportJsonPair<'_>> = pairs
-        .iter()
-        .map(|pair| ExportJsonPair {
+impl<'a> From<&'a DpoPair> for ExportJsonPair<'a> {
+    fn from(pair: &'a DpoPair) -> Self {
+        Self {
             prompt: pair.prompt.as_str(),
             chosen: pair.chosen.as_str(),
             rejected: pair.rejected.as_str(),
-        })
-        .collect();
+        }
+    }
+}
+
+/// The `export_to_json` array as a JSON value, for embedding in a response.
+pub fn export_to_value(pairs: &[DpoPair]) -> Result<serde_json::Value, FeedbackError> {
+    let export_pairs: Vec<ExportJsonPair<'_>> = pairs.iter().map(ExportJsonPair::from).collect();
+
+    let value = serde_json::to_value(&export_pairs)?;
+    Ok(value)
+}
+
+pub fn export_to_json(pairs: &[DpoPair]) -> Result<String, FeedbackError> {
+    let export_pairs: Vec<ExportJsonPair<'_>> = pairs.iter().map(ExportJsonPair::from).collect();
 
     let json = serde_json::to_string(&export_pairs)?;
     Ok(json)
 }
 
+/// One JSON object per line, newline-terminated; the usual input format of
+/// fine-tuning tools.
+pub fn export_to_jsonl(pairs: &[DpoPair]) -> Result<String, FeedbackError> {
+    let mut jsonl = String::new();
+    for pair in pairs {
+        jsonl.push_str(&serde_json::to_string(&ExportJsonPair::from(pair))?);
+        jsonl.push('\n');
+    }
+    Ok(jsonl)
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{DateTime, Utc};
     use mb_core::core::{ClientId, ModelId};
     use uuid::Uuid;
 
-    use super::{export_dpo_pairs, export_to_json, DpoExportFilter};
+    use super::{
+        export_dpo_pairs, export_sft_sample, export_to_json, export_to_jsonl, DpoDedup,
+        DpoExportFilter,
+    };
     use crate::models::{Annotation, Conversation, Turn, TurnRole, Verdict};
     use crate::store::{FeedbackStore, SqliteFeedbackStore};
 
@@ -159,12 +292,16 @@ mod tests {
         annotator_id: &str,
         expected_response: &str,
         base_ts: &str,
-    ) {
+        score: Option<u8>,
+    ) -> Uuid {
         let conversation = Conversation {
             id: Uuid::new_v4(),
             client_id: ClientId::new("team-alpha"),
             model_id: ModelId::new(model_id),
             created_at: ts(base_ts),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         store
             .insert_conversation(&conversation)
@@ -177,6 +314,9 @@ mod tests {
             content: "How do I handle this topic?".to_string(),
             token_count: 6,
             created_at: ts("2026-01-01T10:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         store.insert_turn(&user_turn).expect("insert user turn");
 
@@ -187,6 +327,9 @@ mod tests {
             content: "I cannot help with that.".to_string(),
             token_count: 5,
             created_at: ts("2026-01-01T10:00:02Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         store
             .insert_turn(&assistant_turn)
@@ -199,11 +342,13 @@ mod tests {
             verdict: Verdict::Refused,
             expected_direction: Some("Provide balanced explanation".to_string()),
             expected_response: Some(expected_response.to_string()),
+            score,
             created_at: ts("2026-01-01T10:00:03Z"),
         };
         store
             .insert_annotation(&annotation)
             .expect("insert annotation");
+        conversation.id
     }
 
     #[test]
@@ -225,6 +370,9 @@ mod tests {
             client_id: ClientId::new("team-alpha"),
             model_id: ModelId::new("llama3-70b"),
             created_at: ts("2026-01-01T11:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         store
             .insert_conversation(&conversation)
@@ -237,6 +385,9 @@ mod tests {
             content: "Tell me the history.".to_string(),
             token_count: 4,
             created_at: ts("2026-01-01T11:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         store.insert_turn(&user_turn).expect("insert user turn");
 
@@ -247,6 +398,9 @@ mod tests {
             content: "Here is a balanced answer.".to_string(),
             token_count: 5,
             created_at: ts("2026-01-01T11:00:02Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         store
             .insert_turn(&assistant_turn)
@@ -259,6 +413,7 @@ mod tests {
             verdict: Verdict::Satisfactory,
             expected_direction: None,
             expected_response: Some("Same response".to_string()),
+            score: None,
             created_at: ts("2026-01-01T11:00:03Z"),
         };
         store
@@ -280,6 +435,7 @@ mod tests {
             "ann-1",
             "Offer neutral context and evidence.",
             "2026-01-01T10:00:00Z",
+            None,
         );
 
         let pairs =
@@ -296,6 +452,13 @@ mod tests {
         assert!(json.contains("\"prompt\":\"How do I handle this topic?\""));
         assert!(json.contains("\"chosen\":\"Offer neutral context and evidence.\""));
         assert!(json.contains("\"rejected\":\"I cannot help with that.\""));
+
+        let jsonl = export_to_jsonl(&pairs).expect("export jsonl");
+        assert_eq!(jsonl.lines().count(), 1);
+        assert_eq!(
+            jsonl.trim_end(),
+            json.trim_start_matches('[').trim_end_matches(']')
+        );
     }
 
     #[test]
@@ -308,6 +471,7 @@ mod tests {
             "ann-1",
             "Expected response for model A",
             "2026-01-01T12:00:00Z",
+            None,
         );
         insert_refused_annotation_with_expected(
             &store,
@@ -315,6 +479,7 @@ mod tests {
             "ann-2",
             "Expected response for model B",
             "2026-01-01T13:00:00Z",
+            None,
         );
 
         let filter = DpoExportFilter {
@@ -327,4 +492,245 @@ mod tests {
         assert_eq!(pairs[0].metadata.model_id.as_str(), "qwen2.5-14b");
         assert_eq!(pairs[0].chosen, "Expected response for model B");
     }
+
+    #[test]
+    fn test_export_filter_by_min_score() {
+        let store = setup_store();
+
+        insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "Low score response",
+            "2026-01-01T12:00:00Z",
+            Some(2),
+        );
+        insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "High score response",
+            "2026-01-01T13:00:00Z",
+            Some(4),
+        );
+        insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "Unscored response",
+            "2026-01-01T14:00:00Z",
+            None,
+        );
+
+        let all = export_dpo_pairs(&store, &DpoExportFilter::default()).expect("export all");
+        assert_eq!(all.len(), 3);
+
+        let filter = DpoExportFilter {
+            min_score: Some(3),
+            ..DpoExportFilter::default()
+        };
+        let pairs = export_dpo_pairs(&store, &filter).expect("export dpo pairs");
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].chosen, "High score response");
+    }
+
+    #[test]
+    fn test_export_filter_by_conversation() {
+        let store = setup_store();
+
+        insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "First conversation response",
+            "2026-01-01T12:00:00Z",
+            None,
+        );
+        let second = insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "Second conversation response",
+            "2026-01-01T13:00:00Z",
+            None,
+        );
+
+        let filter = DpoExportFilter {
+            conversation_id: Some(second),
+            ..DpoExportFilter::default()
+        };
+        let pairs = export_dpo_pairs(&store, &filter).expect("export dpo pairs");
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].metadata.conversation_id, second);
+        assert_eq!(pairs[0].chosen, "Second conversation response");
+    }
+
+    #[test]
+    fn test_export_dedup_by_turn_keeps_latest_annotation() {
+        let store = setup_store();
+        let conversation_id = insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "First annotator response",
+            "2026-01-01T10:00:00Z",
+            None,
+        );
+        let turns = store
+            .get_turns_for_conversation(&conversation_id)
+            .expect("get turns");
+        let second = Annotation {
+            id: Uuid::new_v4(),
+            turn_id: turns[1].id,
+            annotator_id: "ann-2".to_string(),
+            verdict: Verdict::Biased,
+            expected_direction: None,
+            expected_response: Some("Second annotator response".to_string()),
+            score: None,
+            created_at: ts("2026-01-01T10:00:04Z"),
+        };
+        store
+            .insert_annotation(&second)
+            .expect("insert second annotation");
+
+        let all = export_dpo_pairs(&store, &DpoExportFilter::default()).expect("export all");
+        assert_eq!(all.len(), 2);
+
+        let filter = DpoExportFilter {
+            dedup_by: Some(DpoDedup::TurnId),
+            ..DpoExportFilter::default()
+        };
+        let pairs = export_dpo_pairs(&store, &filter).expect("export dpo pairs");
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].chosen, "Second annotator response");
+        assert_eq!(pairs[0].metadata.annotator_id, "ann-2");
+    }
+
+    #[test]
+    fn test_export_dedup_by_prompt_and_chosen() {
+        let store = setup_store();
+        for annotator in ["ann-1", "ann-2"] {
+            insert_refused_annotation_with_expected(
+                &store,
+                "llama3-70b",
+                annotator,
+                "Offer neutral context and evidence.",
+                "2026-01-01T10:00:00Z",
+                None,
+            );
+        }
+
+        // Different turns, so only the text-based mode collapses them.
+        let by_turn = DpoExportFilter {
+            dedup_by: Some(DpoDedup::TurnId),
+            ..DpoExportFilter::default()
+        };
+        assert_eq!(export_dpo_pairs(&store, &by_turn).expect("export").len(), 2);
+
+        let by_text = DpoExportFilter {
+            dedup_by: Some(DpoDedup::PromptChosen),
+            ..DpoExportFilter::default()
+        };
+        let pairs = export_dpo_pairs(&store, &by_text).expect("export dpo pairs");
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].chosen, "Offer neutral context and evidence.");
+    }
+
+    #[test]
+    fn test_export_carries_generation_params() {
+        let store = setup_store();
+        let conversation = Conversation {
+            id: Uuid::new_v4(),
+            client_id: ClientId::new("team-alpha"),
+            model_id: ModelId::new("llama3-70b"),
+            created_at: ts("2026-01-01T10:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
+        };
+        store
+            .insert_conversation(&conversation)
+            .expect("insert conversation");
+        let user_turn = Turn {
+            id: Uuid::new_v4(),
+            conversation_id: conversation.id,
+            role: TurnRole::User,
+            content: "How do I handle this topic?".to_string(),
+            token_count: 6,
+            created_at: ts("2026-01-01T10:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
+        };
+        store.insert_turn(&user_turn).expect("insert user turn");
+        let assistant_turn = Turn {
+            id: Uuid::new_v4(),
+            conversation_id: conversation.id,
+            role: TurnRole::Assistant,
+            content: "I cannot help with that.".to_string(),
+            token_count: 5,
+            created_at: ts("2026-01-01T10:00:02Z"),
+            temperature: Some(0.2),
+            seed: Some(42),
+            content_hash: None,
+        };
+        store
+            .insert_turn(&assistant_turn)
+            .expect("insert assistant turn");
+        let annotation = Annotation {
+            id: Uuid::new_v4(),
+            turn_id: assistant_turn.id,
+            annotator_id: "ann-1".to_string(),
+            verdict: Verdict::Refused,
+            expected_direction: None,
+            expected_response: Some("Offer neutral context.".to_string()),
+            score: None,
+            created_at: ts("2026-01-01T10:00:03Z"),
+        };
+        store
+            .insert_annotation(&annotation)
+            .expect("insert annotation");
+
+        let pairs =
+            export_dpo_pairs(&store, &DpoExportFilter::default()).expect("export dpo pairs");
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].metadata.model_id.as_str(), "llama3-70b");
+        assert_eq!(pairs[0].metadata.temperature, Some(0.2));
+        assert_eq!(pairs[0].metadata.seed, Some(42));
+    }
+
+    #[test]
+    fn test_export_sft_sample_uses_expected_response() {
+        let store = setup_store();
+        let conversation_id = insert_refused_annotation_with_expected(
+            &store,
+            "llama3-70b",
+            "ann-1",
+            "Offer neutral context and evidence.",
+            "2026-01-01T10:00:00Z",
+            None,
+        );
+
+        let sample = export_sft_sample(&store, &conversation_id)
+            .expect("export sft sample")
+            .expect("conversation exists");
+
+        assert_eq!(sample.conversation_id, conversation_id);
+        assert_eq!(sample.model_id.as_str(), "llama3-70b");
+        assert_eq!(sample.messages.len(), 2);
+        assert_eq!(sample.messages[0].role, TurnRole::User);
+        assert_eq!(sample.messages[0].content, "How do I handle this topic?");
+        assert_eq!(sample.messages[1].role, TurnRole::Assistant);
+        assert_eq!(
+            sample.messages[1].content,
+            "Offer neutral context and evidence."
+        );
+
+        let missing = export_sft_sample(&store, &Uuid::new_v4()).expect("export sft sample");
+        assert!(missing.is_none());
+    }
 }
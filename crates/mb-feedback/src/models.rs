@@ -1,6 +1,9 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use mb_core::core::{ClientId, ModelId};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 /// Classification of a model response by a human annotator.
@@ -22,6 +25,15 @@ pub struct Conversation {
     pub client_id: ClientId,
     pub model_id: ModelId,
     pub created_at: DateTime<Utc>,
+    /// Client app name from the `X-Title` attribution header.
+    #[serde(default)]
+    pub app_title: Option<String>,
+    /// Client app URL from the `HTTP-Referer` attribution header.
+    #[serde(default)]
+    pub app_referer: Option<String>,
+    /// The OpenAI-style `metadata` map sent with the first request.
+    #[serde(default)]
+    pub metadata: Option<BTreeMap<String, String>>,
 }
 
 /// A single turn (message) in a conversation.
@@ -33,6 +45,24 @@ pub struct Turn {
     pub content: String,
     pub token_count: u32,
     pub created_at: DateTime<Utc>,
+    /// Sampling temperature of the request that produced an assistant turn.
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Sampling seed of the request that produced an assistant turn.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// [`content_hash`] of the original text, which survives redaction so a
+    /// resent history can be matched against stored turns. Never exported.
+    #[serde(skip)]
+    pub content_hash: Option<String>,
+}
+
+/// Hex SHA-256 of a turn's text, as stored in [`Turn::content_hash`].
+pub fn content_hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -52,6 +82,9 @@ pub struct Annotation {
     pub verdict: Verdict,
     pub expected_direction: Option<String>,
     pub expected_response: Option<String>,
+    /// Optional quality score from 1 (worst) to 5 (best).
+    #[serde(default)]
+    pub score: Option<u8>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -79,4 +112,24 @@ pub struct DpoMetadata {
     pub annotator_id: String,
     pub verdict: Verdict,
     pub annotated_at: DateTime<Utc>,
+    /// Sampling temperature that produced the rejected response, if recorded.
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Sampling seed that produced the rejected response, if recorded.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// A supervised fine-tuning sample built from one conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftSample {
+    pub conversation_id: Uuid,
+    pub model_id: ModelId,
+    pub messages: Vec<SftMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftMessage {
+    pub role: TurnRole,
+    pub content: String,
 }
@@ -1,6 +1,7 @@
+use std::collections::BTreeMap;
 use std::io::{Error as IoError, ErrorKind};
 use std::path::Path;
-use std::sync::Mutex;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use mb_core::core::{ClientId, ModelId};
@@ -10,48 +11,28 @@ use uuid::Uuid;
 
 use crate::models::{Annotation, ClaRecord, Conversation, Turn, TurnRole, Verdict};
 
-const SCHEMA_VERSION: i32 = 1;
-const SCHEMA_SQL: &str = r#"
-CREATE TABLE IF NOT EXISTS conversations (
-    id TEXT PRIMARY KEY,
-    client_id TEXT NOT NULL,
-    model_id TEXT NOT NULL,
-    created_at TEXT NOT NULL
-);
-
-CREATE TABLE IF NOT EXISTS turns (
-    id TEXT PRIMARY KEY,
-    conversation_id TEXT NOT NULL REFERENCES conversations(id),
-    role TEXT NOT NULL,
-    content TEXT NOT NULL,
-    token_count INTEGER NOT NULL DEFAULT 0,
-    created_at TEXT NOT NULL
-);
-CREATE INDEX IF NOT EXISTS idx_turns_conversation ON turns(conversation_id);
-
-CREATE TABLE IF NOT EXISTS annotations (
-    id TEXT PRIMARY KEY,
-    turn_id TEXT NOT NULL REFERENCES turns(id),
-    annotator_id TEXT NOT NULL,
-    verdict TEXT NOT NULL,
-    expected_direction TEXT,
-    expected_response TEXT,
-    created_at TEXT NOT NULL
-);
-CREATE INDEX IF NOT EXISTS idx_annotations_turn ON annotations(turn_id);
-CREATE INDEX IF NOT EXISTS idx_annotations_annotator ON annotations(annotator_id);
-
-CREATE TABLE IF NOT EXISTS cla_records (
-    client_id TEXT PRIMARY KEY,
-    signed_at TEXT NOT NULL,
-    github_username TEXT
-);
-"#;
+mod migrations;
+mod pool;
+
+use pool::{ConnectionPool, PooledConnection};
+
+/// Valid range for `Annotation::score`.
+pub const SCORE_RANGE: std::ops::RangeInclusive<u8> = 1..=5;
+
+/// Connections opened by [`SqliteFeedbackStore::new`].
+pub const DEFAULT_POOL_SIZE: usize = 4;
+/// How long a connection waits on another connection's write lock before
+/// failing with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Maximum allowed length for stored content fields (64 KB).
 const MAX_CONTENT_LEN: usize = 65_536;
 /// Maximum allowed length for ID and short string fields (256 bytes).
 const MAX_ID_LEN: usize = 256;
+/// Maximum allowed length for URL fields such as `app_referer`.
+const MAX_URL_LEN: usize = 2_048;
+/// Maximum allowed length for a conversation's serialized `metadata` map.
+const MAX_METADATA_LEN: usize = 16_384;
 
 #[derive(Debug, thiserror::Error)]
 pub enum FeedbackError {
@@ -63,6 +44,10 @@ pub enum FeedbackError {
     Serialization(#[from] serde_json::Error),
     #[error("input too long: {field} exceeds {max} bytes")]
     InputTooLong { field: &'static str, max: usize },
+    #[error("score out of range: {0} (expected 1-5)")]
+    ScoreOutOfRange(u8),
+    #[error("database schema version {found} is newer than supported version {supported}")]
+    UnsupportedSchemaVersion { found: i32, supported: i32 },
 }
 
 pub trait FeedbackStore: Send + Sync {
@@ -75,7 +60,19 @@ pub trait FeedbackStore: Send + Sync {
         &self,
         annotator_id: &str,
     ) -> Result<Vec<Annotation>, FeedbackError>;
-    fn list_conversations(&self, client_id: &str) -> Result<Vec<Conversation>, FeedbackError>;
+    /// Annotations of any turn in `conversation_id`, oldest first.
+    fn get_annotations_for_conversation(
+        &self,
+        conversation_id: &Uuid,
+    ) -> Result<Vec<Annotation>, FeedbackError>;
+    /// Conversations for `client_id`, optionally limited to those created
+    /// within `[since, until]` (both bounds inclusive).
+    fn list_conversations(
+        &self,
+        client_id: &str,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Conversation>, FeedbackError>;
     fn get_conversation_by_id(
         &self,
         conversation_id: &Uuid,
@@ -90,28 +87,43 @@ pub trait FeedbackStore: Send + Sync {
 }
 
 pub struct SqliteFeedbackStore {
-    conn: Mutex<Connection>,
+    pool: ConnectionPool,
 }
 
 impl SqliteFeedbackStore {
     pub fn new(path: &Path) -> Result<Self, FeedbackError> {
-        let conn = Connection::open(path)?;
-        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        Self::with_pool_size(path, DEFAULT_POOL_SIZE)
+    }
+
+    /// Opens `pool_size` connections to the database at `path` in WAL mode,
+    /// so readers do not block on writers and at most `pool_size` operations
+    /// run concurrently. A size of 0 is treated as 1.
+    pub fn with_pool_size(path: &Path, pool_size: usize) -> Result<Self, FeedbackError> {
+        let mut connections = Vec::with_capacity(pool_size.max(1));
+        for _ in 0..pool_size.max(1) {
+            let conn = Connection::open(path)?;
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+            conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+            connections.push(conn);
+        }
         Ok(Self {
-            conn: Mutex::new(conn),
+            pool: ConnectionPool::new(connections),
         })
     }
 
+    /// In-memory databases are private to their connection, so this store
+    /// always has a single connection.
     pub fn new_in_memory() -> Result<Self, FeedbackError> {
         let conn = Connection::open_in_memory()?;
         conn.execute_batch("PRAGMA foreign_keys = ON;")?;
         Ok(Self {
-            conn: Mutex::new(conn),
+            pool: ConnectionPool::new(vec![conn]),
         })
     }
 
-    fn lock_conn(&self) -> std::sync::MutexGuard<'_, Connection> {
-        self.conn.lock().expect("sqlite mutex poisoned")
+    fn lock_conn(&self) -> PooledConnection<'_> {
+        self.pool.get()
     }
 }
 
@@ -124,30 +136,40 @@ fn check_len(value: &str, field: &'static str, max: usize) -> Result<(), Feedbac
 
 impl FeedbackStore for SqliteFeedbackStore {
     fn init(&self) -> Result<(), FeedbackError> {
-        let conn = self.lock_conn();
-        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
-
-        if version < SCHEMA_VERSION {
-            conn.execute_batch(SCHEMA_SQL)?;
-            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
-        } else {
-            conn.execute_batch(SCHEMA_SQL)?;
-        }
-
-        Ok(())
+        let mut conn = self.lock_conn();
+        migrations::migrate(&mut conn)
     }
 
     fn insert_conversation(&self, conv: &Conversation) -> Result<(), FeedbackError> {
         check_len(conv.client_id.as_str(), "client_id", MAX_ID_LEN)?;
         check_len(conv.model_id.as_str(), "model_id", MAX_ID_LEN)?;
+        if let Some(title) = &conv.app_title {
+            check_len(title, "app_title", MAX_ID_LEN)?;
+        }
+        if let Some(referer) = &conv.app_referer {
+            check_len(referer, "app_referer", MAX_URL_LEN)?;
+        }
+        let metadata = conv
+            .metadata
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        if let Some(metadata) = &metadata {
+            check_len(metadata, "metadata", MAX_METADATA_LEN)?;
+        }
         let conn = self.lock_conn();
         conn.execute(
-            "INSERT INTO conversations (id, client_id, model_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+            "INSERT INTO conversations
+                 (id, client_id, model_id, created_at, app_title, app_referer, metadata)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 conv.id.to_string(),
                 conv.client_id.as_str(),
                 conv.model_id.as_str(),
                 conv.created_at.to_rfc3339(),
+                conv.app_title,
+                conv.app_referer,
+                metadata,
             ],
         )?;
         Ok(())
@@ -157,8 +179,9 @@ impl FeedbackStore for SqliteFeedbackStore {
         check_len(&turn.content, "content", MAX_CONTENT_LEN)?;
         let conn = self.lock_conn();
         conn.execute(
-            "INSERT INTO turns (id, conversation_id, role, content, token_count, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO turns (id, conversation_id, role, content, token_count, created_at,
+                                temperature, seed, content_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
                 turn.id.to_string(),
                 turn.conversation_id.to_string(),
@@ -166,6 +189,9 @@ impl FeedbackStore for SqliteFeedbackStore {
                 turn.content.as_str(),
                 turn.token_count,
                 turn.created_at.to_rfc3339(),
+                turn.temperature,
+                turn.seed.map(|seed| seed as i64),
+                turn.content_hash,
             ],
         )?;
         Ok(())
@@ -179,11 +205,16 @@ impl FeedbackStore for SqliteFeedbackStore {
         if let Some(ref resp) = ann.expected_response {
             check_len(resp, "expected_response", MAX_CONTENT_LEN)?;
         }
+        if let Some(score) = ann.score {
+            if !SCORE_RANGE.contains(&score) {
+                return Err(FeedbackError::ScoreOutOfRange(score));
+            }
+        }
         let conn = self.lock_conn();
         conn.execute(
             "INSERT INTO annotations
-             (id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+             (id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at, score)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 ann.id.to_string(),
                 ann.turn_id.to_string(),
@@ -192,6 +223,7 @@ impl FeedbackStore for SqliteFeedbackStore {
                 ann.expected_direction.as_deref(),
                 ann.expected_response.as_deref(),
                 ann.created_at.to_rfc3339(),
+                ann.score,
             ],
         )?;
         Ok(())
@@ -200,7 +232,7 @@ impl FeedbackStore for SqliteFeedbackStore {
     fn list_annotations(&self) -> Result<Vec<Annotation>, FeedbackError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at
+            "SELECT id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at, score
              FROM annotations
              ORDER BY created_at ASC",
         )?;
@@ -213,6 +245,7 @@ impl FeedbackStore for SqliteFeedbackStore {
             let expected_direction: Option<String> = row.get(4)?;
             let expected_response: Option<String> = row.get(5)?;
             let created_at: String = row.get(6)?;
+            let score: Option<u8> = row.get(7)?;
 
             Ok(Annotation {
                 id: parse_uuid(0, &id)?,
@@ -221,6 +254,7 @@ impl FeedbackStore for SqliteFeedbackStore {
                 verdict: parse_verdict(3, &verdict)?,
                 expected_direction,
                 expected_response,
+                score,
                 created_at: parse_datetime_utc(6, &created_at)?,
             })
         })?;
@@ -235,7 +269,7 @@ impl FeedbackStore for SqliteFeedbackStore {
     ) -> Result<Vec<Annotation>, FeedbackError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at
+            "SELECT id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at, score
              FROM annotations
              WHERE annotator_id = ?1
              ORDER BY created_at ASC",
@@ -249,6 +283,7 @@ impl FeedbackStore for SqliteFeedbackStore {
             let expected_direction: Option<String> = row.get(4)?;
             let expected_response: Option<String> = row.get(5)?;
             let created_at: String = row.get(6)?;
+            let score: Option<u8> = row.get(7)?;
 
             Ok(Annotation {
                 id: parse_uuid(0, &id)?,
@@ -257,6 +292,7 @@ impl FeedbackStore for SqliteFeedbackStore {
                 verdict: parse_verdict(3, &verdict)?,
                 expected_direction,
                 expected_response,
+                score,
                 created_at: parse_datetime_utc(6, &created_at)?,
             })
         })?;
@@ -265,16 +301,66 @@ impl FeedbackStore for SqliteFeedbackStore {
         Ok(annotations)
     }
 
-    fn list_conversations(&self, client_id: &str) -> Result<Vec<Conversation>, FeedbackError> {
+    fn get_annotations_for_conversation(
+        &self,
+        conversation_id: &Uuid,
+    ) -> Result<Vec<Annotation>, FeedbackError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, client_id, model_id, created_at
+            "SELECT a.id, a.turn_id, a.annotator_id, a.verdict, a.expected_direction,
+                    a.expected_response, a.created_at, a.score
+             FROM annotations a
+             JOIN turns t ON t.id = a.turn_id
+             WHERE t.conversation_id = ?1
+             ORDER BY a.created_at ASC",
+        )?;
+
+        let rows = stmt.query_map(params![conversation_id.to_string()], |row| {
+            let id: String = row.get(0)?;
+            let turn_id: String = row.get(1)?;
+            let annotator_id: String = row.get(2)?;
+            let verdict: String = row.get(3)?;
+            let expected_direction: Option<String> = row.get(4)?;
+            let expected_response: Option<String> = row.get(5)?;
+            let created_at: String = row.get(6)?;
+            let score: Option<u8> = row.get(7)?;
+
+            Ok(Annotation {
+                id: parse_uuid(0, &id)?,
+                turn_id: parse_uuid(1, &turn_id)?,
+                annotator_id,
+                verdict: parse_verdict(3, &verdict)?,
+                expected_direction,
+                expected_response,
+                score,
+                created_at: parse_datetime_utc(6, &created_at)?,
+            })
+        })?;
+
+        let annotations = rows.collect::<Result<Vec<_>, _>>()?;
+        Ok(annotations)
+    }
+
+    fn list_conversations(
+        &self,
+        client_id: &str,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Conversation>, FeedbackError> {
+        let conn = self.lock_conn();
+        // Timestamps are stored as RFC 3339 UTC text, which sorts
+        // chronologically, so string comparison matches BETWEEN semantics.
+        let mut stmt = conn.prepare(
+            "SELECT id, client_id, model_id, created_at, app_title, app_referer, metadata
              FROM conversations
              WHERE client_id = ?1
+               AND created_at BETWEEN COALESCE(?2, created_at) AND COALESCE(?3, created_at)
              ORDER BY created_at ASC",
         )?;
 
-        let rows = stmt.query_map(params![client_id], |row| {
+        let since = since.map(|value| value.to_rfc3339());
+        let until = until.map(|value| value.to_rfc3339());
+        let rows = stmt.query_map(params![client_id, since, until], |row| {
             let id: String = row.get(0)?;
             let client_id: String = row.get(1)?;
             let model_id: String = row.get(2)?;
@@ -285,6 +371,9 @@ impl FeedbackStore for SqliteFeedbackStore {
                 client_id: ClientId::new(client_id),
                 model_id: ModelId::new(model_id),
                 created_at: parse_datetime_utc(3, &created_at)?,
+                app_title: row.get(4)?,
+                app_referer: row.get(5)?,
+                metadata: parse_metadata(6, row.get(6)?)?,
             })
         })?;
 
@@ -299,7 +388,7 @@ impl FeedbackStore for SqliteFeedbackStore {
         let conn = self.lock_conn();
         let conversation = conn
             .query_row(
-                "SELECT id, client_id, model_id, created_at
+                "SELECT id, client_id, model_id, created_at, app_title, app_referer, metadata
                  FROM conversations
                  WHERE id = ?1",
                 params![conversation_id.to_string()],
@@ -314,6 +403,9 @@ impl FeedbackStore for SqliteFeedbackStore {
                         client_id: ClientId::new(client_id),
                         model_id: ModelId::new(model_id),
                         created_at: parse_datetime_utc(3, &created_at)?,
+                        app_title: row.get(4)?,
+                        app_referer: row.get(5)?,
+                        metadata: parse_metadata(6, row.get(6)?)?,
                     })
                 },
             )
@@ -325,7 +417,8 @@ impl FeedbackStore for SqliteFeedbackStore {
         let conn = self.lock_conn();
         let turn = conn
             .query_row(
-                "SELECT id, conversation_id, role, content, token_count, created_at
+                "SELECT id, conversation_id, role, content, token_count, created_at,
+                        temperature, seed, content_hash
                  FROM turns
                  WHERE id = ?1",
                 params![turn_id.to_string()],
@@ -344,6 +437,9 @@ impl FeedbackStore for SqliteFeedbackStore {
                         content,
                         token_count,
                         created_at: parse_datetime_utc(5, &created_at)?,
+                        temperature: row.get(6)?,
+                        seed: row.get::<_, Option<i64>>(7)?.map(|seed| seed as u64),
+                        content_hash: row.get(8)?,
                     })
                 },
             )
@@ -357,10 +453,11 @@ impl FeedbackStore for SqliteFeedbackStore {
     ) -> Result<Vec<Turn>, FeedbackError> {
         let conn = self.lock_conn();
         let mut stmt = conn.prepare(
-            "SELECT id, conversation_id, role, content, token_count, created_at
+            "SELECT id, conversation_id, role, content, token_count, created_at,
+                    temperature, seed, content_hash
              FROM turns
              WHERE conversation_id = ?1
-             ORDER BY created_at ASC",
+             ORDER BY created_at ASC, rowid ASC",
         )?;
 
         let rows = stmt.query_map(params![conversation_id.to_string()], |row| {
@@ -378,6 +475,9 @@ impl FeedbackStore for SqliteFeedbackStore {
                 content,
                 token_count,
                 created_at: parse_datetime_utc(5, &created_at)?,
+                temperature: row.get(6)?,
+                seed: row.get::<_, Option<i64>>(7)?.map(|seed| seed as u64),
+                content_hash: row.get(8)?,
             })
         })?;
 
@@ -460,6 +560,17 @@ fn parse_datetime_utc(column: usize, value: &str) -> rusqlite::Result<DateTime<U
         .map_err(|_| sql_text_parse_error(column, "datetime", value))
 }
 
+fn parse_metadata(
+    column: usize,
+    value: Option<String>,
+) -> rusqlite::Result<Option<BTreeMap<String, String>>> {
+    value
+        .map(|raw| {
+            serde_json::from_str(&raw).map_err(|_| sql_text_parse_error(column, "metadata", &raw))
+        })
+        .transpose()
+}
+
 fn sql_text_parse_error(column: usize, field: &'static str, value: &str) -> rusqlite::Error {
     rusqlite::Error::FromSqlConversionFailure(
         column,
@@ -477,7 +588,7 @@ mod tests {
     use mb_core::core::{ClientId, ModelId};
     use uuid::Uuid;
 
-    use super::{FeedbackStore, SqliteFeedbackStore};
+    use super::{FeedbackError, FeedbackStore, SqliteFeedbackStore};
     use crate::models::{Annotation, ClaRecord, Conversation, Turn, TurnRole, Verdict};
 
     fn ts(value: &str) -> DateTime<Utc> {
@@ -486,6 +597,35 @@ mod tests {
             .with_timezone(&Utc)
     }
 
+    fn insert_assistant_turn(store: &SqliteFeedbackStore) -> Uuid {
+        let conv = Conversation {
+            id: Uuid::new_v4(),
+            client_id: ClientId::new("team-alpha"),
+            model_id: ModelId::new("llama3-70b"),
+            created_at: ts("2026-01-01T02:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
+        };
+        store
+            .insert_conversation(&conv)
+            .expect("insert conversation");
+
+        let turn = Turn {
+            id: Uuid::new_v4(),
+            conversation_id: conv.id,
+            role: TurnRole::Assistant,
+            content: "Here is an answer.".to_string(),
+            token_count: 4,
+            created_at: ts("2026-01-01T02:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
+        };
+        store.insert_turn(&turn).expect("insert turn");
+        turn.id
+    }
+
     #[test]
     fn test_insert_and_list_conversations() {
         let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
@@ -500,18 +640,27 @@ mod tests {
             client_id: client_id.clone(),
             model_id: model_id.clone(),
             created_at: ts("2026-01-01T00:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         let conv2 = Conversation {
             id: Uuid::new_v4(),
             client_id: client_id.clone(),
             model_id: model_id.clone(),
             created_at: ts("2026-01-01T00:01:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         let conv3 = Conversation {
             id: Uuid::new_v4(),
             client_id: other_client,
             model_id,
             created_at: ts("2026-01-01T00:02:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
 
         store.insert_conversation(&conv1).expect("insert conv1");
@@ -519,7 +668,7 @@ mod tests {
         store.insert_conversation(&conv3).expect("insert conv3");
 
         let conversations = store
-            .list_conversations(client_id.as_str())
+            .list_conversations(client_id.as_str(), None, None)
             .expect("list conversations");
 
         assert_eq!(conversations.len(), 2);
@@ -527,6 +676,100 @@ mod tests {
         assert_eq!(conversations[1].id, conv2.id);
     }
 
+    #[test]
+    fn test_list_conversations_time_window() {
+        let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+        store.init().expect("init schema");
+
+        let client_id = ClientId::new("team-alpha");
+        let dates = [
+            "2026-01-15T12:00:00Z",
+            "2026-02-01T00:00:00Z",
+            "2026-02-14T08:30:00Z",
+            "2026-02-28T23:59:59Z",
+            "2026-03-10T09:00:00Z",
+        ];
+        let conversations: Vec<Conversation> = dates
+            .iter()
+            .map(|date| Conversation {
+                id: Uuid::new_v4(),
+                client_id: client_id.clone(),
+                model_id: ModelId::new("llama3-70b"),
+                created_at: ts(date),
+                app_title: None,
+                app_referer: None,
+                metadata: None,
+            })
+            .collect();
+        for conv in &conversations {
+            store
+                .insert_conversation(conv)
+                .expect("insert conversation");
+        }
+
+        let in_window = store
+            .list_conversations(
+                client_id.as_str(),
+                Some(ts("2026-02-01T00:00:00Z")),
+                Some(ts("2026-02-28T23:59:59Z")),
+            )
+            .expect("list conversations");
+        let ids: Vec<Uuid> = in_window.iter().map(|conv| conv.id).collect();
+        assert_eq!(
+            ids,
+            vec![
+                conversations[1].id,
+                conversations[2].id,
+                conversations[3].id
+            ]
+        );
+
+        let since_only = store
+            .list_conversations(client_id.as_str(), Some(ts("2026-02-15T00:00:00Z")), None)
+            .expect("list conversations");
+        assert_eq!(since_only.len(), 2);
+        assert_eq!(since_only[0].id, conversations[3].id);
+
+        let until_only = store
+            .list_conversations(client_id.as_str(), None, Some(ts("2026-01-31T00:00:00Z")))
+            .expect("list conversations");
+        assert_eq!(until_only.len(), 1);
+        assert_eq!(until_only[0].id, conversations[0].id);
+    }
+
+    #[test]
+    fn test_conversation_metadata_round_trip() {
+        let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+        store.init().expect("init schema");
+
+        let conv = Conversation {
+            id: Uuid::new_v4(),
+            client_id: ClientId::new("team-alpha"),
+            model_id: ModelId::new("llama3-70b"),
+            created_at: ts("2026-01-01T00:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: Some(
+                [("session".to_owned(), "abc-123".to_owned())]
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+        store
+            .insert_conversation(&conv)
+            .expect("insert conversation");
+
+        let stored = store
+            .get_conversation_by_id(&conv.id)
+            .expect("get conversation")
+            .expect("conversation exists");
+        assert_eq!(stored.metadata, conv.metadata);
+        let listed = store
+            .list_conversations("team-alpha", None, None)
+            .expect("list conversations");
+        assert_eq!(listed[0].metadata, conv.metadata);
+    }
+
     #[test]
     fn test_insert_and_get_turns() {
         let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
@@ -537,6 +780,9 @@ mod tests {
             client_id: ClientId::new("team-alpha"),
             model_id: ModelId::new("llama3-70b"),
             created_at: ts("2026-01-01T01:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         store
             .insert_conversation(&conv)
@@ -549,6 +795,9 @@ mod tests {
             content: "How to build a bridge?".to_string(),
             token_count: 7,
             created_at: ts("2026-01-01T01:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         let assistant_turn = Turn {
             id: Uuid::new_v4(),
@@ -557,6 +806,9 @@ mod tests {
             content: "Start with foundations.".to_string(),
             token_count: 4,
             created_at: ts("2026-01-01T01:00:02Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
 
         store.insert_turn(&user_turn).expect("insert user turn");
@@ -575,6 +827,52 @@ mod tests {
         assert_eq!(turns[1].content, assistant_turn.content);
     }
 
+    #[test]
+    fn test_turn_generation_params_round_trip() {
+        let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+        store.init().expect("init schema");
+
+        let conv = Conversation {
+            id: Uuid::new_v4(),
+            client_id: ClientId::new("team-alpha"),
+            model_id: ModelId::new("llama3-70b"),
+            created_at: ts("2026-01-01T01:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
+        };
+        store
+            .insert_conversation(&conv)
+            .expect("insert conversation");
+
+        // Seeds above i64::MAX must survive sqlite's signed INTEGER.
+        let turn = Turn {
+            id: Uuid::new_v4(),
+            conversation_id: conv.id,
+            role: TurnRole::Assistant,
+            content: "Start with foundations.".to_string(),
+            token_count: 4,
+            created_at: ts("2026-01-01T01:00:02Z"),
+            temperature: Some(0.7),
+            seed: Some(u64::MAX),
+            content_hash: None,
+        };
+        store.insert_turn(&turn).expect("insert turn");
+
+        let stored = store
+            .get_turn_by_id(&turn.id)
+            .expect("get turn")
+            .expect("turn exists");
+        assert_eq!(stored.temperature, Some(0.7));
+        assert_eq!(stored.seed, Some(u64::MAX));
+
+        let listed = store
+            .get_turns_for_conversation(&conv.id)
+            .expect("get turns for conversation");
+        assert_eq!(listed[0].temperature, Some(0.7));
+        assert_eq!(listed[0].seed, Some(u64::MAX));
+    }
+
     #[test]
     fn test_insert_and_get_annotations() {
         let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
@@ -585,6 +883,9 @@ mod tests {
             client_id: ClientId::new("team-alpha"),
             model_id: ModelId::new("llama3-70b"),
             created_at: ts("2026-01-01T02:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         store
             .insert_conversation(&conv)
@@ -597,6 +898,9 @@ mod tests {
             content: "I cannot answer that.".to_string(),
             token_count: 5,
             created_at: ts("2026-01-01T02:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         store.insert_turn(&turn).expect("insert turn");
 
@@ -607,6 +911,7 @@ mod tests {
             verdict: Verdict::Refused,
             expected_direction: Some("explain policy constraints".to_string()),
             expected_response: Some("Provide safe alternative".to_string()),
+            score: None,
             created_at: ts("2026-01-01T02:00:02Z"),
         };
         store.insert_annotation(&ann).expect("insert annotation");
@@ -625,6 +930,91 @@ mod tests {
             annotations[0].expected_response.as_deref(),
             Some("Provide safe alternative")
         );
+
+        let by_conversation = store
+            .get_annotations_for_conversation(&conv.id)
+            .expect("get annotations for conversation");
+        assert_eq!(by_conversation.len(), 1);
+        assert_eq!(by_conversation[0].id, ann.id);
+        let other = store
+            .get_annotations_for_conversation(&Uuid::new_v4())
+            .expect("get annotations for conversation");
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_read_scored_annotation() {
+        let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+        store.init().expect("init schema");
+        let turn_id = insert_assistant_turn(&store);
+
+        let ann = Annotation {
+            id: Uuid::new_v4(),
+            turn_id,
+            annotator_id: "annotator-1".to_string(),
+            verdict: Verdict::Satisfactory,
+            expected_direction: None,
+            expected_response: None,
+            score: Some(4),
+            created_at: ts("2026-01-01T02:00:02Z"),
+        };
+        store.insert_annotation(&ann).expect("insert annotation");
+
+        let annotations = store.list_annotations().expect("list annotations");
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].score, Some(4));
+
+        let out_of_range = Annotation {
+            id: Uuid::new_v4(),
+            score: Some(6),
+            ..ann
+        };
+        assert!(matches!(
+            store.insert_annotation(&out_of_range),
+            Err(FeedbackError::ScoreOutOfRange(6))
+        ));
+    }
+
+    #[test]
+    fn test_init_upgrades_v1_database() {
+        let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+        let legacy_annotation_id = Uuid::new_v4();
+        {
+            let mut conn = store.lock_conn();
+            super::migrations::migrate_to(&mut conn, 1).expect("create v1 schema");
+            let conversation_id = Uuid::new_v4().to_string();
+            let turn_id = Uuid::new_v4().to_string();
+            conn.execute(
+                "INSERT INTO conversations VALUES (?1, 'team-alpha', 'llama3-70b', '2026-01-01T00:00:00+00:00')",
+                [&conversation_id],
+            )
+            .expect("insert v1 conversation");
+            conn.execute(
+                "INSERT INTO turns VALUES (?1, ?2, 'assistant', 'Hi', 1, '2026-01-01T00:00:01+00:00')",
+                [&turn_id, &conversation_id],
+            )
+            .expect("insert v1 turn");
+            conn.execute(
+                "INSERT INTO annotations
+                 (id, turn_id, annotator_id, verdict, expected_direction, expected_response, created_at)
+                 VALUES (?1, ?2, 'annotator-1', 'satisfactory', NULL, NULL, '2026-01-01T00:00:02+00:00')",
+                [legacy_annotation_id.to_string(), turn_id],
+            )
+            .expect("insert v1 annotation");
+        }
+
+        store.init().expect("upgrade schema");
+
+        let version: i32 = store
+            .lock_conn()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .expect("read version");
+        assert_eq!(version, super::migrations::SCHEMA_VERSION);
+
+        let annotations = store.list_annotations().expect("list annotations");
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].id, legacy_annotation_id);
+        assert_eq!(annotations[0].score, None);
     }
 
     #[test]
@@ -655,6 +1045,9 @@ mod tests {
             client_id: ClientId::new("team-alpha"),
             model_id: ModelId::new("llama3-70b"),
             created_at: ts("2026-01-01T04:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
         };
         store
             .insert_conversation(&conv)
@@ -667,6 +1060,9 @@ mod tests {
             content: "Some answer".to_string(),
             token_count: 2,
             created_at: ts("2026-01-01T04:00:01Z"),
+            temperature: None,
+            seed: None,
+            content_hash: None,
         };
         store.insert_turn(&turn).expect("insert turn");
 
@@ -677,6 +1073,7 @@ mod tests {
             verdict: Verdict::Biased,
             expected_direction: None,
             expected_response: None,
+            score: None,
             created_at: ts("2026-01-01T04:00:02Z"),
         };
         let ann2 = Annotation {
@@ -686,6 +1083,7 @@ mod tests {
             verdict: Verdict::Satisfactory,
             expected_direction: Some("neutral".to_string()),
             expected_response: Some("balanced response".to_string()),
+            score: None,
             created_at: ts("2026-01-01T04:00:03Z"),
         };
         let ann3 = Annotation {
@@ -695,6 +1093,7 @@ mod tests {
             verdict: Verdict::Refused,
             expected_direction: None,
             expected_response: None,
+            score: None,
             created_at: ts("2026-01-01T04:00:04Z"),
         };
 
@@ -715,4 +1114,73 @@ mod tests {
         assert_eq!(ann_b.len(), 1);
         assert_eq!(ann_b[0].id, ann3.id);
     }
+
+    #[test]
+    fn test_concurrent_inserts_through_pool() {
+        const WRITERS: usize = 16;
+        const TURNS_PER_WRITER: usize = 25;
+
+        let path = std::env::temp_dir().join(format!("mb-feedback-{}.sqlite", Uuid::new_v4()));
+        let store =
+            std::sync::Arc::new(SqliteFeedbackStore::with_pool_size(&path, 4).expect("open"));
+        store.init().expect("init schema");
+        let journal_mode: String = store
+            .lock_conn()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .expect("read journal_mode");
+        assert_eq!(journal_mode, "wal");
+
+        let conv = Conversation {
+            id: Uuid::new_v4(),
+            client_id: ClientId::new("team-alpha"),
+            model_id: ModelId::new("llama3-70b"),
+            created_at: ts("2026-01-01T05:00:00Z"),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
+        };
+        store
+            .insert_conversation(&conv)
+            .expect("insert conversation");
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|_| {
+                let store = std::sync::Arc::clone(&store);
+                std::thread::spawn(move || {
+                    for _ in 0..TURNS_PER_WRITER {
+                        let turn = Turn {
+                            id: Uuid::new_v4(),
+                            conversation_id: conv.id,
+                            role: TurnRole::User,
+                            content: "hello".to_string(),
+                            token_count: 1,
+                            created_at: ts("2026-01-01T05:00:01Z"),
+                            temperature: None,
+                            seed: None,
+                            content_hash: None,
+                        };
+                        store.insert_turn(&turn).expect("insert turn");
+                        // Interleave reads so they contend with the writers.
+                        store
+                            .get_turn_by_id(&turn.id)
+                            .expect("read turn")
+                            .expect("turn visible after insert");
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().expect("writer thread");
+        }
+
+        let turns = store
+            .get_turns_for_conversation(&conv.id)
+            .expect("list turns");
+        assert_eq!(turns.len(), WRITERS * TURNS_PER_WRITER);
+
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{suffix}", path.display())).ok();
+        }
+    }
 }
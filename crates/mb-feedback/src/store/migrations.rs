@@ -0,0 +1,232 @@
+use rusqlite::Connection;
+
+use super::FeedbackError;
+
+/// One forward-only schema step. Applying it moves `user_version` to `version`.
+///
+/// Shipped migrations are immutable: change the schema by appending a new
+/// step, never by editing an existing one.
+pub(super) struct Migration {
+    pub version: i32,
+    pub sql: &'static str,
+}
+
+/// Ordered schema history; versions must be contiguous starting at 1.
+pub(super) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: r#"
+CREATE TABLE IF NOT EXISTS conversations (
+    id TEXT PRIMARY KEY,
+    client_id TEXT NOT NULL,
+    model_id TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS turns (
+    id TEXT PRIMARY KEY,
+    conversation_id TEXT NOT NULL REFERENCES conversations(id),
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    token_count INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_turns_conversation ON turns(conversation_id);
+
+CREATE TABLE IF NOT EXISTS annotations (
+    id TEXT PRIMARY KEY,
+    turn_id TEXT NOT NULL REFERENCES turns(id),
+    annotator_id TEXT NOT NULL,
+    verdict TEXT NOT NULL,
+    expected_direction TEXT,
+    expected_response TEXT,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_annotations_turn ON annotations(turn_id);
+CREATE INDEX IF NOT EXISTS idx_annotations_annotator ON annotations(annotator_id);
+
+CREATE TABLE IF NOT EXISTS cla_records (
+    client_id TEXT PRIMARY KEY,
+    signed_at TEXT NOT NULL,
+    github_username TEXT
+);
+"#,
+    },
+    Migration {
+        version: 2,
+        // Nullable, so rows written before scores existed stay valid.
+        sql: "ALTER TABLE annotations ADD COLUMN score INTEGER;",
+    },
+    Migration {
+        version: 3,
+        sql: "ALTER TABLE conversations ADD COLUMN app_title TEXT;
+              ALTER TABLE conversations ADD COLUMN app_referer TEXT;",
+    },
+    Migration {
+        version: 4,
+        sql: "ALTER TABLE turns ADD COLUMN temperature REAL;
+              ALTER TABLE turns ADD COLUMN seed INTEGER;",
+    },
+    Migration {
+        version: 5,
+        // JSON object of string pairs.
+        sql: "ALTER TABLE conversations ADD COLUMN metadata TEXT;",
+    },
+    Migration {
+        version: 6,
+        sql: "ALTER TABLE turns ADD COLUMN content_hash TEXT;",
+    },
+];
+
+/// Latest schema version known to this build.
+pub(super) const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+/// Brings the database up to [`SCHEMA_VERSION`].
+pub(super) fn migrate(conn: &mut Connection) -> Result<(), FeedbackError> {
+    migrate_to(conn, SCHEMA_VERSION)
+}
+
+/// Applies every pending migration up to and including `target`, each in its
+/// own transaction together with the `user_version` bump, so a failed step
+/// leaves the database at the previous version.
+pub(super) fn migrate_to(conn: &mut Connection, target: i32) -> Result<(), FeedbackError> {
+    let current: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if current > SCHEMA_VERSION {
+        return Err(FeedbackError::UnsupportedSchemaVersion {
+            found: current,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+    {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> i32 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .expect("read user_version")
+    }
+
+    fn table_columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info(?1)")
+            .expect("prepare table_info");
+        stmt.query_map([table], |row| row.get(0))
+            .expect("query table_info")
+            .collect::<Result<Vec<String>, _>>()
+            .expect("collect columns")
+    }
+
+    #[test]
+    fn test_migration_versions_are_contiguous() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_fresh_database_reaches_latest_version() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        migrate(&mut conn).expect("migrate");
+
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+        assert!(table_columns(&conn, "annotations").contains(&"score".to_owned()));
+    }
+
+    #[test]
+    fn test_old_version_database_is_upgraded() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        migrate_to(&mut conn, 1).expect("migrate to v1");
+        assert_eq!(user_version(&conn), 1);
+        assert!(!table_columns(&conn, "annotations").contains(&"score".to_owned()));
+
+        migrate(&mut conn).expect("upgrade");
+
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+        assert!(table_columns(&conn, "annotations").contains(&"score".to_owned()));
+    }
+
+    #[test]
+    fn test_v2_conversations_gain_attribution_columns() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        migrate_to(&mut conn, 2).expect("migrate to v2");
+        conn.execute(
+            "INSERT INTO conversations (id, client_id, model_id, created_at)
+             VALUES ('c1', 'team-alpha', 'llama3-70b', '2026-01-01T00:00:00+00:00')",
+            [],
+        )
+        .expect("insert row");
+
+        migrate(&mut conn).expect("upgrade");
+
+        let columns = table_columns(&conn, "conversations");
+        assert!(columns.contains(&"app_title".to_owned()));
+        assert!(columns.contains(&"app_referer".to_owned()));
+        let title: Option<String> = conn
+            .query_row(
+                "SELECT app_title FROM conversations WHERE id = 'c1'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("read row");
+        assert_eq!(title, None);
+    }
+
+    #[test]
+    fn test_v4_conversations_gain_metadata_column() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        migrate_to(&mut conn, 4).expect("migrate to v4");
+        assert!(!table_columns(&conn, "conversations").contains(&"metadata".to_owned()));
+
+        migrate(&mut conn).expect("upgrade");
+
+        assert!(table_columns(&conn, "conversations").contains(&"metadata".to_owned()));
+    }
+
+    #[test]
+    fn test_current_database_is_noop() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        migrate(&mut conn).expect("first migrate");
+        conn.execute(
+            "INSERT INTO cla_records (client_id, signed_at) VALUES ('team-alpha', '2026-01-01T00:00:00+00:00')",
+            [],
+        )
+        .expect("insert row");
+
+        // Re-running the ALTER TABLE step would fail with a duplicate column,
+        // so success here means nothing was reapplied.
+        migrate(&mut conn).expect("second migrate");
+
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM cla_records", [], |row| row.get(0))
+            .expect("count rows");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_newer_database_is_rejected() {
+        let mut conn = Connection::open_in_memory().expect("open");
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .expect("set version");
+
+        let err = migrate(&mut conn).expect_err("newer schema should be rejected");
+        assert!(matches!(
+            err,
+            FeedbackError::UnsupportedSchemaVersion { found, .. } if found == SCHEMA_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use rusqlite::Connection;
+
+// ---------------------------------------------------------------------------
+// ConnectionPool — fixed set of sqlite connections
+// ---------------------------------------------------------------------------
+
+/// Hands out one of a fixed set of connections, blocking while all are in
+/// use. The size of the set bounds how many store operations run at once.
+pub(super) struct ConnectionPool {
+    idle: Mutex<Vec<Connection>>,
+    returned: Condvar,
+}
+
+impl ConnectionPool {
+    pub fn new(connections: Vec<Connection>) -> Self {
+        assert!(
+            !connections.is_empty(),
+            "pool needs at least one connection"
+        );
+        Self {
+            idle: Mutex::new(connections),
+            returned: Condvar::new(),
+        }
+    }
+
+    pub fn get(&self) -> PooledConnection<'_> {
+        let mut idle = self.lock_idle();
+        loop {
+            if let Some(conn) = idle.pop() {
+                return PooledConnection {
+                    pool: self,
+                    conn: Some(conn),
+                };
+            }
+            idle = self
+                .returned
+                .wait(idle)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// The idle list is only pushed to and popped from under the lock, so a
+    /// poisoned mutex still guards a consistent list.
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<Connection>> {
+        self.idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A checked-out connection; returns itself to the pool on drop.
+pub(super) struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection present until drop")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection present until drop")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.lock_idle().push(conn);
+            self.pool.returned.notify_one();
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use mb_core::core::{AuthError, ClientId, GatewayError};
+use subtle::ConstantTimeEq;
+
+use crate::handler::{current_year_month, extract_api_key, gateway_error_to_response, AppState};
+
+// ---------------------------------------------------------------------------
+// Admin endpoints — operator actions gated by `admin.api_key`
+// ---------------------------------------------------------------------------
+
+/// Checks the bearer token against the configured admin key in constant
+/// time. Client keys are never accepted, and every request fails when no
+/// admin key is configured.
+pub(crate) fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), GatewayError> {
+    let presented = extract_api_key(headers, &state.auth_schemes)?;
+    let authorized = state.admin_key.as_ref().is_some_and(|admin_key| {
+        admin_key
+            .as_str()
+            .as_bytes()
+            .ct_eq(presented.as_str().as_bytes())
+            .into()
+    });
+    if authorized {
+        Ok(())
+    } else {
+        Err(GatewayError::Auth(AuthError::InvalidApiKey))
+    }
+}
+
+/// `GET /admin/clients` — lists configured clients and whether their key has
+/// been revoked. Keys themselves are never returned.
+pub async fn list_clients_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(e) = authorize_admin(&state, &headers) {
+        return gateway_error_to_response(e);
+    }
+
+    let clients: Vec<_> = state
+        .auth
+        .clients()
+        .map(|info| {
+            serde_json::json!({
+                "id": info.id.as_str(),
+                "revoked": state.auth.is_revoked(&info.id),
+            })
+        })
+        .collect();
+    (
+        StatusCode::OK,
+        axum::Json(serde_json::json!({ "clients": clients })),
+    )
+        .into_response()
+}
+
+/// `POST /admin/clients/{id}/revoke` — stops the client's key from
+/// authenticating, effective for the next request. Lasts until restart;
+/// remove the key from the config to make it permanent.
+pub async fn revoke_client_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(e) = authorize_admin(&state, &headers) {
+        return gateway_error_to_response(e);
+    }
+
+    let client_id = ClientId::new(id);
+    if !state.auth.revoke(&client_id) {
+        return client_not_found(&client_id);
+    }
+    tracing::warn!(client = %client_id, "client key revoked by admin");
+
+    let body = serde_json::json!({
+        "client_id": client_id.as_str(),
+        "revoked": true,
+    });
+    (StatusCode::OK, axum::Json(body)).into_response()
+}
+
+/// `POST /admin/clients/{id}/quota/reset` — zeroes the client's token usage
+/// for the current billing month.
+pub async fn reset_quota_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(e) = authorize_admin(&state, &headers) {
+        return gateway_error_to_response(e);
+    }
+
+    let client_id = ClientId::new(id);
+    if state.auth.client(&client_id).is_none() {
+        return client_not_found(&client_id);
+    }
+
+    let period = current_year_month();
+    let cleared = state.quota_tracker.write().await.reset(&client_id, period);
+    tracing::info!(client = %client_id, tokens_cleared = cleared, "monthly quota reset by admin");
+
+    let body = serde_json::json!({
+        "client_id": client_id.as_str(),
+        "period": format!("{:04}-{:02}", period.year(), period.month()),
+        "tokens_cleared": cleared,
+    });
+    (StatusCode::OK, axum::Json(body)).into_response()
+}
+
+fn client_not_found(client_id: &ClientId) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": format!("client {client_id} not found"),
+            "type": "not_found_error",
+            "code": 404,
+        }
+    });
+    (StatusCode::NOT_FOUND, axum::Json(body)).into_response()
+}
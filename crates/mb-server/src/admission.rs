@@ -0,0 +1,325 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use mb_core::core::{GatewayError, ModelId, RoutingError};
+use tokio::sync::oneshot;
+
+// ---------------------------------------------------------------------------
+// AdmissionQueue — weighted fair wait for per-model capacity
+// ---------------------------------------------------------------------------
+
+/// Virtual time a waiter of weight 1 advances its priority's queue by; a
+/// priority `p` waiter advances it by `SHARE_STRIDE / (p + 1)`.
+const SHARE_STRIDE: u64 = 1 << 16;
+
+/// Bounds in-flight requests per model to the combined `max_concurrent` of
+/// its healthy backends. Requests beyond that wait for a freed slot, which
+/// goes out by weighted fair queuing: client priority `p` is served in
+/// proportion to `p + 1`, in arrival order within a priority, so busy
+/// high-priority clients slow lower priorities down without starving them.
+pub struct AdmissionQueue {
+    models: Mutex<HashMap<ModelId, ModelSlots>>,
+    timeout: Duration,
+}
+
+#[derive(Default)]
+struct ModelSlots {
+    /// Healthy capacity as of the latest `acquire`.
+    capacity: u32,
+    in_use: u32,
+    waiters: BinaryHeap<Waiter>,
+    next_seq: u64,
+    /// Tag of the waiter most recently admitted.
+    virtual_time: u64,
+    /// Tag of the last waiter queued at each priority.
+    last_tags: HashMap<u8, u64>,
+}
+
+/// A queued request. Freed slots are handed over as a ready permit, so a
+/// waiter that gives up after the hand-off still releases the slot on drop.
+struct Waiter {
+    /// Virtual finish time; the lowest is admitted first.
+    tag: u64,
+    seq: u64,
+    wake: oneshot::Sender<AdmissionPermit>,
+}
+
+impl Ord for Waiter {
+    /// Max-heap order: lowest tag, then earliest arrival.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .tag
+            .cmp(&self.tag)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+/// One occupied slot; frees it, or hands it to the next waiter, on drop.
+pub struct AdmissionPermit {
+    queue: Arc<AdmissionQueue>,
+    /// `None` for requests the queue does not bound, and for permits whose
+    /// hand-off failed and must not release again.
+    model: Option<ModelId>,
+}
+
+impl ModelSlots {
+    /// Queues a waiter at `priority` behind the virtual time it has used.
+    fn enqueue(&mut self, priority: u8) -> oneshot::Receiver<AdmissionPermit> {
+        let last_tag = self.last_tags.entry(priority).or_default();
+        let tag = (*last_tag).max(self.virtual_time) + SHARE_STRIDE / (u64::from(priority) + 1);
+        *last_tag = tag;
+        let (wake, rx) = oneshot::channel();
+        self.waiters.push(Waiter {
+            tag,
+            seq: self.next_seq,
+            wake,
+        });
+        self.next_seq += 1;
+        rx
+    }
+}
+
+impl AdmissionQueue {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            models: Mutex::new(HashMap::new()),
+            timeout,
+        }
+    }
+
+    /// Takes one of `capacity` slots for `model`, waiting up to the queue
+    /// timeout for its fair share. `capacity` is the model's current healthy
+    /// `max_concurrent`; at 0 the request passes through for the router to
+    /// reject.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        model: &ModelId,
+        priority: u8,
+        capacity: u32,
+    ) -> Result<AdmissionPermit, GatewayError> {
+        if capacity == 0 {
+            return Ok(self.permit(None));
+        }
+        let mut rx = {
+            let mut models = self.lock();
+            let slots = models.entry(model.clone()).or_default();
+            slots.capacity = capacity;
+            // Capacity may have grown since the last release.
+            self.admit_waiters(model, slots);
+            if slots.waiters.is_empty() && slots.in_use < slots.capacity {
+                slots.in_use += 1;
+                return Ok(self.permit(Some(model.clone())));
+            }
+            slots.enqueue(priority)
+        };
+
+        if let Ok(Ok(permit)) = tokio::time::timeout(self.timeout, &mut rx).await {
+            return Ok(permit);
+        }
+        // A slot may have been handed over just as the timeout fired.
+        rx.close();
+        if let Ok(permit) = rx.try_recv() {
+            return Ok(permit);
+        }
+        Err(GatewayError::Routing(RoutingError::QueueTimeout {
+            model: model.clone(),
+            waited_ms: self.timeout.as_millis() as u64,
+        }))
+    }
+
+    fn permit(self: &Arc<Self>, model: Option<ModelId>) -> AdmissionPermit {
+        AdmissionPermit {
+            queue: Arc::clone(self),
+            model,
+        }
+    }
+
+    /// Hands free slots to waiters, lowest tag first.
+    fn admit_waiters(self: &Arc<Self>, model: &ModelId, slots: &mut ModelSlots) {
+        while slots.in_use < slots.capacity {
+            let Some(waiter) = slots.waiters.pop() else {
+                return;
+            };
+            slots.virtual_time = slots.virtual_time.max(waiter.tag);
+            match waiter.wake.send(self.permit(Some(model.clone()))) {
+                Ok(()) => slots.in_use += 1,
+                // The waiter timed out or went away; disarm the returned
+                // permit so dropping it does not re-enter `release`.
+                Err(mut unclaimed) => unclaimed.model = None,
+            }
+        }
+    }
+
+    fn release(self: &Arc<Self>, model: ModelId) {
+        let mut models = self.lock();
+        let Some(slots) = models.get_mut(&model) else {
+            return;
+        };
+        slots.in_use = slots.in_use.saturating_sub(1);
+        self.admit_waiters(&model, slots);
+    }
+
+    /// The map is only mutated under the lock by non-panicking code, so a
+    /// poisoned mutex still guards consistent counts.
+    fn lock(&self) -> MutexGuard<'_, HashMap<ModelId, ModelSlots>> {
+        self.models
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        if let Some(model) = self.model.take() {
+            self.queue.release(model);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    type Acquire = Pin<Box<dyn Future<Output = Result<AdmissionPermit, GatewayError>>>>;
+
+    fn queue(timeout_ms: u64) -> Arc<AdmissionQueue> {
+        Arc::new(AdmissionQueue::new(Duration::from_millis(timeout_ms)))
+    }
+
+    fn model() -> ModelId {
+        ModelId::new("llama3-70b")
+    }
+
+    fn in_use(queue: &AdmissionQueue) -> u32 {
+        queue.lock()[&model()].in_use
+    }
+
+    /// Starts an acquire and polls it once, so it is queued in call order.
+    fn queued(queue: &Arc<AdmissionQueue>, priority: u8, capacity: u32) -> Acquire {
+        let queue = Arc::clone(queue);
+        let mut acquire: Acquire =
+            Box::pin(async move { queue.acquire(&model(), priority, capacity).await });
+        assert!((&mut acquire).now_or_never().is_none(), "should wait");
+        acquire
+    }
+
+    /// Frees `held` and returns the index of the one waiter it admitted.
+    fn hand_over(
+        held: AdmissionPermit,
+        waiters: &mut Vec<(usize, Acquire)>,
+    ) -> (usize, AdmissionPermit) {
+        drop(held);
+        let mut admitted = None;
+        for (pos, (index, acquire)) in waiters.iter_mut().enumerate() {
+            if let Some(result) = acquire.now_or_never() {
+                assert!(admitted.is_none(), "one slot admits one waiter");
+                admitted = Some((pos, *index, result.expect("admitted")));
+            }
+        }
+        let (pos, index, permit) = admitted.expect("a waiter is admitted");
+        drop(waiters.remove(pos));
+        (index, permit)
+    }
+
+    #[tokio::test]
+    async fn test_freed_slots_are_shared_by_priority_weight() {
+        let queue = queue(5_000);
+        let mut held = queue.acquire(&model(), 0, 1).await.unwrap();
+
+        // Twelve priority-10 requests queue ahead of one priority-0 request.
+        let mut waiters: Vec<(usize, Acquire)> =
+            (0..12).map(|i| (i, queued(&queue, 10, 1))).collect();
+        waiters.push((12, queued(&queue, 0, 1)));
+
+        let mut order = Vec::new();
+        while !waiters.is_empty() {
+            let (index, permit) = hand_over(held, &mut waiters);
+            order.push(index);
+            held = permit;
+        }
+        drop(held);
+
+        // Weight 11 against 1: the low-priority request gets its turn after
+        // eleven high-priority ones instead of waiting for all of them.
+        assert_eq!(order, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 12, 11]);
+        assert_eq!(in_use(&queue), 0);
+    }
+
+    #[tokio::test]
+    async fn test_higher_priority_waiter_goes_first() {
+        let queue = queue(5_000);
+        let held = queue.acquire(&model(), 0, 1).await.unwrap();
+        let mut waiters = vec![(0, queued(&queue, 0, 1)), (1, queued(&queue, 10, 1))];
+
+        let (first, permit) = hand_over(held, &mut waiters);
+        assert_eq!(first, 1);
+        let (second, permit) = hand_over(permit, &mut waiters);
+        assert_eq!(second, 0);
+        drop(permit);
+        assert_eq!(in_use(&queue), 0);
+    }
+
+    #[tokio::test]
+    async fn test_grown_capacity_admits_waiters() {
+        let queue = queue(5_000);
+        let _held = queue.acquire(&model(), 0, 1).await.unwrap();
+        let mut waiting = queued(&queue, 0, 1);
+
+        // A backend came back: the next acquire sees two slots.
+        let _next = queue.acquire(&model(), 0, 2).now_or_never();
+        let admitted = (&mut waiting).now_or_never();
+        assert!(matches!(admitted, Some(Ok(_))));
+        assert_eq!(in_use(&queue), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_times_out_without_leaking_a_slot() {
+        let queue = queue(30);
+        let held = queue.acquire(&model(), 0, 1).await.unwrap();
+
+        let err = queue
+            .acquire(&model(), 5, 1)
+            .await
+            .err()
+            .expect("should time out");
+        assert!(matches!(
+            err,
+            GatewayError::Routing(RoutingError::QueueTimeout { .. })
+        ));
+
+        // The timed-out waiter is skipped when the slot frees.
+        drop(held);
+        assert_eq!(in_use(&queue), 0);
+        let _again = queue.acquire(&model(), 0, 1).await.unwrap();
+        assert_eq!(in_use(&queue), 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_healthy_capacity_passes_through() {
+        let queue = queue(30);
+        let permit = queue.acquire(&model(), 0, 0).await;
+        assert!(permit.is_ok());
+        assert!(queue.lock().is_empty());
+    }
+}
@@ -0,0 +1,347 @@
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use axum::body::{Body, BodyDataStream, Bytes};
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use futures_core::Stream;
+use mb_core::core::{BackendId, ClientId, ModelId, RequestId};
+use rusqlite::{params, Connection};
+
+use crate::handler::RequestTrail;
+
+// ---------------------------------------------------------------------------
+// AuditLog — one sqlite row per completed request
+// ---------------------------------------------------------------------------
+
+/// A completed request as written to the audit log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub request_id: RequestId,
+    pub client_id: ClientId,
+    pub model_id: ModelId,
+    /// `None` when the request failed before a backend answered it.
+    pub backend_id: Option<BackendId>,
+    /// HTTP status returned to the client.
+    pub status: u16,
+    /// `None` when the backend did not report usage, e.g. on live streams.
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Append-only request audit trail, kept apart from the feedback store so it
+/// works without the `feedback` feature.
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS audit_log (
+        request_id        TEXT NOT NULL,
+        client_id         TEXT NOT NULL,
+        model_id          TEXT NOT NULL,
+        backend_id        TEXT,
+        status            INTEGER NOT NULL,
+        prompt_tokens     INTEGER,
+        completion_tokens INTEGER,
+        created_at        TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_audit_client_created
+        ON audit_log (client_id, created_at);
+";
+
+impl AuditLog {
+    /// Opens (or creates) the database at `path` and ensures the schema.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn insert(&self, record: &AuditRecord) -> rusqlite::Result<()> {
+        self.lock_conn().execute(
+            "INSERT INTO audit_log (request_id, client_id, model_id, backend_id, status,
+                                    prompt_tokens, completion_tokens, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.request_id.as_str(),
+                record.client_id.as_str(),
+                record.model_id.as_str(),
+                record.backend_id.as_ref().map(BackendId::as_str),
+                record.status,
+                record.prompt_tokens.map(|t| t as i64),
+                record.completion_tokens.map(|t| t as i64),
+                record.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Rows for `client_id`, oldest first.
+    pub fn records_for_client(&self, client_id: &ClientId) -> rusqlite::Result<Vec<AuditRecord>> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT request_id, client_id, model_id, backend_id, status,
+                    prompt_tokens, completion_tokens, created_at
+             FROM audit_log WHERE client_id = ?1 ORDER BY created_at, rowid",
+        )?;
+        let rows = stmt.query_map(params![client_id.as_str()], |row| {
+            let created_at: String = row.get(7)?;
+            Ok(AuditRecord {
+                request_id: RequestId::new(row.get::<_, String>(0)?),
+                client_id: ClientId::new(row.get::<_, String>(1)?),
+                model_id: ModelId::new(row.get::<_, String>(2)?),
+                backend_id: row.get::<_, Option<String>>(3)?.map(BackendId::new),
+                status: row.get(4)?,
+                prompt_tokens: row.get::<_, Option<i64>>(5)?.map(|t| t as u64),
+                completion_tokens: row.get::<_, Option<i64>>(6)?.map(|t| t as u64),
+                created_at: DateTime::parse_from_rfc3339(&created_at)
+                    .map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            7,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })?
+                    .with_timezone(&Utc),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Every statement runs to completion under the lock, so a poisoned
+    /// connection is still usable.
+    fn lock_conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Writes the audit row for a finished request once its status is known.
+/// Requests rejected before they were authenticated have no client to file
+/// the row under and are skipped. A live stream's row is written when its
+/// body ends or the client goes away.
+pub(crate) fn record_response(
+    log: Option<&Arc<AuditLog>>,
+    trail: RequestTrail,
+    response: Response,
+) -> Response {
+    let (Some(log), Some((request_id, client_id, model_id))) = (log, trail.request) else {
+        return response;
+    };
+    let record = AuditRecord {
+        request_id,
+        client_id,
+        model_id,
+        backend_id: trail.backend,
+        status: response.status().as_u16(),
+        prompt_tokens: trail.usage.as_ref().map(|u| u.prompt_tokens),
+        completion_tokens: trail.usage.as_ref().map(|u| u.completion_tokens),
+        created_at: Utc::now(),
+    };
+    if !trail.live_stream {
+        write_record(Arc::clone(log), record);
+        return response;
+    }
+    response.map(|body| {
+        Body::from_stream(AuditedStream {
+            inner: body.into_data_stream(),
+            pending: Some((Arc::clone(log), record)),
+        })
+    })
+}
+
+/// A response body that writes its audit row once it has been fully sent
+/// or dropped, whichever comes first.
+struct AuditedStream {
+    inner: BodyDataStream,
+    pending: Option<(Arc<AuditLog>, AuditRecord)>,
+}
+
+impl AuditedStream {
+    fn finish(&mut self) {
+        if let Some((log, mut record)) = self.pending.take() {
+            record.created_at = Utc::now();
+            write_record(log, record);
+        }
+    }
+}
+
+impl Stream for AuditedStream {
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(None) = item {
+            self.finish();
+        }
+        item
+    }
+}
+
+impl Drop for AuditedStream {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Inserts `record` on the blocking pool without holding up the response;
+/// failures are logged and otherwise ignored.
+fn write_record(log: Arc<AuditLog>, record: AuditRecord) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = log.insert(&record) {
+            tracing::warn!(
+                error = %err,
+                request_id = %record.request_id,
+                "failed to write audit record"
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(request_id: &str, client_id: &str, tokens: Option<(u64, u64)>) -> AuditRecord {
+        AuditRecord {
+            request_id: RequestId::new(request_id),
+            client_id: ClientId::new(client_id),
+            model_id: ModelId::new("llama3-70b"),
+            backend_id: Some(BackendId::new("gpu-0")),
+            status: 200,
+            prompt_tokens: tokens.map(|t| t.0),
+            completion_tokens: tokens.map(|t| t.1),
+            created_at: DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z")
+                .expect("valid timestamp")
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn test_insert_and_query_by_client() {
+        let log = AuditLog::open_in_memory().expect("in-memory audit log");
+        let first = record("req-1", "team-alpha", Some((12, 30)));
+        let streamed = record("req-2", "team-alpha", None);
+        log.insert(&first).expect("insert first");
+        log.insert(&record("req-3", "team-beta", Some((1, 1))))
+            .expect("insert other client");
+        log.insert(&streamed).expect("insert streamed");
+
+        let rows = log
+            .records_for_client(&ClientId::new("team-alpha"))
+            .expect("query");
+        assert_eq!(rows, vec![first, streamed]);
+        assert!(log
+            .records_for_client(&ClientId::new("team-gamma"))
+            .expect("query")
+            .is_empty());
+    }
+
+    fn trail(live_stream: bool) -> RequestTrail {
+        RequestTrail {
+            request: Some((
+                RequestId::new("req-1"),
+                ClientId::new("team-alpha"),
+                ModelId::new("llama3-70b"),
+            )),
+            backend: Some(BackendId::new("gpu-0")),
+            usage: None,
+            live_stream,
+        }
+    }
+
+    /// Rows are written on the blocking pool; waits for `count` of them.
+    async fn wait_for_rows(log: &AuditLog, count: usize) -> Vec<AuditRecord> {
+        for _ in 0..200 {
+            let rows = log
+                .records_for_client(&ClientId::new("team-alpha"))
+                .expect("query");
+            if rows.len() >= count {
+                return rows;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        panic!("audit rows were not written");
+    }
+
+    #[tokio::test]
+    async fn test_record_response_keeps_error_status() {
+        let log = Arc::new(AuditLog::open_in_memory().expect("in-memory audit log"));
+        let mut failed = trail(false);
+        failed.backend = None;
+        let response = Response::builder()
+            .status(429)
+            .body(Body::empty())
+            .expect("response");
+        record_response(Some(&log), failed, response);
+
+        let rows = wait_for_rows(&log, 1).await;
+        assert_eq!(rows[0].status, 429);
+        assert_eq!(rows[0].backend_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_record_response_skips_unauthenticated_requests() {
+        let log = Arc::new(AuditLog::open_in_memory().expect("in-memory audit log"));
+        let response = Response::builder()
+            .status(401)
+            .body(Body::empty())
+            .expect("response");
+        record_response(Some(&log), RequestTrail::default(), response);
+
+        // Nothing was handed to the blocking pool, so there is nothing to wait for.
+        assert!(log
+            .records_for_client(&ClientId::new("team-alpha"))
+            .expect("query")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_live_stream_row_waits_for_body_end() {
+        let log = Arc::new(AuditLog::open_in_memory().expect("in-memory audit log"));
+        let response = Response::new(Body::from("data: chunk\n\n"));
+        let response = record_response(Some(&log), trail(true), response);
+        assert!(log
+            .records_for_client(&ClientId::new("team-alpha"))
+            .expect("query")
+            .is_empty());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(body, "data: chunk\n\n");
+        let rows = wait_for_rows(&log, 1).await;
+        assert_eq!(rows[0].status, 200);
+        assert_eq!(rows[0].backend_id, Some(BackendId::new("gpu-0")));
+    }
+
+    #[test]
+    fn test_open_is_idempotent_on_existing_file() {
+        let path = std::env::temp_dir().join(format!("mb-audit-{}.sqlite", uuid::Uuid::new_v4()));
+        AuditLog::open(&path)
+            .expect("create")
+            .insert(&record("req-1", "team-alpha", Some((5, 5))))
+            .expect("insert");
+
+        let reopened = AuditLog::open(&path).expect("reopen");
+        let rows = reopened
+            .records_for_client(&ClientId::new("team-alpha"))
+            .expect("query");
+        assert_eq!(rows.len(), 1);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -1,12 +1,24 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
-use anyhow::ensure;
+use anyhow::{anyhow, ensure};
 use mb_core::core::{
     AllowedModels, ApiKey, AuthService, BackendId, BackendInfo, BackendSpec, ClientId, ClientInfo,
-    ModelId, QuotaConfig, RateLimit, RoutingStrategy,
+    ForbiddenParamAction, ModelCapabilities, ModelId, ParamPolicy, QuotaConfig, RateLimit,
+    RoutingStrategy, TokenCounterRegistry, ToolSupport, GENERATION_PARAM_NAMES,
 };
 
-use crate::config::{AllowedModelsConfig, AppConfig, BackendSpecConfig, RoutingStrategyConfig};
+use crate::config::{
+    AllowedModelsConfig, AppConfig, AttributionHeaders, BackendConfig, BackendSpecConfig,
+    CapabilityCheck, ErrorVerbosity, FanoutMode, ForbiddenParamActionConfig, JsonOutputValidation,
+    PenaltyRangeCheck, ResponseModelCheck, RoutingStrategyConfig, ToolFallback,
+};
+use crate::tokenizer;
+
+/// Upper bound on `routing.retry_on_429`; longer retry chains mostly keep a
+/// client waiting on a backend that is still throttling.
+const MAX_RETRY_ON_429: u32 = 5;
 
 // ---------------------------------------------------------------------------
 // CacheConfig — cache-aware routing configuration
@@ -17,6 +29,8 @@ pub struct CacheConfig {
     pub enabled: bool,
     pub prefix_depth: usize,
     pub max_entries: usize,
+    /// Skip affinity rather than wait on a contended shard.
+    pub skip_contended: bool,
 }
 
 // ---------------------------------------------------------------------------
@@ -27,27 +41,191 @@ pub struct RuntimeConfig {
     pub auth_service: AuthService,
     pub backends: Vec<BackendInfo>,
     pub routing_strategy: RoutingStrategy,
+    /// Per-model overrides of `routing_strategy`.
+    pub model_strategies: HashMap<ModelId, RoutingStrategy>,
+    /// Models with a `routing.model_capabilities` entry.
+    pub model_capabilities: HashMap<ModelId, ModelCapabilities>,
+    pub capability_check: CapabilityCheck,
     pub health_check_interval_secs: u64,
     pub health_timeout_ms: u64,
     pub unhealthy_threshold: u32,
     pub degraded_latency_ms: u64,
+    pub health_verify_models: bool,
+    pub health_max_concurrent_probes: usize,
     pub cache_config: CacheConfig,
-    pub listen_addr: String,
+    pub verify_response_model: ResponseModelCheck,
+    pub validate_json_output: JsonOutputValidation,
+    pub penalty_range: PenaltyRangeCheck,
+    pub allow_empty_choices: bool,
+    pub require_user_message: bool,
+    pub merge_system_messages: bool,
+    /// Lower-cased name to configured name of each served model; empty
+    /// unless `routing.case_insensitive_models` is set.
+    pub model_casing: HashMap<String, ModelId>,
+    pub default_model: Option<ModelId>,
+    /// Prompt token counters from `routing.tokenizers`.
+    pub token_counters: TokenCounterRegistry,
+    pub provider_prefix: bool,
+    pub fanout: FanoutMode,
+    pub max_fanout: u32,
+    pub coalesce: bool,
+    /// Capacity of the response cache; `None` when it is off.
+    pub response_cache_entries: Option<usize>,
+    /// Capacity and TTL of the idempotency cache; `None` when it is off.
+    pub idempotency: Option<(usize, u64)>,
+    /// Wait timeout for the saturation queue; `None` when it is off.
+    pub queue_timeout_ms: Option<u64>,
+    pub retry_on_429: u32,
+    pub retry_on_429_max_backoff_ms: u64,
+    pub listen_addr: SocketAddr,
+    pub request_timeout_secs: u64,
+    pub connect_timeout_ms: u64,
+    pub read_timeout_ms: u64,
+    pub stream_read_timeout_ms: u64,
+    pub trust_forwarded: bool,
+    pub trusted_proxy_hops: usize,
+    pub ip_rate_limit_rpm: Option<u32>,
+    pub error_verbosity: ErrorVerbosity,
+    pub attribution_headers: AttributionHeaders,
+    pub sse_keepalive_secs: u64,
+    pub auth_schemes: Vec<String>,
     pub log_level: String,
     pub log_format: String,
+    pub redact_log_content: bool,
     /// Per-client rate limit (RPM) for lazy RateLimiter creation.
     pub client_rate_limits: std::collections::HashMap<ClientId, u32>,
+    /// Per-(client, model) RPM caps from `clients.model_rate_limits`.
+    pub model_rate_limits: HashMap<(ClientId, ModelId), u32>,
     /// Per-backend API keys for authenticating outbound requests.
     pub backend_api_keys: std::collections::HashMap<BackendId, ApiKey>,
+    /// Backends with `warmup = true`, preloaded once at startup.
+    pub warmup_backends: Vec<BackendId>,
+    /// `max_rpm` of backends that set one.
+    pub backend_rate_limits: HashMap<BackendId, u32>,
+    /// Key for `/admin/*` endpoints; `None` disables them.
+    pub admin_key: Option<ApiKey>,
+    /// sqlite file for the request audit log; `None` disables it.
+    pub audit_db_path: Option<PathBuf>,
 }
 
 // ---------------------------------------------------------------------------
 // into_runtime — converts raw AppConfig into validated RuntimeConfig
 // ---------------------------------------------------------------------------
 
+/// Lower-cased name to configured name of every served model, for
+/// `routing.case_insensitive_models`. Two served names differing only in
+/// case would make folding ambiguous, so they are rejected.
+fn model_casing(backends: &[BackendConfig]) -> Result<HashMap<String, ModelId>, anyhow::Error> {
+    let mut casing: HashMap<String, ModelId> = HashMap::new();
+    for model in backends.iter().flat_map(|b| &b.models) {
+        let folded = model.to_ascii_lowercase();
+        if let Some(existing) = casing.get(&folded) {
+            ensure!(
+                existing.as_str() == model,
+                "routing.case_insensitive_models is set but models {existing} and {model} differ only in case"
+            );
+            continue;
+        }
+        casing.insert(folded, ModelId::new(model.as_str()));
+    }
+    Ok(casing)
+}
+
 pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
     ensure!(!config.clients.is_empty(), "at least one client required");
     ensure!(!config.backends.is_empty(), "at least one backend required");
+    ensure!(
+        config.server.request_timeout_secs > 0,
+        "server.request_timeout_secs must be greater than zero"
+    );
+    for (field, value) in [
+        ("connect_timeout_ms", config.server.connect_timeout_ms),
+        ("read_timeout_ms", config.server.read_timeout_ms),
+        (
+            "stream_read_timeout_ms",
+            config.server.stream_read_timeout_ms,
+        ),
+    ] {
+        ensure!(value > 0, "server.{field} must be greater than zero");
+    }
+    ensure!(
+        config.server.sse_keepalive_secs > 0,
+        "server.sse_keepalive_secs must be greater than zero"
+    );
+    ensure!(
+        config.health.max_concurrent_probes > 0,
+        "health.max_concurrent_probes must be greater than zero"
+    );
+    ensure!(
+        !config.server.trust_forwarded || config.server.trusted_proxy_hops > 0,
+        "server.trusted_proxy_hops must be greater than zero when trust_forwarded is set"
+    );
+    ensure!(
+        config.server.ip_rate_limit_rpm != Some(0),
+        "server.ip_rate_limit_rpm must be greater than zero when set"
+    );
+    ensure!(
+        !config.server.auth_schemes.is_empty(),
+        "server.auth_schemes must list at least one scheme"
+    );
+    for scheme in &config.server.auth_schemes {
+        ensure!(
+            !scheme.is_empty() && !scheme.contains(char::is_whitespace),
+            "server.auth_schemes entry {scheme:?} must be a single non-empty word"
+        );
+    }
+    // Hashing zero messages gives every request the same prefix, pinning all
+    // traffic for a model to one backend.
+    ensure!(
+        !config.routing.cache_aware || config.routing.prefix_depth > 0,
+        "routing.prefix_depth must be greater than zero when cache_aware is set \
+         (set cache_aware = false to disable affinity routing)"
+    );
+    ensure!(
+        !config.routing.queue_when_saturated || config.routing.queue_timeout_ms > 0,
+        "routing.queue_timeout_ms must be greater than zero when queue_when_saturated is set"
+    );
+    ensure!(
+        config.routing.retry_on_429 <= MAX_RETRY_ON_429,
+        "routing.retry_on_429 must be at most {MAX_RETRY_ON_429}"
+    );
+    ensure!(
+        config.routing.retry_on_429 == 0 || config.routing.retry_on_429_max_backoff_ms > 0,
+        "routing.retry_on_429_max_backoff_ms must be greater than zero when retry_on_429 is set"
+    );
+    ensure!(
+        !config.routing.response_cache || config.routing.response_cache_entries > 0,
+        "routing.response_cache_entries must be greater than zero when response_cache is set"
+    );
+    ensure!(
+        config.routing.fanout == FanoutMode::Off || (2..=128).contains(&config.routing.max_fanout),
+        "routing.max_fanout must be between 2 and 128 when fanout is set"
+    );
+    ensure!(
+        config.routing.idempotency_ttl_secs == 0 || config.routing.idempotency_entries > 0,
+        "routing.idempotency_entries must be greater than zero when idempotency_ttl_secs is set"
+    );
+    let listen_addr: SocketAddr = config.server.listen.parse().map_err(|e| {
+        anyhow!(
+            "server.listen {:?} is not a valid socket address: {e}",
+            config.server.listen
+        )
+    })?;
+
+    if let Some(db_path) = &config.audit.db_path {
+        ensure!(
+            !db_path.as_os_str().is_empty(),
+            "audit.db_path must not be empty"
+        );
+    }
+
+    if let Some(admin_key) = &config.admin.api_key {
+        ensure!(!admin_key.is_empty(), "admin.api_key must not be empty");
+        ensure!(
+            config.clients.iter().all(|c| &c.api_key != admin_key),
+            "admin.api_key must differ from every client api_key"
+        );
+    }
 
     // Detect duplicate client IDs
     let mut seen_clients = HashSet::with_capacity(config.clients.len());
@@ -57,7 +235,50 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
             "duplicate client id: {}",
             client.id
         );
+        for param in &client.forbidden_params {
+            ensure!(
+                GENERATION_PARAM_NAMES.contains(&param.as_str()),
+                "client {}: forbidden_params entry {param:?} is not a generation parameter (expected one of {})",
+                client.id,
+                GENERATION_PARAM_NAMES.join(", ")
+            );
+        }
+        for (model, rpm) in &client.model_rate_limits {
+            ensure!(
+                *rpm > 0,
+                "client {}: model_rate_limits.{model} must be greater than zero",
+                client.id
+            );
+        }
+        ensure!(
+            client.rate_limit_tpm != Some(0),
+            "client {}: rate_limit_tpm must be greater than zero",
+            client.id
+        );
     }
+    let model_casing = if config.routing.case_insensitive_models {
+        model_casing(&config.backends)?
+    } else {
+        HashMap::new()
+    };
+    // Client model lists take the configured casing so permission checks
+    // and per-model limits match the folded request model exactly.
+    let fold_model = |name: &str| {
+        model_casing
+            .get(&name.to_ascii_lowercase())
+            .cloned()
+            .unwrap_or_else(|| ModelId::new(name))
+    };
+
+    let model_rate_limits: HashMap<(ClientId, ModelId), u32> = config
+        .clients
+        .iter()
+        .flat_map(|c| {
+            c.model_rate_limits
+                .iter()
+                .map(|(model, rpm)| ((ClientId::new(c.id.as_str()), fold_model(model)), *rpm))
+        })
+        .collect();
 
     // Detect duplicate backend IDs
     let mut seen_backends = HashSet::with_capacity(config.backends.len());
@@ -67,9 +288,16 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
             "duplicate backend id: {}",
             backend.id
         );
+        validate_base_url(&backend.id, &backend.base_url)?;
+        ensure!(
+            backend.max_rpm != Some(0),
+            "backend {}: max_rpm must be greater than zero",
+            backend.id
+        );
     }
 
     // Convert clients → AuthService
+    let default_max_tools = config.routing.max_tools;
     let client_entries: Vec<(ApiKey, ClientInfo)> = config
         .clients
         .into_iter()
@@ -78,7 +306,10 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
             let allowed_models = match c.allowed_models {
                 AllowedModelsConfig::All(_) => AllowedModels::All,
                 AllowedModelsConfig::Specific(list) => {
-                    AllowedModels::Specific(list.into_iter().map(ModelId::new).collect())
+                    AllowedModels::Specific(list.iter().map(|m| fold_model(m)).collect())
+                }
+                AllowedModelsConfig::AllExcept { all_except } => {
+                    AllowedModels::AllExcept(all_except.iter().map(|m| fold_model(m)).collect())
                 }
             };
             let info = ClientInfo {
@@ -91,6 +322,15 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
                 quota: QuotaConfig {
                     monthly_token_limit: c.monthly_token_limit,
                 },
+                param_policy: ParamPolicy {
+                    forbidden: c.forbidden_params,
+                    action: match c.forbidden_param_action {
+                        ForbiddenParamActionConfig::Strip => ForbiddenParamAction::Strip,
+                        ForbiddenParamActionConfig::Reject => ForbiddenParamAction::Reject,
+                    },
+                },
+                priority: c.priority,
+                max_tools: c.max_tools.or(default_max_tools),
             };
             (key, info)
         })
@@ -104,6 +344,8 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
 
     // Convert backends → Vec<BackendInfo> and extract API keys
     let mut backend_api_keys = std::collections::HashMap::new();
+    let mut warmup_backends = Vec::new();
+    let mut backend_rate_limits = HashMap::new();
     let backends: Vec<BackendInfo> = config
         .backends
         .into_iter()
@@ -112,6 +354,12 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
             if let Some(key) = b.api_key {
                 backend_api_keys.insert(id.clone(), ApiKey::new(key));
             }
+            if b.warmup {
+                warmup_backends.push(id.clone());
+            }
+            if let Some(rpm) = b.max_rpm {
+                backend_rate_limits.insert(id.clone(), rpm);
+            }
             BackendInfo {
                 id,
                 spec: match b.spec {
@@ -121,39 +369,175 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
                 models: b.models.into_iter().map(ModelId::new).collect(),
                 max_concurrent: b.max_concurrent,
                 base_url: b.base_url,
+                model_map: b
+                    .model_map
+                    .into_iter()
+                    .map(|(canonical, wire)| (ModelId::new(canonical), wire))
+                    .collect(),
+                tool_support: match (b.supports_tools, b.tool_fallback) {
+                    (true, _) => ToolSupport::Native,
+                    (false, ToolFallback::Reject) => ToolSupport::Reject,
+                    (false, ToolFallback::Downgrade) => ToolSupport::Downgrade,
+                },
+                cost_weight: b.cost_weight,
             }
         })
         .collect();
 
-    // Convert routing strategy
-    let routing_strategy = match config.routing.strategy {
-        RoutingStrategyConfig::LeastLoaded => RoutingStrategy::LeastLoaded,
-        RoutingStrategyConfig::RoundRobin => RoutingStrategy::RoundRobin,
-    };
+    for backend in &backends {
+        for (model, wire) in &backend.model_map {
+            ensure!(
+                backend.models.contains(model),
+                "backend {} maps model {model}, which is not in its models list",
+                backend.id
+            );
+            ensure!(
+                !wire.is_empty(),
+                "backend {} maps model {model} to an empty name",
+                backend.id
+            );
+        }
+    }
+
+    // Convert routing strategy and per-model overrides
+    let routing_strategy = convert_strategy(&config.routing.strategy);
+    let mut model_strategies = HashMap::with_capacity(config.routing.per_model.len());
+    for (model, strategy) in &config.routing.per_model {
+        let model = ModelId::new(model.as_str());
+        ensure!(
+            backends.iter().any(|b| b.models.contains(&model)),
+            "routing.per_model references model {model}, which no backend serves"
+        );
+        model_strategies.insert(model, convert_strategy(strategy));
+    }
+
+    let mut model_capabilities = HashMap::with_capacity(config.routing.model_capabilities.len());
+    for (model, caps) in &config.routing.model_capabilities {
+        let model = ModelId::new(model.as_str());
+        ensure!(
+            backends.iter().any(|b| b.models.contains(&model)),
+            "routing.model_capabilities references model {model}, which no backend serves"
+        );
+        model_capabilities.insert(
+            model,
+            ModelCapabilities {
+                tools: caps.tools,
+                vision: caps.vision,
+                json_mode: caps.json_mode,
+            },
+        );
+    }
+
+    let default_model = config.routing.default_model.as_deref().map(ModelId::new);
+    if let Some(model) = &default_model {
+        ensure!(
+            backends.iter().any(|b| b.models.contains(model)),
+            "routing.default_model is {model}, which no backend serves"
+        );
+    }
+
+    for (prefix, tokenizer) in &config.routing.tokenizers {
+        ensure!(
+            tokenizer::is_available(*tokenizer),
+            "routing.tokenizers.{prefix} needs a build with the tiktoken feature"
+        );
+    }
+    let token_counters = tokenizer::registry(&config.routing.tokenizers);
 
     let cache_config = CacheConfig {
         enabled: config.routing.cache_aware,
         prefix_depth: config.routing.prefix_depth,
         max_entries: config.routing.max_affinity_entries,
+        skip_contended: config.routing.affinity_skip_on_contention,
     };
 
     Ok(RuntimeConfig {
         auth_service,
         backends,
         routing_strategy,
+        model_strategies,
+        model_capabilities,
+        capability_check: config.routing.capability_check,
         health_check_interval_secs: config.health.check_interval_secs,
         health_timeout_ms: config.health.timeout_ms,
         unhealthy_threshold: config.health.unhealthy_threshold,
         degraded_latency_ms: config.health.degraded_latency_ms,
+        health_verify_models: config.health.verify_models,
+        health_max_concurrent_probes: config.health.max_concurrent_probes,
         cache_config,
-        listen_addr: config.server.listen,
+        verify_response_model: config.routing.verify_response_model,
+        validate_json_output: config.routing.validate_json_output,
+        penalty_range: config.routing.penalty_range,
+        allow_empty_choices: config.routing.allow_empty_choices,
+        require_user_message: config.routing.require_user_message,
+        merge_system_messages: config.routing.merge_system_messages,
+        model_casing,
+        default_model,
+        token_counters,
+        provider_prefix: config.routing.provider_prefix,
+        fanout: config.routing.fanout,
+        max_fanout: config.routing.max_fanout,
+        coalesce: config.routing.coalesce,
+        response_cache_entries: config
+            .routing
+            .response_cache
+            .then_some(config.routing.response_cache_entries),
+        idempotency: (config.routing.idempotency_ttl_secs > 0).then_some((
+            config.routing.idempotency_entries,
+            config.routing.idempotency_ttl_secs.saturating_mul(1_000),
+        )),
+        queue_timeout_ms: config
+            .routing
+            .queue_when_saturated
+            .then_some(config.routing.queue_timeout_ms),
+        retry_on_429: config.routing.retry_on_429,
+        retry_on_429_max_backoff_ms: config.routing.retry_on_429_max_backoff_ms,
+        listen_addr,
+        request_timeout_secs: config.server.request_timeout_secs,
+        connect_timeout_ms: config.server.connect_timeout_ms,
+        read_timeout_ms: config.server.read_timeout_ms,
+        stream_read_timeout_ms: config.server.stream_read_timeout_ms,
+        trust_forwarded: config.server.trust_forwarded,
+        trusted_proxy_hops: config.server.trusted_proxy_hops,
+        ip_rate_limit_rpm: config.server.ip_rate_limit_rpm,
+        error_verbosity: config.server.error_verbosity,
+        attribution_headers: config.server.attribution_headers,
+        sse_keepalive_secs: config.server.sse_keepalive_secs,
+        auth_schemes: config.server.auth_schemes,
         log_level: config.logging.level,
         log_format: config.logging.format,
+        redact_log_content: config.logging.redact_content,
         client_rate_limits,
+        model_rate_limits,
         backend_api_keys,
+        warmup_backends,
+        backend_rate_limits,
+        admin_key: config.admin.api_key.map(ApiKey::new),
+        audit_db_path: config.audit.db_path,
     })
 }
 
+/// Requires an absolute http(s) URL, so a typo fails at startup instead of
+/// on the first request routed to the backend.
+fn validate_base_url(backend_id: &str, base_url: &str) -> anyhow::Result<()> {
+    let url = reqwest::Url::parse(base_url).map_err(|e| {
+        anyhow!("backend {backend_id}: base_url {base_url:?} is not a valid URL: {e}")
+    })?;
+    ensure!(
+        matches!(url.scheme(), "http" | "https"),
+        "backend {backend_id}: base_url {base_url:?} must use http or https"
+    );
+    Ok(())
+}
+
+fn convert_strategy(strategy: &RoutingStrategyConfig) -> RoutingStrategy {
+    match strategy {
+        RoutingStrategyConfig::LeastLoaded => RoutingStrategy::LeastLoaded,
+        RoutingStrategyConfig::RoundRobin => RoutingStrategy::RoundRobin,
+        RoutingStrategyConfig::CheapestFirst => RoutingStrategy::CheapestFirst,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -162,9 +546,11 @@ pub fn into_runtime(config: AppConfig) -> Result<RuntimeConfig, anyhow::Error> {
 mod tests {
     use super::*;
     use crate::config::{
-        BackendConfig, BackendSpecConfig, ClientConfig, HealthConfig, LoggingConfig, RoutingConfig,
-        ServerConfig, WildcardMarker,
+        AdminConfig, AuditConfig, BackendConfig, BackendSpecConfig, ClientConfig, HealthConfig,
+        LoggingConfig, ModelCapabilitiesConfig, RoutingConfig, ServerConfig, TokenizerConfig,
+        WildcardMarker,
     };
+    use mb_core::core::{Message, MessageContent, Role};
 
     fn make_client(id: &str, api_key: &str) -> ClientConfig {
         ClientConfig {
@@ -174,6 +560,11 @@ mod tests {
             rate_limit_rpm: 60,
             rate_limit_tpm: None,
             monthly_token_limit: None,
+            model_rate_limits: HashMap::new(),
+            forbidden_params: Vec::new(),
+            forbidden_param_action: ForbiddenParamActionConfig::Strip,
+            priority: 0,
+            max_tools: None,
         }
     }
 
@@ -185,6 +576,12 @@ mod tests {
             spec: BackendSpecConfig::OpenaiChat,
             models: vec!["llama3-70b".to_owned()],
             max_concurrent: 10,
+            warmup: false,
+            model_map: HashMap::new(),
+            supports_tools: true,
+            tool_fallback: ToolFallback::Reject,
+            cost_weight: 0,
+            max_rpm: None,
         }
     }
 
@@ -194,6 +591,8 @@ mod tests {
             routing: RoutingConfig::default(),
             health: HealthConfig::default(),
             logging: LoggingConfig::default(),
+            admin: AdminConfig::default(),
+            audit: AuditConfig::default(),
             clients: vec![make_client(
                 "team-alpha",
                 "mb-sk-test00000000000000000000000",
@@ -217,8 +616,13 @@ mod tests {
         assert_eq!(runtime.health_timeout_ms, 5000);
         assert_eq!(runtime.unhealthy_threshold, 3);
         assert_eq!(runtime.degraded_latency_ms, 2000);
+        assert!(!runtime.health_verify_models);
         assert!(runtime.cache_config.enabled);
-        assert_eq!(runtime.listen_addr, "0.0.0.0:8080");
+        assert_eq!(runtime.listen_addr.to_string(), "0.0.0.0:8080");
+        assert_eq!(runtime.request_timeout_secs, 120);
+        assert_eq!(runtime.connect_timeout_ms, 5_000);
+        assert_eq!(runtime.read_timeout_ms, 30_000);
+        assert_eq!(runtime.stream_read_timeout_ms, 300_000);
     }
 
     #[test]
@@ -236,6 +640,28 @@ mod tests {
         assert!(matches!(client.allowed_models, AllowedModels::All));
     }
 
+    #[test]
+    fn test_all_except_models() {
+        let mut config = make_config();
+        config.clients[0].allowed_models = AllowedModelsConfig::AllExcept {
+            all_except: vec!["gpt-4".to_owned()],
+        };
+
+        let runtime = into_runtime(config).expect("all_except config should convert");
+
+        let key = ApiKey::new("mb-sk-test00000000000000000000000");
+        let client = runtime
+            .auth_service
+            .validate(&key)
+            .expect("key should be valid");
+        match &client.allowed_models {
+            AllowedModels::AllExcept(denied) => {
+                assert_eq!(denied, &vec![ModelId::new("gpt-4")]);
+            }
+            other => panic!("expected AllExcept, got {other:?}"),
+        }
+    }
+
     #[test]
     fn test_empty_clients_rejected() {
         let mut config = make_config();
@@ -258,6 +684,446 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_invalid_listen_address_rejected() {
+        let mut config = make_config();
+        config.server.listen = "0.0.0.0:80800".to_owned();
+
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("server.listen")),
+            Ok(_) => panic!("expected error for invalid listen address"),
+        }
+    }
+
+    #[test]
+    fn test_malformed_base_url_rejected() {
+        for base_url in [
+            "100.64.0.1:8000",
+            "ftp://100.64.0.1",
+            "http//100.64.0.1:8000",
+        ] {
+            let mut config = make_config();
+            config.backends[0].base_url = base_url.to_owned();
+
+            match into_runtime(config) {
+                Err(e) => {
+                    let message = e.to_string();
+                    assert!(message.contains("gpu-desktop"), "{message}");
+                    assert!(message.contains("base_url"), "{message}");
+                }
+                Ok(_) => panic!("expected error for base_url {base_url:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_request_timeout_rejected() {
+        let mut config = make_config();
+        config.server.request_timeout_secs = 0;
+
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("request_timeout_secs")),
+            Ok(_) => panic!("expected error for zero request timeout"),
+        }
+    }
+
+    #[test]
+    fn test_zero_backend_timeouts_rejected() {
+        let mut config = make_config();
+        config.server.connect_timeout_ms = 0;
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("server.connect_timeout_ms")),
+            Ok(_) => panic!("expected error for zero connect timeout"),
+        }
+
+        let mut config = make_config();
+        config.server.stream_read_timeout_ms = 0;
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("server.stream_read_timeout_ms")),
+            Ok(_) => panic!("expected error for zero stream read timeout"),
+        }
+    }
+
+    #[test]
+    fn test_retry_on_429_bounds_checked() {
+        let mut config = make_config();
+        config.routing.retry_on_429 = MAX_RETRY_ON_429 + 1;
+        match into_runtime(config) {
+            Err(e) => assert!(e
+                .to_string()
+                .contains("routing.retry_on_429 must be at most")),
+            Ok(_) => panic!("expected error for too many 429 retries"),
+        }
+
+        let mut config = make_config();
+        config.routing.retry_on_429 = 2;
+        config.routing.retry_on_429_max_backoff_ms = 0;
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("retry_on_429_max_backoff_ms")),
+            Ok(_) => panic!("expected error for zero 429 backoff"),
+        }
+    }
+
+    #[test]
+    fn test_zero_prefix_depth_rejected_when_cache_aware() {
+        let mut config = make_config();
+        config.routing.cache_aware = true;
+        config.routing.prefix_depth = 0;
+
+        match into_runtime(config) {
+            Err(e) => assert!(e
+                .to_string()
+                .contains("prefix_depth must be greater than zero")),
+            Ok(_) => panic!("expected error for zero prefix depth"),
+        }
+    }
+
+    #[test]
+    fn test_zero_prefix_depth_allowed_without_affinity() {
+        let mut config = make_config();
+        config.routing.cache_aware = false;
+        config.routing.prefix_depth = 0;
+
+        let runtime = into_runtime(config).expect("unused prefix depth is valid");
+        assert!(!runtime.cache_config.enabled);
+    }
+
+    #[test]
+    fn test_default_model_must_be_served() {
+        let mut config = make_config();
+        config.routing.default_model = Some("llama3-70b".to_owned());
+        let runtime = into_runtime(config).expect("served default model is valid");
+        assert_eq!(runtime.default_model, Some(ModelId::new("llama3-70b")));
+
+        let mut config = make_config();
+        config.routing.default_model = Some("mistral-7b".to_owned());
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("routing.default_model")),
+            Ok(_) => panic!("expected error for an unserved default model"),
+        }
+    }
+
+    #[test]
+    fn test_max_fanout_checked_only_when_fanout_is_on() {
+        let mut config = make_config();
+        config.routing.max_fanout = 0;
+        into_runtime(config).expect("max_fanout is unused while fanout is off");
+
+        let mut config = make_config();
+        config.routing.fanout = FanoutMode::Strict;
+        config.routing.max_fanout = 1;
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("routing.max_fanout")),
+            Ok(_) => panic!("expected error for a max_fanout below 2"),
+        }
+    }
+
+    #[test]
+    fn test_tokenizers_build_the_counter_registry() {
+        let mut config = make_config();
+        config
+            .routing
+            .tokenizers
+            .insert("llama3".to_owned(), TokenizerConfig::Heuristic);
+        let runtime = into_runtime(config).expect("heuristic tokenizer is always available");
+        let messages = [Message {
+            role: Role::User,
+            content: MessageContent::Text("eight by".to_owned()),
+            name: None,
+            tool_call_id: None,
+        }];
+        assert_eq!(
+            runtime
+                .token_counters
+                .count(&ModelId::new("llama3-70b"), &messages),
+            2
+        );
+
+        let mut config = make_config();
+        config
+            .routing
+            .tokenizers
+            .insert("gpt-4".to_owned(), TokenizerConfig::Cl100kBase);
+        let result = into_runtime(config);
+        if cfg!(feature = "tiktoken") {
+            assert!(result.is_ok());
+        } else {
+            match result {
+                Err(e) => assert!(e.to_string().contains("routing.tokenizers.gpt-4")),
+                Ok(_) => panic!("expected error for a tokenizer this build lacks"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_map_must_reference_served_models() {
+        let mut config = make_config();
+        config.backends[0]
+            .model_map
+            .insert("llama3-70b".to_owned(), "llama3:70b".to_owned());
+        let runtime = into_runtime(config).expect("mapping a served model is valid");
+        assert_eq!(
+            runtime.backends[0].wire_model(&ModelId::new("llama3-70b")),
+            "llama3:70b"
+        );
+
+        let mut config = make_config();
+        config.backends[0]
+            .model_map
+            .insert("gpt-5".to_owned(), "gpt-5-preview".to_owned());
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("not in its models list")),
+            Ok(_) => panic!("expected error for mapping an unserved model"),
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_models_fold_client_lists() {
+        let mut config = make_config();
+        config.routing.case_insensitive_models = true;
+        config.backends[0].models = vec!["Llama3-70B".to_owned()];
+        let runtime = into_runtime(config).expect("valid case-insensitive config");
+        assert_eq!(
+            runtime.model_casing.get("llama3-70b"),
+            Some(&ModelId::new("Llama3-70B"))
+        );
+        let client = runtime
+            .auth_service
+            .client(&ClientId::new("team-alpha"))
+            .expect("client registered");
+        assert!(matches!(
+            &client.allowed_models,
+            AllowedModels::Specific(models) if models == &[ModelId::new("Llama3-70B")]
+        ));
+
+        let config = make_config();
+        let runtime = into_runtime(config).expect("exact matching is the default");
+        assert!(runtime.model_casing.is_empty());
+
+        let mut config = make_config();
+        config.routing.case_insensitive_models = true;
+        config.backends.push(make_backend("gpu-laptop"));
+        config.backends[1].models = vec!["LLAMA3-70B".to_owned()];
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("differ only in case")),
+            Ok(_) => panic!("expected error for models differing only in case"),
+        }
+    }
+
+    #[test]
+    fn test_model_capabilities_converted_and_checked() {
+        let mut config = make_config();
+        config.routing.model_capabilities.insert(
+            "llama3-70b".to_owned(),
+            ModelCapabilitiesConfig {
+                tools: false,
+                ..ModelCapabilitiesConfig::default()
+            },
+        );
+        config.routing.capability_check = CapabilityCheck::Strict;
+        let runtime = into_runtime(config).expect("valid config");
+        let caps = runtime
+            .model_capabilities
+            .get(&ModelId::new("llama3-70b"))
+            .expect("capabilities registered");
+        assert!(!caps.tools);
+        assert!(caps.vision && caps.json_mode);
+        assert_eq!(runtime.capability_check, CapabilityCheck::Strict);
+
+        let mut config = make_config();
+        config
+            .routing
+            .model_capabilities
+            .insert("gpt-4".to_owned(), ModelCapabilitiesConfig::default());
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("routing.model_capabilities")),
+            Ok(_) => panic!("expected error for unknown model"),
+        }
+    }
+
+    #[test]
+    fn test_per_model_strategy_overrides() {
+        let mut config = make_config();
+        config
+            .routing
+            .per_model
+            .insert("llama3-70b".to_owned(), RoutingStrategyConfig::RoundRobin);
+        let runtime = into_runtime(config).expect("override for a served model is valid");
+        assert_eq!(
+            runtime.model_strategies.get(&ModelId::new("llama3-70b")),
+            Some(&RoutingStrategy::RoundRobin)
+        );
+
+        let mut config = make_config();
+        config
+            .routing
+            .per_model
+            .insert("gpt-5".to_owned(), RoutingStrategyConfig::RoundRobin);
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("routing.per_model")),
+            Ok(_) => panic!("expected error for override of an unserved model"),
+        }
+    }
+
+    #[test]
+    fn test_admin_key_must_differ_from_client_keys() {
+        let mut config = make_config();
+        config.admin.api_key = Some("mb-sk-test00000000000000000000000".to_owned());
+
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("admin.api_key")),
+            Ok(_) => panic!("expected error for admin key reused by a client"),
+        }
+
+        let mut config = make_config();
+        config.admin.api_key = Some("mb-sk-admin0000000000000000000000".to_owned());
+        let runtime = into_runtime(config).expect("distinct admin key is valid");
+        assert!(runtime.admin_key.is_some());
+    }
+
+    #[test]
+    fn test_zero_sse_keepalive_rejected() {
+        let mut config = make_config();
+        config.server.sse_keepalive_secs = 0;
+
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("sse_keepalive_secs")),
+            Ok(_) => panic!("expected error for zero SSE keep-alive"),
+        }
+    }
+
+    #[test]
+    fn test_zero_max_concurrent_probes_rejected() {
+        let mut config = make_config();
+        config.health.max_concurrent_probes = 0;
+
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("max_concurrent_probes")),
+            Ok(_) => panic!("expected error for zero concurrent probes"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_auth_schemes_rejected() {
+        for schemes in [vec![], vec!["Bearer Token".to_owned()], vec![String::new()]] {
+            let mut config = make_config();
+            config.server.auth_schemes = schemes;
+
+            match into_runtime(config) {
+                Err(e) => assert!(e.to_string().contains("server.auth_schemes")),
+                Ok(_) => panic!("expected error for invalid auth schemes"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_rate_limits_converted_and_validated() {
+        let mut config = make_config();
+        config.clients[0]
+            .model_rate_limits
+            .insert("llama3-70b".to_owned(), 5);
+        let runtime = into_runtime(config).expect("valid model rate limit");
+        let key = (ClientId::new("team-alpha"), ModelId::new("llama3-70b"));
+        assert_eq!(runtime.model_rate_limits.get(&key), Some(&5));
+
+        let mut config = make_config();
+        config.clients[0]
+            .model_rate_limits
+            .insert("llama3-70b".to_owned(), 0);
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("model_rate_limits.llama3-70b")),
+            Ok(_) => panic!("expected error for zero model rate limit"),
+        }
+    }
+
+    #[test]
+    fn test_forbidden_params_converted_and_validated() {
+        let mut config = make_config();
+        config.clients[0].forbidden_params = vec!["temperature".to_owned()];
+        config.clients[0].forbidden_param_action = ForbiddenParamActionConfig::Reject;
+        let runtime = into_runtime(config).expect("known parameter is valid");
+        let client = runtime
+            .auth_service
+            .client(&ClientId::new("team-alpha"))
+            .expect("client registered");
+        assert_eq!(client.param_policy.forbidden, ["temperature"]);
+        assert_eq!(client.param_policy.action, ForbiddenParamAction::Reject);
+
+        let mut config = make_config();
+        config.clients[0].forbidden_params = vec!["temprature".to_owned()];
+        match into_runtime(config) {
+            Err(e) => assert!(e
+                .to_string()
+                .contains("forbidden_params entry \"temprature\"")),
+            Ok(_) => panic!("expected error for unknown parameter name"),
+        }
+    }
+
+    #[test]
+    fn test_client_max_tools_overrides_routing_default() {
+        let mut config = make_config();
+        config.routing.max_tools = Some(8);
+        config.clients[0].max_tools = Some(32);
+        config.clients.push(make_client(
+            "team-beta",
+            "mb-sk-beta00000000000000000000000",
+        ));
+        let runtime = into_runtime(config).expect("valid max_tools");
+        let max_tools = |id: &str| {
+            runtime
+                .auth_service
+                .client(&ClientId::new(id))
+                .expect("client registered")
+                .max_tools
+        };
+        assert_eq!(max_tools("team-alpha"), Some(32));
+        assert_eq!(max_tools("team-beta"), Some(8));
+    }
+
+    #[test]
+    fn test_backend_max_rpm_converted_and_validated() {
+        let mut config = make_config();
+        config.backends[0].max_rpm = Some(30);
+        let runtime = into_runtime(config).expect("valid backend max_rpm");
+        assert_eq!(
+            runtime
+                .backend_rate_limits
+                .get(&BackendId::new("gpu-desktop")),
+            Some(&30)
+        );
+
+        let mut config = make_config();
+        config.backends[0].max_rpm = Some(0);
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("max_rpm must be greater than zero")),
+            Ok(_) => panic!("expected error for zero backend max_rpm"),
+        }
+    }
+
+    #[test]
+    fn test_zero_rate_limit_tpm_rejected() {
+        let mut config = make_config();
+        config.clients[0].rate_limit_tpm = Some(0);
+
+        match into_runtime(config) {
+            Err(e) => assert!(e
+                .to_string()
+                .contains("rate_limit_tpm must be greater than zero")),
+            Ok(_) => panic!("expected error for zero rate_limit_tpm"),
+        }
+    }
+
+    #[test]
+    fn test_zero_ip_rate_limit_rejected() {
+        let mut config = make_config();
+        config.server.ip_rate_limit_rpm = Some(0);
+
+        match into_runtime(config) {
+            Err(e) => assert!(e.to_string().contains("ip_rate_limit_rpm")),
+            Ok(_) => panic!("expected error for zero per-IP rate limit"),
+        }
+    }
+
     #[test]
     fn test_duplicate_client_ids() {
         let mut config = make_config();
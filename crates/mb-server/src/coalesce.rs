@@ -0,0 +1,196 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+use mb_core::core::{BackendId, CanonicalRequest, CanonicalResponse, PrefixHash};
+use tokio::sync::oneshot;
+
+use crate::response_cache::request_hashes;
+
+// ---------------------------------------------------------------------------
+// Coalescer — single-flight for identical concurrent requests
+// ---------------------------------------------------------------------------
+
+/// Identifies requests that may share one backend call: the prefix hash plus
+/// a hash of the request as it will be sent, after per-client policy.
+pub type CoalesceKey = (PrefixHash, u64);
+
+/// The backend that answered and its parsed response.
+pub type SharedResponse = (BackendId, CanonicalResponse);
+
+type Waiters<K> = HashMap<K, Vec<oneshot::Sender<SharedResponse>>>;
+
+/// Tracks in-flight backend calls so identical concurrent requests wait on
+/// the first one instead of each calling the backend. Keyed by
+/// [`CoalesceKey`] unless another notion of "identical" is needed.
+pub struct Coalescer<K = CoalesceKey> {
+    inflight: Mutex<Waiters<K>>,
+}
+
+impl<K> Default for Coalescer<K> {
+    fn default() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Outcome of [`Coalescer::join`].
+pub enum Flight<'a, K: Hash + Eq + Clone = CoalesceKey> {
+    /// No identical call is in flight; the caller must make it and then
+    /// [`LeaderGuard::complete`] it.
+    Leader(LeaderGuard<'a, K>),
+    /// An identical call is in flight. The receiver yields its response, or
+    /// an error when the leader failed and the caller should dispatch itself.
+    Follower(oneshot::Receiver<SharedResponse>),
+}
+
+impl Coalescer {
+    /// Hashes the model, messages and output-shaping fields of `req`, so
+    /// clients whose policies rewrite the same body differently never
+    /// share a call.
+    pub fn key(prefix: PrefixHash, req: &CanonicalRequest) -> CoalesceKey {
+        let mut hasher = DefaultHasher::new();
+        (&req.model, request_hashes(req)).hash(&mut hasher);
+        (prefix, hasher.finish())
+    }
+}
+
+impl<K: Hash + Eq + Clone> Coalescer<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn join(&self, key: K) -> Flight<'_, K> {
+        let mut inflight = self.lock();
+        if let Some(waiters) = inflight.get_mut(&key) {
+            let (tx, rx) = oneshot::channel();
+            waiters.push(tx);
+            return Flight::Follower(rx);
+        }
+        inflight.insert(key.clone(), Vec::new());
+        Flight::Leader(LeaderGuard {
+            coalescer: self,
+            key: Some(key),
+        })
+    }
+
+    /// Number of distinct calls currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Every mutation is a single HashMap operation, so a poisoned map is
+    /// still consistent.
+    fn lock(&self) -> MutexGuard<'_, Waiters<K>> {
+        self.inflight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Held by the request making the backend call. Dropping it without
+/// completing (error, timeout, cancellation) releases the key and wakes the
+/// followers empty-handed.
+pub struct LeaderGuard<'a, K: Hash + Eq + Clone = CoalesceKey> {
+    coalescer: &'a Coalescer<K>,
+    key: Option<K>,
+}
+
+impl<K: Hash + Eq + Clone> LeaderGuard<'_, K> {
+    /// Hands `response` to every follower and releases the key.
+    pub fn complete(mut self, response: &SharedResponse) {
+        self.release(Some(response));
+    }
+
+    fn release(&mut self, response: Option<&SharedResponse>) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let waiters = self.coalescer.lock().remove(&key).unwrap_or_default();
+        if let Some(response) = response {
+            for tx in waiters {
+                // The follower may have been cancelled; nothing to do then.
+                let _ = tx.send(response.clone());
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Drop for LeaderGuard<'_, K> {
+    fn drop(&mut self) {
+        self.release(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_core::core::{InboundAdapter, ModelId, TokenUsage};
+
+    use super::*;
+    use crate::inbound::openai_chat::OpenAiChatInboundAdapter;
+
+    fn request(body: &str) -> CanonicalRequest {
+        OpenAiChatInboundAdapter
+            .parse_request(body.as_bytes(), None)
+            .expect("valid request")
+    }
+
+    fn response() -> SharedResponse {
+        let resp = CanonicalResponse {
+            id: "resp-1".to_owned(),
+            model: ModelId::new("llama3-70b"),
+            choices: vec![],
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            created: 0,
+        };
+        (BackendId::new("gpu-0"), resp)
+    }
+
+    #[tokio::test]
+    async fn test_followers_receive_leader_response() {
+        let coalescer = Coalescer::new();
+        let req = request(r#"{"model":"m","messages":[{"role":"user","content":"hi"}]}"#);
+        let key = Coalescer::key(PrefixHash::new(1), &req);
+
+        let Flight::Leader(guard) = coalescer.join(key) else {
+            panic!("first request should lead");
+        };
+        let Flight::Follower(rx) = coalescer.join(key) else {
+            panic!("identical request should follow");
+        };
+        // Different parameters are not coalesced.
+        let hotter = request(
+            r#"{"model":"m","messages":[{"role":"user","content":"hi"}],"temperature":1.5}"#,
+        );
+        assert!(matches!(
+            coalescer.join(Coalescer::key(PrefixHash::new(1), &hotter)),
+            Flight::Leader(_)
+        ));
+
+        guard.complete(&response());
+        assert_eq!(rx.await.expect("response shared"), response());
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_leader_releases_followers() {
+        let coalescer = Coalescer::new();
+        let req = request(r#"{"model":"m","messages":[{"role":"user","content":"hi"}]}"#);
+        let key = Coalescer::key(PrefixHash::new(1), &req);
+
+        let leader = coalescer.join(key);
+        let Flight::Follower(rx) = coalescer.join(key) else {
+            panic!("identical request should follow");
+        };
+        drop(leader);
+
+        assert!(rx.await.is_err());
+        assert!(matches!(coalescer.join(key), Flight::Leader(_)));
+    }
+}
@@ -1,5 +1,7 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+use anyhow::{bail, ensure, Context};
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -12,16 +14,160 @@ pub struct AppConfig {
     pub health: HealthConfig,
     #[serde(default)]
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
     pub clients: Vec<ClientConfig>,
     pub backends: Vec<BackendConfig>,
 }
 
 impl AppConfig {
+    /// Loads `path`, which is either a single TOML file or a directory whose
+    /// `*.toml` files are merged (see [`AppConfig::from_dir`]).
     pub fn from_file(path: &Path) -> Result<Self, anyhow::Error> {
+        if path.is_dir() {
+            return Self::from_dir(path);
+        }
         let content = std::fs::read_to_string(path)?;
         let config: Self = toml::from_str(&content)?;
         Ok(config)
     }
+
+    /// Merges every `*.toml` file in `dir`, in file-name order.
+    ///
+    /// Lists of tables (`[[clients]]`, `[[backends]]`) are concatenated;
+    /// any other key set in several files takes the value from the file that
+    /// sorts last. A client or backend id defined in two files is an error
+    /// naming both.
+    pub fn from_dir(dir: &Path) -> Result<Self, anyhow::Error> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read {}", dir.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        files.retain(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "toml"));
+        files.sort();
+        ensure!(!files.is_empty(), "no *.toml files in {}", dir.display());
+
+        let mut merged = toml::Table::new();
+        let mut defined_in: HashMap<(&str, String), &Path> = HashMap::new();
+        for file in &files {
+            let content = std::fs::read_to_string(file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            let table: toml::Table = toml::from_str(&content)
+                .with_context(|| format!("failed to parse {}", file.display()))?;
+
+            for (list, kind) in [("clients", "client"), ("backends", "backend")] {
+                let entries = table.get(list).and_then(toml::Value::as_array);
+                for entry in entries.into_iter().flatten() {
+                    let Some(id) = entry.get("id").and_then(toml::Value::as_str) else {
+                        continue;
+                    };
+                    if let Some(first) = defined_in.insert((kind, id.to_owned()), file) {
+                        bail!(
+                            "duplicate {kind} id {id}: defined in {} and {}",
+                            first.display(),
+                            file.display()
+                        );
+                    }
+                }
+            }
+            merge_tables(&mut merged, table);
+        }
+
+        let config = toml::Value::Table(merged)
+            .try_into()
+            .with_context(|| format!("invalid merged config from {}", dir.display()))?;
+        Ok(config)
+    }
+}
+
+/// Appends a `[[clients]]` entry to the TOML file at `path`, leaving the
+/// existing content untouched. A missing file is created. Fails when `path`
+/// is a directory or already defines a client with the same `id`.
+///
+/// An empty `allowed_models` list grants every model (`"*"`).
+pub fn append_client(
+    path: &Path,
+    id: &str,
+    api_key: &str,
+    allowed_models: &[String],
+    rate_limit_rpm: u32,
+) -> Result<(), anyhow::Error> {
+    ensure!(
+        !path.is_dir(),
+        "{} is a directory; pass a single config file",
+        path.display()
+    );
+    let existing = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    let table: toml::Table =
+        toml::from_str(&existing).with_context(|| format!("failed to parse {}", path.display()))?;
+    let clients = table.get("clients").and_then(toml::Value::as_array);
+    let duplicate = clients
+        .into_iter()
+        .flatten()
+        .any(|c| c.get("id").and_then(toml::Value::as_str) == Some(id));
+    ensure!(
+        !duplicate,
+        "client id {id} already exists in {}",
+        path.display()
+    );
+
+    let mut entry = toml::Table::new();
+    entry.insert("id".to_owned(), id.into());
+    entry.insert("api_key".to_owned(), api_key.into());
+    let models = if allowed_models.is_empty() {
+        toml::Value::from("*")
+    } else {
+        toml::Value::from(allowed_models.to_vec())
+    };
+    entry.insert("allowed_models".to_owned(), models);
+    entry.insert(
+        "rate_limit_rpm".to_owned(),
+        i64::from(rate_limit_rpm).into(),
+    );
+
+    let mut block = String::new();
+    if !existing.is_empty() {
+        if !existing.ends_with('\n') {
+            block.push('\n');
+        }
+        block.push('\n');
+    }
+    block.push_str("[[clients]]\n");
+    block.push_str(&toml::to_string(&entry)?);
+
+    use std::io::Write;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    file.write_all(block.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(table)) => {
+                merge_tables(existing, table);
+            }
+            (Some(toml::Value::Array(existing)), toml::Value::Array(items))
+                if items.iter().all(toml::Value::is_table) =>
+            {
+                existing.extend(items);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -30,6 +176,37 @@ pub struct ServerConfig {
     pub listen: String,
     pub tls_cert: Option<String>,
     pub tls_key: Option<String>,
+    /// Hard ceiling on total handler time before a 504 is returned.
+    pub request_timeout_secs: u64,
+    /// Time allowed to open a connection to a backend.
+    pub connect_timeout_ms: u64,
+    /// Longest a non-streaming backend response may go without sending
+    /// data; total time is still bounded by `request_timeout_secs`.
+    pub read_timeout_ms: u64,
+    /// Like `read_timeout_ms`, for streaming responses. Kept long so a slow
+    /// time-to-first-token or a pause mid-generation is not cut off.
+    pub stream_read_timeout_ms: u64,
+    /// Honour `Forwarded` / `X-Forwarded-For` when resolving the client IP.
+    /// Enable only behind a reverse proxy that sets these headers.
+    pub trust_forwarded: bool,
+    /// Reverse proxies in front of the gateway that append to those headers.
+    /// The client is the hop this many entries from the right; anything
+    /// further left was sent by the client and is ignored.
+    pub trusted_proxy_hops: usize,
+    /// Optional per-IP requests-per-minute limit, applied before auth.
+    pub ip_rate_limit_rpm: Option<u32>,
+    /// How much detail 5xx error bodies carry: `full` (the default) or
+    /// `terse`, which hides it behind a correlation id.
+    pub error_verbosity: ErrorVerbosity,
+    /// Interval of SSE comment lines sent while a stream is idle, including
+    /// the wait for the first token and stalls between backend chunks.
+    pub sse_keepalive_secs: u64,
+    /// `Authorization` schemes accepted for client keys (e.g. `Bearer`,
+    /// `Token`), matched case-insensitively. `X-API-Key` is always accepted
+    /// when no `Authorization` header is sent.
+    pub auth_schemes: Vec<String>,
+    /// Handling of OpenRouter-style `X-Title` / `HTTP-Referer` headers.
+    pub attribution_headers: AttributionHeaders,
 }
 
 impl Default for ServerConfig {
@@ -38,6 +215,17 @@ impl Default for ServerConfig {
             listen: "0.0.0.0:8080".to_owned(),
             tls_cert: None,
             tls_key: None,
+            request_timeout_secs: 120,
+            connect_timeout_ms: 5_000,
+            read_timeout_ms: 30_000,
+            stream_read_timeout_ms: 300_000,
+            trust_forwarded: false,
+            trusted_proxy_hops: 1,
+            ip_rate_limit_rpm: None,
+            error_verbosity: ErrorVerbosity::Full,
+            sse_keepalive_secs: 15,
+            auth_schemes: vec!["Bearer".to_owned()],
+            attribution_headers: AttributionHeaders::Off,
         }
     }
 }
@@ -49,6 +237,75 @@ pub struct RoutingConfig {
     pub cache_aware: bool,
     pub prefix_depth: usize,
     pub max_affinity_entries: usize,
+    /// When an affinity shard is locked by another request, route by
+    /// strategy alone instead of waiting for it.
+    pub affinity_skip_on_contention: bool,
+    /// Compare the `model` a backend reports against the requested one.
+    pub verify_response_model: ResponseModelCheck,
+    /// Check non-streaming output against a requested `json_schema`
+    /// response format.
+    pub validate_json_output: JsonOutputValidation,
+    /// What to do with a `frequency_penalty` / `presence_penalty` outside
+    /// OpenAI's -2.0..=2.0.
+    pub penalty_range: PenaltyRangeCheck,
+    /// Pass through backend responses with an empty `choices` array instead
+    /// of failing them with a 502.
+    pub allow_empty_choices: bool,
+    /// Reject conversations that carry no user or system message.
+    pub require_user_message: bool,
+    /// Fold consecutive system messages into one before routing.
+    pub merge_system_messages: bool,
+    /// Match model names without regard to ASCII case, routing and
+    /// authorizing `Llama3-70B` as the configured `llama3-70b`. Off keeps
+    /// names exact.
+    pub case_insensitive_models: bool,
+    /// Most tool definitions a request may carry; unset is unlimited.
+    /// Clients can override it with their own `max_tools`.
+    pub max_tools: Option<u32>,
+    /// Model used when a request omits `model`; unset makes it required.
+    pub default_model: Option<String>,
+    /// Read OpenRouter-style `provider/model` names: the model routes by
+    /// its bare name, preferring backends of the named spec (`openai`,
+    /// `ollama`).
+    pub provider_prefix: bool,
+    /// How a non-streaming request asking for `n` > 1 choices is served.
+    pub fanout: FanoutMode,
+    /// Largest `n` served by fan-out; requests above it fail with 400.
+    pub max_fanout: u32,
+    /// Let identical concurrent non-streaming requests share one backend call.
+    pub coalesce: bool,
+    /// Answer identical `temperature: 0` / greedy non-streaming requests from
+    /// a cache of recent responses.
+    pub response_cache: bool,
+    /// LRU eviction threshold for `response_cache`.
+    pub response_cache_entries: usize,
+    /// How long a non-streaming response is replayed to requests repeating
+    /// its `Idempotency-Key`; 0 ignores the header.
+    pub idempotency_ttl_secs: u64,
+    /// LRU eviction threshold for idempotency-keyed responses.
+    pub idempotency_entries: usize,
+    /// Hold requests beyond a model's combined backend `max_concurrent` in a
+    /// queue weighted by client priority instead of routing them onto full
+    /// backends.
+    pub queue_when_saturated: bool,
+    /// How long a queued request waits for capacity before failing with 503.
+    pub queue_timeout_ms: u64,
+    /// How many times a backend 429 is retried before it reaches the client;
+    /// 0 disables retrying.
+    pub retry_on_429: u32,
+    /// Longest pause before a 429 retry. A `Retry-After` beyond this fails
+    /// the request instead of waiting.
+    pub retry_on_429_max_backoff_ms: u64,
+    /// Strategy overrides keyed by model id; other models use `strategy`.
+    pub per_model: HashMap<String, RoutingStrategyConfig>,
+    /// Features each listed model supports, keyed by model id. Unlisted
+    /// models are assumed to support everything.
+    pub model_capabilities: HashMap<String, ModelCapabilitiesConfig>,
+    /// What to do when a request uses a feature its model lacks.
+    pub capability_check: CapabilityCheck,
+    /// Tokenizer used to estimate prompt tokens, keyed by model-name prefix
+    /// (longest match wins); other models use the byte heuristic.
+    pub tokenizers: HashMap<String, TokenizerConfig>,
 }
 
 impl Default for RoutingConfig {
@@ -58,6 +315,32 @@ impl Default for RoutingConfig {
             cache_aware: true,
             prefix_depth: 3,
             max_affinity_entries: 10_000,
+            affinity_skip_on_contention: false,
+            verify_response_model: ResponseModelCheck::Off,
+            validate_json_output: JsonOutputValidation::Off,
+            penalty_range: PenaltyRangeCheck::Off,
+            allow_empty_choices: false,
+            require_user_message: false,
+            merge_system_messages: false,
+            case_insensitive_models: false,
+            max_tools: None,
+            default_model: None,
+            provider_prefix: false,
+            fanout: FanoutMode::Off,
+            max_fanout: 8,
+            coalesce: false,
+            response_cache: false,
+            response_cache_entries: 1_000,
+            idempotency_ttl_secs: 0,
+            idempotency_entries: 1_000,
+            queue_when_saturated: false,
+            queue_timeout_ms: 30_000,
+            retry_on_429: 0,
+            retry_on_429_max_backoff_ms: 2_000,
+            per_model: HashMap::new(),
+            model_capabilities: HashMap::new(),
+            capability_check: CapabilityCheck::Lenient,
+            tokenizers: HashMap::new(),
         }
     }
 }
@@ -68,6 +351,131 @@ pub enum RoutingStrategyConfig {
     #[default]
     LeastLoaded,
     RoundRobin,
+    CheapestFirst,
+}
+
+/// One `routing.model_capabilities` entry; omitted features default to
+/// supported.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ModelCapabilitiesConfig {
+    pub tools: bool,
+    pub vision: bool,
+    pub json_mode: bool,
+}
+
+impl Default for ModelCapabilitiesConfig {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            vision: true,
+            json_mode: true,
+        }
+    }
+}
+
+/// What to do when a request uses a feature `routing.model_capabilities`
+/// says its model lacks.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CapabilityCheck {
+    /// Log a warning and forward the request anyway.
+    #[default]
+    Lenient,
+    /// Reject the request with a 400.
+    Strict,
+}
+
+/// Prompt token estimator for one `routing.tokenizers` entry.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenizerConfig {
+    /// Byte length divided by four.
+    Heuristic,
+    /// The GPT-3.5 / GPT-4 BPE; needs the `tiktoken` feature.
+    Cl100kBase,
+    /// The GPT-4o BPE; needs the `tiktoken` feature.
+    O200kBase,
+}
+
+/// What to do when a backend answers with a different model than requested.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResponseModelCheck {
+    /// Skip the comparison; the response `model` is informational.
+    #[default]
+    Off,
+    /// Log a warning and pass the response through.
+    Warn,
+    /// Fail the request with a 502.
+    Strict,
+}
+
+/// What to do when a backend's output does not match the `json_schema`
+/// response format the client asked for.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonOutputValidation {
+    /// Pass the output through unchecked.
+    #[default]
+    Off,
+    /// Fail the request with a 502 naming the violation.
+    Reject,
+    /// Ask a backend once more, then fail like `Reject`.
+    Retry,
+}
+
+/// How a non-streaming request asking for several choices (`n` > 1) is
+/// served.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FanoutMode {
+    /// Ask one backend for a single choice, as if `n` were 1.
+    #[default]
+    Off,
+    /// Send one call per choice; any failed call fails the request.
+    Strict,
+    /// Send one call per choice and return the choices that succeeded,
+    /// counting the failed calls in `x-fanout-failures`.
+    Partial,
+}
+
+/// What to do with a request penalty outside the range OpenAI accepts.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PenaltyRangeCheck {
+    /// Forward the value as sent.
+    #[default]
+    Off,
+    /// Pull the value to the nearest bound.
+    Clamp,
+    /// Fail the request with 400.
+    Reject,
+}
+
+/// How much error detail reaches clients for 5xx responses.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorVerbosity {
+    /// Pass backend and internal error messages through verbatim.
+    #[default]
+    Full,
+    /// Return a generic message plus a correlation id; log the detail.
+    Terse,
+}
+
+/// What to do with the `X-Title` / `HTTP-Referer` attribution headers that
+/// OpenRouter-compatible clients send.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AttributionHeaders {
+    /// Ignore them.
+    #[default]
+    Off,
+    /// Record them in the request metadata, access log and feedback store.
+    Capture,
+    /// Capture them and also send them on to the backend.
+    Forward,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -77,6 +485,11 @@ pub struct HealthConfig {
     pub timeout_ms: u64,
     pub unhealthy_threshold: u32,
     pub degraded_latency_ms: u64,
+    /// Fail the probe when the backend's model listing omits a configured
+    /// model, instead of only checking that the listing endpoint answers.
+    pub verify_models: bool,
+    /// Backends probed at once within a check interval.
+    pub max_concurrent_probes: usize,
 }
 
 impl Default for HealthConfig {
@@ -86,6 +499,8 @@ impl Default for HealthConfig {
             timeout_ms: 5000,
             unhealthy_threshold: 3,
             degraded_latency_ms: 2000,
+            verify_models: false,
+            max_concurrent_probes: 8,
         }
     }
 }
@@ -95,6 +510,8 @@ impl Default for HealthConfig {
 pub struct LoggingConfig {
     pub level: String,
     pub format: String,
+    /// Log only the length of message and response text, never the text.
+    pub redact_content: bool,
 }
 
 impl Default for LoggingConfig {
@@ -102,10 +519,26 @@ impl Default for LoggingConfig {
         Self {
             level: "info".to_owned(),
             format: "json".to_owned(),
+            redact_content: false,
         }
     }
 }
 
+/// Operator access to `/admin/*` endpoints; disabled when no key is set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub api_key: Option<String>,
+}
+
+/// Per-request audit trail written to sqlite; disabled when no path is set.
+/// Only takes effect in builds with the `audit` feature.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub db_path: Option<PathBuf>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ClientConfig {
     pub id: String,
@@ -114,6 +547,32 @@ pub struct ClientConfig {
     pub rate_limit_rpm: u32,
     pub rate_limit_tpm: Option<u64>,
     pub monthly_token_limit: Option<u64>,
+    /// Per-model RPM caps, enforced on top of `rate_limit_rpm`.
+    #[serde(default)]
+    pub model_rate_limits: HashMap<String, u32>,
+    /// Generation parameters this client may not set (e.g. `temperature`).
+    #[serde(default)]
+    pub forbidden_params: Vec<String>,
+    /// What to do when a request sets one of `forbidden_params`.
+    #[serde(default)]
+    pub forbidden_param_action: ForbiddenParamActionConfig,
+    /// Share of freed slots when `routing.queue_when_saturated` is on: a
+    /// client gets turns in proportion to `priority + 1`.
+    #[serde(default)]
+    pub priority: u8,
+    /// Overrides `routing.max_tools` for this client.
+    #[serde(default)]
+    pub max_tools: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForbiddenParamActionConfig {
+    /// Drop the parameter and serve the request.
+    #[default]
+    Strip,
+    /// Fail the request with 400.
+    Reject,
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -121,6 +580,10 @@ pub struct ClientConfig {
 pub enum AllowedModelsConfig {
     All(WildcardMarker),
     Specific(Vec<String>),
+    /// `{ all_except = ["model-a", ...] }` — every model except the listed ones.
+    AllExcept {
+        all_except: Vec<String>,
+    },
 }
 
 /// Deserializes only the literal string `"*"`.
@@ -152,12 +615,44 @@ pub struct BackendConfig {
     pub models: Vec<String>,
     #[serde(default = "default_max_concurrent")]
     pub max_concurrent: u32,
+    /// Send a one-token completion per model at startup to preload weights.
+    #[serde(default)]
+    pub warmup: bool,
+    /// Canonical model id → the name this backend knows it by.
+    #[serde(default)]
+    pub model_map: HashMap<String, String>,
+    /// Whether the backend accepts tool definitions and `tool` messages.
+    #[serde(default = "default_supports_tools")]
+    pub supports_tools: bool,
+    /// What to do with tool-using requests when `supports_tools` is false.
+    #[serde(default)]
+    pub tool_fallback: ToolFallback,
+    /// Relative cost used by the `cheapest-first` strategy; lower wins.
+    #[serde(default)]
+    pub cost_weight: u32,
+    /// Requests per minute this backend may receive; `None` is unlimited.
+    #[serde(default)]
+    pub max_rpm: Option<u32>,
 }
 
 fn default_max_concurrent() -> u32 {
     64
 }
 
+fn default_supports_tools() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolFallback {
+    /// Fail the request with 400 (unsupported feature).
+    #[default]
+    Reject,
+    /// Drop tool definitions and send tool results as plain user text.
+    Downgrade,
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum BackendSpecConfig {
@@ -7,12 +7,35 @@ fn test_parse_full_config() {
 listen = "127.0.0.1:9090"
 tls_cert = "/path/to/cert.pem"
 tls_key = "/path/to/key.pem"
+request_timeout_secs = 45
+trust_forwarded = true
+trusted_proxy_hops = 2
+error_verbosity = "terse"
+sse_keepalive_secs = 5
+ip_rate_limit_rpm = 300
 
 [routing]
 strategy = "round-robin"
 cache_aware = false
 prefix_depth = 2
 max_affinity_entries = 5000
+verify_response_model = "strict"
+require_user_message = true
+merge_system_messages = true
+max_tools = 64
+case_insensitive_models = true
+penalty_range = "clamp"
+coalesce = true
+fanout = "partial"
+max_fanout = 4
+idempotency_ttl_secs = 120
+idempotency_entries = 50
+
+[routing.per_model]
+"llama3-70b" = "least-loaded"
+
+[routing.tokenizers]
+"gpt-4" = "cl100k_base"
 
 [health]
 check_interval_secs = 15
@@ -23,6 +46,10 @@ degraded_latency_ms = 1000
 [logging]
 level = "debug"
 format = "pretty"
+redact_content = true
+
+[admin]
+api_key = "mb-sk-admin0000000000000000000000"
 
 [[clients]]
 id = "team-alpha"
@@ -39,6 +66,9 @@ api_key = "sk-local-xxxxx"
 spec = "openai-chat"
 models = ["llama3-70b"]
 max_concurrent = 20
+model_map = { "llama3-70b" = "meta-llama/Llama-3-70B-Instruct" }
+supports_tools = false
+tool_fallback = "downgrade"
 "#;
 
     let config: AppConfig = toml::from_str(toml_str).unwrap();
@@ -46,11 +76,39 @@ max_concurrent = 20
     assert_eq!(config.server.listen, "127.0.0.1:9090");
     assert_eq!(config.server.tls_cert.as_deref(), Some("/path/to/cert.pem"));
     assert_eq!(config.server.tls_key.as_deref(), Some("/path/to/key.pem"));
+    assert_eq!(config.server.request_timeout_secs, 45);
+    assert!(config.server.trust_forwarded);
+    assert_eq!(config.server.trusted_proxy_hops, 2);
+    assert_eq!(config.server.error_verbosity, ErrorVerbosity::Terse);
+    assert_eq!(config.server.sse_keepalive_secs, 5);
+    assert_eq!(config.server.ip_rate_limit_rpm, Some(300));
 
     assert_eq!(config.routing.strategy, RoutingStrategyConfig::RoundRobin);
     assert!(!config.routing.cache_aware);
     assert_eq!(config.routing.prefix_depth, 2);
     assert_eq!(config.routing.max_affinity_entries, 5000);
+    assert_eq!(
+        config.routing.verify_response_model,
+        ResponseModelCheck::Strict
+    );
+    assert!(config.routing.require_user_message);
+    assert!(config.routing.merge_system_messages);
+    assert_eq!(config.routing.max_tools, Some(64));
+    assert!(config.routing.case_insensitive_models);
+    assert_eq!(config.routing.penalty_range, PenaltyRangeCheck::Clamp);
+    assert!(config.routing.coalesce);
+    assert_eq!(config.routing.fanout, FanoutMode::Partial);
+    assert_eq!(config.routing.max_fanout, 4);
+    assert_eq!(config.routing.idempotency_ttl_secs, 120);
+    assert_eq!(config.routing.idempotency_entries, 50);
+    assert_eq!(
+        config.routing.per_model.get("llama3-70b"),
+        Some(&RoutingStrategyConfig::LeastLoaded)
+    );
+    assert_eq!(
+        config.routing.tokenizers.get("gpt-4"),
+        Some(&TokenizerConfig::Cl100kBase)
+    );
 
     assert_eq!(config.health.check_interval_secs, 15);
     assert_eq!(config.health.timeout_ms, 3000);
@@ -59,6 +117,11 @@ max_concurrent = 20
 
     assert_eq!(config.logging.level, "debug");
     assert_eq!(config.logging.format, "pretty");
+    assert!(config.logging.redact_content);
+    assert_eq!(
+        config.admin.api_key.as_deref(),
+        Some("mb-sk-admin0000000000000000000000")
+    );
 
     assert_eq!(config.clients.len(), 1);
     let client = &config.clients[0];
@@ -79,6 +142,12 @@ max_concurrent = 20
     assert_eq!(backend.spec, BackendSpecConfig::OpenaiChat);
     assert_eq!(backend.models, vec!["llama3-70b"]);
     assert_eq!(backend.max_concurrent, 20);
+    assert_eq!(
+        backend.model_map.get("llama3-70b").map(String::as_str),
+        Some("meta-llama/Llama-3-70B-Instruct")
+    );
+    assert!(!backend.supports_tools);
+    assert_eq!(backend.tool_fallback, ToolFallback::Downgrade);
 }
 
 #[test]
@@ -103,22 +172,44 @@ models = ["llama3"]
     assert_eq!(config.server.listen, "0.0.0.0:8080");
     assert!(config.server.tls_cert.is_none());
     assert!(config.server.tls_key.is_none());
+    assert_eq!(config.server.request_timeout_secs, 120);
+    assert!(!config.server.trust_forwarded);
+    assert_eq!(config.server.trusted_proxy_hops, 1);
+    assert_eq!(config.server.error_verbosity, ErrorVerbosity::Full);
+    assert_eq!(config.server.sse_keepalive_secs, 15);
+    assert!(config.server.ip_rate_limit_rpm.is_none());
 
     // RoutingConfig defaults
     assert_eq!(config.routing.strategy, RoutingStrategyConfig::LeastLoaded);
     assert!(config.routing.cache_aware);
     assert_eq!(config.routing.prefix_depth, 3);
     assert_eq!(config.routing.max_affinity_entries, 10_000);
+    assert_eq!(
+        config.routing.verify_response_model,
+        ResponseModelCheck::Off
+    );
+    assert!(!config.routing.require_user_message);
+    assert!(!config.routing.merge_system_messages);
+    assert_eq!(config.routing.max_tools, None);
+    assert!(!config.routing.case_insensitive_models);
+    assert_eq!(config.routing.penalty_range, PenaltyRangeCheck::Off);
+    assert_eq!(config.routing.idempotency_ttl_secs, 0);
+    assert_eq!(config.routing.idempotency_entries, 1_000);
+    assert_eq!(config.routing.fanout, FanoutMode::Off);
+    assert_eq!(config.routing.max_fanout, 8);
 
     // HealthConfig defaults
     assert_eq!(config.health.check_interval_secs, 30);
     assert_eq!(config.health.timeout_ms, 5000);
     assert_eq!(config.health.unhealthy_threshold, 3);
     assert_eq!(config.health.degraded_latency_ms, 2000);
+    assert_eq!(config.health.max_concurrent_probes, 8);
 
     // LoggingConfig defaults
     assert_eq!(config.logging.level, "info");
     assert_eq!(config.logging.format, "json");
+    assert!(!config.logging.redact_content);
+    assert!(config.admin.api_key.is_none());
 
     // BackendConfig max_concurrent default
     assert_eq!(config.backends[0].max_concurrent, 64);
@@ -169,3 +260,165 @@ models = ["gpt-4"]
         AllowedModelsConfig::Specific(vec!["gpt-4".to_owned(), "claude-3".to_owned()])
     );
 }
+
+#[test]
+fn test_all_except_allowed_models() {
+    let toml_str = r#"
+[[clients]]
+id = "most"
+api_key = "mb-sk-most0000000000000000000000000"
+allowed_models = { all_except = ["gpt-4"] }
+rate_limit_rpm = 30
+
+[[backends]]
+id = "b1"
+base_url = "http://localhost:8000"
+spec = "openai-chat"
+models = ["gpt-4", "m1"]
+"#;
+
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(
+        config.clients[0].allowed_models,
+        AllowedModelsConfig::AllExcept {
+            all_except: vec!["gpt-4".to_owned()]
+        }
+    );
+}
+
+/// Writes `files` into a fresh temporary directory.
+fn config_dir(files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mb-config-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    for (name, content) in files {
+        std::fs::write(dir.join(name), content).unwrap();
+    }
+    dir
+}
+
+const CLIENTS_TOML: &str = r#"
+[server]
+listen = "127.0.0.1:9090"
+request_timeout_secs = 30
+
+[[clients]]
+id = "team-alpha"
+api_key = "mb-sk-alpha0000000000000000000000"
+allowed_models = "*"
+rate_limit_rpm = 60
+"#;
+
+const BACKENDS_TOML: &str = r#"
+[server]
+request_timeout_secs = 90
+
+[[clients]]
+id = "team-beta"
+api_key = "mb-sk-beta00000000000000000000000"
+allowed_models = "*"
+rate_limit_rpm = 60
+
+[[backends]]
+id = "local"
+base_url = "http://localhost:11434"
+spec = "ollama"
+models = ["llama3"]
+"#;
+
+#[test]
+fn test_from_dir_merges_files_in_name_order() {
+    let dir = config_dir(&[
+        ("10-clients.toml", CLIENTS_TOML),
+        ("20-backends.toml", BACKENDS_TOML),
+        ("notes.txt", "not toml"),
+    ]);
+
+    let config = AppConfig::from_file(&dir).expect("directory config should load");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let client_ids: Vec<&str> = config.clients.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(client_ids, ["team-alpha", "team-beta"]);
+    assert_eq!(config.backends.len(), 1);
+    // Keys set in one file survive; keys set in both come from the later one.
+    assert_eq!(config.server.listen, "127.0.0.1:9090");
+    assert_eq!(config.server.request_timeout_secs, 90);
+}
+
+#[test]
+fn test_from_dir_rejects_duplicate_client_across_files() {
+    let duplicate = CLIENTS_TOML.replace("[server]", "[logging]");
+    let dir = config_dir(&[
+        ("a.toml", CLIENTS_TOML),
+        ("b.toml", BACKENDS_TOML),
+        ("c.toml", &duplicate),
+    ]);
+
+    let err = AppConfig::from_file(&dir).expect_err("duplicate id across files");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let message = err.to_string();
+    assert!(
+        message.contains("duplicate client id team-alpha"),
+        "{message}"
+    );
+    assert!(
+        message.contains("a.toml") && message.contains("c.toml"),
+        "{message}"
+    );
+}
+
+#[test]
+fn test_append_client_produces_loadable_entry() {
+    let original = format!(
+        "{CLIENTS_TOML}{}",
+        &BACKENDS_TOML[BACKENDS_TOML.find("[[backends]]").unwrap()..].trim_end()
+    );
+    let dir = config_dir(&[("config.toml", &original)]);
+    let path = dir.join("config.toml");
+
+    let models = ["llama3".to_owned(), "gpt-4".to_owned()];
+    append_client(&path, "team-gamma", "mb-sk-gamma", &models, 30).expect("append");
+    append_client(&path, "team-delta", "mb-sk-delta", &[], 60).expect("append");
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let config = AppConfig::from_file(&path).expect("appended config should load");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(content.starts_with(&original));
+    let client_ids: Vec<&str> = config.clients.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(client_ids, ["team-alpha", "team-gamma", "team-delta"]);
+    assert_eq!(config.server.listen, "127.0.0.1:9090");
+
+    let gamma = &config.clients[1];
+    assert_eq!(gamma.api_key, "mb-sk-gamma");
+    assert_eq!(gamma.rate_limit_rpm, 30);
+    assert_eq!(
+        gamma.allowed_models,
+        AllowedModelsConfig::Specific(models.to_vec())
+    );
+    assert!(matches!(
+        config.clients[2].allowed_models,
+        AllowedModelsConfig::All(_)
+    ));
+}
+
+#[test]
+fn test_append_client_rejects_duplicate_id() {
+    let dir = config_dir(&[("config.toml", CLIENTS_TOML)]);
+    let path = dir.join("config.toml");
+
+    let err = append_client(&path, "team-alpha", "mb-sk-other", &[], 60)
+        .expect_err("duplicate client id");
+    let content = std::fs::read_to_string(&path).unwrap();
+    let dir_err = append_client(&dir, "team-new", "mb-sk-new", &[], 60)
+        .expect_err("directories are not appended to");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(
+        err.to_string()
+            .contains("client id team-alpha already exists"),
+        "{err}"
+    );
+    assert_eq!(content, CLIENTS_TOML);
+    assert!(dir_err.to_string().contains("is a directory"), "{dir_err}");
+}
@@ -0,0 +1,116 @@
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use mb_core::core::GatewayError;
+
+use crate::handler::{
+    check_limits, prepare_request, routing_hint, select_within_backend_rpm, AppState, LimitMode,
+    PreparedRequest,
+};
+
+// ---------------------------------------------------------------------------
+// Dry-run — report the routing decision without calling a backend
+// ---------------------------------------------------------------------------
+
+/// Header that turns a completion request into a dry run.
+pub const DRY_RUN_HEADER: &str = "x-dry-run";
+
+/// Returns true when the request asked for a dry run, via `X-Dry-Run: true`
+/// or a `dry_run=1` / `dry_run=true` query parameter.
+pub fn is_dry_run(headers: &HeaderMap, query: Option<&str>) -> bool {
+    let header = headers
+        .get(DRY_RUN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(is_truthy);
+    let param = query.is_some_and(|q| {
+        q.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .any(|(k, v)| k == "dry_run" && is_truthy(v))
+    });
+    header || param
+}
+
+fn is_truthy(value: &str) -> bool {
+    value == "1" || value.eq_ignore_ascii_case("true")
+}
+
+/// Runs auth, model permission, rate-limit and quota checks and backend
+/// selection exactly as a real request would, through the same pipeline
+/// functions, but in [`LimitMode::Peek`]: limiters are peeked rather than
+/// charged, the round-robin counter is read rather than advanced, and no
+/// affinity is recorded.
+pub(crate) async fn handle_dry_run(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Response, GatewayError> {
+    let PreparedRequest {
+        mut canonical_req,
+        client_info,
+        ..
+    } = prepare_request(state, headers, body)?;
+    check_limits(state, client_info, &canonical_req, LimitMode::Peek).await?;
+    let (cache_routing, affinity_hint) = routing_hint(state, headers, &mut canonical_req);
+
+    let selection = select_within_backend_rpm(
+        state,
+        &canonical_req.model,
+        cache_routing,
+        affinity_hint.as_ref(),
+        canonical_req.metadata.provider_hint,
+        LimitMode::Peek,
+    )
+    .await?;
+    let selected_id = selection.backend;
+
+    let affinity = if !cache_routing {
+        "disabled"
+    } else if affinity_hint.as_ref() == Some(&selected_id) {
+        "hit"
+    } else {
+        "miss"
+    };
+
+    let strategy = state
+        .routing_policy
+        .strategy_for(&canonical_req.model)
+        .as_str();
+
+    let body = serde_json::json!({
+        "dry_run": true,
+        "model": canonical_req.model.as_str(),
+        "backend": selected_id.as_str(),
+        "strategy": strategy,
+        "affinity": affinity,
+        "saturated": selection.saturated,
+        "estimated_input_tokens": canonical_req.metadata.estimated_input_tokens,
+    });
+    Ok((StatusCode::OK, axum::Json(body)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_is_dry_run_header() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_dry_run(&headers, None));
+
+        headers.insert(DRY_RUN_HEADER, HeaderValue::from_static("true"));
+        assert!(is_dry_run(&headers, None));
+
+        headers.insert(DRY_RUN_HEADER, HeaderValue::from_static("false"));
+        assert!(!is_dry_run(&headers, None));
+    }
+
+    #[test]
+    fn test_is_dry_run_query() {
+        let headers = HeaderMap::new();
+        assert!(is_dry_run(&headers, Some("dry_run=1")));
+        assert!(is_dry_run(&headers, Some("foo=bar&dry_run=true")));
+        assert!(!is_dry_run(&headers, Some("dry_run=0")));
+        assert!(!is_dry_run(&headers, Some("not_dry_run=1")));
+    }
+}
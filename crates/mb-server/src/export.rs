@@ -0,0 +1,191 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{ensure, Context};
+use chrono::{DateTime, Utc};
+use mb_feedback::{DpoExportFilter, FeedbackStore, SqliteFeedbackStore};
+
+// ---------------------------------------------------------------------------
+// `mb export` — offline DPO export straight from the feedback database
+// ---------------------------------------------------------------------------
+
+/// Output layout for `mb export`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A single JSON array.
+    Json,
+    /// One JSON object per line.
+    #[default]
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "jsonl" => Ok(Self::Jsonl),
+            other => Err(format!(
+                "unknown export format {other:?}, expected json or jsonl"
+            )),
+        }
+    }
+}
+
+/// Opens an existing feedback database. Unlike the gateway, a missing file
+/// is an error rather than a fresh empty store.
+pub fn open_store(db_path: &Path) -> anyhow::Result<SqliteFeedbackStore> {
+    ensure!(
+        db_path.exists(),
+        "feedback database {} does not exist",
+        db_path.display()
+    );
+    let store = SqliteFeedbackStore::new(db_path)
+        .with_context(|| format!("failed to open {}", db_path.display()))?;
+    store.init().context("failed to migrate feedback schema")?;
+    Ok(store)
+}
+
+/// Renders the DPO pairs matching `model` and `since` in `format`.
+pub fn export_dpo(
+    store: &dyn FeedbackStore,
+    format: ExportFormat,
+    model: Option<String>,
+    since: Option<DateTime<Utc>>,
+) -> anyhow::Result<String> {
+    let filter = DpoExportFilter {
+        model_id: model,
+        since,
+        ..DpoExportFilter::default()
+    };
+    let pairs = mb_feedback::export_dpo_pairs(store, &filter)?;
+    let rendered = match format {
+        ExportFormat::Json => mb_feedback::export_to_json(&pairs)?,
+        ExportFormat::Jsonl => mb_feedback::export_to_jsonl(&pairs)?,
+    };
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_core::core::{ClientId, ModelId};
+    use mb_feedback::{Annotation, Conversation, Turn, TurnRole, Verdict};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn ts(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .expect("valid RFC3339 timestamp")
+            .with_timezone(&Utc)
+    }
+
+    fn annotate(store: &dyn FeedbackStore, model_id: &str, expected: &str, at: &str) {
+        let conversation = Conversation {
+            id: Uuid::new_v4(),
+            client_id: ClientId::new("team-alpha"),
+            model_id: ModelId::new(model_id),
+            created_at: ts(at),
+            app_title: None,
+            app_referer: None,
+            metadata: None,
+        };
+        store
+            .insert_conversation(&conversation)
+            .expect("insert conversation");
+
+        let mut turn_ids = Vec::new();
+        for (role, content) in [
+            (TurnRole::User, "Explain the topic."),
+            (TurnRole::Assistant, "I cannot help with that."),
+        ] {
+            let turn = Turn {
+                id: Uuid::new_v4(),
+                conversation_id: conversation.id,
+                role,
+                content: content.to_owned(),
+                token_count: 4,
+                created_at: ts(at),
+                temperature: None,
+                seed: None,
+                content_hash: None,
+            };
+            store.insert_turn(&turn).expect("insert turn");
+            turn_ids.push(turn.id);
+        }
+
+        let annotation = Annotation {
+            id: Uuid::new_v4(),
+            turn_id: turn_ids[1],
+            annotator_id: "ann-1".to_owned(),
+            verdict: Verdict::Refused,
+            expected_direction: None,
+            expected_response: Some(expected.to_owned()),
+            score: None,
+            created_at: ts(at),
+        };
+        store
+            .insert_annotation(&annotation)
+            .expect("insert annotation");
+    }
+
+    #[test]
+    fn test_export_dpo_formats_and_filters() {
+        let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+        store.init().expect("init schema");
+        annotate(
+            &store,
+            "llama3-70b",
+            "A balanced answer.",
+            "2026-01-01T10:00:00Z",
+        );
+        annotate(
+            &store,
+            "qwen2.5-14b",
+            "Another answer.",
+            "2026-02-01T10:00:00Z",
+        );
+
+        let jsonl = export_dpo(&store, ExportFormat::Jsonl, None, None).expect("export jsonl");
+        let lines: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line is JSON"))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["rejected"], "I cannot help with that.");
+
+        let json = export_dpo(
+            &store,
+            ExportFormat::Json,
+            Some("llama3-70b".to_owned()),
+            None,
+        )
+        .expect("export json");
+        let pairs: serde_json::Value = serde_json::from_str(&json).expect("JSON array");
+        assert_eq!(pairs.as_array().map(Vec::len), Some(1));
+        assert_eq!(pairs[0]["chosen"], "A balanced answer.");
+
+        let since = Some(ts("2026-01-15T00:00:00Z"));
+        let recent = export_dpo(&store, ExportFormat::Jsonl, None, since).expect("export since");
+        assert_eq!(recent.lines().count(), 1);
+        assert!(recent.contains("Another answer."));
+    }
+
+    #[test]
+    fn test_open_store_requires_existing_file() {
+        let missing = std::env::temp_dir().join(format!("mb-export-{}.sqlite", Uuid::new_v4()));
+        let err = open_store(&missing)
+            .err()
+            .expect("missing file is rejected");
+        assert!(err.to_string().contains("does not exist"));
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn test_export_format_from_str() {
+        assert_eq!("json".parse(), Ok(ExportFormat::Json));
+        assert_eq!("jsonl".parse(), Ok(ExportFormat::Jsonl));
+        assert!("csv".parse::<ExportFormat>().is_err());
+    }
+}
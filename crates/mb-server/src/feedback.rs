@@ -2,7 +2,7 @@
 use std::sync::Arc;
 
 #[cfg(feature = "feedback")]
-use axum::extract::{Query, State};
+use axum::extract::{Path, Query, State};
 #[cfg(feature = "feedback")]
 use axum::http::{HeaderMap, StatusCode};
 #[cfg(feature = "feedback")]
@@ -10,7 +10,7 @@ use axum::response::IntoResponse;
 #[cfg(feature = "feedback")]
 use axum::Json;
 #[cfg(feature = "feedback")]
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 #[cfg(feature = "feedback")]
 use mb_core::core::{
     ApiKey, CanonicalRequest, CanonicalResponse, ContentPart, MessageContent, Role,
@@ -23,8 +23,17 @@ use serde_json::json;
 use uuid::Uuid;
 
 #[cfg(feature = "feedback")]
+#[derive(Clone)]
 pub struct FeedbackState {
     pub store: Arc<dyn mb_feedback::FeedbackStore>,
+    /// Fraction of conversations (0.0–1.0) persisted by `record_chat_turns`.
+    pub sample_rate: f64,
+    /// Persist requests sent with `store: true` even when sampling would
+    /// skip them. Off by default so clients cannot bypass `sample_rate`.
+    pub honor_store_flag: bool,
+    /// Store a length placeholder instead of each turn's text, keeping
+    /// only metadata such as roles and token counts.
+    pub redact_content: bool,
 }
 
 #[cfg(feature = "feedback")]
@@ -34,6 +43,8 @@ pub struct FeedbackRequest {
     pub verdict: String,
     pub expected_direction: Option<String>,
     pub expected_response: Option<String>,
+    /// Optional 1–5 quality score.
+    pub score: Option<u8>,
 }
 
 #[cfg(feature = "feedback")]
@@ -44,6 +55,22 @@ pub struct MyAnnotationsQuery {
     pub per_page: Option<u32>,
 }
 
+#[cfg(feature = "feedback")]
+#[derive(Debug, Deserialize)]
+pub struct MyConversationsQuery {
+    /// RFC 3339 lower bound (inclusive) on conversation creation time.
+    pub since: Option<String>,
+    /// RFC 3339 upper bound (inclusive) on conversation creation time.
+    pub until: Option<String>,
+}
+
+#[cfg(feature = "feedback")]
+#[derive(Debug, Deserialize)]
+pub struct ConversationExportQuery {
+    /// `sft` or `dpo`.
+    pub format: Option<String>,
+}
+
 #[cfg(feature = "feedback")]
 pub async fn post_feedback(
     State(state): State<Arc<crate::handler::AppState>>,
@@ -57,7 +84,7 @@ pub async fn post_feedback(
         )
     })?;
 
-    let api_key = extract_feedback_api_key(&headers)?;
+    let api_key = extract_feedback_api_key(&state, &headers)?;
     let client_info = state
         .auth
         .validate(&api_key)
@@ -97,6 +124,16 @@ pub async fn post_feedback(
         )
     })?;
 
+    if body
+        .score
+        .is_some_and(|score| !mb_feedback::SCORE_RANGE.contains(&score))
+    {
+        return Err(json_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid score, expected an integer from 1 to 5",
+        ));
+    }
+
     let annotation = mb_feedback::Annotation {
         id: Uuid::new_v4(),
         turn_id: body.turn_id,
@@ -104,6 +141,7 @@ pub async fn post_feedback(
         verdict,
         expected_direction: body.expected_direction,
         expected_response: body.expected_response,
+        score: body.score,
         created_at: Utc::now(),
     };
     let annotation_id = annotation.id;
@@ -142,7 +180,7 @@ pub async fn get_my_annotations(
         )
     })?;
 
-    let api_key = extract_feedback_api_key(&headers)?;
+    let api_key = extract_feedback_api_key(&state, &headers)?;
     let client_info = state
         .auth
         .validate(&api_key)
@@ -236,6 +274,246 @@ pub async fn get_my_annotations(
     ))
 }
 
+#[cfg(feature = "feedback")]
+pub async fn get_my_annotation_stats(
+    State(state): State<Arc<crate::handler::AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let feedback_state = state.feedback.as_ref().ok_or_else(|| {
+        json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "feedback store unavailable",
+        )
+    })?;
+
+    let api_key = extract_feedback_api_key(&state, &headers)?;
+    let client_info = state
+        .auth
+        .validate(&api_key)
+        .map_err(|_| json_error(StatusCode::UNAUTHORIZED, "invalid API key"))?;
+    let annotator_id = client_info.id.to_string();
+
+    let store = Arc::clone(&feedback_state.store);
+    let stats = tokio::task::spawn_blocking(move || annotator_stats(store.as_ref(), &annotator_id))
+        .await
+        .map_err(|err| {
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to join annotation stats task: {err}"),
+            )
+        })??;
+
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+/// Verdict distribution of `annotator_id`'s annotations, plus how many of
+/// them would become DPO pairs (refused or biased with an expected response).
+#[cfg(feature = "feedback")]
+fn annotator_stats(
+    store: &dyn mb_feedback::FeedbackStore,
+    annotator_id: &str,
+) -> Result<serde_json::Value, (StatusCode, Json<serde_json::Value>)> {
+    let annotations = store
+        .get_annotations_by_annotator(annotator_id)
+        .map_err(|err| {
+            json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to get annotations: {err}"),
+            )
+        })?;
+
+    let (mut refused, mut biased, mut satisfactory, mut dpo_eligible) = (0, 0, 0, 0);
+    for annotation in &annotations {
+        match annotation.verdict {
+            mb_feedback::Verdict::Refused => refused += 1,
+            mb_feedback::Verdict::Biased => biased += 1,
+            mb_feedback::Verdict::Satisfactory => satisfactory += 1,
+        }
+        let has_expected = annotation
+            .expected_response
+            .as_deref()
+            .is_some_and(|response| !response.trim().is_empty());
+        if annotation.verdict != mb_feedback::Verdict::Satisfactory && has_expected {
+            dpo_eligible += 1;
+        }
+    }
+
+    Ok(json!({
+        "total": annotations.len(),
+        "by_verdict": {
+            "refused": refused,
+            "biased": biased,
+            "satisfactory": satisfactory,
+        },
+        "dpo_eligible": dpo_eligible,
+    }))
+}
+
+#[cfg(feature = "feedback")]
+pub async fn get_my_conversations(
+    State(state): State<Arc<crate::handler::AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<MyConversationsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let feedback_state = state.feedback.as_ref().ok_or_else(|| {
+        json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "feedback store unavailable",
+        )
+    })?;
+
+    let api_key = extract_feedback_api_key(&state, &headers)?;
+    let client_info = state
+        .auth
+        .validate(&api_key)
+        .map_err(|_| json_error(StatusCode::UNAUTHORIZED, "invalid API key"))?;
+    let client_id = client_info.id.to_string();
+
+    let since = parse_time_bound(query.since.as_deref(), "since")?;
+    let until = parse_time_bound(query.until.as_deref(), "until")?;
+
+    let conversations = {
+        let store = Arc::clone(&feedback_state.store);
+        tokio::task::spawn_blocking(move || store.list_conversations(&client_id, since, until))
+            .await
+            .map_err(|err| {
+                json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to join list conversations task: {err}"),
+                )
+            })?
+            .map_err(|err| {
+                json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to list conversations: {err}"),
+                )
+            })?
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "conversations": conversations,
+            "total": conversations.len(),
+        })),
+    ))
+}
+
+#[cfg(feature = "feedback")]
+pub async fn get_conversation_export(
+    State(state): State<Arc<crate::handler::AppState>>,
+    headers: HeaderMap,
+    Path(conversation_id): Path<String>,
+    Query(query): Query<ConversationExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let feedback_state = state.feedback.as_ref().ok_or_else(|| {
+        json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "feedback store unavailable",
+        )
+    })?;
+
+    let api_key = extract_feedback_api_key(&state, &headers)?;
+    let client_info = state
+        .auth
+        .validate(&api_key)
+        .map_err(|_| json_error(StatusCode::UNAUTHORIZED, "invalid API key"))?;
+    let client_id = client_info.id.to_string();
+
+    let conversation_id = Uuid::parse_str(&conversation_id).map_err(|_| {
+        json_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid conversation id, expected a UUID",
+        )
+    })?;
+    let format = query.format.as_deref().unwrap_or_default();
+    let format = parse_export_format(format).ok_or_else(|| {
+        json_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid format, expected one of: sft, dpo",
+        )
+    })?;
+
+    let store = Arc::clone(&feedback_state.store);
+    let sample = tokio::task::spawn_blocking(move || {
+        export_conversation(store.as_ref(), &client_id, conversation_id, format)
+    })
+    .await
+    .map_err(|err| {
+        json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to join conversation export task: {err}"),
+        )
+    })??;
+
+    Ok((StatusCode::OK, Json(sample)))
+}
+
+#[cfg(feature = "feedback")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Sft,
+    Dpo,
+}
+
+#[cfg(feature = "feedback")]
+fn parse_export_format(format: &str) -> Option<ExportFormat> {
+    if format.eq_ignore_ascii_case("sft") {
+        Some(ExportFormat::Sft)
+    } else if format.eq_ignore_ascii_case("dpo") {
+        Some(ExportFormat::Dpo)
+    } else {
+        None
+    }
+}
+
+/// Builds the export of one conversation owned by `client_id`. Conversations
+/// of other clients are reported as missing so their ids are not disclosed.
+#[cfg(feature = "feedback")]
+fn export_conversation(
+    store: &dyn mb_feedback::FeedbackStore,
+    client_id: &str,
+    conversation_id: Uuid,
+    format: ExportFormat,
+) -> Result<serde_json::Value, (StatusCode, Json<serde_json::Value>)> {
+    let export_error = |err: mb_feedback::FeedbackError| {
+        json_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to export conversation: {err}"),
+        )
+    };
+
+    let owned = store
+        .get_conversation_by_id(&conversation_id)
+        .map_err(export_error)?
+        .is_some_and(|conversation| conversation.client_id.as_str() == client_id);
+    if !owned {
+        return Err(json_error(StatusCode::NOT_FOUND, "conversation not found"));
+    }
+
+    match format {
+        ExportFormat::Sft => {
+            let sample = mb_feedback::export_sft_sample(store, &conversation_id)
+                .map_err(export_error)?
+                .ok_or_else(|| json_error(StatusCode::NOT_FOUND, "conversation not found"))?;
+            serde_json::to_value(sample).map_err(|err| {
+                json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to serialize sft export: {err}"),
+                )
+            })
+        }
+        ExportFormat::Dpo => {
+            let filter = mb_feedback::DpoExportFilter {
+                conversation_id: Some(conversation_id),
+                ..Default::default()
+            };
+            let pairs = mb_feedback::export_dpo_pairs(store, &filter).map_err(export_error)?;
+            mb_feedback::export_to_value(&pairs).map_err(export_error)
+        }
+    }
+}
+
 #[cfg(feature = "feedback")]
 pub async fn record_chat_turns(
     feedback_state: &FeedbackState,
@@ -243,66 +521,123 @@ pub async fn record_chat_turns(
     request: &CanonicalRequest,
     response: &CanonicalResponse,
 ) {
-    let Some(user_content) = extract_last_user_message(request) else {
+    let request_turns = extract_request_turns(request);
+    if !request_turns
+        .iter()
+        .any(|(role, _)| *role == mb_feedback::TurnRole::User)
+    {
         return;
-    };
+    }
     let Some(assistant_content) = extract_assistant_message(response) else {
         return;
     };
 
     let conversation_id = extract_conversation_id(headers);
+    // `store: true` opts this exchange in when the operator allows it.
+    let opted_in = feedback_state.honor_store_flag && request.metadata.store;
+    if !opted_in && !is_conversation_sampled(&conversation_id, feedback_state.sample_rate) {
+        return;
+    }
+
     let client_id = request.metadata.client_id.clone();
     let model_id = request.model.clone();
-    let user_token_count = estimate_token_count(&user_content);
-    let assistant_token_count = estimate_token_count(&assistant_content);
+    let app_title = request.metadata.app_title.clone();
+    let app_referer = request.metadata.app_referer.clone();
+    let metadata = request.metadata.client_metadata.clone();
+    let (temperature, seed) = (request.params.temperature, request.params.seed);
+    let redact_content = feedback_state.redact_content;
     let store = Arc::clone(&feedback_state.store);
 
     let join_result = tokio::task::spawn_blocking(move || {
         let now = Utc::now();
-        let conversation = mb_feedback::Conversation {
-            id: conversation_id,
-            client_id,
-            model_id,
-            created_at: now,
-        };
-        if let Err(err) = store.insert_conversation(&conversation) {
-            tracing::warn!(
-                error = %err,
-                conversation_id = %conversation_id,
-                "failed to insert feedback conversation"
-            );
+        match store.get_conversation_by_id(&conversation_id) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                let conversation = mb_feedback::Conversation {
+                    id: conversation_id,
+                    client_id,
+                    model_id,
+                    created_at: now,
+                    app_title,
+                    app_referer,
+                    metadata,
+                };
+                if let Err(err) = store.insert_conversation(&conversation) {
+                    tracing::warn!(
+                        error = %err,
+                        conversation_id = %conversation_id,
+                        "failed to insert feedback conversation"
+                    );
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    error = %err,
+                    conversation_id = %conversation_id,
+                    "failed to look up feedback conversation"
+                );
+            }
         }
 
-        let user_turn = mb_feedback::Turn {
-            id: Uuid::new_v4(),
-            conversation_id,
-            role: mb_feedback::TurnRole::User,
-            content: user_content,
-            token_count: user_token_count,
-            created_at: now,
-        };
-        if let Err(err) = store.insert_turn(&user_turn) {
-            tracing::warn!(
-                error = %err,
-                conversation_id = %conversation_id,
-                "failed to insert user feedback turn"
-            );
-        }
+        // Multi-turn clients resend the full history on every request, so
+        // turns already stored at the same position are skipped. They are
+        // matched by hash, since redacted text says nothing about the original.
+        let existing_turns = store
+            .get_turns_for_conversation(&conversation_id)
+            .unwrap_or_else(|err| {
+                tracing::warn!(
+                    error = %err,
+                    conversation_id = %conversation_id,
+                    "failed to load existing feedback turns"
+                );
+                Vec::new()
+            });
+
+        let reply_position = request_turns.len();
+        let turns = request_turns.into_iter().chain(std::iter::once((
+            mb_feedback::TurnRole::Assistant,
+            assistant_content,
+        )));
+        for (position, (role, content)) in turns.enumerate() {
+            let token_count = estimate_token_count(&content);
+            let content_hash = mb_feedback::content_hash(&content);
+            let content = if redact_content {
+                redacted_content(&content)
+            } else {
+                content
+            };
+            let already_stored = existing_turns.get(position).is_some_and(|turn| {
+                turn.role == role
+                    && match &turn.content_hash {
+                        Some(hash) => *hash == content_hash,
+                        // Rows stored before hashes were kept.
+                        None => !redact_content && turn.content == content,
+                    }
+            });
+            if already_stored {
+                continue;
+            }
 
-        let assistant_turn = mb_feedback::Turn {
-            id: Uuid::new_v4(),
-            conversation_id,
-            role: mb_feedback::TurnRole::Assistant,
-            content: assistant_content,
-            token_count: assistant_token_count,
-            created_at: now,
-        };
-        if let Err(err) = store.insert_turn(&assistant_turn) {
-            tracing::warn!(
-                error = %err,
-                conversation_id = %conversation_id,
-                "failed to insert assistant feedback turn"
-            );
+            // Only the reply was produced under this request's parameters.
+            let is_reply = position == reply_position;
+            let turn = mb_feedback::Turn {
+                id: Uuid::new_v4(),
+                conversation_id,
+                role,
+                token_count,
+                content,
+                created_at: now,
+                temperature: temperature.filter(|_| is_reply),
+                seed: seed.filter(|_| is_reply),
+                content_hash: Some(content_hash),
+            };
+            if let Err(err) = store.insert_turn(&turn) {
+                tracing::warn!(
+                    error = %err,
+                    conversation_id = %conversation_id,
+                    "failed to insert feedback turn"
+                );
+            }
         }
     })
     .await;
@@ -334,14 +669,41 @@ fn extract_conversation_id(headers: &HeaderMap) -> Uuid {
     }
 }
 
+/// Decides whether a conversation is persisted under `sample_rate`.
+///
+/// The decision is derived from the id's last seven bytes, random in v4
+/// and v7 UUIDs, so every request of a multi-turn conversation lands on the
+/// same side on every gateway instance and build.
+#[cfg(feature = "feedback")]
+pub fn is_conversation_sampled(conversation_id: &Uuid, sample_rate: f64) -> bool {
+    if sample_rate >= 1.0 {
+        return true;
+    }
+    if sample_rate <= 0.0 || sample_rate.is_nan() {
+        return false;
+    }
+
+    let mut low = [0u8; 8];
+    low[1..].copy_from_slice(&conversation_id.as_bytes()[9..]);
+    let bucket = u64::from_be_bytes(low) as f64 / (1u64 << 56) as f64;
+    bucket < sample_rate
+}
+
 #[cfg(feature = "feedback")]
-fn extract_last_user_message(request: &CanonicalRequest) -> Option<String> {
+fn extract_request_turns(request: &CanonicalRequest) -> Vec<(mb_feedback::TurnRole, String)> {
     request
         .messages
         .iter()
-        .rev()
-        .find(|message| message.role == Role::User)
-        .map(|message| content_to_text(&message.content))
+        .filter_map(|message| {
+            let role = match message.role {
+                Role::System => mb_feedback::TurnRole::System,
+                Role::User => mb_feedback::TurnRole::User,
+                Role::Assistant => mb_feedback::TurnRole::Assistant,
+                Role::Tool => return None,
+            };
+            Some((role, content_to_text(&message.content)))
+        })
+        .collect()
 }
 
 #[cfg(feature = "feedback")]
@@ -361,13 +723,19 @@ fn content_to_text(content: &MessageContent) -> String {
             .iter()
             .filter_map(|part| match part {
                 ContentPart::Text { text } => Some(text.as_str()),
-                ContentPart::ImageUrl { .. } => None,
+                ContentPart::ImageUrl { .. } | ContentPart::InputAudio { .. } => None,
             })
             .collect::<Vec<_>>()
             .join(""),
     }
 }
 
+/// Stand-in stored for a turn's text when `redact_content` is set.
+#[cfg(feature = "feedback")]
+fn redacted_content(text: &str) -> String {
+    format!("[redacted: {} chars]", text.chars().count())
+}
+
 #[cfg(feature = "feedback")]
 fn estimate_token_count(text: &str) -> u32 {
     let approx_tokens = text.chars().count() / 4;
@@ -376,19 +744,32 @@ fn estimate_token_count(text: &str) -> u32 {
 
 #[cfg(feature = "feedback")]
 fn extract_feedback_api_key(
+    state: &crate::handler::AppState,
     headers: &HeaderMap,
 ) -> Result<ApiKey, (StatusCode, Json<serde_json::Value>)> {
-    if let Some(raw) = headers
-        .get("x-api-key")
-        .and_then(|value| value.to_str().ok())
-    {
-        return Ok(ApiKey::new(raw));
-    }
-
-    crate::handler::extract_api_key(headers)
+    crate::handler::extract_api_key(headers, &state.auth_schemes)
         .map_err(|_| json_error(StatusCode::UNAUTHORIZED, "missing or invalid API key"))
 }
 
+#[cfg(feature = "feedback")]
+fn parse_time_bound(
+    value: Option<&str>,
+    field: &str,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, Json<serde_json::Value>)> {
+    value
+        .map(|raw| {
+            DateTime::parse_from_rfc3339(raw)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|_| {
+                    json_error(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("invalid {field}, expected an RFC 3339 timestamp"),
+                    )
+                })
+        })
+        .transpose()
+}
+
 #[cfg(feature = "feedback")]
 fn parse_verdict(verdict: &str) -> Option<mb_feedback::Verdict> {
     match verdict {
@@ -416,3 +797,6 @@ fn json_error(
         })),
     )
 }
+
+#[cfg(all(test, feature = "feedback"))]
+mod tests;
@@ -0,0 +1,582 @@
+use super::*;
+use axum::http::HeaderValue;
+use mb_core::core::{
+    Choice, ClientId, FinishReason, GenerationParams, Message, ModelId, RequestId, RequestMetadata,
+    TokenUsage,
+};
+use mb_feedback::{SqliteFeedbackStore, TurnRole};
+
+fn message(role: Role, text: &str) -> Message {
+    Message {
+        role,
+        content: MessageContent::Text(text.to_owned()),
+        name: None,
+        tool_call_id: None,
+    }
+}
+
+fn make_request(messages: Vec<Message>) -> CanonicalRequest {
+    CanonicalRequest {
+        model: ModelId::new("llama3-70b"),
+        messages,
+        params: GenerationParams::default(),
+        tools: None,
+        tool_choice: None,
+        parallel_tool_calls: None,
+        logprobs: None,
+        top_logprobs: None,
+        response_format: None,
+        echo: None,
+        suffix: None,
+        n: None,
+        stream: false,
+        metadata: RequestMetadata {
+            request_id: RequestId::new("req-test"),
+            client_id: ClientId::new("team-alpha"),
+            estimated_input_tokens: 10,
+            prefix_hash: None,
+            app_title: None,
+            app_referer: None,
+            client_metadata: None,
+            store: false,
+            provider_hint: None,
+        },
+    }
+}
+
+fn make_response(text: &str) -> CanonicalResponse {
+    CanonicalResponse {
+        id: "chatcmpl-test".to_owned(),
+        model: ModelId::new("llama3-70b"),
+        choices: vec![Choice {
+            index: 0,
+            message: message(Role::Assistant, text),
+            finish_reason: FinishReason::Stop,
+            logprobs: None,
+        }],
+        usage: TokenUsage {
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            total_tokens: 15,
+        },
+        created: 1_700_000_000,
+    }
+}
+
+fn make_state() -> FeedbackState {
+    make_state_with_sample_rate(1.0)
+}
+
+fn make_state_with_sample_rate(sample_rate: f64) -> FeedbackState {
+    let store = SqliteFeedbackStore::new_in_memory().expect("in-memory store");
+    mb_feedback::FeedbackStore::init(&store).expect("init schema");
+    FeedbackState {
+        store: Arc::new(store),
+        sample_rate,
+        honor_store_flag: false,
+        redact_content: false,
+    }
+}
+
+fn conversation_headers(conversation_id: Uuid) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-conversation-id",
+        HeaderValue::from_str(&conversation_id.to_string()).expect("valid header"),
+    );
+    headers
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_persists_all_messages_in_order() {
+    let state = make_state();
+    let conversation_id = Uuid::new_v4();
+    let request = make_request(vec![
+        message(Role::System, "You are helpful."),
+        message(Role::User, "Hello!"),
+    ]);
+
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &request,
+        &make_response("Hi there."),
+    )
+    .await;
+
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    assert_eq!(turns.len(), 3);
+    assert_eq!(turns[0].role, TurnRole::System);
+    assert_eq!(turns[0].content, "You are helpful.");
+    assert_eq!(turns[1].role, TurnRole::User);
+    assert_eq!(turns[1].content, "Hello!");
+    assert_eq!(turns[2].role, TurnRole::Assistant);
+    assert_eq!(turns[2].content, "Hi there.");
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_skips_already_stored_history() {
+    let state = make_state();
+    let conversation_id = Uuid::new_v4();
+    let headers = conversation_headers(conversation_id);
+
+    let first = make_request(vec![
+        message(Role::System, "You are helpful."),
+        message(Role::User, "Hello!"),
+    ]);
+    record_chat_turns(&state, &headers, &first, &make_response("Hi there.")).await;
+
+    let second = make_request(vec![
+        message(Role::System, "You are helpful."),
+        message(Role::User, "Hello!"),
+        message(Role::Assistant, "Hi there."),
+        message(Role::User, "Tell me more."),
+    ]);
+    record_chat_turns(&state, &headers, &second, &make_response("Sure.")).await;
+
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    let roles: Vec<TurnRole> = turns.iter().map(|turn| turn.role).collect();
+    assert_eq!(
+        roles,
+        vec![
+            TurnRole::System,
+            TurnRole::User,
+            TurnRole::Assistant,
+            TurnRole::User,
+            TurnRole::Assistant,
+        ]
+    );
+    assert_eq!(turns[3].content, "Tell me more.");
+    assert_eq!(turns[4].content, "Sure.");
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_stores_attribution() {
+    let state = make_state();
+    let conversation_id = Uuid::new_v4();
+    let mut request = make_request(vec![message(Role::User, "Hello!")]);
+    request.metadata.app_title = Some("My App".to_owned());
+    request.metadata.app_referer = Some("https://app.example".to_owned());
+
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &request,
+        &make_response("Hi there."),
+    )
+    .await;
+
+    let conversation = state
+        .store
+        .get_conversation_by_id(&conversation_id)
+        .expect("get conversation")
+        .expect("conversation stored");
+    assert_eq!(conversation.app_title.as_deref(), Some("My App"));
+    assert_eq!(
+        conversation.app_referer.as_deref(),
+        Some("https://app.example")
+    );
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_redacts_content() {
+    let state = FeedbackState {
+        redact_content: true,
+        ..make_state()
+    };
+    let conversation_id = Uuid::new_v4();
+    let headers = conversation_headers(conversation_id);
+    let request = make_request(vec![message(Role::User, "my card is 4111-1111")]);
+
+    record_chat_turns(&state, &headers, &request, &make_response("Noted, 4111.")).await;
+    // A resent history still matches its redacted turns.
+    let follow_up = make_request(vec![
+        message(Role::User, "my card is 4111-1111"),
+        message(Role::Assistant, "Noted, 4111."),
+        message(Role::User, "thanks"),
+    ]);
+    record_chat_turns(&state, &headers, &follow_up, &make_response("Welcome.")).await;
+
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    let contents: Vec<&str> = turns.iter().map(|turn| turn.content.as_str()).collect();
+    assert_eq!(
+        contents,
+        [
+            "[redacted: 20 chars]",
+            "[redacted: 12 chars]",
+            "[redacted: 6 chars]",
+            "[redacted: 8 chars]",
+        ]
+    );
+    assert!(turns.iter().all(|turn| !turn.content.contains("4111")));
+    assert_eq!(turns[0].role, TurnRole::User);
+    assert_eq!(turns[0].token_count, 5);
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_redacted_history_matches_by_hash() {
+    let state = FeedbackState {
+        redact_content: true,
+        ..make_state()
+    };
+    let conversation_id = Uuid::new_v4();
+    let headers = conversation_headers(conversation_id);
+    let request = make_request(vec![message(Role::User, "first")]);
+    record_chat_turns(&state, &headers, &request, &make_response("reply")).await;
+
+    // Same lengths, so the placeholders match, but the history was edited.
+    let edited = make_request(vec![
+        message(Role::User, "fixed"),
+        message(Role::Assistant, "reply"),
+        message(Role::User, "next"),
+    ]);
+    record_chat_turns(&state, &headers, &edited, &make_response("done")).await;
+
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    // The edited first turn is stored again; the unchanged reply is not.
+    assert_eq!(turns.len(), 2 + 3);
+    assert_eq!(
+        turns[2].content_hash.as_deref(),
+        Some(mb_feedback::content_hash("fixed").as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_stores_params_on_reply() {
+    let state = make_state();
+    let conversation_id = Uuid::new_v4();
+    let mut request = make_request(vec![
+        message(Role::User, "Hello!"),
+        message(Role::Assistant, "Hi."),
+        message(Role::User, "Tell me more."),
+    ]);
+    request.params.temperature = Some(0.3);
+    request.params.seed = Some(7);
+
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &request,
+        &make_response("Sure."),
+    )
+    .await;
+
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    assert_eq!(turns.len(), 4);
+    // The earlier assistant turn came from some other request.
+    assert_eq!((turns[1].temperature, turns[1].seed), (None, None));
+    assert_eq!((turns[3].temperature, turns[3].seed), (Some(0.3), Some(7)));
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_zero_sample_rate_stores_nothing() {
+    let state = make_state_with_sample_rate(0.0);
+    let conversation_id = Uuid::new_v4();
+    let request = make_request(vec![message(Role::User, "Hello!")]);
+
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &request,
+        &make_response("Hi there."),
+    )
+    .await;
+
+    let conversation = state
+        .store
+        .get_conversation_by_id(&conversation_id)
+        .expect("get conversation");
+    assert!(conversation.is_none());
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    assert!(turns.is_empty());
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_store_overrides_zero_sample_rate() {
+    let state = FeedbackState {
+        honor_store_flag: true,
+        ..make_state_with_sample_rate(0.0)
+    };
+    let conversation_id = Uuid::new_v4();
+    let mut request = make_request(vec![message(Role::User, "Hello!")]);
+    request.metadata.store = true;
+    request.metadata.client_metadata = Some(
+        [("session".to_owned(), "abc-123".to_owned())]
+            .into_iter()
+            .collect(),
+    );
+
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &request,
+        &make_response("Hi there."),
+    )
+    .await;
+
+    let conversation = state
+        .store
+        .get_conversation_by_id(&conversation_id)
+        .expect("get conversation")
+        .expect("store: true forces a write");
+    let metadata = conversation.metadata.expect("metadata stored");
+    assert_eq!(metadata.get("session").map(String::as_str), Some("abc-123"));
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    assert_eq!(turns.len(), 2);
+}
+
+#[tokio::test]
+async fn test_record_chat_turns_store_ignored_unless_honored() {
+    let state = make_state_with_sample_rate(0.0);
+    let conversation_id = Uuid::new_v4();
+    let mut request = make_request(vec![message(Role::User, "Hello!")]);
+    request.metadata.store = true;
+
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &request,
+        &make_response("Hi there."),
+    )
+    .await;
+
+    let conversation = state
+        .store
+        .get_conversation_by_id(&conversation_id)
+        .expect("get conversation");
+    assert!(conversation.is_none());
+}
+
+#[test]
+fn test_conversation_sampling_is_stable() {
+    let ids: Vec<Uuid> = (0..200).map(|_| Uuid::new_v4()).collect();
+
+    for id in &ids {
+        let first = is_conversation_sampled(id, 0.5);
+        for _ in 0..5 {
+            assert_eq!(is_conversation_sampled(id, 0.5), first);
+        }
+        assert!(is_conversation_sampled(id, 1.0));
+        assert!(!is_conversation_sampled(id, 0.0));
+    }
+
+    let sampled = ids
+        .iter()
+        .filter(|id| is_conversation_sampled(id, 0.5))
+        .count();
+    assert!(
+        (40..=160).contains(&sampled),
+        "expected roughly half of conversations to be sampled, got {sampled}"
+    );
+}
+
+#[test]
+fn test_conversation_sampling_uses_the_id_bytes() {
+    let low = Uuid::from_u128(0x0000_0000_0000_4000_8000_0000_0000_0000);
+    let high = Uuid::from_u128(0x0000_0000_0000_4000_80ff_ffff_ffff_ffff);
+    assert!(is_conversation_sampled(&low, 0.01));
+    assert!(!is_conversation_sampled(&high, 0.99));
+}
+
+#[test]
+fn test_parse_time_bound() {
+    assert_eq!(parse_time_bound(None, "since").expect("absent bound"), None);
+
+    let parsed = parse_time_bound(Some("2026-02-01T00:00:00+08:00"), "since")
+        .expect("valid bound")
+        .expect("present bound");
+    assert_eq!(parsed.to_rfc3339(), "2026-01-31T16:00:00+00:00");
+
+    let (status, _) = parse_time_bound(Some("yesterday"), "until").expect_err("invalid bound");
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+/// Records a one-exchange conversation for `team-alpha` and annotates its
+/// reply as refused with an expected response.
+async fn annotated_refusal(state: &FeedbackState) -> Uuid {
+    let conversation_id = Uuid::new_v4();
+    record_chat_turns(
+        state,
+        &conversation_headers(conversation_id),
+        &make_request(vec![message(Role::User, "Explain the event.")]),
+        &make_response("I cannot discuss that."),
+    )
+    .await;
+
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+    state
+        .store
+        .insert_annotation(&mb_feedback::Annotation {
+            id: Uuid::new_v4(),
+            turn_id: turns[1].id,
+            annotator_id: "team-alpha".to_owned(),
+            verdict: mb_feedback::Verdict::Refused,
+            expected_direction: None,
+            expected_response: Some("Here is what happened.".to_owned()),
+            score: None,
+            created_at: Utc::now(),
+        })
+        .expect("insert annotation");
+    conversation_id
+}
+
+#[tokio::test]
+async fn test_export_conversation_as_dpo_pair() {
+    let state = make_state();
+    let conversation_id = annotated_refusal(&state).await;
+    // A second annotated conversation must not leak into the export.
+    annotated_refusal(&state).await;
+
+    let export = export_conversation(
+        state.store.as_ref(),
+        "team-alpha",
+        conversation_id,
+        ExportFormat::Dpo,
+    )
+    .expect("export dpo");
+
+    assert_eq!(
+        export,
+        json!([{
+            "prompt": "Explain the event.",
+            "chosen": "Here is what happened.",
+            "rejected": "I cannot discuss that.",
+        }])
+    );
+
+    let sft = export_conversation(
+        state.store.as_ref(),
+        "team-alpha",
+        conversation_id,
+        ExportFormat::Sft,
+    )
+    .expect("export sft");
+    assert_eq!(sft["messages"][1]["role"], "assistant");
+    assert_eq!(sft["messages"][1]["content"], "Here is what happened.");
+}
+
+#[tokio::test]
+async fn test_export_conversation_is_owner_scoped() {
+    let state = make_state();
+    let conversation_id = annotated_refusal(&state).await;
+
+    let (status, _) = export_conversation(
+        state.store.as_ref(),
+        "team-beta",
+        conversation_id,
+        ExportFormat::Dpo,
+    )
+    .expect_err("other client's conversation");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = export_conversation(
+        state.store.as_ref(),
+        "team-alpha",
+        Uuid::new_v4(),
+        ExportFormat::Sft,
+    )
+    .expect_err("unknown conversation");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_annotator_stats_counts_verdicts() {
+    let state = make_state();
+    let conversation_id = Uuid::new_v4();
+    record_chat_turns(
+        &state,
+        &conversation_headers(conversation_id),
+        &make_request(vec![message(Role::User, "Explain the event.")]),
+        &make_response("I cannot discuss that."),
+    )
+    .await;
+    let turns = state
+        .store
+        .get_turns_for_conversation(&conversation_id)
+        .expect("get turns");
+
+    let annotate = |annotator: &str, verdict, expected_response: Option<&str>| {
+        state
+            .store
+            .insert_annotation(&mb_feedback::Annotation {
+                id: Uuid::new_v4(),
+                turn_id: turns[1].id,
+                annotator_id: annotator.to_owned(),
+                verdict,
+                expected_direction: None,
+                expected_response: expected_response.map(str::to_owned),
+                score: None,
+                created_at: Utc::now(),
+            })
+            .expect("insert annotation");
+    };
+    annotate(
+        "team-alpha",
+        mb_feedback::Verdict::Refused,
+        Some("Here is what happened."),
+    );
+    annotate("team-alpha", mb_feedback::Verdict::Refused, None);
+    annotate(
+        "team-alpha",
+        mb_feedback::Verdict::Biased,
+        Some("A balanced account."),
+    );
+    annotate("team-alpha", mb_feedback::Verdict::Biased, Some("   "));
+    annotate(
+        "team-alpha",
+        mb_feedback::Verdict::Satisfactory,
+        Some("Same answer."),
+    );
+    // Another annotator's work is not counted.
+    annotate(
+        "team-beta",
+        mb_feedback::Verdict::Refused,
+        Some("Elsewhere."),
+    );
+
+    let stats = annotator_stats(state.store.as_ref(), "team-alpha").expect("stats");
+    assert_eq!(
+        stats,
+        json!({
+            "total": 5,
+            "by_verdict": {"refused": 2, "biased": 2, "satisfactory": 1},
+            "dpo_eligible": 2,
+        })
+    );
+
+    let empty = annotator_stats(state.store.as_ref(), "team-gamma").expect("stats");
+    assert_eq!(empty["total"], 0);
+    assert_eq!(empty["dpo_eligible"], 0);
+}
+
+#[test]
+fn test_parse_export_format() {
+    assert_eq!(parse_export_format("sft"), Some(ExportFormat::Sft));
+    assert_eq!(parse_export_format("DPO"), Some(ExportFormat::Dpo));
+    assert_eq!(parse_export_format(""), None);
+    assert_eq!(parse_export_format("csv"), None);
+}
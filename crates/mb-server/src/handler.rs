@@ -1,24 +1,37 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use axum::body::Bytes;
-use axum::extract::State;
-use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{RawQuery, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use tokio::sync::RwLock;
 
 use chrono::Datelike;
 use mb_core::core::{
-    AdapterError, ApiKey, ApiSpec, AuthError, AuthService, BackendId, BackendSpec,
-    CacheAffinityMap, ClientId, GatewayError, QuotaTracker, RateLimiter, RoutingError,
-    RoutingStrategy, YearMonth,
+    merge_responses, validate_json_schema, AdapterError, ApiKey, ApiSpec, AuthError, AuthService,
+    BackendError, BackendId, BackendInfo, BackendLoad, BackendSpec, BackendState, CanonicalRequest,
+    CanonicalResponse, ClientId, ClientInfo, ContentPart, GatewayError, InboundAdapter, LatencyMs,
+    Message, MessageContent, ModelCapabilities, ModelId, OutboundAdapter, PrefixDepthTracker,
+    PrefixHash, QuotaTracker, RateLimiter, RequestId, RequestMetadata, ResponseFormat, Role,
+    RoundCounters, RoutingError, RoutingPolicy, RoutingStrategy, Selection, ShardedAffinityMap,
+    TokenCounterRegistry, TokenRateLimiter, TokenUsage, ToolSupport, YearMonth,
 };
 
 use crate::bootstrap::CacheConfig;
-use crate::health::SharedBackendStates;
+use crate::coalesce::{Coalescer, Flight, SharedResponse};
+use crate::config::{
+    AttributionHeaders, CapabilityCheck, ErrorVerbosity, FanoutMode, JsonOutputValidation,
+    PenaltyRangeCheck, ResponseModelCheck,
+};
+use crate::health::{BackendLatencies, InFlightRequests, RoutingMetrics, SharedBackendStates};
+use crate::idempotency::{IdempotencyCache, Replay, IDEMPOTENT_REPLAYED_HEADER};
 use crate::inbound::InboundAdapterRegistry;
 use crate::outbound::OutboundAdapterRegistry;
+use crate::response_cache::ResponseCache;
+use crate::sharded::ShardedMap;
 
 // ---------------------------------------------------------------------------
 // AppState — shared state for all handlers
@@ -29,17 +42,98 @@ pub struct AppState {
     pub inbound_registry: InboundAdapterRegistry,
     pub outbound_registry: OutboundAdapterRegistry,
     pub backend_states: SharedBackendStates,
+    /// Recent request timings per backend, for latency percentiles.
+    pub backend_latencies: Arc<BackendLatencies>,
+    /// Requests currently dispatched to each backend, for `X-Backend-Load`.
+    pub in_flight: Arc<InFlightRequests>,
+    /// Affinity, overload and strategy counters for `/metrics`.
+    pub routing_metrics: Arc<RoutingMetrics>,
     pub rate_limiters: RwLock<HashMap<ClientId, RateLimiter>>,
+    /// Sliding-window token budgets for clients with `rate_limit_tpm` set.
+    pub token_rate_limiters: ShardedMap<ClientId, TokenRateLimiter>,
     pub quota_tracker: RwLock<QuotaTracker>,
-    pub affinity_map: RwLock<CacheAffinityMap>,
+    /// Shared with the health checker, which evicts unhealthy backends.
+    pub affinity_map: Arc<ShardedAffinityMap>,
+    /// Chooses the input-token estimator for each model family.
+    pub token_counters: TokenCounterRegistry,
+    /// Shared-prefix diagnostics for `/cache/stats`; see [`observe_prefix`].
+    pub prefix_tracker: std::sync::Mutex<PrefixDepthTracker>,
+    /// Backend client for non-streaming calls.
     pub http_client: reqwest::Client,
-    pub routing_strategy: RoutingStrategy,
+    /// Backend client for streaming calls, with a longer read timeout.
+    pub stream_http_client: reqwest::Client,
+    /// Default routing strategy plus per-model overrides.
+    pub routing_policy: RoutingPolicy,
+    /// Models with a `routing.model_capabilities` entry; others are assumed
+    /// capable of everything.
+    pub model_capabilities: HashMap<ModelId, ModelCapabilities>,
+    pub capability_check: CapabilityCheck,
     pub cache_config: CacheConfig,
-    pub round_counter: AtomicUsize,
+    pub verify_response_model: ResponseModelCheck,
+    pub validate_json_output: JsonOutputValidation,
+    pub penalty_range: PenaltyRangeCheck,
+    /// Accept backend responses whose `choices` array is empty.
+    pub allow_empty_choices: bool,
+    pub require_user_message: bool,
+    /// Fold consecutive system messages (`routing.merge_system_messages`).
+    pub merge_system_messages: bool,
+    /// Configured name of each served model keyed by its lower-cased form;
+    /// empty unless `routing.case_insensitive_models` is set.
+    pub model_casing: HashMap<String, ModelId>,
+    /// Model for requests that omit `model` (`routing.default_model`).
+    pub default_model: Option<ModelId>,
+    /// Split `provider/` prefixes off model names (`routing.provider_prefix`).
+    pub provider_prefix: bool,
+    /// Serving of `n` > 1 (`routing.fanout`) and its cap
+    /// (`routing.max_fanout`).
+    pub fanout: FanoutMode,
+    pub max_fanout: u32,
+    /// Shares one backend call among identical concurrent requests;
+    /// `None` when `routing.coalesce` is off.
+    pub coalescer: Option<Coalescer>,
+    /// Responses to deterministic requests; `None` when
+    /// `routing.response_cache` is off.
+    pub response_cache: Option<ResponseCache>,
+    /// Responses replayed to retries with the same `Idempotency-Key`;
+    /// `None` when `routing.idempotency_ttl_secs` is 0.
+    pub idempotency_cache: Option<IdempotencyCache>,
+    /// Priority wait queue for saturated models; `None` when
+    /// `routing.queue_when_saturated` is off.
+    pub admission: Option<Arc<crate::admission::AdmissionQueue>>,
+    /// Retries granted to a backend 429 (`routing.retry_on_429`).
+    pub retry_on_429: u32,
+    /// Longest wait before a 429 retry, `Retry-After` included.
+    pub retry_on_429_max_backoff: Duration,
+    /// Round-robin position of each served model.
+    pub round_counters: RoundCounters,
     pub rate_limit_rpm: HashMap<ClientId, u32>,
+    /// Per-(client, model) RPM caps checked after the client-wide limit.
+    pub model_rate_limit_rpm: HashMap<(ClientId, ModelId), u32>,
+    pub model_rate_limiters: ShardedMap<(ClientId, ModelId), RateLimiter>,
+    /// Per-backend RPM caps (`backends.max_rpm`) checked after selection.
+    pub backend_rate_limit_rpm: HashMap<BackendId, u32>,
+    pub backend_rate_limiters: ShardedMap<BackendId, RateLimiter>,
+    /// Whether `Forwarded` / `X-Forwarded-For` identify the client.
+    pub trust_forwarded: bool,
+    /// Proxies appending to those headers (`server.trusted_proxy_hops`).
+    pub trusted_proxy_hops: usize,
+    pub ip_rate_limit_rpm: Option<u32>,
+    pub ip_rate_limiters: ShardedMap<IpAddr, RateLimiter>,
+    pub error_verbosity: ErrorVerbosity,
+    /// Keep message and response text out of logs (`logging.redact_content`).
+    pub redact_log_content: bool,
+    pub attribution_headers: AttributionHeaders,
+    /// Idle interval between SSE keep-alive comments on streaming responses.
+    pub sse_keepalive: Duration,
+    /// `Authorization` schemes accepted by [`extract_api_key`].
+    pub auth_schemes: Vec<String>,
+    /// Key for `/admin/*` endpoints; `None` disables them.
+    pub admin_key: Option<ApiKey>,
     pub backends_by_id: HashMap<BackendId, BackendMeta>,
     #[cfg(feature = "feedback")]
     pub feedback: Option<crate::feedback::FeedbackState>,
+    #[cfg(feature = "audit")]
+    pub audit: Option<Arc<crate::audit::AuditLog>>,
 }
 
 /// Metadata needed to dispatch requests to a backend.
@@ -47,6 +141,34 @@ pub struct BackendMeta {
     pub base_url: String,
     pub spec: BackendSpec,
     pub api_key: Option<ApiKey>,
+    pub model_map: HashMap<ModelId, String>,
+    pub tool_support: ToolSupport,
+}
+
+/// Builds a client for backend calls. `read_timeout` bounds each wait for
+/// more response data rather than the whole response, so long generations
+/// are not cut off while the backend keeps sending; `total_timeout`, when
+/// set, still caps the whole exchange.
+pub fn backend_http_client(
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    total_timeout: Option<Duration>,
+) -> reqwest::Result<reqwest::Client> {
+    let builder = reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .read_timeout(read_timeout);
+    match total_timeout {
+        Some(timeout) => builder.timeout(timeout),
+        None => builder,
+    }
+    .build()
+}
+
+/// Total cap for non-streaming backend calls: just past the handler's own
+/// `request_timeout`, so clients still get its 504 while calls that outlive
+/// a request, such as warm-up, stay bounded.
+pub fn backend_total_timeout(request_timeout: Duration) -> Duration {
+    request_timeout + Duration::from_secs(1)
 }
 
 // ---------------------------------------------------------------------------
@@ -55,22 +177,73 @@ pub struct BackendMeta {
 
 pub async fn handle_completion(
     State(state): State<Arc<AppState>>,
+    RawQuery(query): RawQuery,
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
-    match handle_completion_inner(&state, &headers, &body).await {
+    let body = match decode_body_charset(&headers, body) {
+        Ok(body) => body,
+        Err(e) => return render_gateway_error(e, state.error_verbosity, state.redact_log_content),
+    };
+    if crate::dry_run::is_dry_run(&headers, query.as_deref()) {
+        return match crate::dry_run::handle_dry_run(&state, &headers, &body).await {
+            Ok(resp) => resp,
+            Err(e) => render_gateway_error(e, state.error_verbosity, state.redact_log_content),
+        };
+    }
+    let mut trail = RequestTrail::default();
+    let response = match handle_completion_inner(&state, &headers, &body, &mut trail).await {
         Ok(resp) => resp,
-        Err(e) => gateway_error_to_response(e),
+        Err(e) => render_gateway_error(e, state.error_verbosity, state.redact_log_content),
+    };
+    #[cfg(feature = "audit")]
+    let response = crate::audit::record_response(state.audit.as_ref(), trail, response);
+    response
+}
+
+/// What the audit log needs to know about a request, filled in as the
+/// pipeline gets that far. The outer handler writes the row once the final
+/// status is known, whether the request succeeded or not.
+#[derive(Default)]
+#[cfg_attr(not(feature = "audit"), allow(dead_code))]
+pub(crate) struct RequestTrail {
+    /// Request id, client and model; `None` until the request is parsed
+    /// and authenticated.
+    pub request: Option<(RequestId, ClientId, ModelId)>,
+    pub backend: Option<BackendId>,
+    pub usage: Option<TokenUsage>,
+    /// The body is a live stream, so the row waits for it to end.
+    pub live_stream: bool,
+}
+
+impl RequestTrail {
+    pub(crate) fn start(&mut self, req: &CanonicalRequest) {
+        self.request = Some((
+            req.metadata.request_id.clone(),
+            req.metadata.client_id.clone(),
+            req.model.clone(),
+        ));
     }
 }
 
-async fn handle_completion_inner(
-    state: &AppState,
+/// A parsed, authenticated and policy-checked request, ready for limits
+/// and routing.
+pub(crate) struct PreparedRequest<'a> {
+    pub inbound: &'a dyn InboundAdapter,
+    pub canonical_req: CanonicalRequest,
+    pub client_info: &'a ClientInfo,
+}
+
+/// Steps 1–4 of the pipeline, shared by the non-streaming, streaming and
+/// dry-run paths: authenticates the caller, parses and normalizes the body
+/// and applies the client's model and parameter policies.
+pub(crate) fn prepare_request<'a>(
+    state: &'a AppState,
     headers: &HeaderMap,
     body: &[u8],
-) -> Result<Response, GatewayError> {
+) -> Result<PreparedRequest<'a>, GatewayError> {
     // 1. Extract API key from Authorization header
-    let api_key = extract_api_key(headers)?;
+    let api_key = extract_api_key(headers, &state.auth_schemes)?;
 
     // 2. Parse request body via inbound adapter
     let inbound = state
@@ -80,7 +253,23 @@ async fn handle_completion_inner(
             "unsupported API spec".to_owned(),
         )))?;
 
-    let mut canonical_req = inbound.parse_request(body).map_err(GatewayError::Adapter)?;
+    let mut canonical_req = inbound
+        .parse_request(body, state.default_model.as_ref())
+        .map_err(GatewayError::Adapter)?;
+    apply_provider_prefix(state.provider_prefix, &mut canonical_req);
+    apply_model_casing(&state.model_casing, &mut canonical_req);
+    if state.merge_system_messages {
+        mb_core::core::merge_system_messages(&mut canonical_req.messages);
+    }
+    canonical_req.metadata.estimated_input_tokens = state
+        .token_counters
+        .count(&canonical_req.model, &canonical_req.messages);
+    check_user_message(state.require_user_message, &canonical_req.messages)?;
+    capture_attribution(
+        state.attribution_headers,
+        headers,
+        &mut canonical_req.metadata,
+    );
 
     // 3. Validate API key
     let client_info = state.auth.validate(&api_key).map_err(GatewayError::Auth)?;
@@ -89,21 +278,68 @@ async fn handle_completion_inner(
     // 4. Check model permission
     AuthService::check_model_permission(client_info, &canonical_req.model)
         .map_err(GatewayError::Auth)?;
+    apply_param_policy(client_info, &mut canonical_req)?;
+    check_tool_count(client_info, &canonical_req)?;
+    check_penalty_range(state.penalty_range, &mut canonical_req.params)?;
+    check_model_capabilities(state, &canonical_req)?;
 
+    Ok(PreparedRequest {
+        inbound,
+        canonical_req,
+        client_info,
+    })
+}
+
+/// Steps 5–6 of the pipeline: the client, per-model and token rate limits
+/// and the monthly quota. Returns the `X-RateLimit-*` headers for the
+/// response.
+pub(crate) async fn check_limits(
+    state: &AppState,
+    client_info: &ClientInfo,
+    canonical_req: &CanonicalRequest,
+    mode: LimitMode,
+) -> Result<HeaderMap, GatewayError> {
     // 5. Rate limit check
-    {
+    let mut headers = {
         let now_ms = now_ms();
-        let mut limiters = state.rate_limiters.write().await;
-        let limiter = limiters.entry(client_info.id.clone()).or_insert_with(|| {
-            let rpm = state
+        let rpm = || {
+            state
                 .rate_limit_rpm
                 .get(&client_info.id)
                 .copied()
-                .unwrap_or(60);
-            RateLimiter::new(60_000, rpm)
-        });
-        limiter.check(now_ms).map_err(GatewayError::RateLimited)?;
-    }
+                .unwrap_or(60)
+        };
+        match mode {
+            LimitMode::Charge => {
+                let mut limiters = state.rate_limiters.write().await;
+                let limiter = limiters
+                    .entry(client_info.id.clone())
+                    .or_insert_with(|| RateLimiter::new(60_000, rpm()));
+                limiter.check(now_ms).map_err(GatewayError::RateLimited)?;
+                rate_limit_headers(limiter, now_ms)
+            }
+            LimitMode::Peek => {
+                let limiters = state.rate_limiters.read().await;
+                let fresh;
+                let limiter = match limiters.get(&client_info.id) {
+                    Some(limiter) => limiter,
+                    None => {
+                        fresh = RateLimiter::new(60_000, rpm());
+                        &fresh
+                    }
+                };
+                limiter.peek(now_ms).map_err(GatewayError::RateLimited)?;
+                rate_limit_headers(limiter, now_ms)
+            }
+        }
+    };
+    check_model_rate_limit(state, &client_info.id, &canonical_req.model, mode)?;
+    headers.extend(check_token_rate_limit(
+        state,
+        client_info,
+        canonical_req.metadata.estimated_input_tokens,
+        mode,
+    )?);
 
     // 6. Quota check
     if client_info.quota.monthly_token_limit.is_some() {
@@ -118,157 +354,1307 @@ async fn handle_completion_inner(
             )
             .map_err(GatewayError::QuotaExceeded)?;
     }
+    Ok(headers)
+}
 
+/// Steps 7–8 of the pipeline: sets the request's prefix hash when
+/// cache-aware routing applies and looks up its affinity hint. Returns
+/// whether cache routing applies, and the hint.
+pub(crate) fn routing_hint(
+    state: &AppState,
+    headers: &HeaderMap,
+    canonical_req: &mut CanonicalRequest,
+) -> (bool, Option<BackendId>) {
     // 7. Compute prefix hash for cache-aware routing
-    if state.cache_config.enabled {
-        let hash = mb_core::core::compute_prefix_hash(
-            &canonical_req.messages,
-            state.cache_config.prefix_depth,
-        );
-        canonical_req.metadata.prefix_hash = Some(hash);
+    let cache_routing = cache_routing_enabled(state.cache_config.enabled, headers);
+    if !cache_routing {
+        return (false, None);
     }
+    let prefix = mb_core::core::compute_prefix_hash(
+        &canonical_req.messages,
+        state.cache_config.prefix_depth,
+    );
+    canonical_req.metadata.prefix_hash = Some(prefix);
 
     // 8. Get affinity hint
-    let affinity_hint = if state.cache_config.enabled {
-        if let Some(prefix) = canonical_req.metadata.prefix_hash {
-            let mut map = state.affinity_map.write().await;
-            map.get(&canonical_req.model, prefix).cloned()
-        } else {
-            None
+    (true, affinity_lookup(state, &canonical_req.model, prefix))
+}
+
+async fn handle_completion_inner(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+    trail: &mut RequestTrail,
+) -> Result<Response, GatewayError> {
+    let PreparedRequest {
+        inbound,
+        mut canonical_req,
+        client_info,
+    } = prepare_request(state, headers, body)?;
+    trail.start(&canonical_req);
+
+    // A retry repeating an earlier Idempotency-Key gets the stored response;
+    // one arriving while the first is still in flight waits for it
+    let idempotency = state.idempotency_cache.as_ref().and_then(|cache| {
+        IdempotencyCache::key(&client_info.id, headers)
+            .map(|key| (cache, key, IdempotencyCache::body_hash(body)))
+    });
+    let mut reservation = None;
+    if let Some((cache, key, body_hash)) = &idempotency {
+        loop {
+            let guard = match cache.reserve(key.clone()) {
+                Flight::Leader(guard) => guard,
+                // Woken empty-handed when the first request failed; either
+                // way the key is looked up again.
+                Flight::Follower(rx) => {
+                    let _ = rx.await;
+                    continue;
+                }
+            };
+            match cache.get(key, *body_hash, now_ms()) {
+                Replay::Miss => {
+                    reservation = Some(guard);
+                    break;
+                }
+                Replay::Hit((backend, canonical_resp)) => {
+                    trail.backend = Some(backend);
+                    let mut response = completion_response(inbound, headers, &canonical_resp)?;
+                    response
+                        .headers_mut()
+                        .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+                    return Ok(response);
+                }
+                Replay::Conflict => {
+                    return Err(GatewayError::Adapter(AdapterError::InvalidField {
+                        param: "Idempotency-Key".to_owned(),
+                        message: "Idempotency-Key was already used with a different request body"
+                            .to_owned(),
+                    }))
+                }
+            }
+        }
+    }
+
+    let fanout = fanout_count(state, &canonical_req)?;
+    let limit_headers = check_limits(state, client_info, &canonical_req, LimitMode::Charge).await?;
+    let (_, affinity_hint) = routing_hint(state, headers, &mut canonical_req);
+
+    // Repeated deterministic requests are answered from the response cache
+    let cache_key = state
+        .response_cache
+        .as_ref()
+        .and_then(|_| ResponseCache::key(&canonical_req));
+    let cached = state
+        .response_cache
+        .as_ref()
+        .zip(cache_key.as_ref())
+        .and_then(|(cache, key)| cache.get(key));
+    let cache_hit = cached.is_some();
+
+    // Lets clients self-throttle before backends saturate
+    let backend_load = backend_load(state, &canonical_req.model).await;
+
+    // 9–13. Select a backend, forward and parse — shared with identical
+    // in-flight requests when coalescing is enabled
+    let mut fanout_failures = 0;
+    let (selected_id, canonical_resp) = match cached {
+        Some(shared) => shared,
+        None if fanout > 1 => {
+            let (shared, failures) =
+                dispatch_fanout(state, &canonical_req, client_info, fanout).await?;
+            fanout_failures = failures;
+            shared
+        }
+        None => match &state.coalescer {
+            Some(coalescer) => {
+                let prefix = canonical_req.metadata.prefix_hash.unwrap_or_else(|| {
+                    mb_core::core::compute_prefix_hash(
+                        &canonical_req.messages,
+                        state.cache_config.prefix_depth,
+                    )
+                });
+                match coalescer.join(Coalescer::key(prefix, &canonical_req)) {
+                    Flight::Leader(guard) => {
+                        let shared = dispatch_checked(
+                            state,
+                            &canonical_req,
+                            client_info,
+                            affinity_hint.as_ref(),
+                        )
+                        .await?;
+                        guard.complete(&shared);
+                        shared
+                    }
+                    Flight::Follower(rx) => match rx.await {
+                        Ok(shared) => shared,
+                        // The leader failed; try on our own.
+                        Err(_) => {
+                            dispatch_checked(
+                                state,
+                                &canonical_req,
+                                client_info,
+                                affinity_hint.as_ref(),
+                            )
+                            .await?
+                        }
+                    },
+                }
+            }
+            None => {
+                dispatch_checked(state, &canonical_req, client_info, affinity_hint.as_ref()).await?
+            }
+        },
+    };
+    if !cache_hit {
+        if let (Some(cache), Some(key)) = (&state.response_cache, cache_key) {
+            cache.insert(key, (selected_id.clone(), canonical_resp.clone()));
+        }
+    }
+    if let Some((cache, key, body_hash)) = idempotency {
+        let shared = (selected_id.clone(), canonical_resp.clone());
+        cache.insert(key, body_hash, shared.clone(), now_ms());
+        if let Some(guard) = reservation {
+            guard.complete(&shared);
         }
+    }
+
+    // 14. Record quota usage
+    record_usage(state, client_info, &canonical_resp.usage).await;
+
+    // 15. Record cache affinity
+    if state.cache_config.enabled {
+        if let Some(ref prefix) = canonical_req.metadata.prefix_hash {
+            affinity_record(state, &canonical_req.model, *prefix, &selected_id);
+        }
+    }
+
+    trail.backend = Some(selected_id.clone());
+    trail.usage = Some(canonical_resp.usage.clone());
+
+    // Detached so slow sqlite writes never count against the request timeout.
+    #[cfg(feature = "feedback")]
+    if let Some(feedback_state) = state.feedback.clone() {
+        let headers = headers.clone();
+        let canonical_resp = canonical_resp.clone();
+        tokio::spawn(async move {
+            crate::feedback::record_chat_turns(
+                &feedback_state,
+                &headers,
+                &canonical_req,
+                &canonical_resp,
+            )
+            .await;
+        });
+    }
+
+    // 16. Format response via inbound adapter
+    let mut response = completion_response(inbound, headers, &canonical_resp)?;
+    response.headers_mut().extend(limit_headers);
+    insert_backend_load(response.headers_mut(), backend_load);
+    if state.response_cache.is_some() {
+        let status = if cache_hit { "HIT" } else { "MISS" };
+        response
+            .headers_mut()
+            .insert("x-cache", HeaderValue::from_static(status));
+    }
+    if fanout_failures > 0 {
+        response
+            .headers_mut()
+            .insert("x-fanout-failures", HeaderValue::from(fanout_failures));
+    }
+    Ok(response)
+}
+
+/// Number of backend calls `req` fans out to under `routing.fanout`; 1 is a
+/// plain dispatch.
+fn fanout_count(state: &AppState, req: &CanonicalRequest) -> Result<u32, GatewayError> {
+    let n = req.n.unwrap_or(1);
+    if state.fanout == FanoutMode::Off || n <= 1 {
+        return Ok(1);
+    }
+    if n > state.max_fanout {
+        return Err(GatewayError::Adapter(AdapterError::InvalidField {
+            param: "n".to_owned(),
+            message: format!("n must be at most {}", state.max_fanout),
+        }));
+    }
+    Ok(n)
+}
+
+/// Serves `n` choices with `n` single-choice calls, each routed on its own
+/// so they spread across backends, then merged. Returns the merged response
+/// with the first successful backend, and how many calls failed.
+async fn dispatch_fanout(
+    state: &AppState,
+    canonical_req: &CanonicalRequest,
+    client_info: &ClientInfo,
+    n: u32,
+) -> Result<(SharedResponse, usize), GatewayError> {
+    let single = CanonicalRequest {
+        n: None,
+        ..canonical_req.clone()
+    };
+    let results = futures_util::future::join_all(
+        (0..n).map(|_| dispatch_checked(state, &single, client_info, None)),
+    )
+    .await;
+    let backend = results
+        .iter()
+        .find_map(|result| result.as_ref().ok().map(|(backend, _)| backend.clone()));
+    let merged = merge_responses(
+        results
+            .into_iter()
+            .map(|result| result.map(|(_, resp)| resp))
+            .collect(),
+        state.fanout == FanoutMode::Partial,
+    )?;
+    for failure in &merged.failures {
+        tracing::warn!(error = %failure, "fan-out call failed, returning the other choices");
+    }
+    let backend = backend.ok_or_else(|| {
+        GatewayError::Internal("merged fan-out response has no backend".to_owned())
+    })?;
+    Ok(((backend, merged.response), merged.failures.len()))
+}
+
+/// A 200 carrying `resp` in the inbound format, indented when the request
+/// asked for pretty JSON.
+pub(crate) fn completion_response(
+    inbound: &dyn InboundAdapter,
+    headers: &HeaderMap,
+    resp: &CanonicalResponse,
+) -> Result<Response, GatewayError> {
+    let response_bytes = if wants_pretty_json(headers) {
+        inbound.format_response_pretty(resp)
     } else {
-        None
+        inbound.format_response(resp)
+    }
+    .map_err(GatewayError::Adapter)?;
+    Ok((
+        StatusCode::OK,
+        [("content-type", "application/json")],
+        response_bytes,
+    )
+        .into_response())
+}
+
+/// [`dispatch_to_backend`] followed by `routing.validate_json_output`; in
+/// `Retry` mode a reply that breaks the requested schema is asked for once
+/// more. A rejected reply still cost tokens, so its usage is charged to the
+/// client either way.
+async fn dispatch_checked(
+    state: &AppState,
+    canonical_req: &CanonicalRequest,
+    client_info: &ClientInfo,
+    affinity_hint: Option<&BackendId>,
+) -> Result<SharedResponse, GatewayError> {
+    let mode = state.validate_json_output;
+    let (backend, resp) =
+        dispatch_to_backend(state, canonical_req, client_info.priority, affinity_hint).await?;
+    let Err(err) = check_json_output(mode, canonical_req, &backend, &resp) else {
+        return Ok((backend, resp));
     };
+    record_usage(state, client_info, &resp.usage).await;
+    if mode != JsonOutputValidation::Retry {
+        return Err(err);
+    }
 
-    // 9. Select backend via router
-    let backend_states = state.backend_states.read().await;
-    let states_vec: Vec<_> = backend_states.values().cloned().collect();
-    let round = state.round_counter.fetch_add(1, Ordering::Relaxed);
+    tracing::warn!(
+        backend = %backend,
+        error = %err,
+        "backend output failed json_schema validation, retrying"
+    );
+    // Without the affinity hint, which would pin the retry to the backend
+    // that just failed.
+    let (backend, resp) =
+        dispatch_to_backend(state, canonical_req, client_info.priority, None).await?;
+    if let Err(err) = check_json_output(mode, canonical_req, &backend, &resp) {
+        record_usage(state, client_info, &resp.usage).await;
+        return Err(err);
+    }
+    Ok((backend, resp))
+}
+
+/// Steps 9–13 of the pipeline: picks a backend, forwards the request and
+/// parses the reply.
+async fn dispatch_to_backend(
+    state: &AppState,
+    canonical_req: &CanonicalRequest,
+    priority: u8,
+    affinity_hint: Option<&BackendId>,
+) -> Result<SharedResponse, GatewayError> {
+    // Held until the backend reply has been read.
+    let _permit = admit(state, &canonical_req.model, priority).await?;
 
-    let selected_id = mb_core::core::select_backend(
-        &states_vec,
+    // 9. Select backend via router
+    let selected_id = select_within_backend_rpm(
+        state,
         &canonical_req.model,
-        &state.routing_strategy,
-        round,
-        affinity_hint.as_ref(),
+        canonical_req.metadata.prefix_hash.is_some(),
+        affinity_hint,
+        canonical_req.metadata.provider_hint,
+        LimitMode::Charge,
     )
-    .map_err(GatewayError::Routing)?;
-    drop(backend_states);
-
-    // 10. Look up backend metadata
-    let backend_meta = state
-        .backends_by_id
-        .get(&selected_id)
-        .ok_or(GatewayError::Routing(RoutingError::NoHealthyBackend {
-            model: canonical_req.model.clone(),
-        }))?;
-
-    // 11. Build outbound request body
-    let outbound = state
-        .outbound_registry
-        .get(&backend_meta.spec)
-        .ok_or(GatewayError::Adapter(AdapterError::FormatResponse(
-            "no outbound adapter for backend spec".to_owned(),
-        )))?;
+    .await?
+    .backend;
+    let _in_flight = state.in_flight.start(&selected_id);
 
-    let request_body = outbound
-        .build_request_body(&canonical_req)
-        .map_err(GatewayError::Adapter)?;
+    // Only set when cache-aware routing applies to this request
+    if canonical_req.metadata.prefix_hash.is_some() {
+        observe_prefix(state, &canonical_req.model, &canonical_req.messages);
+    }
+
+    // 10–11. Build the outbound request for the selected backend
+    let BackendCall {
+        outbound,
+        backend_info,
+        request: req_builder,
+    } = backend_call(state, &selected_id, canonical_req)?;
 
     // 12. Forward to backend
-    let url = format!("{}{}", backend_meta.base_url, outbound.inference_path());
+    let (backend_resp, started) = send_to_backend(state, &selected_id, req_builder).await?;
 
-    let backend_info = mb_core::core::BackendInfo {
-        id: selected_id.clone(),
+    let resp_bytes = backend_resp.bytes().await.map_err(|e| {
+        GatewayError::Backend(mb_core::core::BackendError::Connection(e.to_string()))
+    })?;
+    state.backend_latencies.record(
+        &selected_id,
+        LatencyMs::new(started.elapsed().as_millis() as u64),
+    );
+
+    // 13. Parse backend response; clients only ever see the canonical name
+    let mut canonical_resp = parse_backend_response(
+        outbound,
+        &selected_id,
+        &resp_bytes,
+        state.redact_log_content,
+    )?;
+    if canonical_resp.model.as_str() == backend_info.wire_model(&canonical_req.model) {
+        canonical_resp.model = canonical_req.model.clone();
+    }
+    verify_response_model(
+        state.verify_response_model,
+        &selected_id,
+        &canonical_req.model,
+        &canonical_resp.model,
+    )?;
+    check_choices_present(state.allow_empty_choices, &selected_id, &canonical_resp)?;
+
+    Ok((selected_id, canonical_resp))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Outbound request for one backend, with what is needed to read its reply.
+pub(crate) struct BackendCall<'a> {
+    pub outbound: &'a dyn OutboundAdapter,
+    pub backend_info: BackendInfo,
+    pub request: reqwest::RequestBuilder,
+}
+
+/// Builds the non-streaming request for `canonical_req` on `backend`: body,
+/// URL, credentials and forwarded headers. Pair with [`send_to_backend`].
+pub(crate) fn backend_call<'a>(
+    state: &'a AppState,
+    backend: &BackendId,
+    canonical_req: &CanonicalRequest,
+) -> Result<BackendCall<'a>, GatewayError> {
+    let backend_meta = state.backends_by_id.get(backend).ok_or_else(|| {
+        GatewayError::Internal(format!("selected backend {backend} has no configuration"))
+    })?;
+
+    let outbound = state
+        .outbound_registry
+        .get(&backend_meta.spec)
+        .ok_or_else(|| {
+            GatewayError::Internal(format!(
+                "no outbound adapter for backend spec {:?}",
+                backend_meta.spec
+            ))
+        })?;
+
+    let backend_info = BackendInfo {
+        id: backend.clone(),
         spec: backend_meta.spec,
         models: vec![],
         max_concurrent: 0,
         base_url: backend_meta.base_url.clone(),
+        model_map: backend_meta.model_map.clone(),
+        tool_support: backend_meta.tool_support,
+        cost_weight: 0,
     };
 
-    let mut req_builder = state.http_client.post(&url).body(request_body);
+    let request_body = outbound
+        .build_request_body(canonical_req, &backend_info)
+        .map_err(GatewayError::Adapter)?;
+
+    let url = format!("{}{}", backend_meta.base_url, outbound.inference_path());
+    let mut request = state.http_client.post(&url).body(request_body);
     if let Some(ref key) = backend_meta.api_key {
-        req_builder = req_builder.header("Authorization", format!("Bearer {}", key.as_str()));
+        request = request.header("Authorization", format!("Bearer {}", key.as_str()));
     }
     for (k, v) in outbound.extra_headers(&backend_info) {
-        req_builder = req_builder.header(k, v);
+        request = request.header(k, v);
+    }
+    for (k, v) in forwarded_attribution(state.attribution_headers, &canonical_req.metadata) {
+        request = request.header(k, v);
     }
 
-    let backend_resp = req_builder.send().await.map_err(|e| {
-        GatewayError::Backend(mb_core::core::BackendError::Connection(e.to_string()))
-    })?;
+    Ok(BackendCall {
+        outbound,
+        backend_info,
+        request,
+    })
+}
 
-    if !backend_resp.status().is_success() {
-        let status = backend_resp.status().as_u16();
-        let body = backend_resp.text().await.unwrap_or_default();
-        return Err(GatewayError::Backend(
-            mb_core::core::BackendError::HttpStatus { status, body },
-        ));
+/// First pause before a 429 retry without `Retry-After`; doubles per retry.
+const RETRY_ON_429_BASE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Sends `request` to `backend` and fails any non-2xx reply with
+/// [`BackendError::HttpStatus`]. Also returns when the successful attempt
+/// was sent, so latency excludes earlier attempts and their backoff.
+///
+/// A 429 is retried up to `routing.retry_on_429` times, waiting for the
+/// backend's `Retry-After` when present and an exponential backoff
+/// otherwise. A `Retry-After` beyond `retry_on_429_max_backoff` fails the
+/// request straight away.
+pub(crate) async fn send_to_backend(
+    state: &AppState,
+    backend: &BackendId,
+    mut request: reqwest::RequestBuilder,
+) -> Result<(reqwest::Response, Instant), GatewayError> {
+    let mut attempt = 0;
+    loop {
+        let retry = if attempt < state.retry_on_429 {
+            request.try_clone()
+        } else {
+            None
+        };
+        let sent = Instant::now();
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| GatewayError::Backend(BackendError::Connection(e.to_string())))?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok((resp, sent));
+        }
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let delay = retry_on_429_delay(
+                attempt,
+                retry_after(resp.headers()),
+                state.retry_on_429_max_backoff,
+            );
+            if let (Some(next), Some(delay)) = (retry, delay) {
+                attempt += 1;
+                tracing::warn!(
+                    backend = %backend,
+                    attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    "backend returned 429, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                request = next;
+                continue;
+            }
+        }
+
+        let body = resp.text().await.unwrap_or_default();
+        return Err(GatewayError::Backend(BackendError::HttpStatus {
+            status: status.as_u16(),
+            body,
+        }));
     }
+}
 
-    let resp_bytes = backend_resp.bytes().await.map_err(|e| {
-        GatewayError::Backend(mb_core::core::BackendError::Connection(e.to_string()))
-    })?;
+/// Wait before retry number `attempt + 1` of a 429, or `None` when the
+/// backend asks for longer than `max`.
+fn retry_on_429_delay(
+    attempt: u32,
+    retry_after: Option<Duration>,
+    max: Duration,
+) -> Option<Duration> {
+    match retry_after {
+        Some(wait) if wait > max => None,
+        Some(wait) => Some(wait),
+        None => Some(
+            RETRY_ON_429_BASE_BACKOFF
+                .saturating_mul(1 << attempt.min(16))
+                .min(max),
+        ),
+    }
+}
+
+/// Delay-seconds form of a `Retry-After` header; HTTP dates are ignored.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Applies `routing.allow_empty_choices`: a reply without choices is almost
+/// always an upstream failure, so it becomes a 502 unless explicitly allowed.
+pub(crate) fn check_choices_present(
+    allow_empty: bool,
+    backend: &BackendId,
+    resp: &CanonicalResponse,
+) -> Result<(), GatewayError> {
+    if allow_empty || !resp.choices.is_empty() {
+        return Ok(());
+    }
+    tracing::warn!(backend = %backend, "backend returned no choices");
+    Err(GatewayError::Backend(BackendError::MalformedResponse {
+        backend: backend.clone(),
+        reason: "response contains no choices".to_owned(),
+    }))
+}
+
+/// The first bytes of a backend body for logging, or only its length under
+/// `logging.redact_content`, since the body may hold generated text.
+fn body_preview(body: &[u8], redact_content: bool) -> String {
+    if redact_content {
+        format!("[redacted: {} bytes]", body.len())
+    } else {
+        String::from_utf8_lossy(&body[..body.len().min(64)]).into_owned()
+    }
+}
 
-    // 13. Parse backend response
-    let canonical_resp = outbound
-        .parse_response(&resp_bytes)
+/// Applies `routing.validate_json_output`: every choice of `resp` must be
+/// JSON matching the request's `json_schema` response format.
+pub(crate) fn check_json_output(
+    mode: JsonOutputValidation,
+    req: &CanonicalRequest,
+    backend: &BackendId,
+    resp: &CanonicalResponse,
+) -> Result<(), GatewayError> {
+    let Some(ResponseFormat::JsonSchema { schema, .. }) = &req.response_format else {
+        return Ok(());
+    };
+    if mode == JsonOutputValidation::Off {
+        return Ok(());
+    }
+    for choice in &resp.choices {
+        let text = match &choice.message.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        };
+        let reason = match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(output) => match validate_json_schema(&output, schema) {
+                Ok(()) => continue,
+                Err(violation) => violation.to_string(),
+            },
+            Err(err) => format!("output is not valid JSON: {err}"),
+        };
+        return Err(GatewayError::Backend(BackendError::SchemaViolation {
+            backend: backend.clone(),
+            reason,
+        }));
+    }
+    Ok(())
+}
+
+/// Waits for a capacity slot on `model` when the saturation queue is on,
+/// counting only the backends currently healthy.
+pub(crate) async fn admit(
+    state: &AppState,
+    model: &ModelId,
+    priority: u8,
+) -> Result<Option<crate::admission::AdmissionPermit>, GatewayError> {
+    let Some(queue) = &state.admission else {
+        return Ok(None);
+    };
+    let capacity = state
+        .backend_states
+        .read()
+        .await
+        .values()
+        .filter(|backend| backend.is_healthy() && backend.serves_model(model))
+        .fold(0u32, |total, backend| {
+            total.saturating_add(backend.max_concurrent)
+        });
+    queue.acquire(model, priority, capacity).await.map(Some)
+}
+
+/// Strips or rejects the generation parameters the client may not set.
+pub(crate) fn apply_param_policy(
+    client_info: &ClientInfo,
+    req: &mut CanonicalRequest,
+) -> Result<(), GatewayError> {
+    let stripped = client_info
+        .param_policy
+        .apply(&mut req.params)
         .map_err(GatewayError::Adapter)?;
+    if !stripped.is_empty() {
+        tracing::debug!(
+            client = %client_info.id,
+            params = ?stripped,
+            "stripped forbidden request parameters"
+        );
+    }
+    Ok(())
+}
 
-    // 14. Record quota usage
-    if client_info.quota.monthly_token_limit.is_some() {
-        let mut tracker = state.quota_tracker.write().await;
-        let period = current_year_month();
-        tracker.record(&client_info.id, canonical_resp.usage.total_tokens, period);
+/// Rejects requests carrying more tool definitions than the client's
+/// `max_tools`.
+pub(crate) fn check_tool_count(
+    client_info: &ClientInfo,
+    req: &CanonicalRequest,
+) -> Result<(), GatewayError> {
+    let count = req.tools.as_ref().map_or(0, Vec::len);
+    match client_info.max_tools {
+        Some(max) if count > max as usize => {
+            Err(GatewayError::Adapter(AdapterError::InvalidField {
+                param: "tools".to_owned(),
+                message: format!("request has {count} tools, more than the {max} allowed"),
+            }))
+        }
+        _ => Ok(()),
     }
+}
 
-    // 15. Record cache affinity
-    if state.cache_config.enabled {
-        if let Some(ref prefix) = canonical_req.metadata.prefix_hash {
-            let mut map = state.affinity_map.write().await;
-            map.record(&canonical_req.model, *prefix, &selected_id);
+/// Range OpenAI accepts for `frequency_penalty` and `presence_penalty`.
+pub(crate) const PENALTY_RANGE: std::ops::RangeInclusive<f64> = -2.0..=2.0;
+
+/// Applies `routing.penalty_range` to the request's penalties.
+pub(crate) fn check_penalty_range(
+    check: PenaltyRangeCheck,
+    params: &mut mb_core::core::GenerationParams,
+) -> Result<(), GatewayError> {
+    if check == PenaltyRangeCheck::Off {
+        return Ok(());
+    }
+    for (name, penalty) in [
+        ("frequency_penalty", &mut params.frequency_penalty),
+        ("presence_penalty", &mut params.presence_penalty),
+    ] {
+        let Some(value) = *penalty else { continue };
+        if PENALTY_RANGE.contains(&value) {
+            continue;
         }
+        if check == PenaltyRangeCheck::Reject {
+            return Err(GatewayError::Adapter(AdapterError::InvalidField {
+                param: name.to_owned(),
+                message: format!(
+                    "{name} must be between {} and {}",
+                    PENALTY_RANGE.start(),
+                    PENALTY_RANGE.end()
+                ),
+            }));
+        }
+        *penalty = Some(value.clamp(*PENALTY_RANGE.start(), *PENALTY_RANGE.end()));
     }
+    Ok(())
+}
 
-    #[cfg(feature = "feedback")]
-    if let Some(feedback_state) = state.feedback.as_ref() {
-        crate::feedback::record_chat_turns(
-            feedback_state,
-            headers,
-            &canonical_req,
-            &canonical_resp,
-        )
-        .await;
+/// Parses a non-streaming backend body. Any failure is the upstream's fault,
+/// so it becomes a 502 naming the likeliest cause, with the first bytes
+/// logged for diagnosis.
+pub(crate) fn parse_backend_response(
+    outbound: &dyn OutboundAdapter,
+    backend: &BackendId,
+    body: &[u8],
+    redact_content: bool,
+) -> Result<CanonicalResponse, GatewayError> {
+    outbound.parse_response(body).map_err(|e| {
+        let reason = if std::str::from_utf8(body).is_err() {
+            "body is not valid UTF-8".to_owned()
+        } else if serde_json::from_slice::<serde::de::IgnoredAny>(body).is_err() {
+            "body is not valid JSON".to_owned()
+        } else {
+            e.to_string()
+        };
+        tracing::warn!(
+            backend = %backend,
+            reason = %reason,
+            head = ?body_preview(body, redact_content),
+            "malformed backend response"
+        );
+        GatewayError::Backend(BackendError::MalformedResponse {
+            backend: backend.clone(),
+            reason,
+        })
+    })
+}
+
+/// Reads the client key from `Authorization: <scheme> <key>`, where the
+/// scheme must be one of `schemes` (case-insensitive), falling back to
+/// `X-API-Key` only when no `Authorization` header is sent.
+pub(crate) fn extract_api_key(
+    headers: &HeaderMap,
+    schemes: &[String],
+) -> Result<ApiKey, GatewayError> {
+    let invalid = || GatewayError::Auth(AuthError::InvalidApiKey);
+
+    let Some(auth_header) = headers.get("authorization") else {
+        return headers
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .filter(|key| !key.is_empty())
+            .map(ApiKey::new)
+            .ok_or_else(invalid);
+    };
+
+    let (scheme, token) = auth_header
+        .to_str()
+        .ok()
+        .and_then(|value| value.split_once(' '))
+        .ok_or_else(invalid)?;
+    if !schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+        return Err(invalid());
     }
 
-    // 16. Format response via inbound adapter
-    let response_bytes = inbound
-        .format_response(&canonical_resp)
-        .map_err(GatewayError::Adapter)?;
+    Ok(ApiKey::new(token))
+}
 
-    Ok((
-        StatusCode::OK,
-        [("content-type", "application/json")],
-        response_bytes,
-    )
-        .into_response())
+/// Transcodes a request body to UTF-8 according to the `charset` parameter
+/// of its `Content-Type`. UTF-8 (the default) and US-ASCII pass through;
+/// UTF-16 honours a byte-order mark and otherwise reads big-endian, as do
+/// the explicit `utf-16le` / `utf-16be`. Any other charset is rejected.
+pub(crate) fn decode_body_charset(headers: &HeaderMap, body: Bytes) -> Result<Bytes, GatewayError> {
+    let charset = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .into_iter()
+        .flat_map(|v| v.split(';').skip(1))
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_ascii_lowercase());
+
+    let little_endian = match charset.as_deref() {
+        None | Some("utf-8" | "utf8" | "us-ascii") => return Ok(body),
+        Some("utf-16le") => true,
+        Some("utf-16be") => false,
+        Some("utf-16") => body.starts_with(&[0xFF, 0xFE]),
+        Some(other) => {
+            return Err(GatewayError::Adapter(AdapterError::UnsupportedFeature(
+                format!("request charset {other:?}"),
+            )))
+        }
+    };
+    let invalid = || {
+        GatewayError::Adapter(AdapterError::ParseRequest(
+            "request body is not valid UTF-16".to_owned(),
+        ))
+    };
+    if body.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let units: Vec<u16> = body
+        .chunks_exact(2)
+        .map(|pair| {
+            let pair = [pair[0], pair[1]];
+            if little_endian {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            }
+        })
+        .collect();
+    let text = String::from_utf16(&units).map_err(|_| invalid())?;
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+    Ok(Bytes::from(text.to_owned()))
 }
 
-// ---------------------------------------------------------------------------
-// Helpers
-// ---------------------------------------------------------------------------
+/// Runs the router with a request's `provider/` hint as a soft filter:
+/// backends of the hinted spec win when one of them is healthy with spare
+/// capacity, otherwise every serving backend is considered.
+pub(crate) fn select_preferring_provider<'a>(
+    backends_by_id: &HashMap<BackendId, BackendMeta>,
+    states: impl IntoIterator<Item = &'a BackendState>,
+    model: &ModelId,
+    strategy: &RoutingStrategy,
+    round: usize,
+    affinity_hint: Option<&BackendId>,
+    provider: Option<BackendSpec>,
+) -> Result<Selection, RoutingError> {
+    let states: Vec<&BackendState> = states.into_iter().collect();
+    if let Some(spec) = provider {
+        let preferred = states.iter().copied().filter(|s| {
+            backends_by_id
+                .get(&s.id)
+                .is_some_and(|meta| meta.spec == spec)
+        });
+        if let Ok(selection) =
+            mb_core::core::select_backend_detailed(preferred, model, strategy, round, affinity_hint)
+        {
+            if !selection.saturated {
+                return Ok(selection);
+            }
+        }
+    }
+    mb_core::core::select_backend_detailed(states, model, strategy, round, affinity_hint)
+}
+
+/// Runs the router and logs why it rejected the request or, when every
+/// healthy backend is saturated, that it fell back to an overloaded one.
+pub(crate) fn select_backend_logged<'a>(
+    state: &AppState,
+    states: impl IntoIterator<Item = &'a BackendState>,
+    model: &ModelId,
+    round: usize,
+    affinity_hint: Option<&BackendId>,
+    provider: Option<BackendSpec>,
+) -> Result<Selection, GatewayError> {
+    let strategy = state.routing_policy.strategy_for(model);
+    match select_preferring_provider(
+        &state.backends_by_id,
+        states,
+        model,
+        strategy,
+        round,
+        affinity_hint,
+        provider,
+    ) {
+        Ok(selection) => {
+            if selection.saturated {
+                tracing::warn!(
+                    model = %model,
+                    backend = %selection.backend,
+                    reason = "all_saturated",
+                    "all healthy backends at capacity, routing on overload"
+                );
+            }
+            Ok(selection)
+        }
+        Err(e) => {
+            tracing::warn!(model = %model, reason = e.reason(), error = %e, "routing rejected request");
+            Err(GatewayError::Routing(e))
+        }
+    }
+}
+
+/// Selects a backend for `model`, skipping any that has reached its
+/// `max_rpm`. With [`LimitMode::Charge`] the request is counted against the
+/// chosen backend's limit, the round-robin position advances and the final
+/// choice lands in [`RoutingMetrics`]; [`LimitMode::Peek`] only reports the
+/// choice. `cache_routing` says whether affinity routing applied.
+pub(crate) async fn select_within_backend_rpm(
+    state: &AppState,
+    model: &ModelId,
+    cache_routing: bool,
+    affinity_hint: Option<&BackendId>,
+    provider: Option<BackendSpec>,
+    mode: LimitMode,
+) -> Result<Selection, GatewayError> {
+    let backend_states = state.backend_states.read().await;
+    let round = match mode {
+        LimitMode::Charge => state.round_counters.next(model),
+        LimitMode::Peek => state.round_counters.peek(model),
+    };
+    let mut throttled: Vec<BackendId> = Vec::new();
+    loop {
+        let candidates = backend_states
+            .values()
+            .filter(|s| !throttled.contains(&s.id));
+        let hint = affinity_hint.filter(|id| !throttled.contains(id));
+        let selection = match select_backend_logged(state, candidates, model, round, hint, provider)
+        {
+            Ok(selection) => selection,
+            Err(e) if throttled.is_empty() => return Err(e),
+            Err(_) => {
+                let err = RoutingError::BackendsThrottled {
+                    model: model.clone(),
+                };
+                tracing::warn!(model = %model, reason = err.reason(), error = %err, "routing rejected request");
+                return Err(GatewayError::Routing(err));
+            }
+        };
+        let selected = &selection.backend;
+        let within_rpm = match state.backend_rate_limit_rpm.get(selected) {
+            None => true,
+            Some(&rpm) => {
+                let now_ms = now_ms();
+                state
+                    .backend_rate_limiters
+                    .with(selected, |limiters| match mode {
+                        LimitMode::Charge => limiters
+                            .entry(selected.clone())
+                            .or_insert_with(|| RateLimiter::new(60_000, rpm))
+                            .check(now_ms),
+                        LimitMode::Peek => limiters
+                            .get(selected)
+                            .map_or(Ok(()), |limiter| limiter.peek(now_ms)),
+                    })
+                    .is_ok()
+            }
+        };
+        if within_rpm {
+            if mode == LimitMode::Charge {
+                state.routing_metrics.record(
+                    *state.routing_policy.strategy_for(model),
+                    cache_routing.then(|| affinity_hint == Some(selected)),
+                    selection.saturated,
+                );
+            }
+            return Ok(selection);
+        }
+        tracing::debug!(model = %model, backend = %selected, "backend at max_rpm, re-selecting");
+        throttled.push(selection.backend);
+    }
+}
+
+/// Combined in-flight requests and capacity of the healthy backends
+/// serving `model`, for `X-Backend-Load`.
+pub(crate) async fn backend_load(state: &AppState, model: &ModelId) -> BackendLoad {
+    BackendLoad::for_model(state.backend_states.read().await.values(), model, |id| {
+        state.in_flight.get(id)
+    })
+}
+
+/// Applies `routing.provider_prefix`: `ollama/llama3-70b` is routed as
+/// `llama3-70b` with Ollama backends preferred. A prefix naming no backend
+/// spec is left in place, since model ids such as `meta-llama/Llama-3-8B`
+/// carry slashes of their own.
+pub(crate) fn apply_provider_prefix(enabled: bool, canonical_req: &mut CanonicalRequest) {
+    if !enabled {
+        return;
+    }
+    let Some((provider, model)) = canonical_req.model.as_str().split_once('/') else {
+        return;
+    };
+    let spec = match provider.to_ascii_lowercase().as_str() {
+        "openai" => BackendSpec::OpenAiChat,
+        "ollama" => BackendSpec::Ollama,
+        _ => return,
+    };
+    if model.is_empty() {
+        return;
+    }
+    canonical_req.model = ModelId::new(model);
+    canonical_req.metadata.provider_hint = Some(spec);
+}
+
+/// Applies `routing.case_insensitive_models`: a model named in other casing
+/// than a backend lists it is routed under the configured name.
+pub(crate) fn apply_model_casing(
+    casing: &HashMap<String, ModelId>,
+    canonical_req: &mut CanonicalRequest,
+) {
+    if let Some(model) = casing.get(&canonical_req.model.as_str().to_ascii_lowercase()) {
+        canonical_req.model = model.clone();
+    }
+}
+
+/// Affinity hint for `(model, prefix)`. With
+/// `routing.affinity_skip_on_contention` a shard held by another request
+/// yields no hint, so routing falls back to the strategy alone.
+pub(crate) fn affinity_lookup(
+    state: &AppState,
+    model: &ModelId,
+    prefix: PrefixHash,
+) -> Option<BackendId> {
+    if state.cache_config.skip_contended {
+        state.affinity_map.try_get(model, prefix)
+    } else {
+        state.affinity_map.get(model, prefix)
+    }
+}
+
+/// Records `backend` as the affinity target of `(model, prefix)`, skipping
+/// the write on a contended shard like [`affinity_lookup`].
+pub(crate) fn affinity_record(
+    state: &AppState,
+    model: &ModelId,
+    prefix: PrefixHash,
+    backend: &BackendId,
+) {
+    if state.cache_config.skip_contended {
+        state.affinity_map.try_record(model, prefix, backend);
+    } else {
+        state.affinity_map.record(model, prefix, backend);
+    }
+}
+
+/// Header that opts a single request out of cache-aware routing.
+pub const DISABLE_CACHE_ROUTING_HEADER: &str = "x-disable-cache-routing";
 
-pub(crate) fn extract_api_key(headers: &HeaderMap) -> Result<ApiKey, GatewayError> {
-    let auth_header = headers
-        .get("authorization")
+/// Whether prefix-affinity routing applies: `routing.cache_aware` is on and
+/// the request did not send `X-Disable-Cache-Routing: true` (or `1`).
+pub(crate) fn cache_routing_enabled(cache_aware: bool, headers: &HeaderMap) -> bool {
+    let disabled = headers
+        .get(DISABLE_CACHE_ROUTING_HEADER)
         .and_then(|v| v.to_str().ok())
-        .ok_or(GatewayError::Auth(AuthError::InvalidApiKey))?;
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    cache_aware && !disabled
+}
 
-    let token = auth_header
-        .strip_prefix("Bearer ")
-        .ok_or(GatewayError::Auth(AuthError::InvalidApiKey))?;
+/// Request header asking for an indented JSON response, for debugging.
+pub const PRETTY_HEADER: &str = "x-pretty";
 
-    Ok(ApiKey::new(token))
+/// Whether the request sent `X-Pretty: true` (or `1`).
+pub(crate) fn wants_pretty_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(PRETTY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// OpenRouter-style attribution headers: the client app's name and URL.
+pub(crate) const TITLE_HEADER: &str = "x-title";
+pub(crate) const REFERER_HEADER: &str = "http-referer";
+
+/// Longest app title kept, matching the feedback store's limit.
+const MAX_APP_TITLE_LEN: usize = 256;
+/// Longest app URL kept, matching the feedback store's limit.
+const MAX_APP_REFERER_LEN: usize = 2_048;
+
+/// Reads `X-Title` and `HTTP-Referer`, skipping blank or non-UTF-8 values.
+/// An over-long title is truncated; an over-long URL is dropped, since a
+/// cut-off URL would point somewhere else.
+pub(crate) fn attribution_from_headers(headers: &HeaderMap) -> (Option<String>, Option<String>) {
+    let read = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    // `to_str` only accepts visible ASCII, so any byte offset is a char boundary.
+    let title =
+        read(TITLE_HEADER).map(|title| title[..title.len().min(MAX_APP_TITLE_LEN)].to_owned());
+    let referer = read(REFERER_HEADER)
+        .filter(|referer| referer.len() <= MAX_APP_REFERER_LEN)
+        .map(str::to_owned);
+    (title, referer)
+}
+
+/// Applies `server.attribution_headers`: copies the client's attribution
+/// headers into the request metadata unless capture is off.
+pub(crate) fn capture_attribution(
+    mode: AttributionHeaders,
+    headers: &HeaderMap,
+    metadata: &mut RequestMetadata,
+) {
+    if mode == AttributionHeaders::Off {
+        return;
+    }
+    (metadata.app_title, metadata.app_referer) = attribution_from_headers(headers);
+}
+
+/// Attribution headers to send to the backend; empty unless
+/// `server.attribution_headers` is `forward`.
+pub(crate) fn forwarded_attribution(
+    mode: AttributionHeaders,
+    metadata: &RequestMetadata,
+) -> Vec<(&'static str, String)> {
+    if mode != AttributionHeaders::Forward {
+        return Vec::new();
+    }
+    [
+        (TITLE_HEADER, &metadata.app_title),
+        (REFERER_HEADER, &metadata.app_referer),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value.clone()?)))
+    .collect()
+}
+
+/// Applies `routing.model_capabilities`: a request using a feature its
+/// model lacks is rejected in strict mode and only logged in lenient mode.
+pub(crate) fn check_model_capabilities(
+    state: &AppState,
+    req: &CanonicalRequest,
+) -> Result<(), GatewayError> {
+    let Some(missing) = state
+        .model_capabilities
+        .get(&req.model)
+        .and_then(|caps| caps.first_missing(req))
+    else {
+        return Ok(());
+    };
+    match state.capability_check {
+        CapabilityCheck::Strict => Err(GatewayError::Adapter(AdapterError::UnsupportedFeature(
+            format!("model {} does not support {missing}", req.model),
+        ))),
+        CapabilityCheck::Lenient => {
+            tracing::warn!(
+                model = %req.model,
+                capability = %missing,
+                "request uses a capability the model lacks; forwarding anyway"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Applies `routing.require_user_message`: a conversation made only of
+/// assistant and tool turns gives the model nothing to answer.
+pub(crate) fn check_user_message(
+    required: bool,
+    messages: &[mb_core::core::Message],
+) -> Result<(), GatewayError> {
+    let has_prompt = messages
+        .iter()
+        .any(|m| matches!(m.role, Role::User | Role::System));
+    if required && !has_prompt {
+        return Err(GatewayError::Adapter(AdapterError::ParseRequest(
+            "messages must include a user or system message".to_owned(),
+        )));
+    }
+    Ok(())
+}
+
+/// Applies `routing.verify_response_model` to a parsed backend response.
+pub(crate) fn verify_response_model(
+    mode: ResponseModelCheck,
+    backend: &BackendId,
+    requested: &ModelId,
+    returned: &ModelId,
+) -> Result<(), GatewayError> {
+    if mode == ResponseModelCheck::Off || requested == returned {
+        return Ok(());
+    }
+    let mismatch = BackendError::ModelMismatch {
+        backend: backend.clone(),
+        requested: requested.clone(),
+        returned: returned.clone(),
+    };
+    if mode == ResponseModelCheck::Strict {
+        return Err(GatewayError::Backend(mismatch));
+    }
+    tracing::warn!(error = %mismatch, "backend response model mismatch");
+    Ok(())
+}
+
+/// Whether a limit check counts the request against the limit, or only
+/// tells whether it would pass, as a dry run does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LimitMode {
+    Charge,
+    Peek,
+}
+
+/// Enforces the client's RPM cap for `model`, when one is configured.
+pub(crate) fn check_model_rate_limit(
+    state: &AppState,
+    client: &ClientId,
+    model: &ModelId,
+    mode: LimitMode,
+) -> Result<(), GatewayError> {
+    let key = (client.clone(), model.clone());
+    let Some(&rpm) = state.model_rate_limit_rpm.get(&key) else {
+        return Ok(());
+    };
+    let now_ms = now_ms();
+    state
+        .model_rate_limiters
+        .with(&key, |limiters| match mode {
+            LimitMode::Charge => limiters
+                .entry(key.clone())
+                .or_insert_with(|| RateLimiter::new(60_000, rpm))
+                .check(now_ms),
+            // A client with no limiter yet has its whole budget left.
+            LimitMode::Peek => limiters.get(&key).map_or(Ok(()), |l| l.peek(now_ms)),
+        })
+        .map_err(GatewayError::RateLimited)
+}
+
+/// Enforces the client's `rate_limit_tpm` budget, charging the estimated
+/// input tokens up front. Returns the `X-RateLimit-*-Tokens` headers, empty
+/// when the client has no TPM limit.
+pub(crate) fn check_token_rate_limit(
+    state: &AppState,
+    client_info: &ClientInfo,
+    estimated_input_tokens: u64,
+    mode: LimitMode,
+) -> Result<HeaderMap, GatewayError> {
+    let Some(tpm) = client_info.rate_limit.tokens_per_minute else {
+        return Ok(HeaderMap::new());
+    };
+    let now_ms = now_ms();
+    state
+        .token_rate_limiters
+        .with(&client_info.id, |limiters| match mode {
+            LimitMode::Charge => {
+                let limiter = limiters
+                    .entry(client_info.id.clone())
+                    .or_insert_with(|| TokenRateLimiter::new(60_000, tpm));
+                limiter.check(now_ms, estimated_input_tokens)?;
+                Ok(token_rate_limit_headers(limiter, now_ms))
+            }
+            LimitMode::Peek => {
+                let fresh;
+                let limiter = match limiters.get(&client_info.id) {
+                    Some(limiter) => limiter,
+                    None => {
+                        fresh = TokenRateLimiter::new(60_000, tpm);
+                        &fresh
+                    }
+                };
+                limiter.peek(now_ms, estimated_input_tokens)?;
+                Ok(token_rate_limit_headers(limiter, now_ms))
+            }
+        })
+        .map_err(GatewayError::RateLimited)
+}
+
+fn token_rate_limit_headers(limiter: &TokenRateLimiter, now_ms: u64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-ratelimit-limit-tokens",
+        HeaderValue::from(limiter.limit()),
+    );
+    headers.insert(
+        "x-ratelimit-remaining-tokens",
+        HeaderValue::from(limiter.remaining(now_ms)),
+    );
+    headers
+}
+
+/// Charges a finished response's usage against the client's TPM budget
+/// and monthly quota.
+pub(crate) async fn record_usage(state: &AppState, client_info: &ClientInfo, usage: &TokenUsage) {
+    record_completion_tokens(state, client_info, usage.completion_tokens);
+    if client_info.quota.monthly_token_limit.is_some() {
+        let mut tracker = state.quota_tracker.write().await;
+        let period = current_year_month();
+        tracker.record(&client_info.id, usage.total_tokens, period);
+    }
+}
+
+/// Charges completion tokens against the client's TPM budget once the
+/// backend has reported them; they count toward the next request's check.
+fn record_completion_tokens(state: &AppState, client_info: &ClientInfo, completion_tokens: u64) {
+    if client_info.rate_limit.tokens_per_minute.is_none() {
+        return;
+    }
+    state.token_rate_limiters.with(&client_info.id, |limiters| {
+        if let Some(limiter) = limiters.get_mut(&client_info.id) {
+            limiter.record(now_ms(), completion_tokens);
+        }
+    });
+}
+
+/// Sets `X-Backend-Load: active/max` for the model's healthy backends.
+pub(crate) fn insert_backend_load(headers: &mut HeaderMap, load: BackendLoad) {
+    if let Ok(value) = HeaderValue::from_str(&load.to_string()) {
+        headers.insert("x-backend-load", value);
+    }
+}
+
+/// Builds `X-RateLimit-*` headers from the client's limiter after a
+/// successful check. `X-RateLimit-Reset` is a Unix timestamp in seconds.
+pub(crate) fn rate_limit_headers(limiter: &RateLimiter, now_ms: u64) -> HeaderMap {
+    let reset_at_secs = now_ms
+        .saturating_add(limiter.reset_after_ms(now_ms))
+        .div_ceil(1000);
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(limiter.limit()));
+    headers.insert(
+        "x-ratelimit-remaining",
+        HeaderValue::from(limiter.remaining(now_ms)),
+    );
+    headers.insert("x-ratelimit-reset", HeaderValue::from(reset_at_secs));
+    headers
 }
 
 pub(crate) fn now_ms() -> u64 {
@@ -283,12 +1669,87 @@ pub(crate) fn current_year_month() -> YearMonth {
     YearMonth::new(now.year() as u16, now.month() as u8)
 }
 
+// ---------------------------------------------------------------------------
+// /cache/stats endpoint handler
+// ---------------------------------------------------------------------------
+
+/// Feeds `messages` to the prefix tracker. The tracker is a diagnostic, so
+/// a request that finds it busy is left out of the sample rather than
+/// waiting on the lock.
+pub(crate) fn observe_prefix(state: &AppState, model: &ModelId, messages: &[Message]) {
+    if let Ok(mut tracker) = state.prefix_tracker.try_lock() {
+        tracker.observe(model, messages);
+    }
+}
+
+/// `GET /cache/stats` — prefix statistics per model. They are derived from
+/// client traffic, so only the admin key may read them.
+pub async fn cache_stats_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(e) = crate::admin::authorize_admin(&state, &headers) {
+        return gateway_error_to_response(e);
+    }
+    let tracker = state
+        .prefix_tracker
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut models: Vec<_> = tracker.models().collect();
+    models.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let per_model: serde_json::Map<String, serde_json::Value> = models
+        .into_iter()
+        .map(|model| {
+            (
+                model.to_string(),
+                serde_json::json!({
+                    "suggested_prefix_depth": tracker.suggested_depth(model),
+                    "samples": tracker.sample_count(model),
+                }),
+            )
+        })
+        .collect();
+
+    let body = serde_json::json!({
+        "enabled": state.cache_config.enabled,
+        "prefix_depth": state.cache_config.prefix_depth,
+        "affinity_skips": state.affinity_map.skipped(),
+        "models": per_model,
+    });
+
+    (StatusCode::OK, axum::Json(body)).into_response()
+}
+
 // ---------------------------------------------------------------------------
 // Error → Response conversion (OpenAI-compatible error format)
 // ---------------------------------------------------------------------------
 
 pub fn gateway_error_to_response(err: GatewayError) -> Response {
-    let (status, error_type, message) = match &err {
+    render_gateway_error(err, ErrorVerbosity::Full, false)
+}
+
+/// Like [`gateway_error_to_response`], but with `ErrorVerbosity::Terse` any
+/// 5xx message (backend bodies, connection URLs, internal errors) is replaced
+/// by the status reason and a correlation id; the original detail is logged
+/// under the same id, or only its length when `redact_content` is set.
+pub fn render_gateway_error(
+    err: GatewayError,
+    verbosity: ErrorVerbosity,
+    redact_content: bool,
+) -> Response {
+    let (status, body) = gateway_error_body(&err, verbosity, redact_content);
+    (status, axum::Json(body)).into_response()
+}
+
+/// The status and OpenAI-style `{"error": {...}}` body for `err`, honouring
+/// `verbosity` and `redact_content` as [`render_gateway_error`] does.
+pub fn gateway_error_body(
+    err: &GatewayError,
+    verbosity: ErrorVerbosity,
+    redact_content: bool,
+) -> (StatusCode, serde_json::Value) {
+    let (status, error_type, message) = match err {
         GatewayError::Auth(AuthError::InvalidApiKey) => (
             StatusCode::UNAUTHORIZED,
             "authentication_error",
@@ -308,31 +1769,333 @@ pub fn gateway_error_to_response(err: GatewayError) -> Response {
         GatewayError::Routing(RoutingError::ModelNotFound { .. }) => {
             (StatusCode::NOT_FOUND, "not_found_error", err.to_string())
         }
-        GatewayError::Routing(RoutingError::NoHealthyBackend { .. }) => (
+        GatewayError::Routing(
+            RoutingError::NoHealthyBackend { .. }
+            | RoutingError::QueueTimeout { .. }
+            | RoutingError::BackendsThrottled { .. },
+        ) => (
             StatusCode::SERVICE_UNAVAILABLE,
             "service_unavailable",
             err.to_string(),
         ),
-        GatewayError::Adapter(AdapterError::ParseRequest(_)) => (
+        GatewayError::Adapter(
+            AdapterError::ParseRequest(_)
+            | AdapterError::InvalidField { .. }
+            | AdapterError::UnsupportedFeature(_),
+        ) => (
             StatusCode::BAD_REQUEST,
             "invalid_request_error",
             err.to_string(),
         ),
         GatewayError::Backend(_) => (StatusCode::BAD_GATEWAY, "backend_error", err.to_string()),
-        _ => (
+        GatewayError::Adapter(AdapterError::FormatResponse(_)) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             "server_error",
             err.to_string(),
         ),
+        // Never passed through, whatever the verbosity.
+        GatewayError::Internal(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "server_error",
+            "internal server error".to_owned(),
+        ),
     };
 
-    let body = serde_json::json!({
-        "error": {
-            "message": message,
-            "type": error_type,
-            "code": status.as_u16(),
+    let body = if verbosity == ErrorVerbosity::Terse && status.is_server_error() {
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+        // Backend errors can carry the backend's body, so it is redacted too.
+        let detail = err.to_string();
+        let detail = if redact_content {
+            body_preview(detail.as_bytes(), true)
+        } else {
+            detail
+        };
+        tracing::error!(
+            correlation_id = %correlation_id,
+            status = status.as_u16(),
+            detail = %detail,
+            "request failed"
+        );
+        serde_json::json!({
+            "error": {
+                "message": format!(
+                    "{} (correlation id: {correlation_id})",
+                    status.canonical_reason().unwrap_or("server error")
+                ),
+                "type": error_type,
+                "code": status.as_u16(),
+                "correlation_id": correlation_id,
+            }
+        })
+    } else {
+        if let GatewayError::Internal(detail) = err {
+            tracing::error!(status = status.as_u16(), detail = %detail, "internal error");
         }
-    });
+        let mut body = serde_json::json!({
+            "error": {
+                "message": message,
+                "type": error_type,
+                "code": status.as_u16(),
+            }
+        });
+        if let GatewayError::Adapter(AdapterError::InvalidField { param, .. }) = err {
+            body["error"]["param"] = param.as_str().into();
+        }
+        body
+    };
 
-    (status, axum::Json(body)).into_response()
+    (status, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(mode: ResponseModelCheck, returned: &str) -> Result<(), GatewayError> {
+        verify_response_model(
+            mode,
+            &BackendId::new("gpu-1"),
+            &ModelId::new("llama3-70b"),
+            &ModelId::new(returned),
+        )
+    }
+
+    fn message(role: Role) -> mb_core::core::Message {
+        mb_core::core::Message {
+            role,
+            content: mb_core::core::MessageContent::Text("hi".to_owned()),
+            name: None,
+            tool_call_id: None,
+        }
+    }
+
+    #[test]
+    fn test_check_user_message() {
+        let assistant_only = [message(Role::Assistant), message(Role::Assistant)];
+        let err = check_user_message(true, &assistant_only).unwrap_err();
+        let resp = gateway_error_to_response(err);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Not enforced unless configured.
+        assert!(check_user_message(false, &assistant_only).is_ok());
+
+        let with_system = [message(Role::System), message(Role::Assistant)];
+        assert!(check_user_message(true, &with_system).is_ok());
+        assert!(check_user_message(true, &[message(Role::User)]).is_ok());
+    }
+
+    #[test]
+    fn test_attribution_capture_and_forward() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-title", HeaderValue::from_static("My App"));
+        headers.insert(
+            "http-referer",
+            HeaderValue::from_static("https://app.example"),
+        );
+        let mut metadata = RequestMetadata {
+            request_id: mb_core::core::RequestId::new("req-1"),
+            client_id: ClientId::new("client-a"),
+            estimated_input_tokens: 0,
+            prefix_hash: None,
+            app_title: None,
+            app_referer: None,
+            client_metadata: None,
+            store: false,
+            provider_hint: None,
+        };
+
+        capture_attribution(AttributionHeaders::Off, &headers, &mut metadata);
+        assert_eq!(metadata.app_title, None);
+
+        capture_attribution(AttributionHeaders::Capture, &headers, &mut metadata);
+        assert_eq!(metadata.app_title.as_deref(), Some("My App"));
+        assert_eq!(metadata.app_referer.as_deref(), Some("https://app.example"));
+        assert!(forwarded_attribution(AttributionHeaders::Capture, &metadata).is_empty());
+
+        assert_eq!(
+            forwarded_attribution(AttributionHeaders::Forward, &metadata),
+            vec![
+                ("x-title", "My App".to_owned()),
+                ("http-referer", "https://app.example".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attribution_bounds_over_long_values() {
+        let mut headers = HeaderMap::new();
+        let title = "t".repeat(MAX_APP_TITLE_LEN + 10);
+        let referer = format!("https://app.example/{}", "p".repeat(MAX_APP_REFERER_LEN));
+        headers.insert("x-title", HeaderValue::from_str(&title).unwrap());
+        headers.insert("http-referer", HeaderValue::from_str(&referer).unwrap());
+
+        let (title, referer) = attribution_from_headers(&headers);
+        assert_eq!(title.map(|t| t.len()), Some(MAX_APP_TITLE_LEN));
+        assert_eq!(referer, None);
+    }
+
+    #[test]
+    fn test_verify_response_model_matching() {
+        assert!(check(ResponseModelCheck::Strict, "llama3-70b").is_ok());
+        assert!(check(ResponseModelCheck::Warn, "llama3-70b").is_ok());
+    }
+
+    #[test]
+    fn test_verify_response_model_mismatch_strict() {
+        let err = check(ResponseModelCheck::Strict, "llama3-8b").unwrap_err();
+        assert!(matches!(
+            err,
+            GatewayError::Backend(BackendError::ModelMismatch { .. })
+        ));
+        let resp = gateway_error_to_response(err);
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_verify_response_model_mismatch_lenient() {
+        assert!(check(ResponseModelCheck::Warn, "llama3-8b").is_ok());
+        assert!(check(ResponseModelCheck::Off, "llama3-8b").is_ok());
+    }
+
+    fn malformed_reason(body: &[u8]) -> String {
+        let adapter = crate::outbound::openai_chat::OpenAiChatOutboundAdapter;
+        match parse_backend_response(&adapter, &BackendId::new("gpu-1"), body, false) {
+            Err(GatewayError::Backend(BackendError::MalformedResponse { reason, .. })) => reason,
+            other => panic!("expected a malformed response error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_backend_response_names_the_cause() {
+        assert_eq!(
+            malformed_reason(b"{\"id\": \"caf\xe9\"}"),
+            "body is not valid UTF-8"
+        );
+        assert_eq!(
+            malformed_reason(br#"{"id": "chatcmpl-1", "choi"#),
+            "body is not valid JSON"
+        );
+        // Well-formed JSON of the wrong shape keeps the adapter's detail.
+        assert!(malformed_reason(br#"{"id": "chatcmpl-1"}"#).contains("missing field"));
+    }
+
+    async fn error_body(resp: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .expect("error body");
+        serde_json::from_slice(&bytes).expect("error body is JSON")
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_maps_to_500() {
+        let err = GatewayError::Internal("no outbound adapter for backend spec Ollama".to_owned());
+        let resp = gateway_error_to_response(err);
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = error_body(resp).await;
+        assert_eq!(body["error"]["type"], "server_error");
+        assert_eq!(body["error"]["code"], 500);
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_detail_not_leaked() {
+        let internal = || GatewayError::Internal("lock poisoned in quota tracker".to_owned());
+
+        let body = error_body(render_gateway_error(
+            internal(),
+            ErrorVerbosity::Full,
+            false,
+        ))
+        .await;
+        assert_eq!(body["error"]["message"], "internal server error");
+
+        let body = error_body(render_gateway_error(
+            internal(),
+            ErrorVerbosity::Terse,
+            false,
+        ))
+        .await;
+        let message = body["error"]["message"].as_str().expect("message");
+        assert!(message.starts_with("Internal Server Error (correlation id:"));
+        assert!(!message.contains("poisoned"));
+    }
+
+    #[test]
+    fn test_retry_on_429_delay() {
+        let max = Duration::from_secs(2);
+        assert_eq!(
+            retry_on_429_delay(0, None, max),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            retry_on_429_delay(2, None, max),
+            Some(Duration::from_millis(400))
+        );
+        assert_eq!(retry_on_429_delay(10, None, max), Some(max));
+        assert_eq!(
+            retry_on_429_delay(0, Some(Duration::from_secs(1)), max),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            retry_on_429_delay(0, Some(Duration::from_secs(3)), max),
+            None
+        );
+    }
+
+    #[test]
+    fn test_retry_after_delay_seconds_only() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("retry-after", HeaderValue::from_static("3"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_decode_body_charset() {
+        let with_charset = |charset: &str| {
+            let mut headers = HeaderMap::new();
+            let value = format!("application/json; charset={charset}");
+            headers.insert("content-type", HeaderValue::from_str(&value).unwrap());
+            headers
+        };
+        let utf16 = |text: &str, le: bool| -> Bytes {
+            text.encode_utf16()
+                .flat_map(|u| if le { u.to_le_bytes() } else { u.to_be_bytes() })
+                .collect::<Vec<u8>>()
+                .into()
+        };
+        let decode = |headers: &HeaderMap, body: Bytes| decode_body_charset(headers, body);
+
+        let plain = Bytes::from_static(b"{\"a\":\"\xc3\xa9\"}");
+        assert_eq!(decode(&HeaderMap::new(), plain.clone()).unwrap(), plain);
+        assert_eq!(
+            decode(&with_charset("UTF-8"), plain.clone()).unwrap(),
+            plain
+        );
+
+        let text = "{\"a\":\"é\"}";
+        for (charset, body) in [
+            ("utf-16le", utf16(text, true)),
+            ("utf-16be", utf16(text, false)),
+            ("utf-16", utf16(text, false)),
+            ("utf-16", utf16(&format!("\u{FEFF}{text}"), true)),
+            ("\"utf-16\"", utf16(&format!("\u{FEFF}{text}"), false)),
+        ] {
+            let decoded = decode(&with_charset(charset), body).unwrap();
+            assert_eq!(decoded, text.as_bytes(), "{charset}");
+        }
+
+        assert!(matches!(
+            decode(&with_charset("utf-16"), Bytes::from_static(b"{\0\"")),
+            Err(GatewayError::Adapter(AdapterError::ParseRequest(_)))
+        ));
+        assert!(matches!(
+            decode(&with_charset("latin1"), plain),
+            Err(GatewayError::Adapter(AdapterError::UnsupportedFeature(_)))
+        ));
+    }
 }
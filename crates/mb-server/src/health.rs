@@ -1,16 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+use futures_util::StreamExt;
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
 use mb_core::core::{
-    BackendId, BackendInfo, BackendSpec, BackendState, HealthError, HealthProbe, LatencyMs,
+    ApiKey, BackendId, BackendInfo, BackendSpec, BackendState, BackendStatus, HealthError,
+    HealthProbe, LatencyMs, LatencyReservoir, RoutingStrategy, ShardedAffinityMap,
 };
 
+use crate::sharded::ShardedMap;
+
 // ---------------------------------------------------------------------------
 // HttpHealthProbe — live HTTP probe for backend health
 // ---------------------------------------------------------------------------
@@ -18,15 +23,77 @@ use mb_core::core::{
 pub struct HttpHealthProbe {
     client: reqwest::Client,
     timeout: Duration,
+    verify_models: bool,
+    /// Sent as a bearer token, like on the inference path.
+    api_keys: HashMap<BackendId, ApiKey>,
 }
 
 impl HttpHealthProbe {
     pub fn new(timeout: Duration) -> Result<Self, reqwest::Error> {
         let client = reqwest::Client::builder().timeout(timeout).build()?;
-        Ok(Self { client, timeout })
+        Ok(Self {
+            client,
+            timeout,
+            verify_models: false,
+            api_keys: HashMap::new(),
+        })
+    }
+
+    /// Also fails the probe when the backend's model listing omits any of
+    /// its configured models.
+    pub fn with_model_verification(mut self, verify_models: bool) -> Self {
+        self.verify_models = verify_models;
+        self
+    }
+
+    /// Authenticates probes of the listed backends with their API key.
+    pub fn with_api_keys(mut self, api_keys: HashMap<BackendId, ApiKey>) -> Self {
+        self.api_keys = api_keys;
+        self
     }
 }
 
+/// Configured models, by wire name, that `listing` does not contain. An
+/// unparseable listing counts as listing nothing.
+fn missing_models(backend: &BackendInfo, listing: &[u8]) -> Vec<String> {
+    let listed = listed_model_names(backend.spec, listing);
+    backend
+        .models
+        .iter()
+        .map(|model| backend.wire_model(model))
+        .filter(|wanted| {
+            !listed.iter().any(|name| {
+                name == wanted
+                    // Ollama lists untagged pulls as `name:latest`.
+                    || (backend.spec == BackendSpec::Ollama
+                        && name.strip_suffix(":latest") == Some(*wanted))
+            })
+        })
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Model names from an OpenAI `/v1/models` (`data[].id`) or Ollama
+/// `/api/tags` (`models[].name`) response.
+fn listed_model_names(spec: BackendSpec, listing: &[u8]) -> Vec<String> {
+    let (array, key) = match spec {
+        BackendSpec::OpenAiChat => ("data", "id"),
+        BackendSpec::Ollama => ("models", "name"),
+    };
+    let Ok(body) = serde_json::from_slice::<serde_json::Value>(listing) else {
+        return Vec::new();
+    };
+    body[array]
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry[key].as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 impl HealthProbe for HttpHealthProbe {
     fn probe<'a>(
         &'a self,
@@ -39,22 +106,33 @@ impl HealthProbe for HttpHealthProbe {
             };
             let url = format!("{}{path}", backend.base_url);
 
+            let mut request = self.client.get(&url).timeout(self.timeout);
+            if let Some(key) = self.api_keys.get(&backend.id) {
+                request = request.header("Authorization", format!("Bearer {}", key.as_str()));
+            }
+
             let start = std::time::Instant::now();
-            let resp = self
-                .client
-                .get(&url)
-                .timeout(self.timeout)
+            let resp = request
                 .send()
                 .await
                 .map_err(|e| HealthError::ConnectionFailed(e.to_string()))?;
 
             let latency_ms = start.elapsed().as_millis() as u64;
 
-            if resp.status().is_success() {
-                Ok(LatencyMs::new(latency_ms))
-            } else {
-                Err(HealthError::UnexpectedStatus(resp.status().as_u16()))
+            if !resp.status().is_success() {
+                return Err(HealthError::UnexpectedStatus(resp.status().as_u16()));
             }
+            if self.verify_models {
+                let listing = resp
+                    .bytes()
+                    .await
+                    .map_err(|e| HealthError::ConnectionFailed(e.to_string()))?;
+                let missing = missing_models(backend, &listing);
+                if !missing.is_empty() {
+                    return Err(HealthError::MissingModels(missing));
+                }
+            }
+            Ok(LatencyMs::new(latency_ms))
         })
     }
 }
@@ -68,20 +146,40 @@ pub type SharedBackendStates = Arc<RwLock<HashMap<BackendId, BackendState>>>;
 
 pub struct HealthCheckManager {
     states: SharedBackendStates,
+    /// Cleared of a backend's entries when it turns unhealthy.
+    affinity_map: Option<Arc<ShardedAffinityMap>>,
+    max_concurrent_probes: usize,
 }
 
 impl HealthCheckManager {
     pub fn new(backends: &[BackendInfo]) -> Self {
         let mut map = HashMap::with_capacity(backends.len());
         for b in backends {
-            let state = BackendState::new(b.id.clone(), b.models.clone(), b.max_concurrent);
+            let state = BackendState::new(b.id.clone(), b.models.clone(), b.max_concurrent)
+                .with_cost_weight(b.cost_weight);
             map.insert(b.id.clone(), state);
         }
         Self {
             states: Arc::new(RwLock::new(map)),
+            affinity_map: None,
+            max_concurrent_probes: 1,
         }
     }
 
+    /// Evicts a backend's cache-affinity entries from `affinity_map` whenever
+    /// it transitions to unhealthy, so affine requests stop aiming at it.
+    pub fn with_affinity_map(mut self, affinity_map: Arc<ShardedAffinityMap>) -> Self {
+        self.affinity_map = Some(affinity_map);
+        self
+    }
+
+    /// Probes up to `limit` backends at once within each tick, so one slow
+    /// backend does not hold up the rest. Defaults to one at a time.
+    pub fn with_max_concurrent_probes(mut self, limit: usize) -> Self {
+        self.max_concurrent_probes = limit.max(1);
+        self
+    }
+
     pub fn shared_states(&self) -> SharedBackendStates {
         Arc::clone(&self.states)
     }
@@ -95,14 +193,26 @@ impl HealthCheckManager {
         probe: Arc<dyn HealthProbe>,
     ) -> JoinHandle<()> {
         let states = self.shared_states();
+        let affinity_map = self.affinity_map.clone();
+        let max_concurrent_probes = self.max_concurrent_probes;
         tokio::spawn(async move {
             let mut tick = tokio::time::interval(interval);
             loop {
                 tick.tick().await;
-                for backend in &backends {
-                    let result = probe.probe(backend).await;
+                let mut probes = futures_util::stream::iter(backends.clone())
+                    .map(|backend| {
+                        let probe = Arc::clone(&probe);
+                        async move {
+                            let result = probe.probe(&backend).await;
+                            (backend, result)
+                        }
+                    })
+                    .buffer_unordered(max_concurrent_probes);
+                while let Some((backend, result)) = probes.next().await {
                     let mut map = states.write().await;
+                    let mut became_unhealthy = false;
                     if let Some(state) = map.remove(&backend.id) {
+                        let was_unhealthy = state.status == BackendStatus::Unhealthy;
                         let updated = match result {
                             Ok(latency) => {
                                 if latency.value() >= degraded_latency_ms {
@@ -111,7 +221,10 @@ impl HealthCheckManager {
                                     state.with_healthy(latency)
                                 }
                             }
-                            Err(_) => {
+                            Err(err) => {
+                                if let HealthError::MissingModels(_) = &err {
+                                    tracing::warn!(backend = %backend.id, error = %err, "health probe failed");
+                                }
                                 let state = state.with_failure();
                                 if state.consecutive_failures >= unhealthy_threshold {
                                     state.with_unhealthy()
@@ -120,8 +233,18 @@ impl HealthCheckManager {
                                 }
                             }
                         };
+                        became_unhealthy =
+                            !was_unhealthy && updated.status == BackendStatus::Unhealthy;
                         map.insert(backend.id.clone(), updated);
                     }
+                    drop(map);
+
+                    if became_unhealthy {
+                        if let Some(affinity_map) = &affinity_map {
+                            affinity_map.evict_backend(&backend.id);
+                            tracing::info!(backend = %backend.id, "evicted cache affinity of unhealthy backend");
+                        }
+                    }
                 }
             }
         })
@@ -135,26 +258,242 @@ impl HealthCheckManager {
 }
 
 // ---------------------------------------------------------------------------
-// /health endpoint handler
+// probe_once — one-shot reachability check for `mb validate --check-backends`
+// ---------------------------------------------------------------------------
+
+/// Outcome of probing one backend.
+#[derive(Debug)]
+pub struct ProbeReport {
+    pub backend: BackendId,
+    pub base_url: String,
+    pub result: Result<LatencyMs, HealthError>,
+}
+
+impl ProbeReport {
+    pub fn is_reachable(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Probes every backend once, concurrently, returning reports in `backends`
+/// order.
+pub async fn probe_once(probe: &dyn HealthProbe, backends: &[BackendInfo]) -> Vec<ProbeReport> {
+    futures_util::future::join_all(backends.iter().map(|backend| async move {
+        ProbeReport {
+            backend: backend.id.clone(),
+            base_url: backend.base_url.clone(),
+            result: probe.probe(backend).await,
+        }
+    }))
+    .await
+}
+
+// ---------------------------------------------------------------------------
+// BackendLatencies — rolling request timings per backend
+// ---------------------------------------------------------------------------
+
+/// Samples kept per backend; older ones are overwritten.
+pub const LATENCY_RESERVOIR_SIZE: usize = 512;
+
+/// Recent non-streaming request latencies per backend, reported as
+/// percentiles by `/health` and `/metrics`. Unlike `last_latency`, which
+/// comes from health probes, these are real completion timings.
+#[derive(Default)]
+pub struct BackendLatencies {
+    reservoirs: ShardedMap<BackendId, LatencyReservoir>,
+}
+
+impl BackendLatencies {
+    pub fn record(&self, backend: &BackendId, latency: LatencyMs) {
+        self.reservoirs.with(backend, |reservoirs| {
+            reservoirs
+                .entry(backend.clone())
+                .or_insert_with(|| LatencyReservoir::new(LATENCY_RESERVOIR_SIZE))
+                .record(latency);
+        });
+    }
+
+    /// Copy of every backend's reservoir, so percentiles are computed
+    /// without holding a lock.
+    pub fn snapshot(&self) -> HashMap<BackendId, LatencyReservoir> {
+        self.reservoirs.snapshot()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// InFlightRequests — live request counts per backend
+// ---------------------------------------------------------------------------
+
+/// Requests currently dispatched to each backend, reported by
+/// `X-Backend-Load`. Kept apart from the health snapshot, so reporting load
+/// never changes routing decisions.
+#[derive(Default)]
+pub struct InFlightRequests {
+    counts: ShardedMap<BackendId, u32>,
+}
+
+impl InFlightRequests {
+    /// Counts one request against `backend` until the guard is dropped.
+    pub fn start(self: &Arc<Self>, backend: &BackendId) -> InFlightGuard {
+        self.counts.with(backend, |counts| {
+            let count = counts.entry(backend.clone()).or_default();
+            *count = count.saturating_add(1);
+        });
+        InFlightGuard {
+            requests: Arc::clone(self),
+            backend: backend.clone(),
+        }
+    }
+
+    pub fn get(&self, backend: &BackendId) -> u32 {
+        self.counts
+            .with(backend, |counts| counts.get(backend).copied())
+            .unwrap_or(0)
+    }
+}
+
+/// One request counted by [`InFlightRequests::start`].
+pub struct InFlightGuard {
+    requests: Arc<InFlightRequests>,
+    backend: BackendId,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.requests.counts.with(&self.backend, |counts| {
+            if let Some(count) = counts.get_mut(&self.backend) {
+                *count = count.saturating_sub(1);
+            }
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// RoutingMetrics — how backend selections were made
 // ---------------------------------------------------------------------------
 
-pub async fn health_handler(states: SharedBackendStates) -> axum::response::Response {
+/// Counters over routing decisions, exported by `/metrics`, showing whether
+/// prefix-affinity routing is paying off.
+#[derive(Default)]
+pub struct RoutingMetrics {
+    affinity_hits: AtomicU64,
+    affinity_misses: AtomicU64,
+    overload_fallbacks: AtomicU64,
+    least_loaded: AtomicU64,
+    round_robin: AtomicU64,
+    cheapest_first: AtomicU64,
+}
+
+impl RoutingMetrics {
+    /// Counts one selection made with `strategy`. `affinity` is `None` when
+    /// affinity routing was off for the request, otherwise whether the
+    /// chosen backend was the affine one; a prefix with no recorded backend
+    /// is a miss, as in a dry run.
+    pub fn record(&self, strategy: RoutingStrategy, affinity: Option<bool>, saturated: bool) {
+        let strategy = match strategy {
+            RoutingStrategy::LeastLoaded => &self.least_loaded,
+            RoutingStrategy::RoundRobin => &self.round_robin,
+            RoutingStrategy::CheapestFirst => &self.cheapest_first,
+        };
+        strategy.fetch_add(1, Ordering::Relaxed);
+        match affinity {
+            Some(true) => self.affinity_hits.fetch_add(1, Ordering::Relaxed),
+            Some(false) => self.affinity_misses.fetch_add(1, Ordering::Relaxed),
+            None => 0,
+        };
+        if saturated {
+            self.overload_fallbacks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn affinity_hits(&self) -> u64 {
+        self.affinity_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn affinity_misses(&self) -> u64 {
+        self.affinity_misses.load(Ordering::Relaxed)
+    }
+
+    /// Selections made while every healthy backend was at capacity.
+    pub fn overload_fallbacks(&self) -> u64 {
+        self.overload_fallbacks.load(Ordering::Relaxed)
+    }
+
+    pub fn selections(&self, strategy: RoutingStrategy) -> u64 {
+        match strategy {
+            RoutingStrategy::LeastLoaded => &self.least_loaded,
+            RoutingStrategy::RoundRobin => &self.round_robin,
+            RoutingStrategy::CheapestFirst => &self.cheapest_first,
+        }
+        .load(Ordering::Relaxed)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// /health and /metrics endpoint handlers
+// ---------------------------------------------------------------------------
+
+/// Aggregate over the healthy backends serving one model.
+#[derive(Default)]
+struct ModelAvailability {
+    healthy_backends: u32,
+    capacity: u32,
+    active_requests: u32,
+}
+
+pub async fn health_handler(
+    states: SharedBackendStates,
+    latencies: Arc<BackendLatencies>,
+) -> axum::response::Response {
     use axum::http::StatusCode;
     use axum::response::IntoResponse;
 
+    let latencies = latencies.snapshot();
     let map = states.read().await;
     let backends: Vec<serde_json::Value> = map
         .values()
         .map(|s| {
+            let percentiles = latencies.get(&s.id).and_then(LatencyReservoir::percentiles);
             serde_json::json!({
                 "id": s.id.as_str(),
                 "status": format!("{:?}", s.status),
                 "active_requests": s.active_requests,
                 "last_latency_ms": s.last_latency.map(|l| l.value()),
+                "latency_ms": percentiles.map(|p| serde_json::json!({
+                    "p50": p.p50.value(),
+                    "p95": p.p95.value(),
+                    "p99": p.p99.value(),
+                    "samples": p.samples,
+                })),
             })
         })
         .collect();
 
+    // Per-model availability: a model is up when any healthy backend serves it.
+    let mut models: BTreeMap<&str, ModelAvailability> = BTreeMap::new();
+    for s in map.values() {
+        for model in &s.models {
+            let entry = models.entry(model.as_str()).or_default();
+            if s.is_healthy() {
+                entry.healthy_backends += 1;
+                entry.capacity += s.max_concurrent;
+                entry.active_requests += s.active_requests;
+            }
+        }
+    }
+    let models: serde_json::Map<String, serde_json::Value> = models
+        .into_iter()
+        .map(|(model, a)| {
+            let value = serde_json::json!({
+                "available": a.healthy_backends > 0,
+                "healthy_backends": a.healthy_backends,
+                "capacity": a.capacity,
+                "active_requests": a.active_requests,
+            });
+            (model.to_owned(), value)
+        })
+        .collect();
+
     let any_healthy = map.values().any(|s| s.is_healthy());
     let status = if any_healthy {
         StatusCode::OK
@@ -165,11 +504,99 @@ pub async fn health_handler(states: SharedBackendStates) -> axum::response::Resp
     let body = serde_json::json!({
         "status": if any_healthy { "ok" } else { "unavailable" },
         "backends": backends,
+        "models": models,
     });
 
     (status, axum::Json(body)).into_response()
 }
 
+/// `GET /metrics` — backend request latency percentiles in the Prometheus
+/// text format, as a `summary` over each backend's recent samples, plus the
+/// count of affinity operations skipped on a contended shard.
+pub async fn metrics_handler(
+    states: SharedBackendStates,
+    latencies: Arc<BackendLatencies>,
+    affinity_map: Arc<ShardedAffinityMap>,
+    routing: Arc<RoutingMetrics>,
+) -> axum::response::Response {
+    use axum::http::{header, StatusCode};
+    use axum::response::IntoResponse;
+    use std::fmt::Write;
+
+    let latencies = latencies.snapshot();
+    let mut ids: Vec<BackendId> = states.read().await.keys().cloned().collect();
+    ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let mut body = String::from(
+        "# HELP mb_backend_latency_ms Latency of recent non-streaming backend requests.\n\
+         # TYPE mb_backend_latency_ms summary\n",
+    );
+    for id in &ids {
+        let Some(reservoir) = latencies.get(id) else {
+            continue;
+        };
+        let Some(p) = reservoir.percentiles() else {
+            continue;
+        };
+        let backend = id.as_str().replace('\\', "\\\\").replace('"', "\\\"");
+        for (quantile, value) in [("0.5", p.p50), ("0.95", p.p95), ("0.99", p.p99)] {
+            let _ = writeln!(
+                body,
+                "mb_backend_latency_ms{{backend=\"{backend}\",quantile=\"{quantile}\"}} {}",
+                value.value()
+            );
+        }
+        let _ = writeln!(
+            body,
+            "mb_backend_latency_ms_count{{backend=\"{backend}\"}} {}",
+            reservoir.recorded()
+        );
+    }
+    let _ = write!(
+        body,
+        "# HELP mb_affinity_skips_total Affinity lookups and records skipped on a contended shard.\n\
+         # TYPE mb_affinity_skips_total counter\n\
+         mb_affinity_skips_total {}\n",
+        affinity_map.skipped()
+    );
+    let _ = write!(
+        body,
+        "# HELP mb_affinity_hits_total Affinity-routed requests sent to their affine backend.\n\
+         # TYPE mb_affinity_hits_total counter\n\
+         mb_affinity_hits_total {}\n\
+         # HELP mb_affinity_misses_total Affinity-routed requests sent elsewhere.\n\
+         # TYPE mb_affinity_misses_total counter\n\
+         mb_affinity_misses_total {}\n\
+         # HELP mb_overload_fallbacks_total Requests routed while every healthy backend was full.\n\
+         # TYPE mb_overload_fallbacks_total counter\n\
+         mb_overload_fallbacks_total {}\n\
+         # HELP mb_routing_selections_total Backend selections by routing strategy.\n\
+         # TYPE mb_routing_selections_total counter\n",
+        routing.affinity_hits(),
+        routing.affinity_misses(),
+        routing.overload_fallbacks(),
+    );
+    for strategy in [
+        RoutingStrategy::LeastLoaded,
+        RoutingStrategy::RoundRobin,
+        RoutingStrategy::CheapestFirst,
+    ] {
+        let _ = writeln!(
+            body,
+            "mb_routing_selections_total{{strategy=\"{}\"}} {}",
+            strategy.as_str(),
+            routing.selections(strategy)
+        );
+    }
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        body,
+    )
+        .into_response()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,6 +609,9 @@ mod tests {
             models: vec![ModelId::new("gpt-4")],
             max_concurrent: 10,
             base_url: "http://localhost:8000".to_owned(),
+            model_map: std::collections::HashMap::new(),
+            tool_support: mb_core::core::ToolSupport::Native,
+            cost_weight: 0,
         }
     }
 
@@ -200,6 +630,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_manager_applies_cost_weights() {
+        let backends = vec![
+            make_backend("gpu-0"),
+            BackendInfo {
+                cost_weight: 10,
+                ..make_backend("paid-api")
+            },
+        ];
+        let manager = HealthCheckManager::new(&backends);
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let states = rt.block_on(manager.shared_states().read_owned());
+        assert_eq!(states[&BackendId::new("paid-api")].cost_weight, 10);
+        assert_eq!(states[&BackendId::new("gpu-0")].cost_weight, 0);
+    }
+
     #[test]
     fn test_health_endpoint_all_unknown() {
         let backends = vec![make_backend("gpu-0")];
@@ -210,7 +659,7 @@ mod tests {
             .enable_all()
             .build()
             .unwrap();
-        let response = rt.block_on(health_handler(shared));
+        let response = rt.block_on(health_handler(shared, Arc::default()));
         // All Unknown → not healthy → 503
         assert_eq!(
             response.status(),
@@ -239,8 +688,252 @@ mod tests {
                     );
                 }
             }
-            let response = health_handler(Arc::clone(&shared)).await;
+            let response = health_handler(Arc::clone(&shared), Arc::default()).await;
             assert_eq!(response.status(), axum::http::StatusCode::OK);
         });
     }
+
+    #[tokio::test]
+    async fn test_health_endpoint_reports_model_availability() {
+        let mut healthy = make_backend("gpu-0");
+        healthy.models = vec![ModelId::new("model-a")];
+        let mut unhealthy = make_backend("gpu-1");
+        unhealthy.models = vec![ModelId::new("model-b")];
+        let manager = HealthCheckManager::new(&[healthy, unhealthy]);
+        let shared = manager.shared_states();
+
+        {
+            let mut map = shared.write().await;
+            for (id, up) in [("gpu-0", true), ("gpu-1", false)] {
+                let state = map.remove(&BackendId::new(id)).unwrap();
+                let state = if up {
+                    state.with_healthy(LatencyMs::new(50))
+                } else {
+                    state.with_unhealthy()
+                };
+                map.insert(BackendId::new(id), state);
+            }
+        }
+
+        let response = health_handler(Arc::clone(&shared), Arc::default()).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        let a = &body["models"]["model-a"];
+        assert_eq!(a["available"], true);
+        assert_eq!(a["healthy_backends"], 1);
+        assert_eq!(a["capacity"], 10);
+        let b = &body["models"]["model-b"];
+        assert_eq!(b["available"], false);
+        assert_eq!(b["capacity"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_latency_percentiles_reported() {
+        let manager = HealthCheckManager::new(&[make_backend("gpu-0"), make_backend("gpu-1")]);
+        let shared = manager.shared_states();
+        let latencies = Arc::new(BackendLatencies::default());
+        for ms in 1..=100 {
+            latencies.record(&BackendId::new("gpu-0"), LatencyMs::new(ms));
+        }
+
+        let response = health_handler(Arc::clone(&shared), Arc::clone(&latencies)).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let backend = |id: &str| {
+            body["backends"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|b| b["id"] == id)
+                .cloned()
+                .unwrap()
+        };
+        let timed = backend("gpu-0");
+        assert_eq!(timed["latency_ms"]["p50"], 50);
+        assert_eq!(timed["latency_ms"]["p95"], 95);
+        assert_eq!(timed["latency_ms"]["samples"], 100);
+        assert!(backend("gpu-1")["latency_ms"].is_null());
+
+        let response = metrics_handler(
+            shared,
+            latencies,
+            Arc::new(ShardedAffinityMap::new(8)),
+            Arc::default(),
+        )
+        .await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(text.contains("# TYPE mb_backend_latency_ms summary"));
+        assert!(text.contains("mb_backend_latency_ms{backend=\"gpu-0\",quantile=\"0.99\"} 99"));
+        assert!(text.contains("mb_backend_latency_ms_count{backend=\"gpu-0\"} 100"));
+        assert!(!text.contains("gpu-1"));
+        assert!(text.contains("mb_affinity_skips_total 0"));
+    }
+
+    struct FailingProbe;
+
+    impl HealthProbe for FailingProbe {
+        fn probe<'a>(
+            &'a self,
+            _backend: &'a BackendInfo,
+        ) -> Pin<Box<dyn Future<Output = Result<LatencyMs, HealthError>> + Send + 'a>> {
+            Box::pin(async { Err(HealthError::ConnectionFailed("refused".to_owned())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_transition_evicts_affinity() {
+        let model = ModelId::new("gpt-4");
+        let (doomed, survivor) = (BackendId::new("gpu-0"), BackendId::new("gpu-1"));
+        let (doomed_prefix, survivor_prefix) = (
+            mb_core::core::PrefixHash::new(1),
+            mb_core::core::PrefixHash::new(2),
+        );
+        let affinity = Arc::new(ShardedAffinityMap::new(100));
+        affinity.record(&model, doomed_prefix, &doomed);
+        affinity.record(&model, survivor_prefix, &survivor);
+
+        let backends = vec![make_backend("gpu-0")];
+        let manager = HealthCheckManager::new(&backends).with_affinity_map(Arc::clone(&affinity));
+        let handle = manager.start_background_checks(
+            backends,
+            Duration::from_millis(10),
+            1,
+            2000,
+            Arc::new(FailingProbe),
+        );
+
+        let mut evicted = false;
+        for _ in 0..100 {
+            if affinity.get(&model, doomed_prefix).is_none() {
+                evicted = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.abort();
+
+        assert!(
+            evicted,
+            "unhealthy backend should lose its affinity entries"
+        );
+        assert_eq!(
+            manager.get_states().await[0].status,
+            BackendStatus::Unhealthy
+        );
+        assert_eq!(affinity.get(&model, survivor_prefix), Some(survivor));
+    }
+
+    /// Answers after `slow_ms` for `slow` and at once for everyone else.
+    struct SlowProbe {
+        slow: BackendId,
+        slow_ms: u64,
+    }
+
+    impl HealthProbe for SlowProbe {
+        fn probe<'a>(
+            &'a self,
+            backend: &'a BackendInfo,
+        ) -> Pin<Box<dyn Future<Output = Result<LatencyMs, HealthError>> + Send + 'a>> {
+            Box::pin(async move {
+                if backend.id == self.slow {
+                    tokio::time::sleep(Duration::from_millis(self.slow_ms)).await;
+                }
+                Ok(LatencyMs::new(1))
+            })
+        }
+    }
+
+    /// Runs one probe round with `limit` concurrent probes and returns how
+    /// long the fast backends took to turn healthy.
+    async fn fast_backends_probed_after(limit: usize) -> Duration {
+        let backends: Vec<BackendInfo> =
+            (0..4).map(|i| make_backend(&format!("gpu-{i}"))).collect();
+        let manager = HealthCheckManager::new(&backends).with_max_concurrent_probes(limit);
+        let started = tokio::time::Instant::now();
+        let handle = manager.start_background_checks(
+            backends,
+            Duration::from_secs(60),
+            1,
+            2000,
+            Arc::new(SlowProbe {
+                slow: BackendId::new("gpu-0"),
+                slow_ms: 500,
+            }),
+        );
+
+        loop {
+            let states = manager.get_states().await;
+            let fast_done = states
+                .iter()
+                .filter(|s| s.id.as_str() != "gpu-0")
+                .all(|s| s.status == BackendStatus::Healthy);
+            if fast_done {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        handle.abort();
+        started.elapsed()
+    }
+
+    #[tokio::test]
+    async fn test_slow_probe_does_not_delay_others() {
+        // The slow backend is probed first; concurrently the rest finish
+        // long before it does, sequentially they wait behind it.
+        assert!(fast_backends_probed_after(4).await < Duration::from_millis(250));
+        assert!(fast_backends_probed_after(1).await >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_missing_models_checks_wire_names() {
+        let mut backend = make_backend("gpu-0");
+        backend.models = vec![ModelId::new("gpt-4"), ModelId::new("llama3")];
+        backend
+            .model_map
+            .insert(ModelId::new("llama3"), "meta-llama/Llama-3-8B".to_owned());
+
+        let listing = br#"{"data":[{"id":"gpt-4"},{"id":"meta-llama/Llama-3-8B"}]}"#;
+        assert!(missing_models(&backend, listing).is_empty());
+
+        let listing = br#"{"data":[{"id":"gpt-4"},{"id":"llama3"}]}"#;
+        assert_eq!(missing_models(&backend, listing), ["meta-llama/Llama-3-8B"]);
+
+        assert_eq!(missing_models(&backend, b"not json").len(), 2);
+    }
+
+    #[test]
+    fn test_missing_models_accepts_ollama_latest_tag() {
+        let mut backend = make_backend("ollama-0");
+        backend.spec = BackendSpec::Ollama;
+        backend.models = vec![ModelId::new("llama3"), ModelId::new("qwen2:7b")];
+
+        let listing = br#"{"models":[{"name":"llama3:latest"},{"name":"qwen2:7b"}]}"#;
+        assert!(missing_models(&backend, listing).is_empty());
+
+        let listing = br#"{"models":[{"name":"llama3:8b"}]}"#;
+        assert_eq!(missing_models(&backend, listing), ["llama3", "qwen2:7b"]);
+    }
+
+    #[test]
+    fn test_in_flight_requests_count_until_guard_drops() {
+        let requests = Arc::new(InFlightRequests::default());
+        let gpu0 = BackendId::new("gpu-0");
+        let first = requests.start(&gpu0);
+        let second = requests.start(&gpu0);
+        assert_eq!(requests.get(&gpu0), 2);
+        assert_eq!(requests.get(&BackendId::new("gpu-1")), 0);
+
+        drop(first);
+        assert_eq!(requests.get(&gpu0), 1);
+        drop(second);
+        assert_eq!(requests.get(&gpu0), 0);
+    }
 }
@@ -0,0 +1,256 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard};
+
+use axum::http::HeaderMap;
+use lru::LruCache;
+use mb_core::core::ClientId;
+
+use crate::coalesce::{Coalescer, Flight, SharedResponse};
+
+// ---------------------------------------------------------------------------
+// IdempotencyCache — replays responses to retried `Idempotency-Key` requests
+// ---------------------------------------------------------------------------
+
+/// Header a client sets to make a completion safe to retry.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header set to `true` on a response served from the idempotency cache.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Keys are namespaced per client, so two clients picking the same key
+/// never see each other's responses.
+pub type IdempotencyKey = (ClientId, String);
+
+/// What a lookup found for a key.
+#[derive(Debug)]
+pub enum Replay {
+    /// No live entry; the request should be dispatched.
+    Miss,
+    /// The key was last used with this same body.
+    Hit(SharedResponse),
+    /// The key was last used with a different body.
+    Conflict,
+}
+
+struct StoredResponse {
+    body_hash: u64,
+    response: SharedResponse,
+    stored_at_ms: u64,
+}
+
+/// Responses to recent non-streaming requests that carried an
+/// `Idempotency-Key`, kept for `ttl_ms` and evicted least recently used
+/// beyond `max_entries`.
+pub struct IdempotencyCache {
+    entries: Mutex<LruCache<IdempotencyKey, StoredResponse>>,
+    /// Keys whose first request is still being dispatched; concurrent
+    /// retries wait here instead of reaching the backend a second time.
+    reservations: Coalescer<IdempotencyKey>,
+    ttl_ms: u64,
+}
+
+impl IdempotencyCache {
+    pub fn new(max_entries: usize, ttl_ms: u64) -> Self {
+        let capacity = NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            reservations: Coalescer::new(),
+            ttl_ms,
+        }
+    }
+
+    /// The client's key from `headers`, if it sent a non-empty one.
+    pub fn key(client: &ClientId, headers: &HeaderMap) -> Option<IdempotencyKey> {
+        let value = headers.get(IDEMPOTENCY_KEY_HEADER)?.to_str().ok()?.trim();
+        (!value.is_empty()).then(|| (client.clone(), value.to_owned()))
+    }
+
+    /// Hash of the raw request body, stored alongside the response so a
+    /// reused key with a different request is caught.
+    pub fn body_hash(body: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Reserves `key` for one request at a time. The leader looks the key
+    /// up and, on a miss, holds the guard until its response is inserted;
+    /// followers wait for it and then look the key up themselves.
+    pub fn reserve(&self, key: IdempotencyKey) -> Flight<'_, IdempotencyKey> {
+        self.reservations.join(key)
+    }
+
+    pub fn get(&self, key: &IdempotencyKey, body_hash: u64, now_ms: u64) -> Replay {
+        let mut entries = self.lock();
+        let Some(stored) = entries.get(key) else {
+            return Replay::Miss;
+        };
+        if now_ms.saturating_sub(stored.stored_at_ms) >= self.ttl_ms {
+            entries.pop(key);
+            return Replay::Miss;
+        }
+        if stored.body_hash != body_hash {
+            return Replay::Conflict;
+        }
+        Replay::Hit(stored.response.clone())
+    }
+
+    pub fn insert(
+        &self,
+        key: IdempotencyKey,
+        body_hash: u64,
+        response: SharedResponse,
+        now_ms: u64,
+    ) {
+        let mut entries = self.lock();
+        // Drop expired entries from the cold end before they cost a live one
+        // its slot.
+        while entries
+            .peek_lru()
+            .is_some_and(|(_, stored)| now_ms.saturating_sub(stored.stored_at_ms) >= self.ttl_ms)
+        {
+            entries.pop_lru();
+        }
+        entries.put(
+            key,
+            StoredResponse {
+                body_hash,
+                response,
+                stored_at_ms: now_ms,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every mutation leaves the cache consistent, so a poisoned lock is
+    /// still usable.
+    fn lock(&self) -> MutexGuard<'_, LruCache<IdempotencyKey, StoredResponse>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use mb_core::core::{BackendId, CanonicalResponse, ModelId, TokenUsage};
+
+    use super::*;
+
+    fn response(id: &str) -> SharedResponse {
+        let resp = CanonicalResponse {
+            id: id.to_owned(),
+            model: ModelId::new("llama3-70b"),
+            choices: vec![],
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            created: 0,
+        };
+        (BackendId::new("gpu-1"), resp)
+    }
+
+    fn key(client: &str, value: &str) -> IdempotencyKey {
+        (ClientId::new(client), value.to_owned())
+    }
+
+    #[test]
+    fn test_key_from_header() {
+        let client = ClientId::new("client-a");
+        let mut headers = HeaderMap::new();
+        assert!(IdempotencyCache::key(&client, &headers).is_none());
+
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static("  "));
+        assert!(IdempotencyCache::key(&client, &headers).is_none());
+
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static("order-1"));
+        assert_eq!(
+            IdempotencyCache::key(&client, &headers),
+            Some(key("client-a", "order-1"))
+        );
+    }
+
+    #[test]
+    fn test_replay_within_ttl_and_namespaced_per_client() {
+        let cache = IdempotencyCache::new(10, 1_000);
+        let body = IdempotencyCache::body_hash(b"{}");
+        cache.insert(key("client-a", "k"), body, response("resp-1"), 0);
+
+        match cache.get(&key("client-a", "k"), body, 999) {
+            Replay::Hit((_, resp)) => assert_eq!(resp.id, "resp-1"),
+            other => panic!("expected hit, got {other:?}"),
+        }
+        assert!(matches!(
+            cache.get(&key("client-b", "k"), body, 999),
+            Replay::Miss
+        ));
+        assert!(matches!(
+            cache.get(&key("client-a", "k"), body, 1_000),
+            Replay::Miss
+        ));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_reused_key_with_other_body_conflicts() {
+        let cache = IdempotencyCache::new(10, 1_000);
+        let body = IdempotencyCache::body_hash(b"{\"a\":1}");
+        cache.insert(key("client-a", "k"), body, response("resp-1"), 0);
+
+        let other = IdempotencyCache::body_hash(b"{\"a\":2}");
+        assert!(matches!(
+            cache.get(&key("client-a", "k"), other, 1),
+            Replay::Conflict
+        ));
+    }
+
+    #[test]
+    fn test_lru_eviction_and_expiry_on_insert() {
+        let cache = IdempotencyCache::new(2, 1_000);
+        cache.insert(key("c", "a"), 0, response("resp-a"), 0);
+        cache.insert(key("c", "b"), 0, response("resp-b"), 0);
+        // Touch "a" so "b" is the least recently used.
+        assert!(matches!(cache.get(&key("c", "a"), 0, 1), Replay::Hit(_)));
+        cache.insert(key("c", "c"), 0, response("resp-c"), 1);
+        assert_eq!(cache.len(), 2);
+        assert!(matches!(cache.get(&key("c", "b"), 0, 1), Replay::Miss));
+
+        // Everything from t=0 has expired by t=1_000.
+        cache.insert(key("c", "d"), 0, response("resp-d"), 1_000);
+        assert_eq!(cache.len(), 2);
+        assert!(matches!(cache.get(&key("c", "a"), 0, 1_000), Replay::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_reserved_key_holds_retries_until_stored() {
+        let cache = IdempotencyCache::new(10, 1_000);
+        let Flight::Leader(guard) = cache.reserve(key("c", "k")) else {
+            panic!("first request should hold the key");
+        };
+        let Flight::Follower(rx) = cache.reserve(key("c", "k")) else {
+            panic!("a concurrent retry should wait");
+        };
+        assert!(matches!(
+            cache.reserve(key("c", "other")),
+            Flight::Leader(_)
+        ));
+
+        let shared = response("resp-1");
+        cache.insert(key("c", "k"), 0, shared.clone(), 0);
+        guard.complete(&shared);
+        rx.await.expect("woken after the response is stored");
+        assert!(matches!(cache.get(&key("c", "k"), 0, 1), Replay::Hit(_)));
+    }
+}
@@ -1,7 +1,7 @@
 use mb_core::core::{
     AdapterError, ApiSpec, CanonicalRequest, CanonicalResponse, CanonicalStreamChunk, ClientId,
-    DeltaContent, GenerationParams, InboundAdapter, ModelId, RequestId, RequestMetadata,
-    ToolDefinition,
+    DeltaContent, GenerationParams, HeuristicTokenCounter, InboundAdapter, ModelId, RequestId,
+    RequestMetadata, StreamContext, TokenCounter, ToolDefinition,
 };
 
 use super::openai_wire::{
@@ -16,9 +16,17 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
         ApiSpec::OpenAiChat
     }
 
-    fn parse_request(&self, body: &[u8]) -> Result<CanonicalRequest, AdapterError> {
-        let oai: openai_wire::OaiRequest =
+    fn parse_request(
+        &self,
+        body: &[u8],
+        default_model: Option<&ModelId>,
+    ) -> Result<CanonicalRequest, AdapterError> {
+        let body = openai_wire::trim_request_body(body);
+        let value: serde_json::Value =
             serde_json::from_slice(body).map_err(|e| AdapterError::ParseRequest(e.to_string()))?;
+        openai_wire::validate_request_shape(&value)?;
+        let oai: openai_wire::OaiRequest =
+            serde_json::from_value(value).map_err(|e| AdapterError::ParseRequest(e.to_string()))?;
 
         let messages = oai
             .messages
@@ -38,67 +46,76 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
 
         let tool_choice = oai.tool_choice.map(openai_wire::convert_tool_choice);
 
+        // Top-level values win over `extra_body`.
+        let extra = oai.extra_body.unwrap_or_default();
         let params = GenerationParams {
             temperature: oai.temperature,
             top_p: oai.top_p,
             max_tokens: oai.max_tokens,
-            stop: oai.stop,
+            stop: oai.stop.map(openai_wire::convert_stop),
             frequency_penalty: oai.frequency_penalty,
             presence_penalty: oai.presence_penalty,
             seed: oai.seed,
+            top_k: oai.top_k.or(extra.top_k),
+            min_p: oai.min_p.or(extra.min_p),
         };
 
-        let estimated_input_tokens = openai_wire::estimate_tokens(&messages);
+        let model = match oai.model {
+            Some(model) => ModelId::new(model),
+            None => default_model
+                .cloned()
+                .ok_or_else(|| openai_wire::missing_field("model"))?,
+        };
+
+        let estimated_input_tokens = HeuristicTokenCounter.count_messages(&messages);
 
         Ok(CanonicalRequest {
-            model: ModelId::new(oai.model),
+            model,
             messages,
             params,
             tools,
             tool_choice,
+            parallel_tool_calls: oai.parallel_tool_calls,
+            logprobs: oai.logprobs,
+            top_logprobs: oai.top_logprobs,
+            response_format: oai
+                .response_format
+                .map(openai_wire::convert_response_format),
+            echo: oai.echo,
+            suffix: oai.suffix,
+            n: oai.n,
             stream: oai.stream.unwrap_or(false),
             metadata: RequestMetadata {
                 request_id: RequestId::new(format!("req-{}", uuid::Uuid::new_v4())),
                 client_id: ClientId::new("unknown"),
                 estimated_input_tokens,
                 prefix_hash: None,
+                app_title: None,
+                app_referer: None,
+                client_metadata: oai.metadata,
+                store: oai.store.unwrap_or(false),
+                provider_hint: None,
             },
         })
     }
 
     fn format_response(&self, response: &CanonicalResponse) -> Result<Vec<u8>, AdapterError> {
-        let choices: Vec<OaiResponseChoice> = response
-            .choices
-            .iter()
-            .map(|c| OaiResponseChoice {
-                index: c.index,
-                message: OaiResponseMessage {
-                    role: openai_wire::role_to_str(&c.message.role).to_owned(),
-                    content: openai_wire::content_to_string(&c.message.content),
-                },
-                finish_reason: openai_wire::finish_reason_to_str(&c.finish_reason).to_owned(),
-            })
-            .collect();
-
-        let oai_resp = OaiResponse {
-            id: response.id.clone(),
-            object: "chat.completion",
-            created: response.created,
-            model: response.model.as_str().to_owned(),
-            choices,
-            usage: OaiUsage {
-                prompt_tokens: response.usage.prompt_tokens,
-                completion_tokens: response.usage.completion_tokens,
-                total_tokens: response.usage.total_tokens,
-            },
-        };
+        serde_json::to_vec(&oai_response(response))
+            .map_err(|e| AdapterError::FormatResponse(e.to_string()))
+    }
 
-        serde_json::to_vec(&oai_resp).map_err(|e| AdapterError::FormatResponse(e.to_string()))
+    fn format_response_pretty(
+        &self,
+        response: &CanonicalResponse,
+    ) -> Result<Vec<u8>, AdapterError> {
+        serde_json::to_vec_pretty(&oai_response(response))
+            .map_err(|e| AdapterError::FormatResponse(e.to_string()))
     }
 
     fn format_stream_chunk(
         &self,
         chunk: &CanonicalStreamChunk,
+        context: &StreamContext,
     ) -> Result<Option<String>, AdapterError> {
         if chunk.choices.is_empty() {
             return Ok(None);
@@ -115,6 +132,7 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
                         content: None,
                     },
                     finish_reason: None,
+                    logprobs: sc.logprobs.clone(),
                 },
                 DeltaContent::Text(text) => OaiStreamChoice {
                     index: sc.index,
@@ -123,6 +141,7 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
                         content: Some(text.clone()),
                     },
                     finish_reason: None,
+                    logprobs: sc.logprobs.clone(),
                 },
                 DeltaContent::Finish(reason) => OaiStreamChoice {
                     index: sc.index,
@@ -131,6 +150,7 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
                         content: None,
                     },
                     finish_reason: Some(openai_wire::finish_reason_to_str(reason).to_owned()),
+                    logprobs: sc.logprobs.clone(),
                 },
                 DeltaContent::ToolCallStart { .. } | DeltaContent::ToolCallDelta { .. } => {
                     OaiStreamChoice {
@@ -140,16 +160,17 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
                             content: None,
                         },
                         finish_reason: None,
+                        logprobs: sc.logprobs.clone(),
                     }
                 }
             })
             .collect();
 
         let stream_chunk = OaiStreamChunk {
-            id: String::new(),
+            id: context.id.clone(),
             object: "chat.completion.chunk",
-            created: 0,
-            model: String::new(),
+            created: context.created,
+            model: context.model.as_str().to_owned(),
             choices,
         };
 
@@ -164,5 +185,35 @@ impl InboundAdapter for OpenAiChatInboundAdapter {
     }
 }
 
+/// The wire form of `response`.
+fn oai_response(response: &CanonicalResponse) -> OaiResponse {
+    let choices: Vec<OaiResponseChoice> = response
+        .choices
+        .iter()
+        .map(|c| OaiResponseChoice {
+            index: c.index,
+            message: OaiResponseMessage {
+                role: openai_wire::role_to_str(&c.message.role).to_owned(),
+                content: openai_wire::content_to_string(&c.message.content),
+            },
+            finish_reason: openai_wire::finish_reason_to_str(&c.finish_reason).to_owned(),
+            logprobs: c.logprobs.clone(),
+        })
+        .collect();
+
+    OaiResponse {
+        id: response.id.clone(),
+        object: "chat.completion",
+        created: response.created,
+        model: response.model.as_str().to_owned(),
+        choices,
+        usage: OaiUsage {
+            prompt_tokens: response.usage.prompt_tokens,
+            completion_tokens: response.usage.completion_tokens,
+            total_tokens: response.usage.total_tokens,
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests;
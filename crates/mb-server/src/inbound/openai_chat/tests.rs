@@ -1,7 +1,7 @@
 use super::*;
 use mb_core::core::{
-    AdapterError, Choice, FinishReason, Message, MessageContent, ModelId, Role, StreamChoice,
-    TokenUsage, ToolChoice,
+    AdapterError, Choice, ContentPart, FinishReason, ImageDetail, Message, MessageContent, ModelId,
+    ResponseFormat, Role, StreamChoice, StreamContext, StreamFraming, TokenUsage, ToolChoice,
 };
 use serde_json::Value;
 
@@ -19,7 +19,7 @@ fn test_parse_simple_request() {
 
     let adapter = OpenAiChatInboundAdapter;
     let req = adapter
-        .parse_request(serde_json::to_vec(&body).unwrap().as_slice())
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
         .unwrap();
 
     assert_eq!(req.model.as_str(), "gpt-4");
@@ -41,6 +41,193 @@ fn test_parse_simple_request() {
     assert!(req.tool_choice.is_none());
 }
 
+#[test]
+fn test_parse_request_stop_string() {
+    let body = serde_json::json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "stop": "END"
+    });
+
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+
+    assert_eq!(req.params.stop, Some(vec!["END".to_owned()]));
+}
+
+#[test]
+fn test_parse_request_stop_array() {
+    let body = serde_json::json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "stop": ["A", "B"]
+    });
+
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+
+    assert_eq!(req.params.stop, Some(vec!["A".to_owned(), "B".to_owned()]));
+}
+
+#[test]
+fn test_parse_request_top_k_min_p() {
+    let top_level = serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "top_k": 40,
+        "min_p": 0.05,
+        "extra_body": {"top_k": 10}
+    });
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&top_level).unwrap().as_slice(), None)
+        .unwrap();
+    assert_eq!(req.params.top_k, Some(40));
+    assert_eq!(req.params.min_p, Some(0.05));
+
+    let extra_body = serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "extra_body": {"top_k": 10, "min_p": 0.1}
+    });
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&extra_body).unwrap().as_slice(), None)
+        .unwrap();
+    assert_eq!(req.params.top_k, Some(10));
+    assert_eq!(req.params.min_p, Some(0.1));
+}
+
+#[test]
+fn test_parse_request_logprobs() {
+    let body = serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "logprobs": true,
+        "top_logprobs": 3
+    });
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+    assert_eq!(req.logprobs, Some(true));
+    assert_eq!(req.top_logprobs, Some(3));
+
+    let (param, message) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "top_logprobs": 21
+    }));
+    assert_eq!(param, "top_logprobs");
+    assert!(message.contains("between 0 and 20"));
+}
+
+#[test]
+fn test_parse_request_response_format() {
+    let body = serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "response_format": {
+            "type": "json_schema",
+            "json_schema": {"name": "reply", "schema": {"type": "object"}, "strict": true}
+        }
+    });
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+    assert_eq!(
+        req.response_format,
+        Some(ResponseFormat::JsonSchema {
+            name: "reply".to_owned(),
+            schema: serde_json::json!({"type": "object"}),
+            strict: Some(true),
+        })
+    );
+
+    let (param, _) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "response_format": {"type": "yaml"}
+    }));
+    assert_eq!(param, "response_format.type");
+
+    let (param, _) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "response_format": {"type": "json_schema"}
+    }));
+    assert_eq!(param, "response_format.json_schema");
+}
+
+#[test]
+fn test_parse_request_echo_suffix() {
+    let body = serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "def add(a, b):"}],
+        "echo": true,
+        "suffix": "    return result"
+    });
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+    assert_eq!(req.echo, Some(true));
+    assert_eq!(req.suffix.as_deref(), Some("    return result"));
+
+    let (param, message) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "suffix": 42
+    }));
+    assert_eq!(param, "suffix");
+    assert!(message.contains("must be a string"));
+}
+
+#[test]
+fn test_parse_request_store_and_metadata() {
+    let body = serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "store": true,
+        "metadata": {"session": "abc-123", "team": "search"}
+    });
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+    assert!(req.metadata.store);
+    let metadata = req.metadata.client_metadata.expect("metadata captured");
+    assert_eq!(metadata.len(), 2);
+    assert_eq!(metadata["session"], "abc-123");
+
+    let (param, message) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "metadata": {"count": 3}
+    }));
+    assert_eq!(param, "metadata.count");
+    assert_eq!(message, "metadata.count must be a string");
+
+    let too_many: serde_json::Map<_, _> = (0..17)
+        .map(|i| (format!("k{i}"), serde_json::Value::from("v")))
+        .collect();
+    let (param, _) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "metadata": too_many
+    }));
+    assert_eq!(param, "metadata");
+
+    // Within the per-value character limits but over the byte budget.
+    let oversized: serde_json::Map<_, _> = (0..16)
+        .map(|i| (format!("k{i}"), serde_json::Value::from("é".repeat(512))))
+        .collect();
+    let (param, message) = field_error(serde_json::json!({
+        "model": "llama3",
+        "messages": [{"role": "user", "content": "Hi"}],
+        "metadata": oversized
+    }));
+    assert_eq!(param, "metadata");
+    assert!(message.contains("16384 bytes"));
+}
+
 #[test]
 fn test_parse_request_with_tools() {
     let body = serde_json::json!({
@@ -63,12 +250,13 @@ fn test_parse_request_with_tools() {
                 }
             }
         ],
-        "tool_choice": "auto"
+        "tool_choice": "auto",
+        "parallel_tool_calls": false
     });
 
     let adapter = OpenAiChatInboundAdapter;
     let req = adapter
-        .parse_request(serde_json::to_vec(&body).unwrap().as_slice())
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
         .unwrap();
 
     let tools = req.tools.unwrap();
@@ -76,6 +264,7 @@ fn test_parse_request_with_tools() {
     assert_eq!(tools[0].name, "get_weather");
     assert_eq!(tools[0].description.as_deref(), Some("Get current weather"));
     assert_eq!(req.tool_choice, Some(ToolChoice::Auto));
+    assert_eq!(req.parallel_tool_calls, Some(false));
 }
 
 #[test]
@@ -93,6 +282,7 @@ fn test_format_response() {
                 tool_call_id: None,
             },
             finish_reason: FinishReason::Stop,
+            logprobs: None,
         }],
         usage: TokenUsage {
             prompt_tokens: 10,
@@ -118,6 +308,46 @@ fn test_format_response() {
     assert_eq!(json["usage"]["total_tokens"], 15);
 }
 
+#[test]
+fn test_format_response_logprobs() {
+    let logprobs = serde_json::json!({
+        "content": [{"token": "Hi", "logprob": -0.01, "top_logprobs": []}]
+    });
+    let response = CanonicalResponse {
+        id: "chatcmpl-123".to_owned(),
+        model: ModelId::new("gpt-4"),
+        choices: vec![Choice {
+            index: 0,
+            message: Message {
+                role: Role::Assistant,
+                content: MessageContent::Text("Hi".to_owned()),
+                name: None,
+                tool_call_id: None,
+            },
+            finish_reason: FinishReason::Stop,
+            logprobs: Some(logprobs.clone()),
+        }],
+        usage: TokenUsage {
+            prompt_tokens: 3,
+            completion_tokens: 1,
+            total_tokens: 4,
+        },
+        created: 1700000000,
+    };
+
+    let bytes = OpenAiChatInboundAdapter.format_response(&response).unwrap();
+    let json: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(json["choices"][0]["logprobs"], logprobs);
+}
+
+fn stream_context() -> StreamContext {
+    StreamContext {
+        id: "chatcmpl-stream".to_owned(),
+        model: ModelId::new("gpt-4"),
+        created: 1700000000,
+    }
+}
+
 #[test]
 fn test_format_stream_chunk_text() {
     let adapter = OpenAiChatInboundAdapter;
@@ -125,18 +355,47 @@ fn test_format_stream_chunk_text() {
         choices: vec![StreamChoice {
             index: 0,
             delta: DeltaContent::Text("Hello".to_owned()),
+            logprobs: None,
         }],
     };
 
-    let result = adapter.format_stream_chunk(&chunk).unwrap().unwrap();
+    let result = adapter
+        .format_stream_chunk(&chunk, &stream_context())
+        .unwrap()
+        .unwrap();
     // format_stream_chunk returns raw JSON without SSE framing;
     // the stream_handler adds "data: " prefix and trailing newlines.
     let json: Value = serde_json::from_str(&result).unwrap();
 
     assert_eq!(json["object"], "chat.completion.chunk");
+    assert_eq!(json["id"], "chatcmpl-stream");
+    assert_eq!(json["model"], "gpt-4");
+    assert_eq!(json["created"], 1700000000);
     assert_eq!(json["choices"][0]["index"], 0);
     assert_eq!(json["choices"][0]["delta"]["content"], "Hello");
     assert!(json["choices"][0]["finish_reason"].is_null());
+    assert!(json["choices"][0].get("logprobs").is_none());
+}
+
+#[test]
+fn test_format_stream_chunk_logprobs() {
+    let adapter = OpenAiChatInboundAdapter;
+    let logprobs = serde_json::json!({"content": [{"token": "Hello", "logprob": -0.2}]});
+    let chunk = CanonicalStreamChunk {
+        choices: vec![StreamChoice {
+            index: 0,
+            delta: DeltaContent::Text("Hello".to_owned()),
+            logprobs: Some(logprobs.clone()),
+        }],
+    };
+
+    let result = adapter
+        .format_stream_chunk(&chunk, &stream_context())
+        .unwrap()
+        .unwrap();
+    let json: Value = serde_json::from_str(&result).unwrap();
+
+    assert_eq!(json["choices"][0]["logprobs"], logprobs);
 }
 
 #[test]
@@ -146,10 +405,14 @@ fn test_format_stream_chunk_finish() {
         choices: vec![StreamChoice {
             index: 0,
             delta: DeltaContent::Finish(FinishReason::Stop),
+            logprobs: None,
         }],
     };
 
-    let result = adapter.format_stream_chunk(&chunk).unwrap().unwrap();
+    let result = adapter
+        .format_stream_chunk(&chunk, &stream_context())
+        .unwrap()
+        .unwrap();
     let json: Value = serde_json::from_str(&result).unwrap();
 
     assert_eq!(json["choices"][0]["finish_reason"], "stop");
@@ -166,7 +429,7 @@ fn test_parse_request_null_content() {
 
     let adapter = OpenAiChatInboundAdapter;
     let req = adapter
-        .parse_request(serde_json::to_vec(&body).unwrap().as_slice())
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
         .unwrap();
 
     assert_eq!(req.messages[0].role, Role::Tool);
@@ -174,13 +437,229 @@ fn test_parse_request_null_content() {
     assert_eq!(req.messages[0].tool_call_id.as_deref(), Some("call_123"));
 }
 
+#[test]
+fn test_parse_request_image_parts() {
+    let data_url = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUg==";
+    let body = serde_json::json!({
+        "model": "gpt-4",
+        "messages": [{
+            "role": "user",
+            "content": [
+                {"type": "text", "text": "Compare these."},
+                {"type": "image_url", "image_url": {"url": "https://example.com/a.png", "detail": "high"}},
+                {"type": "image_url", "image_url": {"url": data_url}}
+            ]
+        }]
+    });
+
+    let adapter = OpenAiChatInboundAdapter;
+    let req = adapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+
+    assert_eq!(
+        req.messages[0].content,
+        MessageContent::Parts(vec![
+            ContentPart::Text {
+                text: "Compare these.".to_owned(),
+            },
+            ContentPart::ImageUrl {
+                url: "https://example.com/a.png".to_owned(),
+                detail: Some(ImageDetail::High),
+            },
+            ContentPart::ImageUrl {
+                url: data_url.to_owned(),
+                detail: None,
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_parse_request_input_audio_part() {
+    let body = serde_json::json!({
+        "model": "gpt-4o-audio",
+        "messages": [{
+            "role": "user",
+            "content": [
+                {"type": "text", "text": "Transcribe this."},
+                {"type": "input_audio", "input_audio": {"data": "UklGRiQAAABXQVZF", "format": "wav"}}
+            ]
+        }]
+    });
+
+    let req = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap();
+
+    assert_eq!(
+        req.messages[0].content,
+        MessageContent::Parts(vec![
+            ContentPart::Text {
+                text: "Transcribe this.".to_owned(),
+            },
+            ContentPart::InputAudio {
+                data: "UklGRiQAAABXQVZF".to_owned(),
+                format: "wav".to_owned(),
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_parse_request_rejects_unsupported_image_url() {
+    let adapter = OpenAiChatInboundAdapter;
+    for url in ["ftp://example.com/a.png", "data:text/plain;base64,aGk="] {
+        let body = serde_json::json!({
+            "model": "gpt-4",
+            "messages": [{
+                "role": "user",
+                "content": [{"type": "image_url", "image_url": {"url": url}}]
+            }]
+        });
+
+        let result = adapter.parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None);
+        assert!(
+            matches!(result, Err(AdapterError::ParseRequest(_))),
+            "expected {url} to be rejected"
+        );
+    }
+}
+
 #[test]
 fn test_parse_request_invalid_json() {
     let adapter = OpenAiChatInboundAdapter;
-    let result = adapter.parse_request(b"not json");
+    let result = adapter.parse_request(b"not json", None);
+    assert!(matches!(result, Err(AdapterError::ParseRequest(_))));
+
+    // Trimming the body does not rescue broken JSON.
+    let result = adapter.parse_request(b"\xEF\xBB\xBF{\"model\": \n", None);
     assert!(matches!(result, Err(AdapterError::ParseRequest(_))));
 }
 
+#[test]
+fn test_parse_request_tolerates_bom_and_whitespace() {
+    let json = br#"{"model": "llama3", "messages": [{"role": "user", "content": "Hi"}]}"#;
+
+    let mut with_bom = b"\xEF\xBB\xBF".to_vec();
+    with_bom.extend_from_slice(json);
+    let req = OpenAiChatInboundAdapter
+        .parse_request(&with_bom, None)
+        .unwrap();
+    assert_eq!(req.model, ModelId::new("llama3"));
+
+    let mut padded = b"\r\n  ".to_vec();
+    padded.extend_from_slice(json);
+    padded.extend_from_slice(b"\n\n");
+    let req = OpenAiChatInboundAdapter
+        .parse_request(&padded, None)
+        .unwrap();
+    assert_eq!(req.messages.len(), 1);
+}
+
+#[test]
+fn test_parse_request_rejects_empty_messages() {
+    let body = serde_json::json!({"model": "gpt-4", "messages": []});
+
+    let err = OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        AdapterError::InvalidField { ref param, ref message }
+            if param == "messages" && message.contains("at least one")
+    ));
+}
+
+fn field_error(body: serde_json::Value) -> (String, String) {
+    match OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None)
+    {
+        Err(AdapterError::InvalidField { param, message }) => (param, message),
+        other => panic!("expected a field error, got {:?}", other.map(|r| r.model)),
+    }
+}
+
+#[test]
+fn test_parse_request_missing_model() {
+    let (param, message) = field_error(serde_json::json!({
+        "messages": [{"role": "user", "content": "hi"}]
+    }));
+    assert_eq!(param, "model");
+    assert_eq!(message, "missing required field: model");
+}
+
+#[test]
+fn test_parse_request_missing_model_uses_default() {
+    let body = serde_json::json!({
+        "messages": [{"role": "user", "content": "hi"}]
+    });
+    let default = ModelId::new("gpt-4o");
+    let req = OpenAiChatInboundAdapter
+        .parse_request(
+            serde_json::to_vec(&body).unwrap().as_slice(),
+            Some(&default),
+        )
+        .unwrap();
+    assert_eq!(req.model, default);
+}
+
+#[test]
+fn test_parse_request_empty_model_not_defaulted() {
+    let body = serde_json::json!({
+        "model": "",
+        "messages": [{"role": "user", "content": "hi"}]
+    });
+    let default = ModelId::new("gpt-4o");
+    let err = OpenAiChatInboundAdapter
+        .parse_request(
+            serde_json::to_vec(&body).unwrap().as_slice(),
+            Some(&default),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        AdapterError::InvalidField { ref param, ref message }
+            if param == "model" && message == "model must not be empty"
+    ));
+}
+
+#[test]
+fn test_parse_request_missing_messages() {
+    let (param, message) = field_error(serde_json::json!({"model": "gpt-4"}));
+    assert_eq!(param, "messages");
+    assert_eq!(message, "missing required field: messages");
+}
+
+#[test]
+fn test_parse_request_non_string_model() {
+    let (param, message) = field_error(serde_json::json!({
+        "model": 4,
+        "messages": [{"role": "user", "content": "hi"}]
+    }));
+    assert_eq!(param, "model");
+    assert_eq!(message, "model must be a string");
+}
+
+#[test]
+fn test_parse_request_field_errors_name_nested_params() {
+    let (param, message) = field_error(serde_json::json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "hi"}, {"content": "no role"}]
+    }));
+    assert_eq!(param, "messages[1].role");
+    assert_eq!(message, "missing required field: messages[1].role");
+
+    let (param, message) = field_error(serde_json::json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "hi"}],
+        "max_tokens": -1
+    }));
+    assert_eq!(param, "max_tokens");
+    assert_eq!(message, "max_tokens must be a non-negative integer");
+}
+
 #[test]
 fn test_parse_request_unknown_role() {
     let body = serde_json::json!({
@@ -191,7 +670,7 @@ fn test_parse_request_unknown_role() {
     });
 
     let adapter = OpenAiChatInboundAdapter;
-    let result = adapter.parse_request(serde_json::to_vec(&body).unwrap().as_slice());
+    let result = adapter.parse_request(serde_json::to_vec(&body).unwrap().as_slice(), None);
     assert!(matches!(result, Err(AdapterError::ParseRequest(_))));
 }
 
@@ -201,6 +680,27 @@ fn test_done_sentinel() {
     assert_eq!(adapter.done_sentinel(), "[DONE]");
 }
 
+#[test]
+fn test_stream_framing() {
+    let adapter = OpenAiChatInboundAdapter;
+    let payload = r#"{"id":"c1"}"#;
+
+    assert_eq!(
+        adapter.frame_stream_chunk(payload, StreamFraming::Sse),
+        "data: {\"id\":\"c1\"}\n\n"
+    );
+    assert_eq!(
+        adapter.stream_trailer(StreamFraming::Sse).as_deref(),
+        Some("data: [DONE]\n\n")
+    );
+
+    assert_eq!(
+        adapter.frame_stream_chunk(payload, StreamFraming::Ndjson),
+        "{\"id\":\"c1\"}\n"
+    );
+    assert_eq!(adapter.stream_trailer(StreamFraming::Ndjson), None);
+}
+
 #[test]
 fn test_api_spec() {
     let adapter = OpenAiChatInboundAdapter;
@@ -211,6 +711,8 @@ fn test_api_spec() {
 fn test_format_stream_chunk_empty() {
     let adapter = OpenAiChatInboundAdapter;
     let chunk = CanonicalStreamChunk { choices: vec![] };
-    let result = adapter.format_stream_chunk(&chunk).unwrap();
+    let result = adapter
+        .format_stream_chunk(&chunk, &stream_context())
+        .unwrap();
     assert!(result.is_none());
 }
@@ -1,4 +1,9 @@
-use mb_core::core::{AdapterError, FinishReason, Message, MessageContent, Role, ToolChoice};
+use std::collections::BTreeMap;
+
+use mb_core::core::{
+    AdapterError, ContentPart, FinishReason, ImageDetail, Message, MessageContent, ResponseFormat,
+    Role, ToolChoice,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -8,7 +13,9 @@ use serde_json::Value;
 
 #[derive(Deserialize)]
 pub(super) struct OaiRequest {
-    pub model: String,
+    /// Optional so the gateway can apply `routing.default_model`.
+    #[serde(default)]
+    pub model: Option<String>,
     pub messages: Vec<OaiMessage>,
     #[serde(default)]
     pub temperature: Option<f64>,
@@ -19,7 +26,7 @@ pub(super) struct OaiRequest {
     #[serde(default)]
     pub stream: Option<bool>,
     #[serde(default)]
-    pub stop: Option<Vec<String>>,
+    pub stop: Option<OaiStop>,
     #[serde(default)]
     pub frequency_penalty: Option<f64>,
     #[serde(default)]
@@ -30,19 +37,88 @@ pub(super) struct OaiRequest {
     pub tools: Option<Vec<OaiToolDef>>,
     #[serde(default)]
     pub tool_choice: Option<OaiToolChoice>,
+    #[serde(default)]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(default)]
+    pub logprobs: Option<bool>,
+    #[serde(default)]
+    pub top_logprobs: Option<u8>,
+    #[serde(default)]
+    pub response_format: Option<OaiResponseFormat>,
+    #[serde(default)]
+    pub echo: Option<bool>,
+    #[serde(default)]
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub n: Option<u32>,
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    #[serde(default)]
+    pub min_p: Option<f64>,
+    /// OpenAI SDKs send non-standard sampling params here.
+    #[serde(default)]
+    pub extra_body: Option<OaiExtraBody>,
+    #[serde(default)]
+    pub store: Option<bool>,
+    #[serde(default)]
+    pub metadata: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Deserialize, Default)]
+pub(super) struct OaiExtraBody {
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    #[serde(default)]
+    pub min_p: Option<f64>,
+}
+
+/// `stop` may be a single sequence or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum OaiStop {
+    Single(String),
+    Multiple(Vec<String>),
 }
 
 #[derive(Deserialize)]
 pub(super) struct OaiMessage {
     pub role: String,
     #[serde(default)]
-    pub content: Option<String>,
+    pub content: Option<OaiContent>,
     #[serde(default)]
     pub name: Option<String>,
     #[serde(default)]
     pub tool_call_id: Option<String>,
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum OaiContent {
+    Text(String),
+    Parts(Vec<OaiContentPart>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum OaiContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OaiImageUrl },
+    InputAudio { input_audio: OaiInputAudio },
+}
+
+#[derive(Deserialize)]
+pub(super) struct OaiImageUrl {
+    pub url: String,
+    #[serde(default)]
+    pub detail: Option<ImageDetail>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct OaiInputAudio {
+    pub data: String,
+    pub format: String,
+}
+
 #[derive(Deserialize)]
 pub(super) struct OaiToolDef {
     pub function: OaiFunctionDef,
@@ -73,6 +149,24 @@ pub(super) struct OaiNamedFunction {
     pub name: String,
 }
 
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum OaiResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: OaiJsonSchema },
+}
+
+#[derive(Deserialize)]
+pub(super) struct OaiJsonSchema {
+    pub name: String,
+    /// Omitted schemas accept any JSON value.
+    #[serde(default)]
+    pub schema: Option<Value>,
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
 // ---------------------------------------------------------------------------
 // Response wire types
 // ---------------------------------------------------------------------------
@@ -92,6 +186,8 @@ pub(super) struct OaiResponseChoice {
     pub index: u32,
     pub message: OaiResponseMessage,
     pub finish_reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Value>,
 }
 
 #[derive(Serialize)]
@@ -126,6 +222,8 @@ pub(super) struct OaiStreamChoice {
     pub delta: OaiDelta,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Value>,
 }
 
 #[derive(Serialize)]
@@ -136,6 +234,185 @@ pub(super) struct OaiDelta {
     pub content: Option<String>,
 }
 
+// ---------------------------------------------------------------------------
+// Request shape validation — field-level errors before typed deserialization
+// ---------------------------------------------------------------------------
+
+/// Drops a leading UTF-8 byte order mark and surrounding whitespace, which
+/// some clients send around an otherwise valid JSON body.
+pub(super) fn trim_request_body(body: &[u8]) -> &[u8] {
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+    body.trim_ascii()
+}
+
+/// Checks the fields clients most often get wrong, so they see
+/// `missing required field: messages` rather than a serde position.
+/// Anything subtler is left to typed deserialization.
+pub(super) fn validate_request_shape(value: &Value) -> Result<(), AdapterError> {
+    let Some(request) = value.as_object() else {
+        return Err(AdapterError::ParseRequest(
+            "request body must be a JSON object".to_owned(),
+        ));
+    };
+
+    // A missing model is resolved against `routing.default_model`.
+    match request.get("model") {
+        None => {}
+        Some(Value::String(model)) if model.is_empty() => {
+            return Err(invalid_field("model", "model must not be empty"))
+        }
+        Some(Value::String(_)) => {}
+        Some(_) => return Err(invalid_field("model", "model must be a string")),
+    }
+
+    let messages = match request.get("messages") {
+        None => return Err(missing_field("messages")),
+        Some(Value::Array(messages)) => messages,
+        Some(_) => return Err(invalid_field("messages", "messages must be an array")),
+    };
+    if messages.is_empty() {
+        return Err(invalid_field(
+            "messages",
+            "messages must contain at least one message",
+        ));
+    }
+    for (i, message) in messages.iter().enumerate() {
+        let param = format!("messages[{i}]");
+        let Some(message) = message.as_object() else {
+            return Err(invalid_field(&param, &format!("{param} must be an object")));
+        };
+        match message.get("role") {
+            None => return Err(missing_field(&format!("{param}.role"))),
+            Some(Value::String(_)) => {}
+            Some(_) => {
+                let param = format!("{param}.role");
+                return Err(invalid_field(&param, &format!("{param} must be a string")));
+            }
+        }
+    }
+
+    for (field, expected, ok) in [
+        (
+            "stream",
+            "a boolean",
+            Value::is_boolean as fn(&Value) -> bool,
+        ),
+        ("parallel_tool_calls", "a boolean", Value::is_boolean),
+        ("logprobs", "a boolean", Value::is_boolean),
+        ("top_logprobs", "an integer between 0 and 20", |v| {
+            v.as_u64().is_some_and(|n| n <= 20)
+        }),
+        ("response_format", "an object", Value::is_object),
+        ("echo", "a boolean", Value::is_boolean),
+        ("suffix", "a string", Value::is_string),
+        ("n", "an integer between 1 and 128", |v| {
+            v.as_u64().is_some_and(|n| (1..=128).contains(&n))
+        }),
+        ("temperature", "a number", Value::is_number),
+        ("top_p", "a number", Value::is_number),
+        ("max_tokens", "a non-negative integer", Value::is_u64),
+        ("store", "a boolean", Value::is_boolean),
+    ] {
+        match request.get(field) {
+            Some(v) if !v.is_null() && !ok(v) => {
+                return Err(invalid_field(field, &format!("{field} must be {expected}")));
+            }
+            _ => {}
+        }
+    }
+    validate_response_format(request.get("response_format"))?;
+    validate_metadata(request.get("metadata"))
+}
+
+/// OpenAI's limits on `metadata`: at most 16 string pairs, keys up to 64
+/// characters and values up to 512.
+const MAX_METADATA_PAIRS: usize = 16;
+const MAX_METADATA_KEY_LEN: usize = 64;
+const MAX_METADATA_VALUE_LEN: usize = 512;
+/// Serialized size cap, matching the feedback store's `MAX_METADATA_LEN`:
+/// 16 pairs of multi-byte or escaped characters can exceed it.
+const MAX_METADATA_BYTES: usize = 16_384;
+
+fn validate_metadata(metadata: Option<&Value>) -> Result<(), AdapterError> {
+    let metadata = match metadata {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::Object(metadata)) => metadata,
+        Some(_) => return Err(invalid_field("metadata", "metadata must be an object")),
+    };
+    if metadata.len() > MAX_METADATA_PAIRS {
+        return Err(invalid_field(
+            "metadata",
+            &format!("metadata must have at most {MAX_METADATA_PAIRS} keys"),
+        ));
+    }
+    for (key, value) in metadata {
+        if key.chars().count() > MAX_METADATA_KEY_LEN {
+            return Err(invalid_field(
+                "metadata",
+                &format!("metadata keys must be at most {MAX_METADATA_KEY_LEN} characters"),
+            ));
+        }
+        let param = format!("metadata.{key}");
+        match value.as_str() {
+            Some(value) if value.chars().count() <= MAX_METADATA_VALUE_LEN => {}
+            Some(_) => {
+                return Err(invalid_field(
+                    &param,
+                    &format!("{param} must be at most {MAX_METADATA_VALUE_LEN} characters"),
+                ))
+            }
+            None => return Err(invalid_field(&param, &format!("{param} must be a string"))),
+        }
+    }
+    let size = serde_json::to_string(metadata).map_or(usize::MAX, |json| json.len());
+    if size > MAX_METADATA_BYTES {
+        return Err(invalid_field(
+            "metadata",
+            &format!("metadata must serialize to at most {MAX_METADATA_BYTES} bytes"),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_response_format(format: Option<&Value>) -> Result<(), AdapterError> {
+    let Some(format) = format.and_then(Value::as_object) else {
+        return Ok(());
+    };
+    match format.get("type").and_then(Value::as_str) {
+        Some("text" | "json_object") => Ok(()),
+        Some("json_schema") => {
+            let Some(json_schema) = format.get("json_schema").and_then(Value::as_object) else {
+                return Err(missing_field("response_format.json_schema"));
+            };
+            if !json_schema.get("name").is_some_and(Value::is_string) {
+                return Err(missing_field("response_format.json_schema.name"));
+            }
+            match json_schema.get("schema") {
+                None | Some(Value::Object(_) | Value::Bool(_)) => Ok(()),
+                Some(_) => Err(invalid_field(
+                    "response_format.json_schema.schema",
+                    "response_format.json_schema.schema must be an object",
+                )),
+            }
+        }
+        _ => Err(invalid_field(
+            "response_format.type",
+            "response_format.type must be one of: text, json_object, json_schema",
+        )),
+    }
+}
+
+pub(super) fn missing_field(param: &str) -> AdapterError {
+    invalid_field(param, &format!("missing required field: {param}"))
+}
+
+fn invalid_field(param: &str, message: &str) -> AdapterError {
+    AdapterError::InvalidField {
+        param: param.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Conversion helpers
 // ---------------------------------------------------------------------------
@@ -170,7 +447,16 @@ pub(super) fn finish_reason_to_str(reason: &FinishReason) -> &'static str {
 
 pub(super) fn convert_oai_message(msg: OaiMessage) -> Result<Message, AdapterError> {
     let role = parse_role(&msg.role)?;
-    let content = MessageContent::Text(msg.content.unwrap_or_default());
+    let content = match msg.content {
+        None => MessageContent::Text(String::new()),
+        Some(OaiContent::Text(text)) => MessageContent::Text(text),
+        Some(OaiContent::Parts(parts)) => MessageContent::Parts(
+            parts
+                .into_iter()
+                .map(convert_oai_content_part)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
     Ok(Message {
         role,
         content,
@@ -179,6 +465,47 @@ pub(super) fn convert_oai_message(msg: OaiMessage) -> Result<Message, AdapterErr
     })
 }
 
+fn convert_oai_content_part(part: OaiContentPart) -> Result<ContentPart, AdapterError> {
+    match part {
+        OaiContentPart::Text { text } => Ok(ContentPart::Text { text }),
+        OaiContentPart::ImageUrl { image_url } => {
+            validate_image_url(&image_url.url)?;
+            Ok(ContentPart::ImageUrl {
+                url: image_url.url,
+                detail: image_url.detail,
+            })
+        }
+        OaiContentPart::InputAudio { input_audio } => Ok(ContentPart::InputAudio {
+            data: input_audio.data,
+            format: input_audio.format,
+        }),
+    }
+}
+
+/// Accepts remote `http(s)` URLs and base64 `data:image/...` URLs.
+///
+/// Data URLs are passed through unchanged; only their header is checked.
+fn validate_image_url(url: &str) -> Result<(), AdapterError> {
+    if let Some(data) = url.strip_prefix("data:") {
+        let header = data.split_once(',').map(|(header, _)| header);
+        return match header {
+            Some(header) if header.starts_with("image/") && header.ends_with(";base64") => Ok(()),
+            _ => Err(AdapterError::ParseRequest(
+                "image data URL must be of the form data:image/<type>;base64,<data>".to_owned(),
+            )),
+        };
+    }
+
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(AdapterError::ParseRequest(format!(
+            "unsupported image URL scheme: {}",
+            url.split(':').next().unwrap_or_default()
+        )))
+    }
+}
+
 pub(super) fn convert_tool_choice(tc: OaiToolChoice) -> ToolChoice {
     match tc {
         OaiToolChoice::Simple(s) => match s.as_str() {
@@ -191,21 +518,25 @@ pub(super) fn convert_tool_choice(tc: OaiToolChoice) -> ToolChoice {
     }
 }
 
-pub(super) fn estimate_tokens(messages: &[Message]) -> u64 {
-    let total_chars: usize = messages
-        .iter()
-        .map(|m| match &m.content {
-            MessageContent::Text(t) => t.len(),
-            MessageContent::Parts(parts) => parts
-                .iter()
-                .map(|p| match p {
-                    mb_core::core::ContentPart::Text { text } => text.len(),
-                    mb_core::core::ContentPart::ImageUrl { url, .. } => url.len(),
-                })
-                .sum(),
-        })
-        .sum();
-    (total_chars / 4) as u64
+pub(super) fn convert_response_format(format: OaiResponseFormat) -> ResponseFormat {
+    match format {
+        OaiResponseFormat::Text => ResponseFormat::Text,
+        OaiResponseFormat::JsonObject => ResponseFormat::JsonObject,
+        OaiResponseFormat::JsonSchema { json_schema } => ResponseFormat::JsonSchema {
+            name: json_schema.name,
+            schema: json_schema
+                .schema
+                .unwrap_or_else(|| Value::Object(Default::default())),
+            strict: json_schema.strict,
+        },
+    }
+}
+
+pub(super) fn convert_stop(stop: OaiStop) -> Vec<String> {
+    match stop {
+        OaiStop::Single(s) => vec![s],
+        OaiStop::Multiple(v) => v,
+    }
 }
 
 pub(super) fn content_to_string(content: &MessageContent) -> String {
@@ -215,7 +546,8 @@ pub(super) fn content_to_string(content: &MessageContent) -> String {
             .iter()
             .filter_map(|p| match p {
                 mb_core::core::ContentPart::Text { text } => Some(text.as_str()),
-                mb_core::core::ContentPart::ImageUrl { .. } => None,
+                mb_core::core::ContentPart::ImageUrl { .. }
+                | mb_core::core::ContentPart::InputAudio { .. } => None,
             })
             .collect::<Vec<_>>()
             .join(""),
@@ -1,9 +1,23 @@
+pub mod admin;
+pub mod admission;
+#[cfg(feature = "audit")]
+pub mod audit;
 pub mod bootstrap;
+pub mod coalesce;
 pub mod config;
+pub mod dry_run;
+#[cfg(feature = "feedback")]
+pub mod export;
 #[cfg(feature = "feedback")]
 pub mod feedback;
 pub mod handler;
 pub mod health;
+pub mod idempotency;
 pub mod inbound;
+pub mod middleware;
 pub mod outbound;
+pub mod response_cache;
+pub mod sharded;
 pub mod stream_handler;
+pub mod tokenizer;
+pub mod warmup;
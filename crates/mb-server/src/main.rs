@@ -1,6 +1,6 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -9,13 +9,23 @@ use axum::routing::{get, post};
 use clap::{Parser, Subcommand};
 use tokio::sync::RwLock;
 
-use mb_core::core::{CacheAffinityMap, QuotaTracker};
+use mb_core::core::{
+    PrefixDepthTracker, QuotaTracker, RoundCounters, RoutingPolicy, ShardedAffinityMap,
+};
+use mb_server::admin;
+use mb_server::admission::AdmissionQueue;
 use mb_server::bootstrap::{self, CacheConfig};
+use mb_server::coalesce::Coalescer;
 use mb_server::config::AppConfig;
 use mb_server::handler::{self, AppState, BackendMeta};
 use mb_server::health::{self, HealthCheckManager, HttpHealthProbe};
+use mb_server::idempotency::IdempotencyCache;
 use mb_server::inbound::InboundAdapterRegistry;
+use mb_server::middleware;
 use mb_server::outbound::OutboundAdapterRegistry;
+use mb_server::response_cache::ResponseCache;
+use mb_server::sharded::ShardedMap;
+use mb_server::warmup;
 // stream_handler is available but streaming dispatch is handled by the
 // request handler detecting stream=true in the parsed canonical request.
 
@@ -25,7 +35,7 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Command>,
 
-    /// Path to the configuration file.
+    /// Path to the configuration file, or a directory of `*.toml` files.
     #[arg(short, long, default_value = "config.toml", global = true)]
     config: PathBuf,
 }
@@ -33,17 +43,61 @@ struct Cli {
 #[derive(Subcommand)]
 enum Command {
     /// Validate configuration file and exit.
-    Validate,
+    Validate {
+        /// Also probe every backend once and fail if any is unreachable.
+        #[arg(long)]
+        check_backends: bool,
+    },
     /// Generate a new API key.
-    Genkey,
+    Genkey {
+        /// Also register the key as this client id in the `--config` file.
+        #[arg(long)]
+        client: Option<String>,
+        /// Models the registered client may use; all models when omitted.
+        #[arg(long, value_delimiter = ',', requires = "client")]
+        models: Vec<String>,
+        /// Requests per minute for the registered client.
+        #[arg(long, default_value_t = 60, requires = "client")]
+        rate_limit_rpm: u32,
+    },
+    /// Export DPO pairs from the feedback database.
+    #[cfg(feature = "feedback")]
+    Export {
+        /// Path to the feedback sqlite database.
+        db_path: PathBuf,
+        /// Output layout: `json` or `jsonl`.
+        #[arg(long, default_value = "jsonl")]
+        format: mb_server::export::ExportFormat,
+        /// Only export conversations with this model.
+        #[arg(long)]
+        model: Option<String>,
+        /// Only export annotations made at or after this RFC 3339 time.
+        #[arg(long, value_parser = parse_rfc3339)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        /// Write to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Command::Validate) => run_validate(&cli.config),
-        Some(Command::Genkey) => run_genkey(),
+        Some(Command::Validate { check_backends }) => run_validate(&cli.config, check_backends),
+        Some(Command::Genkey {
+            client,
+            models,
+            rate_limit_rpm,
+        }) => run_genkey(&cli.config, client.as_deref(), &models, rate_limit_rpm),
+        #[cfg(feature = "feedback")]
+        Some(Command::Export {
+            db_path,
+            format,
+            model,
+            since,
+            output,
+        }) => run_export(&db_path, format, model, since, output.as_deref()),
         None => {
             let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
             rt.block_on(run_gateway(cli.config));
@@ -51,7 +105,7 @@ fn main() {
     }
 }
 
-fn run_validate(path: &std::path::Path) {
+fn run_validate(path: &std::path::Path, check_backends: bool) {
     let config = match AppConfig::from_file(path) {
         Ok(c) => c,
         Err(e) => {
@@ -60,16 +114,51 @@ fn run_validate(path: &std::path::Path) {
         }
     };
 
-    match bootstrap::into_runtime(config) {
-        Ok(_) => println!("Config valid: {}", path.display()),
+    let runtime = match bootstrap::into_runtime(config) {
+        Ok(runtime) => runtime,
         Err(e) => {
             eprintln!("Config invalid: {e}");
             std::process::exit(1);
         }
+    };
+    println!("Config valid: {}", path.display());
+
+    if check_backends && !run_backend_checks(&runtime) {
+        std::process::exit(1);
+    }
+}
+
+/// Probes each backend once and prints a line per backend. Returns whether
+/// all of them were reachable.
+fn run_backend_checks(runtime: &bootstrap::RuntimeConfig) -> bool {
+    let probe = HttpHealthProbe::new(Duration::from_millis(runtime.health_timeout_ms))
+        .expect("failed to build health probe HTTP client")
+        .with_model_verification(runtime.health_verify_models)
+        .with_api_keys(runtime.backend_api_keys.clone());
+    let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
+    let reports = rt.block_on(health::probe_once(&probe, &runtime.backends));
+
+    for report in &reports {
+        match &report.result {
+            Ok(latency) => println!(
+                "  ok      {} ({}) {}ms",
+                report.backend,
+                report.base_url,
+                latency.value()
+            ),
+            Err(e) => println!("  FAILED  {} ({}) {e}", report.backend, report.base_url),
+        }
     }
+    let unreachable = reports.iter().filter(|r| !r.is_reachable()).count();
+    if unreachable > 0 {
+        eprintln!("{unreachable} of {} backends unreachable", reports.len());
+    }
+    unreachable == 0
 }
 
-fn run_genkey() {
+/// Prints a fresh key; with `client`, first appends it to the config file
+/// as a new `[[clients]]` entry.
+fn run_genkey(path: &std::path::Path, client: Option<&str>, models: &[String], rpm: u32) {
     use rand::Rng;
     const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
     let mut rng = rand::rng();
@@ -79,7 +168,45 @@ fn run_genkey() {
             CHARSET[idx] as char
         })
         .collect();
-    println!("mb-sk-{key}");
+    let key = format!("mb-sk-{key}");
+
+    if let Some(id) = client {
+        if let Err(e) = mb_server::config::append_client(path, id, &key, models, rpm) {
+            eprintln!("Failed to register client: {e:#}");
+            std::process::exit(1);
+        }
+        eprintln!("Registered client {id} in {}", path.display());
+    }
+    println!("{key}");
+}
+
+#[cfg(feature = "feedback")]
+fn parse_rfc3339(value: &str) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+    chrono::DateTime::parse_from_rfc3339(value).map(|t| t.with_timezone(&chrono::Utc))
+}
+
+#[cfg(feature = "feedback")]
+fn run_export(
+    db_path: &std::path::Path,
+    format: mb_server::export::ExportFormat,
+    model: Option<String>,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    output: Option<&std::path::Path>,
+) {
+    let result = mb_server::export::open_store(db_path)
+        .and_then(|store| mb_server::export::export_dpo(&store, format, model, since))
+        .and_then(|rendered| match output {
+            Some(path) => std::fs::write(path, rendered)
+                .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", path.display())),
+            None => {
+                print!("{rendered}");
+                Ok(())
+            }
+        });
+    if let Err(e) = result {
+        eprintln!("Export failed: {e:#}");
+        std::process::exit(1);
+    }
 }
 
 async fn run_gateway(config_path: PathBuf) {
@@ -116,57 +243,154 @@ async fn run_gateway(config_path: PathBuf) {
                     base_url: b.base_url.clone(),
                     spec: b.spec,
                     api_key: runtime.backend_api_keys.get(&b.id).cloned(),
+                    model_map: b.model_map.clone(),
+                    tool_support: b.tool_support,
                 },
             )
         })
         .collect();
 
     // Initialize health manager
-    let health_manager = HealthCheckManager::new(&runtime.backends);
+    let affinity_map = Arc::new(ShardedAffinityMap::new(runtime.cache_config.max_entries));
+    let health_manager = HealthCheckManager::new(&runtime.backends)
+        .with_affinity_map(Arc::clone(&affinity_map))
+        .with_max_concurrent_probes(runtime.health_max_concurrent_probes);
     let backend_states = health_manager.shared_states();
+    let backend_latencies = Arc::new(health::BackendLatencies::default());
+    let routing_metrics = Arc::new(health::RoutingMetrics::default());
 
     // Start background health checks
     let probe = Arc::new(
         HttpHealthProbe::new(Duration::from_millis(runtime.health_timeout_ms))
-            .expect("failed to build health probe HTTP client"),
+            .expect("failed to build health probe HTTP client")
+            .with_model_verification(runtime.health_verify_models)
+            .with_api_keys(runtime.backend_api_keys.clone()),
     );
     let _health_handle = health_manager.start_background_checks(
         runtime.backends.clone(),
         Duration::from_secs(runtime.health_check_interval_secs),
         runtime.unhealthy_threshold,
         runtime.degraded_latency_ms,
-        probe,
+        Arc::clone(&probe) as Arc<dyn mb_core::core::HealthProbe>,
     );
 
     // Build AppState
     #[cfg(feature = "feedback")]
     let feedback = init_feedback_state().await;
+    #[cfg(feature = "audit")]
+    let audit = init_audit_log(runtime.audit_db_path.clone()).await;
+    #[cfg(not(feature = "audit"))]
+    if runtime.audit_db_path.is_some() {
+        tracing::warn!(
+            "audit.db_path is set but this build lacks the `audit` feature; audit log disabled"
+        );
+    }
 
     let state = Arc::new(AppState {
         auth: runtime.auth_service,
         inbound_registry: InboundAdapterRegistry::new(),
         outbound_registry: OutboundAdapterRegistry::new(),
         backend_states: backend_states.clone(),
+        backend_latencies: Arc::clone(&backend_latencies),
+        in_flight: Arc::default(),
+        routing_metrics: Arc::clone(&routing_metrics),
         rate_limiters: RwLock::new(HashMap::new()),
+        token_rate_limiters: ShardedMap::new(),
         quota_tracker: RwLock::new(QuotaTracker::new()),
-        affinity_map: RwLock::new(CacheAffinityMap::new(runtime.cache_config.max_entries)),
-        http_client: reqwest::Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("failed to build HTTP client"),
-        routing_strategy: runtime.routing_strategy,
+        affinity_map: Arc::clone(&affinity_map),
+        token_counters: runtime.token_counters.clone(),
+        prefix_tracker: std::sync::Mutex::new(PrefixDepthTracker::new()),
+        http_client: handler::backend_http_client(
+            Duration::from_millis(runtime.connect_timeout_ms),
+            Duration::from_millis(runtime.read_timeout_ms),
+            Some(handler::backend_total_timeout(Duration::from_secs(
+                runtime.request_timeout_secs,
+            ))),
+        )
+        .expect("failed to build HTTP client"),
+        stream_http_client: handler::backend_http_client(
+            Duration::from_millis(runtime.connect_timeout_ms),
+            Duration::from_millis(runtime.stream_read_timeout_ms),
+            None,
+        )
+        .expect("failed to build streaming HTTP client"),
+        routing_policy: RoutingPolicy::new(
+            runtime.routing_strategy,
+            runtime.model_strategies.clone(),
+        ),
+        model_capabilities: runtime.model_capabilities.clone(),
+        capability_check: runtime.capability_check,
         cache_config: CacheConfig {
             enabled: runtime.cache_config.enabled,
             prefix_depth: runtime.cache_config.prefix_depth,
             max_entries: runtime.cache_config.max_entries,
+            skip_contended: runtime.cache_config.skip_contended,
         },
-        round_counter: AtomicUsize::new(0),
+        verify_response_model: runtime.verify_response_model,
+        validate_json_output: runtime.validate_json_output,
+        penalty_range: runtime.penalty_range,
+        allow_empty_choices: runtime.allow_empty_choices,
+        require_user_message: runtime.require_user_message,
+        merge_system_messages: runtime.merge_system_messages,
+        model_casing: runtime.model_casing,
+        default_model: runtime.default_model,
+        provider_prefix: runtime.provider_prefix,
+        fanout: runtime.fanout,
+        max_fanout: runtime.max_fanout,
+        coalescer: runtime.coalesce.then(Coalescer::new),
+        response_cache: runtime.response_cache_entries.map(ResponseCache::new),
+        idempotency_cache: runtime
+            .idempotency
+            .map(|(entries, ttl_ms)| IdempotencyCache::new(entries, ttl_ms)),
+        retry_on_429: runtime.retry_on_429,
+        retry_on_429_max_backoff: Duration::from_millis(runtime.retry_on_429_max_backoff_ms),
+        admission: runtime
+            .queue_timeout_ms
+            .map(|timeout_ms| Arc::new(AdmissionQueue::new(Duration::from_millis(timeout_ms)))),
+        round_counters: RoundCounters::new(
+            runtime
+                .backends
+                .iter()
+                .flat_map(|b| b.models.iter().cloned()),
+        ),
         rate_limit_rpm,
+        model_rate_limit_rpm: runtime.model_rate_limits,
+        backend_rate_limit_rpm: runtime.backend_rate_limits,
+        backend_rate_limiters: ShardedMap::new(),
+        model_rate_limiters: ShardedMap::new(),
+        trust_forwarded: runtime.trust_forwarded,
+        trusted_proxy_hops: runtime.trusted_proxy_hops,
+        ip_rate_limit_rpm: runtime.ip_rate_limit_rpm,
+        ip_rate_limiters: ShardedMap::new(),
+        error_verbosity: runtime.error_verbosity,
+        redact_log_content: runtime.redact_log_content,
+        attribution_headers: runtime.attribution_headers,
+        sse_keepalive: Duration::from_secs(runtime.sse_keepalive_secs),
+        auth_schemes: runtime.auth_schemes.clone(),
+        admin_key: runtime.admin_key.clone(),
         backends_by_id,
         #[cfg(feature = "feedback")]
         feedback,
+        #[cfg(feature = "audit")]
+        audit,
     });
 
+    // One-shot warm-up, detached so slow model loads never delay serving.
+    if !runtime.warmup_backends.is_empty() {
+        let state = Arc::clone(&state);
+        let backends = runtime.backends.clone();
+        let warmup_ids = runtime.warmup_backends.clone();
+        tokio::spawn(async move {
+            let outcome =
+                warmup::warm_up_backends(&state, probe.as_ref(), &backends, &warmup_ids).await;
+            tracing::info!(
+                succeeded = outcome.succeeded,
+                failed = outcome.failed,
+                "backend warm-up finished"
+            );
+        });
+    }
+
     // Build axum router
     // Both streaming and non-streaming requests arrive via POST.
     // The handler inspects the parsed request's `stream` field to dispatch.
@@ -174,13 +398,33 @@ async fn run_gateway(config_path: PathBuf) {
     // Streaming is dispatched internally based on the request body.
     let app = axum::Router::new()
         .route("/v1/chat/completions", post(handler::handle_completion))
+        .route("/cache/stats", get(handler::cache_stats_handler))
+        .route("/admin/clients", get(admin::list_clients_handler))
+        .route(
+            "/admin/clients/{id}/revoke",
+            post(admin::revoke_client_handler),
+        )
+        .route(
+            "/admin/clients/{id}/quota/reset",
+            post(admin::reset_quota_handler),
+        )
         .route(
             "/health",
+            get({
+                let states = Arc::clone(&backend_states);
+                let latencies = Arc::clone(&backend_latencies);
+                move || health::health_handler(states, latencies)
+            }),
+        )
+        .route(
+            "/metrics",
             get({
                 let states = backend_states;
-                move || health::health_handler(states)
+                let latencies = backend_latencies;
+                move || health::metrics_handler(states, latencies, affinity_map, routing_metrics)
             }),
-        );
+        )
+        .route("/openapi.json", get(openapi_handler));
 
     #[cfg(feature = "feedback")]
     let app = app
@@ -188,9 +432,24 @@ async fn run_gateway(config_path: PathBuf) {
         .route(
             "/v1/my-annotations",
             get(mb_server::feedback::get_my_annotations),
+        )
+        .route(
+            "/v1/my-annotations/stats",
+            get(mb_server::feedback::get_my_annotation_stats),
+        )
+        .route(
+            "/v1/my-conversations",
+            get(mb_server::feedback::get_my_conversations),
+        )
+        .route(
+            "/v1/feedback/conversations/{id}/export",
+            get(mb_server::feedback::get_conversation_export),
         );
 
-    let app = app
+    let app =
+        middleware::with_request_timeout(app, Duration::from_secs(runtime.request_timeout_secs));
+    let app = middleware::with_panic_recovery(app);
+    let app = middleware::with_access_log(app, Arc::clone(&state))
         .layer(DefaultBodyLimit::max(2 * 1024 * 1024))
         .with_state(state);
 
@@ -200,24 +459,41 @@ async fn run_gateway(config_path: PathBuf) {
         .expect("failed to bind listener");
     tracing::info!("Listening on {}", runtime.listen_addr);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .expect("server error");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .expect("server error");
 
     tracing::info!("Gateway shut down");
 }
 
+/// Hand-maintained description of the routes registered in `run_gateway`;
+/// update it alongside the router.
+const OPENAPI_DOCUMENT: &str = include_str!("../openapi.json");
+
+async fn openapi_handler() -> impl axum::response::IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "application/json")],
+        OPENAPI_DOCUMENT,
+    )
+}
+
 #[cfg(feature = "feedback")]
 async fn init_feedback_state() -> Option<mb_server::feedback::FeedbackState> {
     let db_path =
         std::env::var("MB_FEEDBACK_DB_PATH").unwrap_or_else(|_| "feedback.sqlite".to_owned());
     let db_path_for_task = db_path.clone();
+    let pool_size = feedback_pool_size();
 
     let init_result = tokio::task::spawn_blocking(move || {
-        let sqlite_store =
-            mb_feedback::SqliteFeedbackStore::new(std::path::Path::new(&db_path_for_task))
-                .map_err(|err| err.to_string())?;
+        let sqlite_store = mb_feedback::SqliteFeedbackStore::with_pool_size(
+            std::path::Path::new(&db_path_for_task),
+            pool_size,
+        )
+        .map_err(|err| err.to_string())?;
         mb_feedback::FeedbackStore::init(&sqlite_store).map_err(|err| err.to_string())?;
         let store: Arc<dyn mb_feedback::FeedbackStore> = Arc::new(sqlite_store);
         Ok::<Arc<dyn mb_feedback::FeedbackStore>, String>(store)
@@ -226,8 +502,16 @@ async fn init_feedback_state() -> Option<mb_server::feedback::FeedbackState> {
 
     match init_result {
         Ok(Ok(store)) => {
+            let sample_rate = feedback_sample_rate();
+            let honor_store_flag = feedback_honor_store_flag();
+            let redact_content = feedback_redact_content();
             tracing::info!("feedback store initialized at {}", db_path);
-            Some(mb_server::feedback::FeedbackState { store })
+            Some(mb_server::feedback::FeedbackState {
+                store,
+                sample_rate,
+                honor_store_flag,
+                redact_content,
+            })
         }
         Ok(Err(err)) => {
             tracing::warn!(
@@ -248,6 +532,89 @@ async fn init_feedback_state() -> Option<mb_server::feedback::FeedbackState> {
     }
 }
 
+#[cfg(feature = "audit")]
+async fn init_audit_log(db_path: Option<PathBuf>) -> Option<Arc<mb_server::audit::AuditLog>> {
+    let db_path = db_path?;
+    let path_for_task = db_path.clone();
+    match tokio::task::spawn_blocking(move || mb_server::audit::AuditLog::open(&path_for_task))
+        .await
+    {
+        Ok(Ok(log)) => {
+            tracing::info!("audit log initialized at {}", db_path.display());
+            Some(Arc::new(log))
+        }
+        Ok(Err(err)) => {
+            tracing::warn!(
+                error = %err,
+                db_path = %db_path.display(),
+                "failed to open audit log; audit logging disabled"
+            );
+            None
+        }
+        Err(err) => {
+            tracing::warn!(
+                error = %err,
+                db_path = %db_path.display(),
+                "audit initialization task failed; audit logging disabled"
+            );
+            None
+        }
+    }
+}
+
+#[cfg(feature = "feedback")]
+fn feedback_sample_rate() -> f64 {
+    let Ok(raw) = std::env::var("MB_FEEDBACK_SAMPLE_RATE") else {
+        return 1.0;
+    };
+
+    match raw.trim().parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+        _ => {
+            tracing::warn!(
+                value = %raw,
+                "invalid MB_FEEDBACK_SAMPLE_RATE, expected 0.0-1.0; sampling all conversations"
+            );
+            1.0
+        }
+    }
+}
+
+/// `MB_FEEDBACK_HONOR_STORE=1` (or `true`) persists `store: true` requests
+/// regardless of the sample rate.
+#[cfg(feature = "feedback")]
+fn feedback_honor_store_flag() -> bool {
+    std::env::var("MB_FEEDBACK_HONOR_STORE")
+        .is_ok_and(|raw| raw.trim() == "1" || raw.trim().eq_ignore_ascii_case("true"))
+}
+
+/// `MB_FEEDBACK_REDACT_CONTENT=1` (or `true`) stores turns without their
+/// text.
+#[cfg(feature = "feedback")]
+fn feedback_redact_content() -> bool {
+    std::env::var("MB_FEEDBACK_REDACT_CONTENT")
+        .is_ok_and(|raw| raw.trim() == "1" || raw.trim().eq_ignore_ascii_case("true"))
+}
+
+#[cfg(feature = "feedback")]
+fn feedback_pool_size() -> usize {
+    let Ok(raw) = std::env::var("MB_FEEDBACK_POOL_SIZE") else {
+        return mb_feedback::DEFAULT_POOL_SIZE;
+    };
+
+    match raw.trim().parse::<usize>() {
+        Ok(size) if size > 0 => size,
+        _ => {
+            tracing::warn!(
+                value = %raw,
+                default = mb_feedback::DEFAULT_POOL_SIZE,
+                "invalid MB_FEEDBACK_POOL_SIZE, expected a positive integer; using the default"
+            );
+            mb_feedback::DEFAULT_POOL_SIZE
+        }
+    }
+}
+
 fn init_tracing(level: &str, format: &str) {
     use tracing_subscriber::EnvFilter;
 
@@ -283,3 +650,40 @@ async fn shutdown_signal() {
     }
     tracing::info!("Shutdown signal received");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_document_lists_gateway_routes() {
+        let doc: serde_json::Value =
+            serde_json::from_str(OPENAPI_DOCUMENT).expect("openapi.json is valid JSON");
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+
+        let paths = doc["paths"].as_object().expect("paths object");
+        for path in [
+            "/v1/chat/completions",
+            "/health",
+            "/metrics",
+            "/cache/stats",
+            "/admin/clients",
+            "/admin/clients/{id}/revoke",
+            "/admin/clients/{id}/quota/reset",
+            "/v1/feedback",
+            "/v1/my-annotations",
+            "/v1/my-annotations/stats",
+            "/v1/my-conversations",
+            "/v1/feedback/conversations/{id}/export",
+            "/openapi.json",
+        ] {
+            assert!(paths.contains_key(path), "missing {path}");
+        }
+
+        let error = &doc["components"]["schemas"]["Error"]["properties"]["error"];
+        assert_eq!(
+            error["required"],
+            serde_json::json!(["message", "type", "code"])
+        );
+    }
+}
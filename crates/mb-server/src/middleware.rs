@@ -0,0 +1,314 @@
+use std::any::Any;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::error_handling::HandleErrorLayer;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use mb_core::core::{GatewayError, RateLimiter};
+
+use crate::config::{AttributionHeaders, ErrorVerbosity};
+use crate::handler::{render_gateway_error, AppState};
+use tower::timeout::error::Elapsed;
+use tower::timeout::TimeoutLayer;
+use tower::{BoxError, ServiceBuilder};
+use tower_http::catch_panic::CatchPanicLayer;
+
+// ---------------------------------------------------------------------------
+// Request timeout — hard ceiling on total handler time
+// ---------------------------------------------------------------------------
+
+/// Wraps every route of `router` in a timeout that answers with a 504
+/// OpenAI-style error envelope once `timeout` elapses.
+///
+/// Only the time until the response head is produced is bounded; streaming
+/// bodies keep flowing after the handler has returned.
+pub fn with_request_timeout<S>(router: Router<S>, timeout: Duration) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(move |err: BoxError| async move {
+                layer_error_to_response(err, timeout)
+            }))
+            .layer(TimeoutLayer::new(timeout)),
+    )
+}
+
+fn layer_error_to_response(err: BoxError, timeout: Duration) -> Response {
+    let (status, error_type, message) = if err.is::<Elapsed>() {
+        (
+            StatusCode::GATEWAY_TIMEOUT,
+            "timeout_error",
+            format!("request timed out after {}ms", timeout.as_millis()),
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "server_error",
+            format!("unhandled middleware error: {err}"),
+        )
+    };
+
+    let body = serde_json::json!({
+        "error": {
+            "message": message,
+            "type": error_type,
+            "code": status.as_u16(),
+        }
+    });
+
+    (status, axum::Json(body)).into_response()
+}
+
+// ---------------------------------------------------------------------------
+// Panic recovery — a panicking handler still answers with a 500
+// ---------------------------------------------------------------------------
+
+/// Wraps every route of `router` so a panic inside a handler becomes a 500
+/// OpenAI-style error envelope instead of a dropped connection.
+///
+/// The panic message is logged under a fresh correlation id and never sent
+/// to the client, regardless of the configured error verbosity.
+pub fn with_panic_recovery<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(CatchPanicLayer::custom(panic_to_response))
+}
+
+fn panic_to_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let detail = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("non-string panic payload");
+    render_gateway_error(
+        GatewayError::Internal(format!("handler panicked: {detail}")),
+        ErrorVerbosity::Terse,
+        false,
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Client IP resolution, per-IP rate limiting and access logging
+// ---------------------------------------------------------------------------
+
+/// Resolved client address, inserted as a request extension by
+/// [`access_log`] for downstream handlers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Per-IP limiter maps are pruned of idle entries past this size, counted
+/// across all shards.
+const MAX_IDLE_IP_LIMITERS: usize = 10_000;
+
+/// Wraps every route of `router` with [`access_log`].
+///
+/// Serve the app with `into_make_service_with_connect_info::<SocketAddr>()`
+/// so the peer address is available; without it only forwarded headers
+/// (when trusted) can identify the client.
+pub fn with_access_log(
+    router: Router<Arc<AppState>>,
+    state: Arc<AppState>,
+) -> Router<Arc<AppState>> {
+    router.layer(axum::middleware::from_fn_with_state(state, access_log))
+}
+
+/// Resolves the client IP, enforces the optional per-IP rate limit and logs
+/// one line per request.
+pub async fn access_log(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let trusted_hops = state.trust_forwarded.then_some(state.trusted_proxy_hops);
+    let ip = client_ip(req.headers(), peer, trusted_hops);
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let (app_title, app_referer) = match state.attribution_headers {
+        AttributionHeaders::Off => (None, None),
+        _ => crate::handler::attribution_from_headers(req.headers()),
+    };
+
+    let response = match check_ip_rate_limit(&state, ip) {
+        Err(err) => crate::handler::gateway_error_to_response(err),
+        Ok(()) => {
+            if let Some(ip) = ip {
+                req.extensions_mut().insert(ClientIp(ip));
+            }
+            next.run(req).await
+        }
+    };
+
+    tracing::info!(
+        client_ip = ip.map(|ip| ip.to_string()).as_deref().unwrap_or("-"),
+        %method,
+        path = %path,
+        status = response.status().as_u16(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        app_title = app_title.as_deref().unwrap_or("-"),
+        app_referer = app_referer.as_deref().unwrap_or("-"),
+        "request"
+    );
+    response
+}
+
+fn check_ip_rate_limit(state: &AppState, ip: Option<IpAddr>) -> Result<(), GatewayError> {
+    let (Some(rpm), Some(ip)) = (state.ip_rate_limit_rpm, ip) else {
+        return Ok(());
+    };
+
+    let now_ms = crate::handler::now_ms();
+    let limiters = &state.ip_rate_limiters;
+    let max_per_shard = MAX_IDLE_IP_LIMITERS.div_ceil(limiters.shard_count());
+    limiters.with(&ip, |shard| {
+        if shard.len() >= max_per_shard && !shard.contains_key(&ip) {
+            shard.retain(|_, limiter| limiter.remaining(now_ms) < limiter.limit());
+        }
+        shard
+            .entry(ip)
+            .or_insert_with(|| RateLimiter::new(60_000, rpm))
+            .check(now_ms)
+            .map_err(GatewayError::RateLimited)
+    })
+}
+
+/// Determines the originating client address.
+///
+/// With `trusted_hops` set, the hop that many entries from the right of
+/// `Forwarded` (RFC 7239) or, failing that, `X-Forwarded-For` wins over the
+/// socket peer: each trusted proxy appends the address it saw, so entries
+/// further left are whatever the client sent. Without it those headers are
+/// ignored, since any client can set them.
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    trusted_hops: Option<usize>,
+) -> Option<IpAddr> {
+    if let Some(hops) = trusted_hops {
+        if let Some(ip) = forwarded_for(headers, hops).or_else(|| x_forwarded_for(headers, hops)) {
+            return Some(ip);
+        }
+    }
+    peer
+}
+
+/// The element `hops` from the right of a comma-separated hop list, or the
+/// leftmost when the list is shorter.
+fn trusted_hop(value: &str, hops: usize) -> Option<&str> {
+    let hop_list: Vec<&str> = value.split(',').collect();
+    hop_list
+        .get(hop_list.len().saturating_sub(hops.max(1)))
+        .copied()
+}
+
+fn forwarded_for(headers: &HeaderMap, hops: usize) -> Option<IpAddr> {
+    let value = headers.get("forwarded")?.to_str().ok()?;
+    trusted_hop(value, hops)?
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+        .and_then(|(_, node)| parse_node(node))
+}
+
+fn x_forwarded_for(headers: &HeaderMap, hops: usize) -> Option<IpAddr> {
+    let value = headers.get("x-forwarded-for")?.to_str().ok()?;
+    parse_node(trusted_hop(value, hops)?)
+}
+
+/// Parses a forwarded node: bare IPv4/IPv6, `ip:port`, or `"[v6]:port"`.
+fn parse_node(raw: &str) -> Option<IpAddr> {
+    let node = raw.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(v6, _)| v6.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).expect("valid header"));
+        }
+        map
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().expect("valid ip")
+    }
+
+    #[test]
+    fn test_untrusted_forwarded_headers_ignored() {
+        let peer = Some(ip("10.0.0.1"));
+        let map = headers(&[
+            ("x-forwarded-for", "203.0.113.7"),
+            ("forwarded", "for=198.51.100.2"),
+        ]);
+
+        assert_eq!(client_ip(&map, peer, None), peer);
+    }
+
+    #[test]
+    fn test_trusted_x_forwarded_for_uses_rightmost_trusted_hop() {
+        // The client spoofed 198.51.100.9; the proxy appended 203.0.113.7.
+        let map = headers(&[("x-forwarded-for", "198.51.100.9, 203.0.113.7")]);
+        let peer = Some(ip("10.0.0.1"));
+
+        assert_eq!(client_ip(&map, peer, Some(1)), Some(ip("203.0.113.7")));
+        // Two proxies: the second appended the first proxy's view.
+        assert_eq!(client_ip(&map, peer, Some(2)), Some(ip("198.51.100.9")));
+        // Fewer hops than proxies falls back to the leftmost entry.
+        assert_eq!(client_ip(&map, peer, Some(5)), Some(ip("198.51.100.9")));
+    }
+
+    #[test]
+    fn test_trusted_forwarded_preferred_over_x_forwarded_for() {
+        let map = headers(&[
+            (
+                "forwarded",
+                "for=10.0.0.2, for=\"[2001:db8::1]:4711\";proto=https",
+            ),
+            ("x-forwarded-for", "203.0.113.7"),
+        ]);
+
+        assert_eq!(client_ip(&map, None, Some(1)), Some(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn test_trusted_falls_back_to_peer() {
+        let peer = Some(ip("10.0.0.1"));
+
+        assert_eq!(client_ip(&HeaderMap::new(), peer, Some(1)), peer);
+        let garbage = headers(&[("x-forwarded-for", "unknown")]);
+        assert_eq!(client_ip(&garbage, peer, Some(1)), peer);
+    }
+
+    #[test]
+    fn test_parse_node_variants() {
+        assert_eq!(parse_node(" 192.0.2.60 "), Some(ip("192.0.2.60")));
+        assert_eq!(parse_node("192.0.2.60:8080"), Some(ip("192.0.2.60")));
+        assert_eq!(parse_node("\"[2001:db8::1]\""), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("_hidden"), None);
+    }
+}
@@ -24,6 +24,14 @@ impl OutboundAdapterRegistry {
         Self { adapters }
     }
 
+    /// Registers `adapter` under its own backend spec, replacing any adapter
+    /// already registered for that spec.
+    pub fn register(&mut self, adapter: Box<dyn OutboundAdapter>) {
+        let spec = adapter.backend_spec();
+        self.adapters.retain(|(s, _)| *s != spec);
+        self.adapters.push((spec, adapter));
+    }
+
     pub fn get(&self, spec: &BackendSpec) -> Option<&dyn OutboundAdapter> {
         self.adapters
             .iter()
@@ -57,4 +65,14 @@ mod tests {
         assert!(adapter.is_some());
         assert_eq!(adapter.unwrap().backend_spec(), BackendSpec::Ollama);
     }
+
+    #[test]
+    fn test_register_replaces_adapter_for_spec() {
+        let mut registry = OutboundAdapterRegistry::new();
+        registry.register(Box::new(ollama::OllamaOutboundAdapter));
+        assert_eq!(registry.adapters.len(), 2);
+        assert!(registry
+            .get(&BackendSpec::Ollama)
+            .is_some_and(|adapter| adapter.supports_streaming()));
+    }
 }
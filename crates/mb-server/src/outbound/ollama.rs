@@ -1,7 +1,7 @@
 use mb_core::core::{
     AdapterError, BackendInfo, BackendSpec, CanonicalRequest, CanonicalResponse,
     CanonicalStreamChunk, Choice, DeltaContent, FinishReason, Message, MessageContent, ModelId,
-    OutboundAdapter, Role, StreamChoice, TokenUsage,
+    OutboundAdapter, ResponseFormat, Role, StreamChoice, TokenUsage,
 };
 
 pub struct OllamaOutboundAdapter;
@@ -11,7 +11,28 @@ impl OutboundAdapter for OllamaOutboundAdapter {
         BackendSpec::Ollama
     }
 
-    fn build_request_body(&self, req: &CanonicalRequest) -> Result<Vec<u8>, AdapterError> {
+    fn build_request_body(
+        &self,
+        req: &CanonicalRequest,
+        backend: &BackendInfo,
+    ) -> Result<Vec<u8>, AdapterError> {
+        let req = backend.tool_support.apply(req)?;
+        if req.messages.iter().any(|m| has_image_content(&m.content)) {
+            return Err(AdapterError::UnsupportedFeature(
+                "image input is not supported by ollama backends".to_owned(),
+            ));
+        }
+        if req.messages.iter().any(|m| has_audio_content(&m.content)) {
+            return Err(AdapterError::UnsupportedFeature(
+                "audio input is not supported by ollama backends".to_owned(),
+            ));
+        }
+        if req.echo.is_some() || req.suffix.is_some() {
+            return Err(AdapterError::UnsupportedFeature(
+                "echo and suffix are not supported by ollama chat backends".to_owned(),
+            ));
+        }
+
         let messages: Vec<serde_json::Value> = req
             .messages
             .iter()
@@ -24,7 +45,7 @@ impl OutboundAdapter for OllamaOutboundAdapter {
             .collect();
 
         let mut body = serde_json::json!({
-            "model": req.model.as_str(),
+            "model": backend.wire_model(&req.model),
             "messages": messages,
             "stream": req.stream,
         });
@@ -53,6 +74,12 @@ impl OutboundAdapter for OllamaOutboundAdapter {
         if let Some(stop) = &req.params.stop {
             options.insert("stop".into(), serde_json::json!(stop));
         }
+        if let Some(k) = req.params.top_k {
+            options.insert("top_k".into(), k.into());
+        }
+        if let Some(p) = req.params.min_p {
+            options.insert("min_p".into(), p.into());
+        }
         if !options.is_empty() {
             obj.insert("options".into(), serde_json::Value::Object(options));
         }
@@ -62,6 +89,17 @@ impl OutboundAdapter for OllamaOutboundAdapter {
             obj.insert("num_predict".into(), m.into());
         }
 
+        // Ollama takes "json" or a JSON schema as the structured output format.
+        match &req.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                obj.insert("format".into(), "json".into());
+            }
+            Some(ResponseFormat::JsonSchema { schema, .. }) => {
+                obj.insert("format".into(), schema.clone());
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
+
         serde_json::to_vec(&body).map_err(|e| AdapterError::FormatResponse(e.to_string()))
     }
 
@@ -80,8 +118,16 @@ impl OutboundAdapter for OllamaOutboundAdapter {
             total_tokens: prompt_tokens.saturating_add(completion_tokens),
         };
 
+        // Ollama sends neither an id nor a unix timestamp; synthesize an
+        // OpenAI-style id and take `created` from `created_at` when present.
+        let created = resp
+            .created_at
+            .as_deref()
+            .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+            .map_or_else(|| chrono::Utc::now().timestamp(), |at| at.timestamp());
+
         Ok(CanonicalResponse {
-            id: String::new(),
+            id: format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
             model: ModelId::new(resp.model),
             choices: vec![Choice {
                 index: 0,
@@ -96,9 +142,10 @@ impl OutboundAdapter for OllamaOutboundAdapter {
                 } else {
                     FinishReason::Length
                 },
+                logprobs: None,
             }],
             usage,
-            created: 0,
+            created: u64::try_from(created).unwrap_or(0),
         })
     }
 
@@ -117,6 +164,7 @@ impl OutboundAdapter for OllamaOutboundAdapter {
                 choices: vec![StreamChoice {
                     index: 0,
                     delta: DeltaContent::Finish(FinishReason::Stop),
+                    logprobs: None,
                 }],
             }));
         }
@@ -130,6 +178,7 @@ impl OutboundAdapter for OllamaOutboundAdapter {
             choices: vec![StreamChoice {
                 index: 0,
                 delta: DeltaContent::Text(text),
+                logprobs: None,
             }],
         }))
     }
@@ -150,6 +199,7 @@ impl OutboundAdapter for OllamaOutboundAdapter {
 #[derive(serde::Deserialize)]
 struct OllamaResponseWire {
     model: String,
+    created_at: Option<String>,
     message: OllamaMessageWire,
     done: Option<bool>,
     #[serde(flatten)]
@@ -201,6 +251,24 @@ fn parse_role(s: &str) -> Result<Role, AdapterError> {
     }
 }
 
+fn has_image_content(content: &MessageContent) -> bool {
+    match content {
+        MessageContent::Text(_) => false,
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .any(|p| matches!(p, mb_core::core::ContentPart::ImageUrl { .. })),
+    }
+}
+
+fn has_audio_content(content: &MessageContent) -> bool {
+    match content {
+        MessageContent::Text(_) => false,
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .any(|p| matches!(p, mb_core::core::ContentPart::InputAudio { .. })),
+    }
+}
+
 fn content_to_text(content: &MessageContent) -> String {
     match content {
         MessageContent::Text(t) => t.clone(),
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use super::*;
-use mb_core::core::{ClientId, GenerationParams, RequestId, RequestMetadata};
+use mb_core::core::{ClientId, GenerationParams, RequestId, RequestMetadata, ToolSupport};
 use serde_json::Value;
 
 fn make_request(
@@ -13,16 +15,41 @@ fn make_request(
         params,
         tools: None,
         tool_choice: None,
+        parallel_tool_calls: None,
+        logprobs: None,
+        top_logprobs: None,
+        response_format: None,
+        echo: None,
+        suffix: None,
+        n: None,
         stream,
         metadata: RequestMetadata {
             request_id: RequestId::new("req-test"),
             client_id: ClientId::new("client-test"),
             estimated_input_tokens: 10,
             prefix_hash: None,
+            app_title: None,
+            app_referer: None,
+            client_metadata: None,
+            store: false,
+            provider_hint: None,
         },
     }
 }
 
+fn make_backend() -> BackendInfo {
+    BackendInfo {
+        id: mb_core::core::BackendId::new("test"),
+        spec: BackendSpec::Ollama,
+        models: vec![ModelId::new("llama3-70b")],
+        max_concurrent: 4,
+        base_url: "http://localhost:11434".to_owned(),
+        model_map: HashMap::new(),
+        tool_support: ToolSupport::Native,
+        cost_weight: 0,
+    }
+}
+
 fn simple_message(role: Role, text: &str) -> Message {
     Message {
         role,
@@ -45,7 +72,7 @@ fn test_build_request_body_simple() {
         false,
     );
 
-    let body = adapter.build_request_body(&req).unwrap();
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["model"], "llama3-70b");
@@ -58,9 +85,150 @@ fn test_build_request_body_simple() {
 }
 
 #[test]
-fn test_build_request_body_with_options() {
+fn test_build_request_body_uses_backend_model_name() {
+    let adapter = OllamaOutboundAdapter;
+    let req = make_request(
+        vec![simple_message(Role::User, "Hello!")],
+        GenerationParams::default(),
+        false,
+    );
+    let mut backend = make_backend();
+    backend
+        .model_map
+        .insert(ModelId::new("llama3-70b"), "llama3:70b".to_owned());
+
+    let body = adapter.build_request_body(&req, &backend).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["model"], "llama3:70b");
+}
+
+#[test]
+fn test_build_request_body_rejects_image_parts() {
+    let adapter = OllamaOutboundAdapter;
+    let req = make_request(
+        vec![Message {
+            role: Role::User,
+            content: MessageContent::Parts(vec![
+                mb_core::core::ContentPart::Text {
+                    text: "Describe this.".to_owned(),
+                },
+                mb_core::core::ContentPart::ImageUrl {
+                    url: "https://example.com/cat.png".to_owned(),
+                    detail: None,
+                },
+            ]),
+            name: None,
+            tool_call_id: None,
+        }],
+        GenerationParams::default(),
+        false,
+    );
+
+    let result = adapter.build_request_body(&req, &make_backend());
+    assert!(matches!(result, Err(AdapterError::UnsupportedFeature(_))));
+}
+
+#[test]
+fn test_build_request_body_rejects_audio_parts() {
+    let req = make_request(
+        vec![Message {
+            role: Role::User,
+            content: MessageContent::Parts(vec![mb_core::core::ContentPart::InputAudio {
+                data: "UklGRiQAAABXQVZF".to_owned(),
+                format: "wav".to_owned(),
+            }]),
+            name: None,
+            tool_call_id: None,
+        }],
+        GenerationParams::default(),
+        false,
+    );
+
+    let result = OllamaOutboundAdapter.build_request_body(&req, &make_backend());
+    assert!(matches!(
+        result,
+        Err(AdapterError::UnsupportedFeature(ref msg)) if msg.contains("audio")
+    ));
+}
+
+#[test]
+fn test_build_request_body_rejects_echo_and_suffix() {
+    let mut req = make_request(
+        vec![simple_message(Role::User, "def add(a, b):")],
+        GenerationParams::default(),
+        false,
+    );
+    req.suffix = Some("    return result".to_owned());
+    let result = OllamaOutboundAdapter.build_request_body(&req, &make_backend());
+    assert!(matches!(
+        result,
+        Err(AdapterError::UnsupportedFeature(ref msg)) if msg.contains("suffix")
+    ));
+
+    req.suffix = None;
+    req.echo = Some(true);
+    let result = OllamaOutboundAdapter.build_request_body(&req, &make_backend());
+    assert!(matches!(result, Err(AdapterError::UnsupportedFeature(_))));
+}
+
+#[test]
+fn test_build_request_body_tools_unsupported() {
     let adapter = OllamaOutboundAdapter;
+    let mut tool_result = simple_message(Role::Tool, "18C");
+    tool_result.tool_call_id = Some("call_1".to_owned());
     let req = make_request(
+        vec![simple_message(Role::User, "Weather?"), tool_result],
+        GenerationParams::default(),
+        false,
+    );
+    let mut backend = make_backend();
+
+    backend.tool_support = ToolSupport::Reject;
+    let result = adapter.build_request_body(&req, &backend);
+    assert!(matches!(result, Err(AdapterError::UnsupportedFeature(_))));
+
+    backend.tool_support = ToolSupport::Downgrade;
+    let body = adapter.build_request_body(&req, &backend).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["messages"][1]["role"], "user");
+    assert_eq!(json["messages"][1]["content"], "[tool result call_1]\n18C");
+}
+
+#[test]
+fn test_build_request_body_response_format() {
+    let adapter = OllamaOutboundAdapter;
+    let mut req = make_request(
+        vec![simple_message(Role::User, "List two colors.")],
+        GenerationParams::default(),
+        false,
+    );
+    let schema = serde_json::json!({"type": "array", "items": {"type": "string"}});
+
+    req.response_format = Some(ResponseFormat::JsonSchema {
+        name: "colors".to_owned(),
+        schema: schema.clone(),
+        strict: Some(true),
+    });
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["format"], schema);
+
+    req.response_format = Some(ResponseFormat::JsonObject);
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["format"], "json");
+
+    req.response_format = Some(ResponseFormat::Text);
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.get("format").is_none());
+}
+
+#[test]
+fn test_build_request_body_with_options() {
+    let adapter = OllamaOutboundAdapter;
+    let mut req = make_request(
         vec![simple_message(Role::User, "Hi")],
         GenerationParams {
             temperature: Some(0.7),
@@ -68,12 +236,15 @@ fn test_build_request_body_with_options() {
             max_tokens: Some(256),
             stop: Some(vec!["END".to_owned()]),
             seed: Some(42),
+            top_k: Some(40),
+            min_p: Some(0.05),
             ..Default::default()
         },
         true,
     );
+    req.parallel_tool_calls = Some(true);
 
-    let body = adapter.build_request_body(&req).unwrap();
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["stream"], true);
@@ -81,7 +252,12 @@ fn test_build_request_body_with_options() {
     assert_eq!(json["options"]["top_p"], 0.9);
     assert_eq!(json["options"]["seed"], 42);
     assert_eq!(json["options"]["stop"], serde_json::json!(["END"]));
+    assert_eq!(json["options"]["top_k"], 40);
+    assert_eq!(json["options"]["min_p"], 0.05);
+    assert!(json.get("top_k").is_none());
     assert_eq!(json["num_predict"], 256);
+    // Ollama has no such flag.
+    assert!(json.get("parallel_tool_calls").is_none());
 }
 
 // ---------------------------------------------------------------------------
@@ -117,6 +293,32 @@ fn test_parse_response_simple() {
     assert_eq!(resp.usage.prompt_tokens, 12);
     assert_eq!(resp.usage.completion_tokens, 4);
     assert_eq!(resp.usage.total_tokens, 16);
+    assert!(resp.id.starts_with("chatcmpl-"), "id: {}", resp.id);
+    assert!(resp.id.len() > "chatcmpl-".len());
+    let now = u64::try_from(chrono::Utc::now().timestamp()).unwrap();
+    assert!(
+        resp.created + 60 >= now && resp.created <= now,
+        "created: {}",
+        resp.created
+    );
+}
+
+#[test]
+fn test_parse_response_uses_backend_created_at() {
+    let adapter = OllamaOutboundAdapter;
+    let resp_json = serde_json::json!({
+        "model": "llama3-70b",
+        "created_at": "2024-08-04T19:22:45.499127Z",
+        "message": {"role": "assistant", "content": "Hi"},
+        "done": true
+    });
+    let body = serde_json::to_vec(&resp_json).unwrap();
+
+    let first = adapter.parse_response(&body).unwrap();
+    let second = adapter.parse_response(&body).unwrap();
+
+    assert_eq!(first.created, 1_722_799_365);
+    assert_ne!(first.id, second.id, "each response gets its own id");
 }
 
 #[test]
@@ -199,19 +401,13 @@ fn test_backend_spec() {
 fn test_inference_path() {
     let adapter = OllamaOutboundAdapter;
     assert_eq!(adapter.inference_path(), "/api/chat");
+    // Ollama streams from the same endpoint, toggled by `stream` in the body.
+    assert_eq!(adapter.streaming_inference_path(), "/api/chat");
 }
 
 #[test]
 fn test_extra_headers_empty() {
     let adapter = OllamaOutboundAdapter;
-    let backend = BackendInfo {
-        id: mb_core::core::BackendId::new("test"),
-        spec: BackendSpec::Ollama,
-        models: vec![ModelId::new("llama3-70b")],
-        max_concurrent: 4,
-        base_url: "http://localhost:11434".to_owned(),
-    };
-
-    let headers = adapter.extra_headers(&backend);
+    let headers = adapter.extra_headers(&make_backend());
     assert!(headers.is_empty());
 }
@@ -11,7 +11,12 @@ impl OutboundAdapter for OpenAiChatOutboundAdapter {
         BackendSpec::OpenAiChat
     }
 
-    fn build_request_body(&self, req: &CanonicalRequest) -> Result<Vec<u8>, AdapterError> {
+    fn build_request_body(
+        &self,
+        req: &CanonicalRequest,
+        backend: &BackendInfo,
+    ) -> Result<Vec<u8>, AdapterError> {
+        let req = backend.tool_support.apply(req)?;
         let messages: Vec<serde_json::Value> = req
             .messages
             .iter()
@@ -31,7 +36,7 @@ impl OutboundAdapter for OpenAiChatOutboundAdapter {
             .collect();
 
         let mut body = serde_json::json!({
-            "model": req.model.as_str(),
+            "model": backend.wire_model(&req.model),
             "messages": messages,
             "stream": req.stream,
         });
@@ -60,6 +65,8 @@ impl OutboundAdapter for OpenAiChatOutboundAdapter {
         if let Some(s) = req.params.seed {
             obj.insert("seed".into(), s.into());
         }
+        // top_k / min_p are not part of the OpenAI API; strict
+        // implementations reject unknown fields, so they are not forwarded.
         if let Some(tools) = &req.tools {
             let tools_json: Vec<serde_json::Value> = tools
                 .iter()
@@ -79,6 +86,24 @@ impl OutboundAdapter for OpenAiChatOutboundAdapter {
         if let Some(tc) = &req.tool_choice {
             obj.insert("tool_choice".into(), tool_choice_to_json(tc));
         }
+        if let Some(parallel) = req.parallel_tool_calls {
+            obj.insert("parallel_tool_calls".into(), parallel.into());
+        }
+        if let Some(logprobs) = req.logprobs {
+            obj.insert("logprobs".into(), logprobs.into());
+        }
+        if let Some(top) = req.top_logprobs {
+            obj.insert("top_logprobs".into(), top.into());
+        }
+        if let Some(format) = &req.response_format {
+            obj.insert("response_format".into(), response_format_to_json(format));
+        }
+        if let Some(echo) = req.echo {
+            obj.insert("echo".into(), echo.into());
+        }
+        if let Some(suffix) = &req.suffix {
+            obj.insert("suffix".into(), suffix.as_str().into());
+        }
 
         serde_json::to_vec(&body).map_err(|e| AdapterError::FormatResponse(e.to_string()))
     }
@@ -100,6 +125,7 @@ impl OutboundAdapter for OpenAiChatOutboundAdapter {
                         tool_call_id: None,
                     },
                     finish_reason: parse_finish_reason(&c.finish_reason)?,
+                    logprobs: c.logprobs,
                 })
             })
             .collect::<Result<Vec<_>, AdapterError>>()?;
@@ -147,6 +173,7 @@ impl OutboundAdapter for OpenAiChatOutboundAdapter {
                 Ok(Some(StreamChoice {
                     index: c.index,
                     delta,
+                    logprobs: c.logprobs,
                 }))
             })
             .filter_map(Result::transpose)
@@ -186,6 +213,9 @@ struct OaiChoiceWire {
     index: u32,
     message: OaiMessageWire,
     finish_reason: String,
+    /// `null` when not requested, which deserializes to `None`.
+    #[serde(default)]
+    logprobs: Option<serde_json::Value>,
 }
 
 #[derive(serde::Deserialize)]
@@ -215,6 +245,7 @@ struct OaiStreamChoiceWire {
     index: u32,
     delta: OaiDeltaWire,
     finish_reason: Option<String>,
+    logprobs: Option<serde_json::Value>,
 }
 
 #[derive(serde::Deserialize)]
@@ -276,6 +307,12 @@ fn content_to_json(content: &MessageContent) -> serde_json::Value {
                         }
                         img
                     }
+                    mb_core::core::ContentPart::InputAudio { data, format } => {
+                        serde_json::json!({
+                            "type": "input_audio",
+                            "input_audio": {"data": data, "format": format},
+                        })
+                    }
                 })
                 .collect();
             serde_json::Value::Array(arr)
@@ -294,5 +331,23 @@ fn tool_choice_to_json(tc: &mb_core::core::ToolChoice) -> serde_json::Value {
     }
 }
 
+fn response_format_to_json(format: &mb_core::core::ResponseFormat) -> serde_json::Value {
+    match format {
+        mb_core::core::ResponseFormat::Text => serde_json::json!({"type": "text"}),
+        mb_core::core::ResponseFormat::JsonObject => serde_json::json!({"type": "json_object"}),
+        mb_core::core::ResponseFormat::JsonSchema {
+            name,
+            schema,
+            strict,
+        } => {
+            let mut json_schema = serde_json::json!({"name": name, "schema": schema});
+            if let Some(strict) = strict {
+                json_schema["strict"] = (*strict).into();
+            }
+            serde_json::json!({"type": "json_schema", "json_schema": json_schema})
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests;
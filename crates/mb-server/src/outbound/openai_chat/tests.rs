@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use super::*;
 use mb_core::core::{
-    ClientId, GenerationParams, RequestId, RequestMetadata, ToolChoice, ToolDefinition,
+    ClientId, GenerationParams, RequestId, RequestMetadata, ToolChoice, ToolDefinition, ToolSupport,
 };
 use serde_json::Value;
 
@@ -15,16 +17,41 @@ fn make_request(
         params,
         tools: None,
         tool_choice: None,
+        parallel_tool_calls: None,
+        logprobs: None,
+        top_logprobs: None,
+        response_format: None,
+        echo: None,
+        suffix: None,
+        n: None,
         stream,
         metadata: RequestMetadata {
             request_id: RequestId::new("req-test"),
             client_id: ClientId::new("client-test"),
             estimated_input_tokens: 10,
             prefix_hash: None,
+            app_title: None,
+            app_referer: None,
+            client_metadata: None,
+            store: false,
+            provider_hint: None,
         },
     }
 }
 
+fn make_backend() -> BackendInfo {
+    BackendInfo {
+        id: mb_core::core::BackendId::new("test"),
+        spec: BackendSpec::OpenAiChat,
+        models: vec![ModelId::new("gpt-4")],
+        max_concurrent: 10,
+        base_url: "http://localhost:8000".to_owned(),
+        model_map: HashMap::new(),
+        tool_support: ToolSupport::Native,
+        cost_weight: 0,
+    }
+}
+
 fn simple_message(role: Role, text: &str) -> Message {
     Message {
         role,
@@ -50,7 +77,7 @@ fn test_build_request_body_simple() {
         false,
     );
 
-    let body = adapter.build_request_body(&req).unwrap();
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["model"], "gpt-4");
@@ -65,6 +92,26 @@ fn test_build_request_body_simple() {
     assert!(json.get("max_tokens").is_none());
 }
 
+#[test]
+fn test_build_request_body_uses_backend_model_name() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let req = make_request(
+        vec![simple_message(Role::User, "Hello!")],
+        GenerationParams::default(),
+        false,
+    );
+    let mut backend = make_backend();
+    backend.model_map.insert(
+        ModelId::new("gpt-4"),
+        "meta-llama/Llama-3-70B-Instruct".to_owned(),
+    );
+
+    let body = adapter.build_request_body(&req, &backend).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["model"], "meta-llama/Llama-3-70B-Instruct");
+}
+
 #[test]
 fn test_build_request_body_with_params() {
     let adapter = OpenAiChatOutboundAdapter;
@@ -78,11 +125,13 @@ fn test_build_request_body_with_params() {
             frequency_penalty: Some(0.5),
             presence_penalty: Some(0.3),
             seed: Some(42),
+            top_k: Some(40),
+            min_p: Some(0.05),
         },
         true,
     );
 
-    let body = adapter.build_request_body(&req).unwrap();
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["stream"], true);
@@ -93,6 +142,8 @@ fn test_build_request_body_with_params() {
     assert_eq!(json["frequency_penalty"], 0.5);
     assert_eq!(json["presence_penalty"], 0.3);
     assert_eq!(json["seed"], 42);
+    assert!(json.get("top_k").is_none());
+    assert!(json.get("min_p").is_none());
 }
 
 #[test]
@@ -110,12 +161,161 @@ fn test_build_request_body_with_tools() {
     }]);
     req.tool_choice = Some(ToolChoice::Auto);
 
-    let body = adapter.build_request_body(&req).unwrap();
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["tools"][0]["type"], "function");
     assert_eq!(json["tools"][0]["function"]["name"], "get_weather");
     assert_eq!(json["tool_choice"], "auto");
+    assert!(json.get("parallel_tool_calls").is_none());
+}
+
+#[test]
+fn test_build_request_body_parallel_tool_calls() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let mut req = make_request(
+        vec![simple_message(Role::User, "Weather in Paris and Rome?")],
+        GenerationParams::default(),
+        false,
+    );
+    req.tools = Some(vec![ToolDefinition {
+        name: "get_weather".to_owned(),
+        description: None,
+        parameters: serde_json::json!({"type": "object"}),
+    }]);
+    req.parallel_tool_calls = Some(false);
+
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["parallel_tool_calls"], false);
+
+    // Dropped along with the tools when the backend cannot use them.
+    let mut backend = make_backend();
+    backend.tool_support = ToolSupport::Downgrade;
+    let body = adapter.build_request_body(&req, &backend).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.get("parallel_tool_calls").is_none());
+}
+
+#[test]
+fn test_build_request_body_logprobs() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let mut req = make_request(
+        vec![simple_message(Role::User, "Hi")],
+        GenerationParams::default(),
+        false,
+    );
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.get("logprobs").is_none());
+    assert!(json.get("top_logprobs").is_none());
+
+    req.logprobs = Some(true);
+    req.top_logprobs = Some(5);
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["logprobs"], true);
+    assert_eq!(json["top_logprobs"], 5);
+}
+
+#[test]
+fn test_build_request_body_response_format() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let mut req = make_request(
+        vec![simple_message(Role::User, "List two colors.")],
+        GenerationParams::default(),
+        false,
+    );
+    req.response_format = Some(mb_core::core::ResponseFormat::JsonSchema {
+        name: "colors".to_owned(),
+        schema: serde_json::json!({"type": "array"}),
+        strict: Some(true),
+    });
+
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json["response_format"],
+        serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {"name": "colors", "schema": {"type": "array"}, "strict": true}
+        })
+    );
+}
+
+#[test]
+fn test_build_request_body_echo_suffix() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let mut req = make_request(
+        vec![simple_message(Role::User, "def add(a, b):")],
+        GenerationParams::default(),
+        false,
+    );
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.get("echo").is_none());
+    assert!(json.get("suffix").is_none());
+
+    req.echo = Some(true);
+    req.suffix = Some("    return result".to_owned());
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["echo"], true);
+    assert_eq!(json["suffix"], "    return result");
+}
+
+#[test]
+fn test_build_request_body_tools_unsupported() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let mut tool_result = simple_message(Role::Tool, "18C");
+    tool_result.tool_call_id = Some("call_1".to_owned());
+    let mut req = make_request(
+        vec![simple_message(Role::User, "Weather?"), tool_result],
+        GenerationParams::default(),
+        false,
+    );
+    req.tools = Some(vec![ToolDefinition {
+        name: "get_weather".to_owned(),
+        description: None,
+        parameters: serde_json::json!({"type": "object"}),
+    }]);
+    let mut backend = make_backend();
+
+    // Strict: the request is refused.
+    backend.tool_support = ToolSupport::Reject;
+    let result = adapter.build_request_body(&req, &backend);
+    assert!(matches!(result, Err(AdapterError::UnsupportedFeature(_))));
+
+    // Lenient: tools are dropped and the tool result becomes user text.
+    backend.tool_support = ToolSupport::Downgrade;
+    let body = adapter.build_request_body(&req, &backend).unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.get("tools").is_none());
+    assert_eq!(json["messages"][1]["role"], "user");
+    assert_eq!(json["messages"][1]["content"], "[tool result call_1]\n18C");
+    assert!(json["messages"][1].get("tool_call_id").is_none());
+}
+
+#[test]
+fn test_build_request_body_input_audio_round_trip() {
+    let wire_part = serde_json::json!({
+        "type": "input_audio",
+        "input_audio": {"data": "UklGRiQAAABXQVZF", "format": "wav"}
+    });
+    let inbound_body = serde_json::json!({
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": [wire_part]}]
+    });
+    use mb_core::core::InboundAdapter;
+    let req = crate::inbound::openai_chat::OpenAiChatInboundAdapter
+        .parse_request(serde_json::to_vec(&inbound_body).unwrap().as_slice(), None)
+        .unwrap();
+
+    let body = OpenAiChatOutboundAdapter
+        .build_request_body(&req, &make_backend())
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["messages"][0]["content"][0], wire_part);
 }
 
 #[test]
@@ -128,7 +328,7 @@ fn test_build_request_body_named_tool_choice() {
     );
     req.tool_choice = Some(ToolChoice::Named("my_fn".to_owned()));
 
-    let body = adapter.build_request_body(&req).unwrap();
+    let body = adapter.build_request_body(&req, &make_backend()).unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
     assert_eq!(json["tool_choice"]["type"], "function");
@@ -182,6 +382,41 @@ fn test_parse_response_simple() {
     assert_eq!(resp.usage.total_tokens, 16);
 }
 
+#[test]
+fn test_parse_response_logprobs() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let logprobs = serde_json::json!({
+        "content": [{"token": "Hi", "logprob": -0.25, "bytes": [72, 105], "top_logprobs": []}]
+    });
+    let resp_json = serde_json::json!({
+        "id": "chatcmpl-abc",
+        "created": 1700000000_u64,
+        "model": "gpt-4",
+        "choices": [
+            {
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi"},
+                "logprobs": logprobs,
+                "finish_reason": "stop"
+            },
+            {
+                "index": 1,
+                "message": {"role": "assistant", "content": "Hey"},
+                "logprobs": null,
+                "finish_reason": "stop"
+            }
+        ],
+        "usage": { "prompt_tokens": 5, "completion_tokens": 1, "total_tokens": 6 }
+    });
+
+    let resp = adapter
+        .parse_response(&serde_json::to_vec(&resp_json).unwrap())
+        .unwrap();
+
+    assert_eq!(resp.choices[0].logprobs, Some(logprobs));
+    assert_eq!(resp.choices[1].logprobs, None);
+}
+
 #[test]
 fn test_parse_response_invalid_json() {
     let adapter = OpenAiChatOutboundAdapter;
@@ -237,6 +472,19 @@ fn test_parse_stream_line_text_delta() {
     );
 }
 
+#[test]
+fn test_parse_stream_line_keeps_logprobs() {
+    let adapter = OpenAiChatOutboundAdapter;
+    let line = r#"data: {"id":"chatcmpl-1","object":"chat.completion.chunk","created":1700000000,"model":"gpt-4","choices":[{"index":0,"delta":{"content":"Hi"},"logprobs":{"content":[{"token":"Hi","logprob":-0.1}]},"finish_reason":null}]}"#;
+
+    let chunk = adapter.parse_stream_line(line).unwrap().unwrap();
+
+    assert_eq!(
+        chunk.choices[0].logprobs,
+        Some(serde_json::json!({"content": [{"token": "Hi", "logprob": -0.1}]}))
+    );
+}
+
 #[test]
 fn test_parse_stream_line_role_delta() {
     let adapter = OpenAiChatOutboundAdapter;
@@ -295,20 +543,13 @@ fn test_backend_spec() {
 fn test_inference_path() {
     let adapter = OpenAiChatOutboundAdapter;
     assert_eq!(adapter.inference_path(), "/v1/chat/completions");
+    assert_eq!(adapter.streaming_inference_path(), adapter.inference_path());
 }
 
 #[test]
 fn test_extra_headers_includes_content_type() {
     let adapter = OpenAiChatOutboundAdapter;
-    let backend = BackendInfo {
-        id: mb_core::core::BackendId::new("test"),
-        spec: BackendSpec::OpenAiChat,
-        models: vec![ModelId::new("gpt-4")],
-        max_concurrent: 10,
-        base_url: "http://localhost:8000".to_owned(),
-    };
-
-    let headers = adapter.extra_headers(&backend);
+    let headers = adapter.extra_headers(&make_backend());
     assert_eq!(headers.len(), 1);
     assert_eq!(headers[0].0, "Content-Type");
     assert_eq!(headers[0].1, "application/json");
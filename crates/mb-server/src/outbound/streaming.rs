@@ -6,15 +6,29 @@ use futures_core::Stream;
 
 /// Maximum SSE buffer size (1 MB). If the buffer exceeds this limit,
 /// it is cleared to prevent unbounded memory growth from malformed streams.
-const MAX_SSE_BUFFER_SIZE: usize = 1_048_576;
+pub(crate) const MAX_SSE_BUFFER_SIZE: usize = 1_048_576;
 
 /// Reassembles raw byte chunks into complete SSE data lines.
 ///
 /// Wraps an inner byte stream and yields complete `data: ` payloads
-/// (with the `data: ` prefix stripped) one line at a time.
+/// (with the `data: ` prefix stripped) one line at a time. With
+/// [`coalesce_data_lines`](Self::coalesce_data_lines) enabled, consecutive
+/// `data:` lines of one event are joined with `\n` and yielded once the
+/// event's terminating blank line arrives, as the SSE spec describes.
 pub struct SseLineParser<S> {
     inner: Pin<Box<S>>,
     buffer: String,
+    coalesce: bool,
+    pending: PendingEvent,
+}
+
+/// The `data:` lines of an event still waiting for its blank line.
+#[derive(Default)]
+struct PendingEvent {
+    data: Option<String>,
+    /// Set once `data` would outgrow [`MAX_SSE_BUFFER_SIZE`]; the rest of
+    /// the event is dropped up to its blank line.
+    oversized: bool,
 }
 
 impl<S> SseLineParser<S> {
@@ -22,6 +36,22 @@ impl<S> SseLineParser<S> {
         Self {
             inner: Box::pin(inner),
             buffer: String::new(),
+            coalesce: false,
+            pending: PendingEvent::default(),
+        }
+    }
+
+    /// Join multi-line `data:` fields into a single payload per event.
+    pub fn coalesce_data_lines(mut self, coalesce: bool) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    fn take_next(&mut self) -> Option<String> {
+        if self.coalesce {
+            take_next_event(&mut self.buffer, &mut self.pending)
+        } else {
+            take_next_data_line(&mut self.buffer)
         }
     }
 }
@@ -37,7 +67,7 @@ where
 
         loop {
             // Try to extract a complete line from the buffer first.
-            if let Some(line) = take_next_data_line(&mut this.buffer) {
+            if let Some(line) = this.take_next() {
                 return Poll::Ready(Some(Ok(line)));
             }
 
@@ -66,11 +96,12 @@ where
                 }
                 Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
                 Poll::Ready(None) => {
-                    // Stream ended. Drain any remaining data line in buffer.
-                    if let Some(line) = take_next_data_line(&mut this.buffer) {
+                    // Stream ended. Drain any remaining data line in buffer,
+                    // then flush an event left open without a blank line.
+                    if let Some(line) = this.take_next() {
                         return Poll::Ready(Some(Ok(line)));
                     }
-                    return Poll::Ready(None);
+                    return Poll::Ready(this.pending.data.take().map(Ok));
                 }
                 Poll::Pending => return Poll::Pending,
             }
@@ -106,6 +137,64 @@ fn take_next_data_line(buffer: &mut String) -> Option<String> {
     }
 }
 
+/// Like [`take_next_data_line`], but accumulates consecutive `data:` lines
+/// into `pending` and only yields them when a blank line ends the event.
+/// Lines that are not SSE fields (raw JSON) are still returned immediately.
+/// An event larger than [`MAX_SSE_BUFFER_SIZE`] is dropped whole.
+fn take_next_event(buffer: &mut String, pending: &mut PendingEvent) -> Option<String> {
+    loop {
+        let newline_pos = buffer.find('\n')?;
+        let line = buffer[..newline_pos].trim_end_matches('\r').to_owned();
+        buffer.drain(..=newline_pos);
+
+        if line.is_empty() {
+            pending.oversized = false;
+            if let Some(data) = pending.data.take() {
+                return Some(data);
+            }
+            continue;
+        }
+        if line.starts_with(':') {
+            continue;
+        }
+
+        if let Some(value) = line.strip_prefix("data:") {
+            if pending.oversized {
+                continue;
+            }
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            let pending_len = pending.data.as_ref().map_or(0, |data| data.len() + 1);
+            if pending_len.saturating_add(value.len()) > MAX_SSE_BUFFER_SIZE {
+                tracing::warn!(
+                    pending_len,
+                    line_len = value.len(),
+                    "SSE event exceeded size limit, dropping it"
+                );
+                pending.data = None;
+                pending.oversized = true;
+                continue;
+            }
+            match &mut pending.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => pending.data = Some(value.to_owned()),
+            }
+            continue;
+        }
+
+        if ["event:", "id:", "retry:"]
+            .iter()
+            .any(|field| line.starts_with(field))
+        {
+            continue;
+        }
+
+        return Some(line);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -225,4 +314,64 @@ mod tests {
             vec!["{\"message\":{\"content\":\"Hi\"}}", "{\"done\":true}"]
         );
     }
+
+    #[test]
+    fn test_coalesce_multi_line_data_field() {
+        let stream = MockByteStream::new(vec![
+            "event: message\ndata: {\"a\":\ndata: 1}\n\ndata: second\n\n",
+        ]);
+        let mut parser = SseLineParser::new(stream).coalesce_data_lines(true);
+        let lines = collect_lines(&mut parser);
+        assert_eq!(lines, vec!["{\"a\":\n1}", "second"]);
+    }
+
+    #[test]
+    fn test_coalesce_crlf_only_stream() {
+        let stream = MockByteStream::new(vec![
+            "data: first\r\n",
+            "data: line\r",
+            "\n\r\n: ping\r\n\r\ndata: [DONE]\r\n\r\n",
+        ]);
+        let mut parser = SseLineParser::new(stream).coalesce_data_lines(true);
+        let lines = collect_lines(&mut parser);
+        assert_eq!(lines, vec!["first\nline", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_crlf_only_stream_without_coalescing() {
+        let stream = MockByteStream::new(vec!["data: first\r\n\r\ndata: second\r\n\r\n"]);
+        let mut parser = SseLineParser::new(stream);
+        let lines = collect_lines(&mut parser);
+        assert_eq!(lines, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_coalesce_flushes_unterminated_event_at_end() {
+        let stream = MockByteStream::new(vec!["data: tail\n"]);
+        let mut parser = SseLineParser::new(stream).coalesce_data_lines(true);
+        let lines = collect_lines(&mut parser);
+        assert_eq!(lines, vec!["tail"]);
+    }
+
+    #[test]
+    fn test_coalesce_drops_oversized_event() {
+        let line = format!("data: {}\n", "x".repeat(MAX_SSE_BUFFER_SIZE / 2));
+        let stream = MockByteStream::new(vec![&line, &line, &line, "\n", "data: next\n\n"]);
+        let mut parser = SseLineParser::new(stream).coalesce_data_lines(true);
+        let lines = collect_lines(&mut parser);
+        assert_eq!(lines, vec!["next"]);
+    }
+
+    #[test]
+    fn test_coalesce_keeps_raw_json_passthrough() {
+        let stream = MockByteStream::new(vec![
+            "{\"message\":{\"content\":\"Hi\"}}\n{\"done\":true}\n",
+        ]);
+        let mut parser = SseLineParser::new(stream).coalesce_data_lines(true);
+        let lines = collect_lines(&mut parser);
+        assert_eq!(
+            lines,
+            vec!["{\"message\":{\"content\":\"Hi\"}}", "{\"done\":true}"]
+        );
+    }
 }
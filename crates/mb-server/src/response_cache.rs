@@ -0,0 +1,201 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard};
+
+use lru::LruCache;
+use mb_core::core::{CanonicalRequest, ModelId};
+
+use crate::coalesce::SharedResponse;
+
+// ---------------------------------------------------------------------------
+// ResponseCache — LRU of responses to deterministic requests
+// ---------------------------------------------------------------------------
+
+/// Identifies requests with the same answer: the model plus hashes of the
+/// full message list and of everything else that shapes the output.
+pub type ResponseCacheKey = (ModelId, u64, u64);
+
+/// Recent responses to `temperature: 0` / greedy requests, so an identical
+/// request is answered without a backend call.
+pub struct ResponseCache {
+    entries: Mutex<LruCache<ResponseCacheKey, SharedResponse>>,
+}
+
+impl ResponseCache {
+    pub fn new(max_entries: usize) -> Self {
+        let capacity = NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// The cache key for `req`, or `None` when sampling makes its answer
+    /// vary between calls.
+    pub fn key(req: &CanonicalRequest) -> Option<ResponseCacheKey> {
+        let greedy = req.params.temperature == Some(0.0) || req.params.top_k == Some(1);
+        if !greedy {
+            return None;
+        }
+        let (messages, params) = request_hashes(req);
+        Some((req.model.clone(), messages, params))
+    }
+
+    pub fn get(&self, key: &ResponseCacheKey) -> Option<SharedResponse> {
+        self.lock().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: ResponseCacheKey, response: SharedResponse) {
+        self.lock().put(key, response);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every mutation leaves the cache consistent, so a poisoned lock is
+    /// still usable.
+    fn lock(&self) -> MutexGuard<'_, LruCache<ResponseCacheKey, SharedResponse>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hashes of `req`'s full message list and of everything else that shapes
+/// its output, leaving out per-request metadata.
+pub(crate) fn request_hashes(req: &CanonicalRequest) -> (u64, u64) {
+    let messages = hash_json(&req.messages);
+    let params = hash_json(&(
+        &req.params,
+        &req.tools,
+        &req.tool_choice,
+        req.parallel_tool_calls,
+        req.logprobs,
+        req.top_logprobs,
+        &req.response_format,
+        req.echo,
+        &req.suffix,
+        req.n,
+    ));
+    (messages, params)
+}
+
+fn hash_json<T: serde::Serialize>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // Canonical types always serialize; an empty body would only merge keys.
+    serde_json::to_vec(value)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_core::core::{
+        BackendId, CanonicalResponse, ClientId, GenerationParams, Message, MessageContent,
+        RequestId, RequestMetadata, Role, TokenUsage,
+    };
+
+    use super::*;
+
+    fn request(text: &str, temperature: Option<f64>) -> CanonicalRequest {
+        CanonicalRequest {
+            model: ModelId::new("llama3-70b"),
+            messages: vec![Message {
+                role: Role::User,
+                content: MessageContent::Text(text.to_owned()),
+                name: None,
+                tool_call_id: None,
+            }],
+            params: GenerationParams {
+                temperature,
+                ..GenerationParams::default()
+            },
+            tools: None,
+            tool_choice: None,
+            parallel_tool_calls: None,
+            logprobs: None,
+            top_logprobs: None,
+            response_format: None,
+            echo: None,
+            suffix: None,
+            n: None,
+            stream: false,
+            metadata: RequestMetadata {
+                request_id: RequestId::new("req-test"),
+                client_id: ClientId::new("client-a"),
+                estimated_input_tokens: 0,
+                prefix_hash: None,
+                app_title: None,
+                app_referer: None,
+                client_metadata: None,
+                store: false,
+                provider_hint: None,
+            },
+        }
+    }
+
+    fn response(id: &str) -> SharedResponse {
+        let resp = CanonicalResponse {
+            id: id.to_owned(),
+            model: ModelId::new("llama3-70b"),
+            choices: vec![],
+            usage: TokenUsage {
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                total_tokens: 2,
+            },
+            created: 0,
+        };
+        (BackendId::new("gpu-1"), resp)
+    }
+
+    #[test]
+    fn test_key_only_for_deterministic_requests() {
+        assert!(ResponseCache::key(&request("hi", Some(0.0))).is_some());
+        assert!(ResponseCache::key(&request("hi", Some(0.7))).is_none());
+        assert!(ResponseCache::key(&request("hi", None)).is_none());
+
+        let mut greedy = request("hi", None);
+        greedy.params.top_k = Some(1);
+        assert!(ResponseCache::key(&greedy).is_some());
+    }
+
+    #[test]
+    fn test_key_ignores_request_metadata() {
+        let a = request("hi", Some(0.0));
+        let mut b = request("hi", Some(0.0));
+        b.metadata.request_id = RequestId::new("req-other");
+        assert_eq!(ResponseCache::key(&a), ResponseCache::key(&b));
+
+        let mut c = request("hi", Some(0.0));
+        c.params.max_tokens = Some(16);
+        assert_ne!(ResponseCache::key(&a), ResponseCache::key(&c));
+        assert_ne!(
+            ResponseCache::key(&a),
+            ResponseCache::key(&request("hello", Some(0.0)))
+        );
+    }
+
+    #[test]
+    fn test_lru_eviction_keeps_recently_used() {
+        let cache = ResponseCache::new(2);
+        let key = |text: &str| ResponseCache::key(&request(text, Some(0.0))).unwrap();
+
+        cache.insert(key("a"), response("resp-a"));
+        cache.insert(key("b"), response("resp-b"));
+        // Touch "a" so "b" is the least recently used.
+        assert!(cache.get(&key("a")).is_some());
+        cache.insert(key("c"), response("resp-c"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("b")).is_none());
+        assert_eq!(cache.get(&key("a")).unwrap().1.id, "resp-a");
+        assert_eq!(cache.get(&key("c")).unwrap().1.id, "resp-c");
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+// ---------------------------------------------------------------------------
+// ShardedMap — a HashMap split across independently locked shards
+// ---------------------------------------------------------------------------
+
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// Per-key state touched on every request (rate limiters, counters).
+///
+/// Keys are distributed by hash so requests for different keys rarely
+/// contend; each shard is a plain `HashMap` behind a std `Mutex`, held only
+/// for the synchronous closure passed to [`with`](Self::with).
+pub struct ShardedMap<K, V> {
+    shards: Vec<Mutex<HashMap<K, V>>>,
+}
+
+impl<K: Hash + Eq, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        Self::with_shard_count(DEFAULT_SHARDS)
+    }
+}
+
+impl<K: Hash + Eq, V> ShardedMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_shard_count(shard_count: usize) -> Self {
+        Self {
+            shards: (0..shard_count.max(1))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Runs `f` on the shard `key` belongs to, with that shard locked.
+    pub fn with<R>(&self, key: &K, f: impl FnOnce(&mut HashMap<K, V>) -> R) -> R {
+        f(&mut self.lock_shard(self.shard_index(key)))
+    }
+
+    /// Total entries across shards; each shard is locked in turn, so the
+    /// count is only a snapshot.
+    pub fn len(&self) -> usize {
+        (0..self.shards.len())
+            .map(|index| self.lock_shard(index).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy of every entry; like [`len`](Self::len), shards are locked one
+    /// at a time, so this is only a snapshot.
+    pub fn snapshot(&self) -> HashMap<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        (0..self.shards.len())
+            .flat_map(|index| {
+                self.lock_shard(index)
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn shard_index(&self, key: &K) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        // Truncation is fine: only the low bits pick the shard.
+        (hasher.finish() as usize) % self.shards.len()
+    }
+
+    /// Closures only make single-entry updates, so a poisoned shard is
+    /// still consistent.
+    fn lock_shard(&self, index: usize) -> MutexGuard<'_, HashMap<K, V>> {
+        self.shards[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_persist_per_key_across_shards() {
+        let map: ShardedMap<u32, u32> = ShardedMap::with_shard_count(4);
+        for key in 0..100 {
+            map.with(&key, |shard| *shard.entry(key).or_default() += key);
+        }
+        map.with(&7, |shard| *shard.get_mut(&7).unwrap() += 1);
+
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.with(&7, |shard| shard[&7]), 8);
+        assert_eq!(map.with(&99, |shard| shard.get(&99).copied()), Some(99));
+    }
+
+    #[test]
+    fn test_snapshot_collects_every_shard() {
+        let map: ShardedMap<u32, u32> = ShardedMap::with_shard_count(4);
+        for key in 0..10 {
+            map.with(&key, |shard| shard.insert(key, key * 2));
+        }
+
+        let snapshot = map.snapshot();
+        assert_eq!(snapshot.len(), 10);
+        assert!(snapshot.iter().all(|(key, value)| *value == key * 2));
+    }
+}
@@ -1,214 +1,614 @@
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
-use axum::body::Bytes;
+use axum::body::{Body, Bytes};
 use axum::extract::State;
-use axum::http::HeaderMap;
+use axum::http::{header, HeaderMap};
 use axum::response::{IntoResponse, Response};
 use futures_util::StreamExt;
 
 use mb_core::core::{
-    AdapterError, ApiSpec, BackendSpec, ClientId, DeltaContent, GatewayError, ModelId, PrefixHash,
-    RoutingError,
+    AdapterError, ApiSpec, BackendError, BackendId, BackendSpec, CanonicalResponse,
+    CanonicalStreamChunk, Choice, ClientId, ContentPart, DeltaContent, FinishReason, GatewayError,
+    Message, MessageContent, OutboundAdapter, PrefixHash, Role, StreamChoice, StreamContext,
+    StreamFraming, TokenCounter, TokenUsage,
 };
+use serde_json::Value;
 
-use crate::handler::{gateway_error_to_response, AppState};
-use crate::outbound::streaming::SseLineParser;
+use crate::admission::AdmissionPermit;
+use crate::handler::{
+    gateway_error_body, parse_backend_response, render_gateway_error, AppState, LimitMode,
+    PreparedRequest, RequestTrail,
+};
+use crate::health::InFlightGuard;
+use crate::outbound::streaming::{SseLineParser, MAX_SSE_BUFFER_SIZE};
 
 // ---------------------------------------------------------------------------
 // Streaming (SSE) request handler
 // ---------------------------------------------------------------------------
 
+/// Request header selecting the stream framing (`sse` or `ndjson`).
+pub const STREAM_FORMAT_HEADER: &str = "x-stream-format";
+
+/// Request header asking for a streamed completion to be answered with one
+/// buffered JSON response, for clients that cannot parse SSE.
+pub const COLLAPSE_STREAM_HEADER: &str = "x-collapse-stream";
+
 pub async fn handle_completion_stream(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
-    match handle_stream_inner(state, &headers, &body).await {
+    let (verbosity, redact) = (state.error_verbosity, state.redact_log_content);
+    let body = match crate::handler::decode_body_charset(&headers, body) {
+        Ok(body) => body,
+        Err(e) => return render_gateway_error(e, verbosity, redact),
+    };
+    let mut trail = RequestTrail::default();
+    #[cfg(feature = "audit")]
+    let audit = state.audit.clone();
+    let response = match handle_stream_inner(state, &headers, &body, &mut trail).await {
         Ok(resp) => resp,
-        Err(e) => gateway_error_to_response(e),
-    }
+        Err(e) => render_gateway_error(e, verbosity, redact),
+    };
+    #[cfg(feature = "audit")]
+    let response = crate::audit::record_response(audit.as_ref(), trail, response);
+    response
 }
 
 async fn handle_stream_inner(
     state: Arc<AppState>,
     headers: &HeaderMap,
     body: &[u8],
+    trail: &mut RequestTrail,
 ) -> Result<Response, GatewayError> {
-    // Steps 1-9: auth, parse, rate-limit, quota, route (shared logic)
-    let api_key = crate::handler::extract_api_key(headers)?;
+    // Steps 1-8: auth, parse, rate-limit, quota and affinity (shared logic)
+    let framing = stream_framing(headers)?;
+    let collapse = headers
+        .get(COLLAPSE_STREAM_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+
+    let PreparedRequest {
+        inbound,
+        mut canonical_req,
+        client_info,
+    } = crate::handler::prepare_request(&state, headers, body)?;
+    trail.start(&canonical_req);
+    // Tool call deltas cannot be folded into a canonical response.
+    if collapse
+        && canonical_req
+            .tools
+            .as_ref()
+            .is_some_and(|tools| !tools.is_empty())
+    {
+        return Err(GatewayError::Adapter(AdapterError::UnsupportedFeature(
+            format!("{COLLAPSE_STREAM_HEADER} with tools"),
+        )));
+    }
+    // Completion tokens are charged once a full response is in hand; a
+    // live stream reports none, so it pays for its estimated input only.
+    let limit_headers =
+        crate::handler::check_limits(&state, client_info, &canonical_req, LimitMode::Charge)
+            .await?;
+    let (cache_routing, affinity_hint) =
+        crate::handler::routing_hint(&state, headers, &mut canonical_req);
+
+    // Held until the response stream is dropped.
+    let permit = crate::handler::admit(&state, &canonical_req.model, client_info.priority).await?;
+
+    let selected_id = crate::handler::select_within_backend_rpm(
+        &state,
+        &canonical_req.model,
+        cache_routing,
+        affinity_hint.as_ref(),
+        canonical_req.metadata.provider_hint,
+        LimitMode::Charge,
+    )
+    .await?
+    .backend;
+    trail.backend = Some(selected_id.clone());
+    let backend_load = crate::handler::backend_load(&state, &canonical_req.model).await;
+    let in_flight = state.in_flight.start(&selected_id);
+
+    if canonical_req.metadata.prefix_hash.is_some() {
+        crate::handler::observe_prefix(&state, &canonical_req.model, &canonical_req.messages);
+    }
+
+    let backend_meta = state.backends_by_id.get(&selected_id).ok_or_else(|| {
+        GatewayError::Internal(format!(
+            "selected backend {selected_id} has no configuration"
+        ))
+    })?;
 
-    let inbound = state
-        .inbound_registry
-        .get(&ApiSpec::OpenAiChat)
-        .ok_or(GatewayError::Adapter(AdapterError::ParseRequest(
-            "unsupported API spec".to_owned(),
-        )))?;
+    let outbound_spec = backend_meta.spec;
+    let outbound = state.outbound_registry.get(&outbound_spec).ok_or_else(|| {
+        GatewayError::Internal(format!(
+            "no outbound adapter for backend spec {outbound_spec:?}"
+        ))
+    })?;
+
+    // Force stream=true, unless the backend can only answer in one piece
+    let supports_streaming = outbound.supports_streaming();
+    let mut stream_req = canonical_req.clone();
+    stream_req.stream = supports_streaming;
 
-    let mut canonical_req = inbound.parse_request(body).map_err(GatewayError::Adapter)?;
+    let backend_info = mb_core::core::BackendInfo {
+        id: selected_id.clone(),
+        spec: backend_meta.spec,
+        models: vec![],
+        max_concurrent: 0,
+        base_url: backend_meta.base_url.clone(),
+        model_map: backend_meta.model_map.clone(),
+        tool_support: backend_meta.tool_support,
+        cost_weight: 0,
+    };
 
-    let client_info = state.auth.validate(&api_key).map_err(GatewayError::Auth)?;
-    canonical_req.metadata.client_id = client_info.id.clone();
+    let request_body = outbound
+        .build_request_body(&stream_req, &backend_info)
+        .map_err(GatewayError::Adapter)?;
 
-    mb_core::core::AuthService::check_model_permission(client_info, &canonical_req.model)
-        .map_err(GatewayError::Auth)?;
+    let path = if supports_streaming {
+        outbound.streaming_inference_path()
+    } else {
+        outbound.inference_path()
+    };
+    let url = format!("{}{}", backend_meta.base_url, path);
 
+    let mut req_builder = state.stream_http_client.post(&url).body(request_body);
+    for (k, v) in outbound.extra_headers(&backend_info) {
+        req_builder = req_builder.header(k, v);
+    }
+    for (k, v) in
+        crate::handler::forwarded_attribution(state.attribution_headers, &stream_req.metadata)
     {
-        let now_ms = crate::handler::now_ms();
-        let mut limiters = state.rate_limiters.write().await;
-        let limiter = limiters.entry(client_info.id.clone()).or_insert_with(|| {
-            let rpm = state
-                .rate_limit_rpm
-                .get(&client_info.id)
-                .copied()
-                .unwrap_or(60);
-            mb_core::core::RateLimiter::new(60_000, rpm)
-        });
-        limiter.check(now_ms).map_err(GatewayError::RateLimited)?;
+        req_builder = req_builder.header(k, v);
     }
 
-    if client_info.quota.monthly_token_limit.is_some() {
-        let tracker = state.quota_tracker.read().await;
-        let period = crate::handler::current_year_month();
-        tracker
-            .check(
-                &client_info.id,
+    let (backend_resp, _) =
+        crate::handler::send_to_backend(&state, &selected_id, req_builder).await?;
+
+    let done_sentinel = inbound.done_sentinel().to_owned();
+    let trailer = inbound.stream_trailer(framing);
+    let context = StreamContext {
+        id: format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
+        model: canonical_req.model.clone(),
+        created: crate::handler::now_ms() / 1000,
+    };
+
+    let payloads = if supports_streaming && !collapse {
+        // Live streams carry no usage, so their audit rows leave tokens empty.
+        trail.live_stream = true;
+        // Build SSE event stream
+        let byte_stream = backend_resp.bytes_stream();
+        let sse_parser = SseLineParser::new(byte_stream)
+            .coalesce_data_lines(matches!(outbound_spec, BackendSpec::OpenAiChat));
+
+        make_payload_stream(
+            sse_parser,
+            outbound_spec,
+            Arc::clone(&state),
+            client_info.id.clone(),
+            context,
+            selected_id,
+            canonical_req.metadata.prefix_hash,
+            state.sse_keepalive,
+        )
+        .left_stream()
+    } else {
+        let canonical_resp = if supports_streaming {
+            let lines = SseLineParser::new(backend_resp.bytes_stream())
+                .coalesce_data_lines(matches!(outbound_spec, BackendSpec::OpenAiChat));
+            let counter = state.token_counters.counter_for(&canonical_req.model);
+            let chunks = collect_stream(lines, outbound, &selected_id).await?;
+            collapse_stream_chunks(
+                &chunks,
+                &context,
+                counter,
                 canonical_req.metadata.estimated_input_tokens,
-                &client_info.quota,
-                period,
             )
-            .map_err(GatewayError::QuotaExceeded)?;
-    }
+        } else {
+            let resp_bytes = backend_resp.bytes().await.map_err(|e| {
+                GatewayError::Backend(mb_core::core::BackendError::Connection(e.to_string()))
+            })?;
+            parse_backend_response(
+                outbound,
+                &selected_id,
+                &resp_bytes,
+                state.redact_log_content,
+            )?
+        };
+        crate::handler::check_choices_present(
+            state.allow_empty_choices,
+            &selected_id,
+            &canonical_resp,
+        )?;
+
+        crate::handler::record_usage(&state, client_info, &canonical_resp.usage).await;
+
+        trail.usage = Some(canonical_resp.usage.clone());
 
-    if state.cache_config.enabled {
-        let hash = mb_core::core::compute_prefix_hash(
-            &canonical_req.messages,
-            state.cache_config.prefix_depth,
-        );
-        canonical_req.metadata.prefix_hash = Some(hash);
-    }
+        if state.cache_config.enabled {
+            if let Some(prefix) = canonical_req.metadata.prefix_hash {
+                crate::handler::affinity_record(&state, &canonical_req.model, prefix, &selected_id);
+            }
+        }
 
-    let affinity_hint = if state.cache_config.enabled {
-        if let Some(prefix) = canonical_req.metadata.prefix_hash {
-            let mut map = state.affinity_map.write().await;
-            map.get(&canonical_req.model, prefix).cloned()
-        } else {
-            None
+        if collapse {
+            let mut response =
+                crate::handler::completion_response(inbound, headers, &canonical_resp)?;
+            response.headers_mut().extend(limit_headers);
+            crate::handler::insert_backend_load(response.headers_mut(), backend_load);
+            return Ok(response);
         }
-    } else {
-        None
+
+        let mut payloads = Vec::new();
+        for chunk in synthesize_stream_chunks(&canonical_resp) {
+            if let Some(payload) = inbound
+                .format_stream_chunk(&chunk, &context)
+                .map_err(GatewayError::Adapter)?
+            {
+                payloads.push(StreamItem::Payload(payload));
+            }
+        }
+        futures_util::stream::iter(payloads).right_stream()
+    };
+    let payloads = GuardedStream {
+        inner: Box::pin(payloads),
+        _permit: permit,
+        _in_flight: in_flight,
     };
 
-    let backend_states = state.backend_states.read().await;
-    let states_vec: Vec<_> = backend_states.values().cloned().collect();
-    let round = state
-        .round_counter
-        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    let selected_id = mb_core::core::select_backend(
-        &states_vec,
-        &canonical_req.model,
-        &state.routing_strategy,
-        round,
-        affinity_hint.as_ref(),
-    )
-    .map_err(GatewayError::Routing)?;
-    drop(backend_states);
+    let mut response = match framing {
+        StreamFraming::Sse => {
+            let events = payloads
+                .chain(futures_util::stream::once(async move {
+                    StreamItem::Payload(done_sentinel)
+                }))
+                .map(|item| {
+                    let event = axum::response::sse::Event::default();
+                    Ok::<_, Infallible>(match item {
+                        StreamItem::Payload(data) => event.data(data),
+                        StreamItem::Heartbeat => event.comment("keep-alive"),
+                    })
+                });
+            axum::response::sse::Sse::new(events).into_response()
+        }
+        // NDJSON has no comment syntax, so heartbeats are dropped rather than
+        // risk a blank line tripping strict line parsers.
+        StreamFraming::Ndjson => {
+            let lines = payloads
+                .filter_map(move |item| {
+                    let line = match item {
+                        StreamItem::Payload(payload) => state
+                            .inbound_registry
+                            .get(&ApiSpec::OpenAiChat)
+                            .map(|inbound| inbound.frame_stream_chunk(&payload, framing)),
+                        StreamItem::Heartbeat => None,
+                    };
+                    async move { line }
+                })
+                .chain(futures_util::stream::iter(trailer))
+                .map(Ok::<_, Infallible>);
+            (
+                [(header::CONTENT_TYPE, framing.content_type())],
+                Body::from_stream(lines),
+            )
+                .into_response()
+        }
+    };
+    response.headers_mut().extend(limit_headers);
+    crate::handler::insert_backend_load(response.headers_mut(), backend_load);
+    Ok(response)
+}
 
-    let backend_meta = state
-        .backends_by_id
-        .get(&selected_id)
-        .ok_or(GatewayError::Routing(RoutingError::NoHealthyBackend {
-            model: canonical_req.model.clone(),
-        }))?;
+/// Reads `X-Stream-Format`; absent or `sse` keeps SSE, `ndjson` switches to
+/// newline-delimited JSON.
+fn stream_framing(headers: &HeaderMap) -> Result<StreamFraming, GatewayError> {
+    let Some(value) = headers.get(STREAM_FORMAT_HEADER) else {
+        return Ok(StreamFraming::Sse);
+    };
+    match value
+        .to_str()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "sse" => Ok(StreamFraming::Sse),
+        "ndjson" => Ok(StreamFraming::Ndjson),
+        other => Err(GatewayError::Adapter(AdapterError::UnsupportedFeature(
+            format!("stream format {other:?}"),
+        ))),
+    }
+}
 
-    let outbound_spec = backend_meta.spec;
-    let outbound = state
-        .outbound_registry
-        .get(&outbound_spec)
-        .ok_or(GatewayError::Adapter(AdapterError::FormatResponse(
-            "no outbound adapter".to_owned(),
-        )))?;
-
-    // Force stream=true
-    let mut stream_req = canonical_req.clone();
-    stream_req.stream = true;
+/// Reads a backend stream to its end for `X-Collapse-Stream`. The request
+/// timeout bounds how long this takes; the stream's payload is capped at
+/// [`MAX_SSE_BUFFER_SIZE`] so a runaway backend cannot exhaust memory.
+async fn collect_stream(
+    lines: SseLineParser<
+        impl futures_core::Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    >,
+    outbound: &dyn OutboundAdapter,
+    backend: &BackendId,
+) -> Result<Vec<CanonicalStreamChunk>, GatewayError> {
+    let mut lines = Box::pin(lines);
+    let mut chunks = Vec::new();
+    let mut collected = 0usize;
+    while let Some(line) = lines.next().await {
+        let line =
+            line.map_err(|e| GatewayError::Backend(BackendError::Connection(e.to_string())))?;
+        collected = collected.saturating_add(line.len());
+        if collected > MAX_SSE_BUFFER_SIZE {
+            return Err(GatewayError::Backend(BackendError::MalformedResponse {
+                backend: backend.clone(),
+                reason: format!("collapsed stream exceeds {MAX_SSE_BUFFER_SIZE} bytes"),
+            }));
+        }
+        // Keep-alives, [DONE] and malformed lines are skipped as in a live stream.
+        if let Ok(Some(chunk)) = outbound.parse_stream_line(&line) {
+            chunks.push(chunk);
+        }
+    }
+    Ok(chunks)
+}
 
-    let request_body = outbound
-        .build_request_body(&stream_req)
-        .map_err(GatewayError::Adapter)?;
+/// One choice's deltas as joined so far by [`collapse_stream_chunks`].
+struct CollapsedChoice {
+    role: Role,
+    text: String,
+    finish: Option<FinishReason>,
+    logprobs: Option<Value>,
+}
 
-    let url = format!("{}{}", backend_meta.base_url, outbound.inference_path());
+/// Joins the deltas of each choice into a full message with its logprobs,
+/// the inverse of [`synthesize_stream_chunks`]. A choice the backend never
+/// finished gets `stop`. Requests with tools are refused before dispatch, so tool call
+/// deltas never carry anything to keep. Streams report no usage, so it is
+/// estimated: `prompt_tokens` for the input and `counter` over the text.
+fn collapse_stream_chunks(
+    chunks: &[CanonicalStreamChunk],
+    context: &StreamContext,
+    counter: &dyn TokenCounter,
+    prompt_tokens: u64,
+) -> CanonicalResponse {
+    let mut choices: BTreeMap<u32, CollapsedChoice> = BTreeMap::new();
+    for sc in chunks.iter().flat_map(|chunk| &chunk.choices) {
+        let choice = choices.entry(sc.index).or_insert_with(|| CollapsedChoice {
+            role: Role::Assistant,
+            text: String::new(),
+            finish: None,
+            logprobs: None,
+        });
+        match &sc.delta {
+            DeltaContent::Role(r) => choice.role = r.clone(),
+            DeltaContent::Text(t) => choice.text.push_str(t),
+            DeltaContent::Finish(reason) => choice.finish = Some(reason.clone()),
+            DeltaContent::ToolCallStart { .. } | DeltaContent::ToolCallDelta { .. } => {}
+        }
+        if let Some(logprobs) = &sc.logprobs {
+            append_logprobs(&mut choice.logprobs, logprobs);
+        }
+    }
+    let completion_tokens = choices
+        .values()
+        .map(|choice| counter.count_text(&choice.text))
+        .fold(0u64, u64::saturating_add);
+    CanonicalResponse {
+        id: context.id.clone(),
+        model: context.model.clone(),
+        choices: choices
+            .into_iter()
+            .map(|(index, choice)| Choice {
+                index,
+                message: Message {
+                    role: choice.role,
+                    content: MessageContent::Text(choice.text),
+                    name: None,
+                    tool_call_id: None,
+                },
+                finish_reason: choice.finish.unwrap_or(FinishReason::Stop),
+                logprobs: choice.logprobs,
+            })
+            .collect(),
+        usage: TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens.saturating_add(completion_tokens),
+        },
+        created: context.created,
+    }
+}
 
-    let backend_info = mb_core::core::BackendInfo {
-        id: selected_id.clone(),
-        spec: backend_meta.spec,
-        models: vec![],
-        max_concurrent: 0,
-        base_url: backend_meta.base_url.clone(),
+/// Appends the token lists of a delta's `logprobs` (`content`, `refusal`)
+/// to those joined so far for its choice; other fields take the latest value.
+fn append_logprobs(joined: &mut Option<Value>, delta: &Value) {
+    match (joined.as_mut(), delta) {
+        (Some(Value::Object(joined)), Value::Object(delta)) => {
+            for (key, value) in delta {
+                match (joined.get_mut(key), value) {
+                    (Some(Value::Array(tokens)), Value::Array(more)) => {
+                        tokens.extend(more.iter().cloned());
+                    }
+                    _ => {
+                        joined.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        _ => *joined = Some(delta.clone()),
+    }
+}
+
+/// Replays a complete response as stream chunks: the role, the whole text
+/// with its logprobs and the finish reason of every choice.
+fn synthesize_stream_chunks(response: &CanonicalResponse) -> Vec<CanonicalStreamChunk> {
+    let chunk = |delta: fn(&Choice) -> DeltaContent, with_logprobs: bool| CanonicalStreamChunk {
+        choices: response
+            .choices
+            .iter()
+            .map(|choice| StreamChoice {
+                index: choice.index,
+                delta: delta(choice),
+                logprobs: choice.logprobs.clone().filter(|_| with_logprobs),
+            })
+            .collect(),
     };
+    vec![
+        chunk(
+            |choice| DeltaContent::Role(choice.message.role.clone()),
+            false,
+        ),
+        chunk(
+            |choice| DeltaContent::Text(message_text(&choice.message.content)),
+            true,
+        ),
+        chunk(
+            |choice| DeltaContent::Finish(choice.finish_reason.clone()),
+            false,
+        ),
+    ]
+}
 
-    let mut req_builder = state.http_client.post(&url).body(request_body);
-    for (k, v) in outbound.extra_headers(&backend_info) {
-        req_builder = req_builder.header(k, v);
+fn message_text(content: &MessageContent) -> String {
+    match content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect(),
     }
+}
 
-    let backend_resp = req_builder.send().await.map_err(|e| {
-        GatewayError::Backend(mb_core::core::BackendError::Connection(e.to_string()))
-    })?;
+/// A chunk of assistant role deltas for the choices in `chunk` that start
+/// without one, since OpenAI clients expect every choice to open with its
+/// role. `announced` holds the choices that already have a role.
+fn missing_role_deltas(
+    chunk: &CanonicalStreamChunk,
+    announced: &mut HashSet<u32>,
+) -> Option<CanonicalStreamChunk> {
+    let choices: Vec<StreamChoice> = chunk
+        .choices
+        .iter()
+        .filter(|sc| announced.insert(sc.index) && !matches!(sc.delta, DeltaContent::Role(_)))
+        .map(|sc| StreamChoice {
+            index: sc.index,
+            delta: DeltaContent::Role(Role::Assistant),
+            logprobs: None,
+        })
+        .collect();
+    (!choices.is_empty()).then_some(CanonicalStreamChunk { choices })
+}
+
+/// A chunk finishing with `stop` every choice in `started` that is not in
+/// `finished`, in index order.
+fn missing_finish_deltas(
+    started: &HashSet<u32>,
+    finished: &HashSet<u32>,
+) -> Option<CanonicalStreamChunk> {
+    let mut open: Vec<u32> = started.difference(finished).copied().collect();
+    open.sort_unstable();
+    let choices: Vec<StreamChoice> = open
+        .into_iter()
+        .map(|index| StreamChoice {
+            index,
+            delta: DeltaContent::Finish(FinishReason::Stop),
+            logprobs: None,
+        })
+        .collect();
+    (!choices.is_empty()).then_some(CanonicalStreamChunk { choices })
+}
+
+/// The client-facing stream together with what must live exactly as long
+/// as it: the admission permit and the backend's in-flight count.
+struct GuardedStream<S> {
+    inner: Pin<Box<S>>,
+    _permit: Option<AdmissionPermit>,
+    _in_flight: InFlightGuard,
+}
+
+impl<S: futures_core::Stream> futures_core::Stream for GuardedStream<S> {
+    type Item = S::Item;
 
-    if !backend_resp.status().is_success() {
-        let status = backend_resp.status().as_u16();
-        let body_text = backend_resp.text().await.unwrap_or_default();
-        return Err(GatewayError::Backend(
-            mb_core::core::BackendError::HttpStatus {
-                status,
-                body: body_text,
-            },
-        ));
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        self.inner.as_mut().poll_next(cx)
     }
+}
 
-    // Build SSE event stream
-    let byte_stream = backend_resp.bytes_stream();
-    let sse_parser = SseLineParser::new(byte_stream);
-
-    let client_id_owned = client_info.id.clone();
-    let model_owned = canonical_req.model.clone();
-    let prefix_hash_owned = canonical_req.metadata.prefix_hash;
-
-    let event_stream = make_event_stream(
-        sse_parser,
-        outbound_spec,
-        state,
-        client_id_owned,
-        model_owned,
-        selected_id,
-        prefix_hash_owned,
-    );
-
-    Ok(axum::response::sse::Sse::new(event_stream)
-        .keep_alive(axum::response::sse::KeepAlive::default())
-        .into_response())
+/// One item of the client-facing stream, before framing.
+enum StreamItem {
+    /// A chunk formatted by the inbound adapter.
+    Payload(String),
+    /// Nothing has been sent for a full heartbeat interval.
+    Heartbeat,
 }
 
-fn make_event_stream(
+/// Yields each chunk formatted by the inbound adapter, without framing, plus
+/// a [`StreamItem::Heartbeat`] whenever the backend stays silent for
+/// `heartbeat` so idle proxies do not cut the connection mid-generation.
+///
+/// If the backend connection fails after content has been sent, a final
+/// OpenAI-style `{"error": {...}}` payload is yielded before the stream ends.
+/// A stream that ends cleanly with choices still open gets a synthesized
+/// `stop` finish for them.
+#[allow(clippy::too_many_arguments)]
+fn make_payload_stream(
     sse_parser: SseLineParser<
         impl futures_core::Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
     >,
     outbound_spec: BackendSpec,
     state: Arc<AppState>,
     client_id: ClientId,
-    model: ModelId,
-    selected_backend: mb_core::core::BackendId,
+    context: StreamContext,
+    selected_backend: BackendId,
     prefix_hash: Option<PrefixHash>,
-) -> impl futures_core::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>
-{
+    heartbeat: std::time::Duration,
+) -> impl futures_core::Stream<Item = StreamItem> + Send {
     async_stream::stream! {
         let mut lines = Box::pin(sse_parser);
-        let mut finished = false;
-
-        while let Some(line_result) = lines.next().await {
+        // Choices the backend has sent a finish reason for.
+        let mut finished = HashSet::new();
+        // Whether any chunk has reached the client.
+        let mut started = false;
+        let mut failed = false;
+        let mut announced_roles = HashSet::new();
+        let mut deadline = tokio::time::Instant::now() + heartbeat;
+
+        loop {
+            // Race the next backend line against the heartbeat timer; the
+            // timer restarts whenever something reaches the client.
+            let line_result = match tokio::time::timeout_at(deadline, lines.next()).await {
+                Ok(Some(line_result)) => line_result,
+                Ok(None) => break,
+                Err(_) => {
+                    deadline = tokio::time::Instant::now() + heartbeat;
+                    yield StreamItem::Heartbeat;
+                    continue;
+                }
+            };
             let line = match line_result {
                 Ok(l) => l,
-                Err(_) => break, // Connection error, stop streaming
+                Err(e) => {
+                    // Once content has gone out the status is already 200,
+                    // so tell the client in-band why the stream stops short.
+                    if started {
+                        let err = GatewayError::Backend(BackendError::Connection(e.to_string()));
+                        let (_, body) = gateway_error_body(
+                            &err,
+                            state.error_verbosity,
+                            state.redact_log_content,
+                        );
+                        yield StreamItem::Payload(body.to_string());
+                    }
+                    failed = true;
+                    break;
+                }
             };
 
             // Get adapters each iteration (they're behind shared refs)
@@ -231,30 +631,46 @@ fn make_event_stream(
             // Check for finish signal
             for sc in &chunk.choices {
                 if matches!(sc.delta, DeltaContent::Finish(_)) {
-                    finished = true;
+                    finished.insert(sc.index);
+                }
+            }
+
+            if let Some(roles) = missing_role_deltas(&chunk, &mut announced_roles) {
+                if let Ok(Some(payload)) = inbound.format_stream_chunk(&roles, &context) {
+                    started = true;
+                    yield StreamItem::Payload(payload);
                 }
             }
 
             // Format through inbound adapter
-            match inbound.format_stream_chunk(&chunk) {
-                Ok(Some(sse_text)) => {
-                    yield Ok(axum::response::sse::Event::default().data(sse_text));
+            match inbound.format_stream_chunk(&chunk, &context) {
+                Ok(Some(payload)) => {
+                    deadline = tokio::time::Instant::now() + heartbeat;
+                    started = true;
+                    yield StreamItem::Payload(payload);
                 }
                 Ok(None) => continue,
                 Err(_) => continue,
             }
         }
 
-        // Send done sentinel
-        if let Some(inbound) = state.inbound_registry.get(&ApiSpec::OpenAiChat) {
-            yield Ok(axum::response::sse::Event::default().data(inbound.done_sentinel()));
+        // A backend that ends its stream without a finish reason (Ollama
+        // dropping its `done: true` line, say) would leave clients waiting
+        // on open choices, so close each of them with `stop`.
+        if !failed {
+            if let Some(finish) = missing_finish_deltas(&announced_roles, &finished) {
+                if let Some(inbound) = state.inbound_registry.get(&ApiSpec::OpenAiChat) {
+                    if let Ok(Some(payload)) = inbound.format_stream_chunk(&finish, &context) {
+                        yield StreamItem::Payload(payload);
+                    }
+                }
+            }
         }
 
         // Record cache affinity after successful streaming
         if state.cache_config.enabled {
             if let Some(prefix) = prefix_hash {
-                let mut map = state.affinity_map.write().await;
-                map.record(&model, prefix, &selected_backend);
+                crate::handler::affinity_record(&state, &context.model, prefix, &selected_backend);
             }
         }
 
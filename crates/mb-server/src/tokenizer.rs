@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use mb_core::core::{HeuristicTokenCounter, TokenCounter, TokenCounterRegistry};
+
+use crate::config::TokenizerConfig;
+
+// ---------------------------------------------------------------------------
+// Tokenizers — builds the prompt token counter registry from config
+// ---------------------------------------------------------------------------
+
+/// Whether this build can count tokens with `tokenizer`.
+pub fn is_available(tokenizer: TokenizerConfig) -> bool {
+    match tokenizer {
+        TokenizerConfig::Heuristic => true,
+        TokenizerConfig::Cl100kBase | TokenizerConfig::O200kBase => cfg!(feature = "tiktoken"),
+    }
+}
+
+/// Registry with one family per `routing.tokenizers` entry. Callers check
+/// [`is_available`] first; an unavailable tokenizer falls back to the
+/// heuristic.
+pub fn registry(tokenizers: &HashMap<String, TokenizerConfig>) -> TokenCounterRegistry {
+    tokenizers.iter().fold(
+        TokenCounterRegistry::new(),
+        |registry, (prefix, tokenizer)| registry.with_family(prefix.as_str(), counter(*tokenizer)),
+    )
+}
+
+fn counter(tokenizer: TokenizerConfig) -> Arc<dyn TokenCounter> {
+    match tokenizer {
+        #[cfg(feature = "tiktoken")]
+        TokenizerConfig::Cl100kBase => {
+            Arc::new(TiktokenCounter(tiktoken_rs::cl100k_base_singleton()))
+        }
+        #[cfg(feature = "tiktoken")]
+        TokenizerConfig::O200kBase => {
+            Arc::new(TiktokenCounter(tiktoken_rs::o200k_base_singleton()))
+        }
+        _ => Arc::new(HeuristicTokenCounter),
+    }
+}
+
+/// Exact BPE token counts from one of OpenAI's encodings.
+#[cfg(feature = "tiktoken")]
+struct TiktokenCounter(&'static tiktoken_rs::CoreBPE);
+
+#[cfg(feature = "tiktoken")]
+impl TokenCounter for TiktokenCounter {
+    fn count_text(&self, text: &str) -> u64 {
+        self.0.encode_ordinary(text).len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mb_core::core::{Message, MessageContent, ModelId, Role};
+
+    use super::*;
+
+    fn user(text: &str) -> Message {
+        Message {
+            role: Role::User,
+            content: MessageContent::Text(text.to_owned()),
+            name: None,
+            tool_call_id: None,
+        }
+    }
+
+    #[test]
+    fn test_heuristic_family_and_fallback() {
+        let registry = registry(&HashMap::from([(
+            "gpt-4".to_owned(),
+            TokenizerConfig::Heuristic,
+        )]));
+        let messages = [user("twelve bytes")];
+        assert_eq!(registry.count(&ModelId::new("gpt-4o"), &messages), 3);
+        assert_eq!(registry.count(&ModelId::new("llama3-70b"), &messages), 3);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_tiktoken_counts_bpe_tokens() {
+        let registry = registry(&HashMap::from([(
+            "gpt-4".to_owned(),
+            TokenizerConfig::Cl100kBase,
+        )]));
+        let messages = [user("hello world hello world")];
+        assert_eq!(registry.count(&ModelId::new("gpt-4"), &messages), 4);
+        // Unlisted families keep the bytes / 4 estimate.
+        assert_eq!(registry.count(&ModelId::new("llama3-70b"), &messages), 5);
+    }
+}
@@ -0,0 +1,102 @@
+use mb_core::core::{
+    BackendId, BackendInfo, CanonicalRequest, ClientId, GatewayError, GenerationParams,
+    HealthProbe, Message, MessageContent, ModelId, RequestId, RequestMetadata, Role,
+};
+
+use crate::handler::{backend_call, send_to_backend, AppState};
+
+// ---------------------------------------------------------------------------
+// Backend warm-up — one-shot model preload at startup
+// ---------------------------------------------------------------------------
+
+/// Tally of warm-up completions sent by [`warm_up_backends`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WarmupOutcome {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Sends a minimal one-token completion to every model of each backend listed
+/// in `warmup_ids`, so local runtimes load weights before real traffic.
+/// Requests go out exactly as dispatched ones do, through `state`'s client,
+/// adapters and 429 retries.
+///
+/// Each backend is probed first; unreachable backends are skipped and left to
+/// the regular health checks. Failures are logged, never fatal.
+pub async fn warm_up_backends(
+    state: &AppState,
+    probe: &dyn HealthProbe,
+    backends: &[BackendInfo],
+    warmup_ids: &[BackendId],
+) -> WarmupOutcome {
+    let mut outcome = WarmupOutcome::default();
+
+    for backend in backends.iter().filter(|b| warmup_ids.contains(&b.id)) {
+        if let Err(e) = probe.probe(backend).await {
+            tracing::warn!(backend = %backend.id, error = %e, "skipping warm-up, backend not healthy");
+            continue;
+        }
+
+        for model in &backend.models {
+            match warm_up_model(state, &backend.id, model).await {
+                Ok(()) => {
+                    tracing::info!(backend = %backend.id, model = %model, "warm-up completed");
+                    outcome.succeeded += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(backend = %backend.id, model = %model, error = %e, "warm-up failed");
+                    outcome.failed += 1;
+                }
+            }
+        }
+    }
+
+    outcome
+}
+
+async fn warm_up_model(
+    state: &AppState,
+    backend: &BackendId,
+    model: &ModelId,
+) -> Result<(), GatewayError> {
+    let call = backend_call(state, backend, &warmup_request(model))?;
+    send_to_backend(state, backend, call.request).await?;
+    Ok(())
+}
+
+fn warmup_request(model: &ModelId) -> CanonicalRequest {
+    CanonicalRequest {
+        model: model.clone(),
+        messages: vec![Message {
+            role: Role::User,
+            content: MessageContent::Text("ping".to_owned()),
+            name: None,
+            tool_call_id: None,
+        }],
+        params: GenerationParams {
+            max_tokens: Some(1),
+            ..GenerationParams::default()
+        },
+        tools: None,
+        tool_choice: None,
+        parallel_tool_calls: None,
+        logprobs: None,
+        top_logprobs: None,
+        response_format: None,
+        echo: None,
+        suffix: None,
+        n: None,
+        stream: false,
+        metadata: RequestMetadata {
+            request_id: RequestId::new("warmup"),
+            client_id: ClientId::new("mb-warmup"),
+            estimated_input_tokens: 1,
+            prefix_hash: None,
+            app_title: None,
+            app_referer: None,
+            client_metadata: None,
+            store: false,
+            provider_hint: None,
+        },
+    }
+}
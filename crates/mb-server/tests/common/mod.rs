@@ -1,24 +1,38 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::atomic::AtomicUsize;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use axum::body::Bytes;
-use axum::extract::State;
-use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{RawQuery, State};
+use axum::http::{HeaderMap, StatusCode, Uri};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use tokio::sync::RwLock;
 
-use mb_core::core::{BackendState, CacheAffinityMap, LatencyMs, QuotaTracker};
+use mb_core::core::{
+    AdapterError, BackendInfo, BackendSpec, BackendState, CanonicalRequest, CanonicalResponse,
+    CanonicalStreamChunk, LatencyMs, OutboundAdapter, PrefixDepthTracker, QuotaTracker,
+    RoundCounters, RoutingPolicy, ShardedAffinityMap, TokenCounterRegistry,
+};
+use mb_server::admission::AdmissionQueue;
 use mb_server::bootstrap::CacheConfig;
+use mb_server::coalesce::Coalescer;
 use mb_server::config::{
-    AllowedModelsConfig, AppConfig, BackendConfig, BackendSpecConfig, ClientConfig, HealthConfig,
-    LoggingConfig, RoutingConfig, RoutingStrategyConfig, ServerConfig,
+    AdminConfig, AllowedModelsConfig, AppConfig, AttributionHeaders, AuditConfig, BackendConfig,
+    BackendSpecConfig, CapabilityCheck, ClientConfig, ErrorVerbosity, FanoutMode,
+    ForbiddenParamActionConfig, HealthConfig, JsonOutputValidation, LoggingConfig,
+    ModelCapabilitiesConfig, PenaltyRangeCheck, ResponseModelCheck, RoutingConfig,
+    RoutingStrategyConfig, ServerConfig, ToolFallback,
 };
 use mb_server::handler::{AppState, BackendMeta};
+use mb_server::idempotency::IdempotencyCache;
 use mb_server::inbound::InboundAdapterRegistry;
+use mb_server::outbound::openai_chat::OpenAiChatOutboundAdapter;
 use mb_server::outbound::OutboundAdapterRegistry;
+use mb_server::response_cache::ResponseCache;
+use mb_server::sharded::ShardedMap;
 
 // ---------------------------------------------------------------------------
 // MockBackendServer — configurable mock that mimics an LLM backend
@@ -26,17 +40,42 @@ use mb_server::outbound::OutboundAdapterRegistry;
 
 enum MockMode {
     Json {
-        body: String,
+        body: Bytes,
         status: u16,
         delay_ms: u64,
     },
     Sse {
-        body: String,
+        /// Complete SSE events, each sent as its own body chunk.
+        events: Vec<String>,
+        first_chunk_delay_ms: u64,
+        /// Pause between consecutive events.
+        chunk_gap_ms: u64,
+        /// Abort the body with an error after the last event.
+        then_error: bool,
     },
 }
 
+struct MockState {
+    mode: Arc<MockMode>,
+    completions: Arc<AtomicUsize>,
+    last_body: Arc<Mutex<Option<Bytes>>>,
+    last_headers: Arc<Mutex<Option<HeaderMap>>>,
+    last_path: Arc<Mutex<Option<String>>>,
+    /// Bearer token `/v1/models` demands; `None` serves it to anyone.
+    models_key: Option<String>,
+    /// Completion requests still to be answered with a 429 before `mode`
+    /// takes over.
+    throttled: AtomicUsize,
+    /// `Retry-After` sent with those 429s.
+    retry_after: Option<String>,
+}
+
 pub struct MockBackendServer {
     addr: SocketAddr,
+    completions: Arc<AtomicUsize>,
+    last_body: Arc<Mutex<Option<Bytes>>>,
+    last_headers: Arc<Mutex<Option<HeaderMap>>>,
+    last_path: Arc<Mutex<Option<String>>>,
     _handle: tokio::task::JoinHandle<()>,
 }
 
@@ -46,31 +85,124 @@ impl MockBackendServer {
     }
 
     pub async fn start_with_options(response_body: &str, status: u16, delay_ms: u64) -> Self {
+        Self::start_raw(response_body.as_bytes(), status, delay_ms).await
+    }
+
+    /// Like `start_with_options`, but replies with arbitrary bytes, which
+    /// need not be UTF-8.
+    pub async fn start_raw(response_body: &[u8], status: u16, delay_ms: u64) -> Self {
         let mode = Arc::new(MockMode::Json {
-            body: response_body.to_owned(),
+            body: Bytes::copy_from_slice(response_body),
             status,
             delay_ms,
         });
-        Self::start_server(mode).await
+        Self::start_server(mode, None).await
+    }
+
+    /// Like `start`, but the first `throttled` completion requests get a 429,
+    /// with `Retry-After: {retry_after}` when given.
+    pub async fn start_throttled(
+        response_body: &str,
+        throttled: usize,
+        retry_after: Option<&str>,
+    ) -> Self {
+        let mode = Arc::new(MockMode::Json {
+            body: Bytes::copy_from_slice(response_body.as_bytes()),
+            status: 200,
+            delay_ms: 0,
+        });
+        Self::start_server_throttled(mode, None, throttled, retry_after.map(str::to_owned)).await
+    }
+
+    /// Like `start`, but `/v1/models` answers 401 unless called with
+    /// `Authorization: Bearer {key}`.
+    pub async fn start_with_models_key(response_body: &str, key: &str) -> Self {
+        let mode = Arc::new(MockMode::Json {
+            body: Bytes::copy_from_slice(response_body.as_bytes()),
+            status: 200,
+            delay_ms: 0,
+        });
+        Self::start_server(mode, Some(key.to_owned())).await
     }
 
     /// Start a mock that returns SSE-formatted streaming events.
     pub async fn start_sse(events: &[&str]) -> Self {
-        let sse_body: String = events
+        Self::start_sse_timed(events, 0, 0).await
+    }
+
+    /// Like `start_sse`, but sends response headers immediately and holds the
+    /// body back for `delay_ms`, mimicking a slow time-to-first-token.
+    pub async fn start_sse_delayed(events: &[&str], delay_ms: u64) -> Self {
+        Self::start_sse_timed(events, delay_ms, 0).await
+    }
+
+    /// Like `start_sse`, but pauses `gap_ms` between events, mimicking a
+    /// backend that stalls mid-generation.
+    pub async fn start_sse_paced(events: &[&str], gap_ms: u64) -> Self {
+        Self::start_sse_timed(events, 0, gap_ms).await
+    }
+
+    /// Like `start_sse`, but instead of `[DONE]` the connection fails after
+    /// the last event, mimicking a backend that dies mid-generation.
+    pub async fn start_sse_then_error(events: &[&str]) -> Self {
+        let events = events.iter().map(|e| format!("data: {e}\n\n")).collect();
+        let mode = Arc::new(MockMode::Sse {
+            events,
+            first_chunk_delay_ms: 0,
+            chunk_gap_ms: 0,
+            then_error: true,
+        });
+        Self::start_server(mode, None).await
+    }
+
+    async fn start_sse_timed(
+        events: &[&str],
+        first_chunk_delay_ms: u64,
+        chunk_gap_ms: u64,
+    ) -> Self {
+        let events = events
             .iter()
             .map(|e| format!("data: {e}\n\n"))
-            .collect::<String>()
-            + "data: [DONE]\n\n";
+            .chain(std::iter::once("data: [DONE]\n\n".to_owned()))
+            .collect();
+
+        let mode = Arc::new(MockMode::Sse {
+            events,
+            first_chunk_delay_ms,
+            chunk_gap_ms,
+            then_error: false,
+        });
+        Self::start_server(mode, None).await
+    }
 
-        let mode = Arc::new(MockMode::Sse { body: sse_body });
-        Self::start_server(mode).await
+    async fn start_server(mode: Arc<MockMode>, models_key: Option<String>) -> Self {
+        Self::start_server_throttled(mode, models_key, 0, None).await
     }
 
-    async fn start_server(mode: Arc<MockMode>) -> Self {
+    async fn start_server_throttled(
+        mode: Arc<MockMode>,
+        models_key: Option<String>,
+        throttled: usize,
+        retry_after: Option<String>,
+    ) -> Self {
+        let completions = Arc::new(AtomicUsize::new(0));
+        let last_body = Arc::new(Mutex::new(None));
+        let last_headers = Arc::new(Mutex::new(None));
+        let last_path = Arc::new(Mutex::new(None));
         let app = axum::Router::new()
             .route("/v1/chat/completions", post(mock_handler))
+            .route("/api/chat", post(mock_handler))
             .route("/v1/models", get(mock_models_handler))
-            .with_state(mode);
+            .with_state(Arc::new(MockState {
+                mode,
+                completions: Arc::clone(&completions),
+                last_body: Arc::clone(&last_body),
+                last_headers: Arc::clone(&last_headers),
+                last_path: Arc::clone(&last_path),
+                models_key,
+                throttled: AtomicUsize::new(throttled),
+                retry_after,
+            }));
 
         let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
             .await
@@ -83,6 +215,10 @@ impl MockBackendServer {
 
         Self {
             addr,
+            completions,
+            last_body,
+            last_headers,
+            last_path,
             _handle: handle,
         }
     }
@@ -90,6 +226,27 @@ impl MockBackendServer {
     pub fn url(&self) -> String {
         format!("http://{}", self.addr)
     }
+
+    /// Number of chat completion requests received so far.
+    pub fn completion_requests(&self) -> usize {
+        self.completions.load(Ordering::SeqCst)
+    }
+
+    /// JSON body of the most recent chat completion request.
+    pub fn last_request_body(&self) -> Option<serde_json::Value> {
+        let body = self.last_body.lock().unwrap().clone()?;
+        Some(serde_json::from_slice(&body).expect("gateway sends JSON"))
+    }
+
+    /// Headers of the most recent chat completion request.
+    pub fn last_request_headers(&self) -> Option<HeaderMap> {
+        self.last_headers.lock().unwrap().clone()
+    }
+
+    /// Path of the most recent chat completion request.
+    pub fn last_request_path(&self) -> Option<String> {
+        self.last_path.lock().unwrap().clone()
+    }
 }
 
 impl Drop for MockBackendServer {
@@ -98,8 +255,77 @@ impl Drop for MockBackendServer {
     }
 }
 
-async fn mock_handler(State(mode): State<Arc<MockMode>>, _body: Bytes) -> Response {
-    match mode.as_ref() {
+/// Backend address whose TCP handshake never completes: the listen backlog
+/// is already full and nothing accepts, so new connects hang until the
+/// client gives up.
+///
+/// How many connections a backlog admits varies by kernel, so it is filled
+/// until a connect attempt stalls rather than with a fixed count.
+pub struct UnacceptingBackend {
+    addr: SocketAddr,
+    _listener: tokio::net::TcpListener,
+    _queued: Vec<tokio::net::TcpStream>,
+}
+
+impl UnacceptingBackend {
+    pub async fn start() -> Self {
+        let socket = tokio::net::TcpSocket::new_v4().expect("create socket");
+        socket
+            .bind("127.0.0.1:0".parse().unwrap())
+            .expect("bind unaccepting backend");
+        let listener = socket.listen(0).expect("listen");
+        let addr = listener.local_addr().unwrap();
+        let mut queued = Vec::new();
+        loop {
+            assert!(queued.len() < 64, "listen backlog never filled");
+            let connect = tokio::net::TcpStream::connect(addr);
+            match tokio::time::timeout(Duration::from_millis(100), connect).await {
+                Ok(stream) => queued.push(stream.expect("fill the backlog")),
+                Err(_) => break,
+            }
+        }
+        Self {
+            addr,
+            _listener: listener,
+            _queued: queued,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+async fn mock_handler(
+    State(state): State<Arc<MockState>>,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    state.completions.fetch_add(1, Ordering::SeqCst);
+    *state.last_path.lock().unwrap() = Some(uri.path().to_owned());
+    *state.last_body.lock().unwrap() = Some(body);
+    *state.last_headers.lock().unwrap() = Some(headers);
+    let throttle = state
+        .throttled
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok();
+    if throttle {
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(axum::http::header::CONTENT_TYPE, "application/json")],
+            r#"{"error":{"message":"rate limited","type":"rate_limit_error"}}"#,
+        )
+            .into_response();
+        if let Some(retry_after) = &state.retry_after {
+            response.headers_mut().insert(
+                axum::http::header::RETRY_AFTER,
+                retry_after.parse().expect("valid Retry-After"),
+            );
+        }
+        return response;
+    }
+    match state.mode.as_ref() {
         MockMode::Json {
             body,
             status,
@@ -116,17 +342,127 @@ async fn mock_handler(State(mode): State<Arc<MockMode>>, _body: Bytes) -> Respon
             )
                 .into_response()
         }
-        MockMode::Sse { body } => (
-            StatusCode::OK,
-            [(axum::http::header::CONTENT_TYPE, "text/event-stream")],
-            body.clone(),
-        )
-            .into_response(),
+        MockMode::Sse {
+            events,
+            first_chunk_delay_ms,
+            chunk_gap_ms,
+            then_error,
+        } => {
+            let events = events.clone();
+            let delay = std::time::Duration::from_millis(*first_chunk_delay_ms);
+            let gap = std::time::Duration::from_millis(*chunk_gap_ms);
+            let then_error = *then_error;
+            let stream = async_stream::stream! {
+                tokio::time::sleep(delay).await;
+                for (i, event) in events.into_iter().enumerate() {
+                    if i > 0 {
+                        tokio::time::sleep(gap).await;
+                    }
+                    yield Ok::<_, std::io::Error>(Bytes::from(event));
+                }
+                if then_error {
+                    // Let the last event reach the gateway before the reset.
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    yield Err(std::io::Error::other("backend crashed"));
+                }
+            };
+            (
+                StatusCode::OK,
+                [(axum::http::header::CONTENT_TYPE, "text/event-stream")],
+                axum::body::Body::from_stream(stream),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// OpenAI-spec adapter whose non-streaming path does not exist on the mock,
+/// so only calls to `streaming_inference_path` can succeed.
+struct SplitPathAdapter;
+
+impl OutboundAdapter for SplitPathAdapter {
+    fn backend_spec(&self) -> BackendSpec {
+        BackendSpec::OpenAiChat
+    }
+
+    fn build_request_body(
+        &self,
+        req: &CanonicalRequest,
+        backend: &BackendInfo,
+    ) -> Result<Vec<u8>, AdapterError> {
+        OpenAiChatOutboundAdapter.build_request_body(req, backend)
+    }
+
+    fn parse_response(&self, body: &[u8]) -> Result<CanonicalResponse, AdapterError> {
+        OpenAiChatOutboundAdapter.parse_response(body)
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Result<Option<CanonicalStreamChunk>, AdapterError> {
+        OpenAiChatOutboundAdapter.parse_stream_line(line)
+    }
+
+    fn extra_headers(&self, backend: &BackendInfo) -> Vec<(String, String)> {
+        OpenAiChatOutboundAdapter.extra_headers(backend)
+    }
+
+    fn inference_path(&self) -> &str {
+        "/v1/generate"
+    }
+
+    fn streaming_inference_path(&self) -> &str {
+        "/v1/chat/completions"
+    }
+}
+
+/// OpenAI-spec adapter that reports no streaming support.
+struct NonStreamingAdapter;
+
+impl OutboundAdapter for NonStreamingAdapter {
+    fn backend_spec(&self) -> BackendSpec {
+        BackendSpec::OpenAiChat
+    }
+
+    fn build_request_body(
+        &self,
+        req: &CanonicalRequest,
+        backend: &BackendInfo,
+    ) -> Result<Vec<u8>, AdapterError> {
+        OpenAiChatOutboundAdapter.build_request_body(req, backend)
+    }
+
+    fn parse_response(&self, body: &[u8]) -> Result<CanonicalResponse, AdapterError> {
+        OpenAiChatOutboundAdapter.parse_response(body)
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Result<Option<CanonicalStreamChunk>, AdapterError> {
+        OpenAiChatOutboundAdapter.parse_stream_line(line)
+    }
+
+    fn extra_headers(&self, backend: &BackendInfo) -> Vec<(String, String)> {
+        OpenAiChatOutboundAdapter.extra_headers(backend)
+    }
+
+    fn inference_path(&self) -> &str {
+        "/v1/chat/completions"
+    }
+
+    fn supports_streaming(&self) -> bool {
+        false
     }
 }
 
-async fn mock_models_handler() -> Response {
-    (StatusCode::OK, axum::Json(serde_json::json!({"data": []}))).into_response()
+async fn mock_models_handler(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(key) = &state.models_key {
+        let expected = format!("Bearer {key}");
+        let authorized = headers
+            .get("authorization")
+            .is_some_and(|value| value.as_bytes() == expected.as_bytes());
+        if !authorized {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    let body = serde_json::json!({"data": [{"id": TEST_MODEL, "object": "model"}]});
+    (StatusCode::OK, axum::Json(body)).into_response()
 }
 
 // ---------------------------------------------------------------------------
@@ -135,6 +471,7 @@ async fn mock_models_handler() -> Response {
 
 async fn dispatch_handler(
     State(state): State<Arc<AppState>>,
+    query: RawQuery,
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
@@ -152,7 +489,12 @@ async fn dispatch_handler(
             .await;
         }
     }
-    mb_server::handler::handle_completion(State(state), headers, body).await
+    mb_server::handler::handle_completion(State(state), query, headers, body).await
+}
+
+/// Test-only route that always panics, to exercise panic recovery.
+async fn panic_handler() -> Response {
+    panic!("test handler panic: secret detail");
 }
 
 // ---------------------------------------------------------------------------
@@ -162,9 +504,70 @@ async fn dispatch_handler(
 pub struct TestGatewayOptions {
     pub mark_healthy: bool,
     pub rate_limit_rpm: u32,
+    /// Applied to every test client.
+    pub rate_limit_tpm: Option<u64>,
+    /// Per-model RPM caps applied to every client.
+    pub model_rate_limits: HashMap<String, u32>,
+    /// Applied to every test client not in `client_forbidden_params`.
+    pub forbidden_params: Vec<String>,
+    /// Client id → `forbidden_params`, overriding the shared list.
+    pub client_forbidden_params: HashMap<String, Vec<String>>,
+    pub forbidden_param_action: ForbiddenParamActionConfig,
     pub routing_strategy: RoutingStrategyConfig,
     pub enable_stream_dispatch: bool,
     pub cache_aware: bool,
+    pub affinity_skip_on_contention: bool,
+    pub request_timeout: Option<Duration>,
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub stream_read_timeout: Duration,
+    pub trust_forwarded: bool,
+    pub ip_rate_limit_rpm: Option<u32>,
+    pub error_verbosity: ErrorVerbosity,
+    pub redact_log_content: bool,
+    pub attribution_headers: AttributionHeaders,
+    pub sse_keepalive: Duration,
+    pub verify_response_model: ResponseModelCheck,
+    pub validate_json_output: JsonOutputValidation,
+    pub penalty_range: PenaltyRangeCheck,
+    pub allow_empty_choices: bool,
+    pub token_counters: TokenCounterRegistry,
+    pub require_user_message: bool,
+    pub merge_system_messages: bool,
+    pub case_insensitive_models: bool,
+    pub max_tools: Option<u32>,
+    pub default_model: Option<String>,
+    pub provider_prefix: bool,
+    pub fanout: FanoutMode,
+    pub coalesce: bool,
+    pub response_cache: bool,
+    pub idempotency_ttl_secs: u64,
+    pub queue_when_saturated: bool,
+    pub retry_on_429: u32,
+    /// Applied to every mock backend.
+    pub max_concurrent: u32,
+    /// Backend id (`mock-{i}`) → `max_rpm`.
+    pub backend_max_rpm: HashMap<String, u32>,
+    /// Client id → priority; unlisted clients get 0.
+    pub client_priorities: HashMap<String, u8>,
+    /// Client id → `max_tools`; unlisted clients use the routing default.
+    pub client_max_tools: HashMap<String, u32>,
+    pub monthly_token_limit: Option<u64>,
+    pub admin_key: Option<String>,
+    pub auth_schemes: Vec<String>,
+    /// Serve OpenAI-spec backends through an adapter that cannot stream.
+    pub non_streaming_backends: bool,
+    /// Serve OpenAI-spec backends through an adapter with a separate
+    /// streaming endpoint.
+    pub split_stream_path_backends: bool,
+    /// Backend ids (`mock-{i}`) configured with the Ollama spec; the rest
+    /// are OpenAI-spec.
+    pub ollama_backends: Vec<String>,
+    pub per_model: HashMap<String, RoutingStrategyConfig>,
+    pub model_capabilities: HashMap<String, ModelCapabilitiesConfig>,
+    pub capability_check: CapabilityCheck,
+    /// Applied to every mock backend.
+    pub model_map: HashMap<String, String>,
 }
 
 impl Default for TestGatewayOptions {
@@ -172,25 +575,79 @@ impl Default for TestGatewayOptions {
         Self {
             mark_healthy: true,
             rate_limit_rpm: 60,
+            rate_limit_tpm: None,
+            model_rate_limits: HashMap::new(),
+            forbidden_params: Vec::new(),
+            client_forbidden_params: HashMap::new(),
+            forbidden_param_action: ForbiddenParamActionConfig::Strip,
             routing_strategy: RoutingStrategyConfig::LeastLoaded,
             enable_stream_dispatch: false,
             cache_aware: true,
+            affinity_skip_on_contention: false,
+            request_timeout: None,
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(30),
+            stream_read_timeout: Duration::from_secs(300),
+            trust_forwarded: false,
+            ip_rate_limit_rpm: None,
+            error_verbosity: ErrorVerbosity::Full,
+            redact_log_content: false,
+            attribution_headers: AttributionHeaders::Off,
+            sse_keepalive: Duration::from_secs(15),
+            verify_response_model: ResponseModelCheck::Off,
+            validate_json_output: JsonOutputValidation::Off,
+            penalty_range: PenaltyRangeCheck::Off,
+            allow_empty_choices: false,
+            token_counters: TokenCounterRegistry::new(),
+            require_user_message: false,
+            merge_system_messages: false,
+            case_insensitive_models: false,
+            max_tools: None,
+            default_model: None,
+            provider_prefix: false,
+            fanout: FanoutMode::Off,
+            coalesce: false,
+            response_cache: false,
+            idempotency_ttl_secs: 0,
+            queue_when_saturated: false,
+            retry_on_429: 0,
+            max_concurrent: 64,
+            backend_max_rpm: HashMap::new(),
+            client_priorities: HashMap::new(),
+            client_max_tools: HashMap::new(),
+            monthly_token_limit: None,
+            admin_key: None,
+            auth_schemes: vec!["Bearer".to_owned()],
+            non_streaming_backends: false,
+            split_stream_path_backends: false,
+            ollama_backends: Vec::new(),
+            per_model: HashMap::new(),
+            model_capabilities: HashMap::new(),
+            capability_check: CapabilityCheck::Lenient,
+            model_map: HashMap::new(),
         }
     }
 }
 
 pub struct TestGateway {
     pub addr: SocketAddr,
+    /// Shared with the running server, for asserting on internal state.
+    pub state: Arc<AppState>,
     _handle: tokio::task::JoinHandle<()>,
 }
 
 impl TestGateway {
     /// Start a gateway with one mock backend and one client (defaults).
     pub async fn start_simple(mock_url: &str) -> Self {
+        Self::start_with(mock_url, TestGatewayOptions::default()).await
+    }
+
+    /// Start a gateway with one mock backend and one client under `options`.
+    pub async fn start_with(mock_url: &str, options: TestGatewayOptions) -> Self {
         Self::start(
             &[(mock_url.to_owned(), vec![TEST_MODEL.to_owned()])],
             &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
-            TestGatewayOptions::default(),
+            options,
         )
         .await
     }
@@ -208,8 +665,17 @@ impl TestGateway {
                 api_key: key.to_string(),
                 allowed_models: AllowedModelsConfig::Specific(models.clone()),
                 rate_limit_rpm: options.rate_limit_rpm,
-                rate_limit_tpm: None,
-                monthly_token_limit: None,
+                rate_limit_tpm: options.rate_limit_tpm,
+                monthly_token_limit: options.monthly_token_limit,
+                model_rate_limits: options.model_rate_limits.clone(),
+                forbidden_params: options
+                    .client_forbidden_params
+                    .get(*id)
+                    .unwrap_or(&options.forbidden_params)
+                    .clone(),
+                forbidden_param_action: options.forbidden_param_action,
+                priority: options.client_priorities.get(*id).copied().unwrap_or(0),
+                max_tools: options.client_max_tools.get(*id).copied(),
             })
             .collect();
 
@@ -220,24 +686,59 @@ impl TestGateway {
                 id: format!("mock-{i}"),
                 base_url: url.clone(),
                 api_key: None,
-                spec: BackendSpecConfig::OpenaiChat,
+                spec: if options.ollama_backends.contains(&format!("mock-{i}")) {
+                    BackendSpecConfig::Ollama
+                } else {
+                    BackendSpecConfig::OpenaiChat
+                },
                 models: models.clone(),
-                max_concurrent: 64,
+                max_concurrent: options.max_concurrent,
+                warmup: false,
+                model_map: options.model_map.clone(),
+                supports_tools: true,
+                tool_fallback: ToolFallback::Reject,
+                cost_weight: 0,
+                max_rpm: options.backend_max_rpm.get(&format!("mock-{i}")).copied(),
             })
             .collect();
 
         let config = AppConfig {
             server: ServerConfig {
                 listen: "127.0.0.1:0".to_owned(),
+                auth_schemes: options.auth_schemes.clone(),
                 ..ServerConfig::default()
             },
             routing: RoutingConfig {
                 strategy: options.routing_strategy,
                 cache_aware: options.cache_aware,
+                affinity_skip_on_contention: options.affinity_skip_on_contention,
+                verify_response_model: options.verify_response_model,
+                validate_json_output: options.validate_json_output,
+                penalty_range: options.penalty_range,
+                allow_empty_choices: options.allow_empty_choices,
+                require_user_message: options.require_user_message,
+                merge_system_messages: options.merge_system_messages,
+                case_insensitive_models: options.case_insensitive_models,
+                max_tools: options.max_tools,
+                default_model: options.default_model.clone(),
+                provider_prefix: options.provider_prefix,
+                fanout: options.fanout,
+                coalesce: options.coalesce,
+                response_cache: options.response_cache,
+                idempotency_ttl_secs: options.idempotency_ttl_secs,
+                queue_when_saturated: options.queue_when_saturated,
+                retry_on_429: options.retry_on_429,
+                per_model: options.per_model.clone(),
+                model_capabilities: options.model_capabilities.clone(),
+                capability_check: options.capability_check,
                 ..RoutingConfig::default()
             },
             health: HealthConfig::default(),
             logging: LoggingConfig::default(),
+            admin: AdminConfig {
+                api_key: options.admin_key.clone(),
+            },
+            audit: AuditConfig::default(),
             clients,
             backends,
         };
@@ -255,6 +756,8 @@ impl TestGateway {
                         base_url: b.base_url.clone(),
                         spec: b.spec,
                         api_key: None,
+                        model_map: b.model_map.clone(),
+                        tool_support: b.tool_support,
                     },
                 )
             })
@@ -262,7 +765,8 @@ impl TestGateway {
 
         let mut backend_state_map = HashMap::new();
         for b in &runtime.backends {
-            let state = BackendState::new(b.id.clone(), b.models.clone(), b.max_concurrent);
+            let state = BackendState::new(b.id.clone(), b.models.clone(), b.max_concurrent)
+                .with_cost_weight(b.cost_weight);
             let state = if options.mark_healthy {
                 state.with_healthy(LatencyMs::new(10))
             } else {
@@ -272,26 +776,101 @@ impl TestGateway {
         }
         let backend_states = Arc::new(RwLock::new(backend_state_map));
 
+        let mut outbound_registry = OutboundAdapterRegistry::new();
+        if options.non_streaming_backends {
+            outbound_registry.register(Box::new(NonStreamingAdapter));
+        }
+        if options.split_stream_path_backends {
+            outbound_registry.register(Box::new(SplitPathAdapter));
+        }
+
         let state = Arc::new(AppState {
             auth: runtime.auth_service,
             inbound_registry: InboundAdapterRegistry::new(),
-            outbound_registry: OutboundAdapterRegistry::new(),
-            backend_states,
+            outbound_registry,
+            backend_states: Arc::clone(&backend_states),
+            backend_latencies: Arc::default(),
+            in_flight: Arc::default(),
+            routing_metrics: Arc::default(),
             rate_limiters: RwLock::new(HashMap::new()),
+            token_rate_limiters: ShardedMap::new(),
             quota_tracker: RwLock::new(QuotaTracker::new()),
-            affinity_map: RwLock::new(CacheAffinityMap::new(runtime.cache_config.max_entries)),
-            http_client: reqwest::Client::new(),
-            routing_strategy: runtime.routing_strategy,
+            affinity_map: Arc::new(ShardedAffinityMap::new(runtime.cache_config.max_entries)),
+            token_counters: options.token_counters,
+            prefix_tracker: std::sync::Mutex::new(PrefixDepthTracker::new()),
+            http_client: mb_server::handler::backend_http_client(
+                options.connect_timeout,
+                options.read_timeout,
+                options
+                    .request_timeout
+                    .map(mb_server::handler::backend_total_timeout),
+            )
+            .expect("build http client"),
+            stream_http_client: mb_server::handler::backend_http_client(
+                options.connect_timeout,
+                options.stream_read_timeout,
+                None,
+            )
+            .expect("build streaming http client"),
+            routing_policy: RoutingPolicy::new(
+                runtime.routing_strategy,
+                runtime.model_strategies.clone(),
+            ),
+            model_capabilities: runtime.model_capabilities.clone(),
+            capability_check: runtime.capability_check,
             cache_config: CacheConfig {
                 enabled: runtime.cache_config.enabled,
                 prefix_depth: runtime.cache_config.prefix_depth,
                 max_entries: runtime.cache_config.max_entries,
+                skip_contended: runtime.cache_config.skip_contended,
             },
-            round_counter: AtomicUsize::new(0),
+            verify_response_model: runtime.verify_response_model,
+            validate_json_output: runtime.validate_json_output,
+            penalty_range: runtime.penalty_range,
+            allow_empty_choices: runtime.allow_empty_choices,
+            require_user_message: runtime.require_user_message,
+            merge_system_messages: runtime.merge_system_messages,
+            model_casing: runtime.model_casing,
+            default_model: runtime.default_model,
+            provider_prefix: runtime.provider_prefix,
+            fanout: runtime.fanout,
+            max_fanout: runtime.max_fanout,
+            coalescer: runtime.coalesce.then(Coalescer::new),
+            response_cache: runtime.response_cache_entries.map(ResponseCache::new),
+            idempotency_cache: runtime
+                .idempotency
+                .map(|(entries, ttl_ms)| IdempotencyCache::new(entries, ttl_ms)),
+            retry_on_429: runtime.retry_on_429,
+            retry_on_429_max_backoff: Duration::from_millis(runtime.retry_on_429_max_backoff_ms),
+            admission: runtime
+                .queue_timeout_ms
+                .map(|timeout_ms| Arc::new(AdmissionQueue::new(Duration::from_millis(timeout_ms)))),
+            round_counters: RoundCounters::new(
+                runtime
+                    .backends
+                    .iter()
+                    .flat_map(|b| b.models.iter().cloned()),
+            ),
             rate_limit_rpm: runtime.client_rate_limits,
+            model_rate_limit_rpm: runtime.model_rate_limits,
+            backend_rate_limit_rpm: runtime.backend_rate_limits,
+            backend_rate_limiters: ShardedMap::new(),
+            model_rate_limiters: ShardedMap::new(),
+            trust_forwarded: options.trust_forwarded,
+            trusted_proxy_hops: 1,
+            ip_rate_limit_rpm: options.ip_rate_limit_rpm,
+            ip_rate_limiters: ShardedMap::new(),
+            error_verbosity: options.error_verbosity,
+            redact_log_content: options.redact_log_content,
+            attribution_headers: options.attribution_headers,
+            sse_keepalive: options.sse_keepalive,
+            auth_schemes: runtime.auth_schemes.clone(),
+            admin_key: runtime.admin_key.clone(),
             backends_by_id,
             #[cfg(feature = "feedback")]
             feedback: None,
+            #[cfg(feature = "audit")]
+            audit: None,
         });
 
         let handler = if options.enable_stream_dispatch {
@@ -302,7 +881,47 @@ impl TestGateway {
 
         let app = axum::Router::new()
             .route("/v1/chat/completions", handler)
-            .with_state(state);
+            .route("/cache/stats", get(mb_server::handler::cache_stats_handler))
+            .route(
+                "/admin/clients",
+                get(mb_server::admin::list_clients_handler),
+            )
+            .route(
+                "/admin/clients/{id}/revoke",
+                post(mb_server::admin::revoke_client_handler),
+            )
+            .route(
+                "/admin/clients/{id}/quota/reset",
+                post(mb_server::admin::reset_quota_handler),
+            )
+            .route(
+                "/health",
+                get({
+                    let states = Arc::clone(&backend_states);
+                    let latencies = Arc::clone(&state.backend_latencies);
+                    move || mb_server::health::health_handler(states, latencies)
+                }),
+            )
+            .route(
+                "/metrics",
+                get({
+                    let states = backend_states;
+                    let latencies = Arc::clone(&state.backend_latencies);
+                    let affinity_map = Arc::clone(&state.affinity_map);
+                    let routing = Arc::clone(&state.routing_metrics);
+                    move || {
+                        mb_server::health::metrics_handler(states, latencies, affinity_map, routing)
+                    }
+                }),
+            )
+            .route("/test/panic", post(panic_handler));
+        let app = match options.request_timeout {
+            Some(timeout) => mb_server::middleware::with_request_timeout(app, timeout),
+            None => app,
+        };
+        let app = mb_server::middleware::with_panic_recovery(app);
+        let app = mb_server::middleware::with_access_log(app, Arc::clone(&state))
+            .with_state(Arc::clone(&state));
 
         let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
             .await
@@ -310,11 +929,17 @@ impl TestGateway {
         let addr = listener.local_addr().unwrap();
 
         let handle = tokio::spawn(async move {
-            axum::serve(listener, app).await.ok();
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .ok();
         });
 
         Self {
             addr,
+            state,
             _handle: handle,
         }
     }
@@ -322,6 +947,23 @@ impl TestGateway {
     pub fn url(&self) -> String {
         format!("http://{}", self.addr)
     }
+
+    /// POST `body` to `/v1/chat/completions` as the test client, with any
+    /// extra `headers`.
+    pub async fn post_chat(
+        &self,
+        body: &serde_json::Value,
+        headers: &[(&str, &str)],
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", self.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .json(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.send().await.expect("request should succeed")
+    }
 }
 
 impl Drop for TestGateway {
@@ -365,6 +1007,22 @@ pub fn sample_openai_response_with_id(id: &str) -> String {
     .to_string()
 }
 
+/// Non-streaming `/api/chat` reply in Ollama's wire format.
+pub fn sample_ollama_response() -> String {
+    serde_json::json!({
+        "model": TEST_MODEL,
+        "created_at": "2023-11-14T22:13:20Z",
+        "message": {
+            "role": "assistant",
+            "content": "Hello from Ollama!"
+        },
+        "done": true,
+        "prompt_eval_count": 10,
+        "eval_count": 5
+    })
+    .to_string()
+}
+
 pub fn sample_request_body() -> String {
     serde_json::json!({
         "model": TEST_MODEL,
@@ -1,7 +1,16 @@
 mod common;
 
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use common::*;
-use mb_server::config::RoutingStrategyConfig;
+use mb_core::core::{TokenCounter, TokenCounterRegistry};
+use mb_server::config::{
+    AttributionHeaders, CapabilityCheck, FanoutMode, ForbiddenParamActionConfig,
+    JsonOutputValidation, ModelCapabilitiesConfig, PenaltyRangeCheck, ResponseModelCheck,
+    RoutingStrategyConfig,
+};
 
 // ---------------------------------------------------------------------------
 // Basic proxy tests
@@ -29,6 +38,161 @@ async fn test_proxy_non_streaming_basic() {
     assert!(body["choices"][0]["message"]["content"].is_string());
 }
 
+#[tokio::test]
+async fn test_pretty_header_indents_response() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let send = |pretty: Option<&'static str>| {
+        let mut req = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body());
+        if let Some(value) = pretty {
+            req = req.header("X-Pretty", value);
+        }
+        req.send()
+    };
+
+    let compact = send(None).await.unwrap().text().await.unwrap();
+    assert!(!compact.contains('\n'), "{compact}");
+
+    let pretty = send(Some("true")).await.unwrap().text().await.unwrap();
+    assert!(pretty.contains("\n  \"choices\": ["), "{pretty}");
+
+    let compact: serde_json::Value = serde_json::from_str(&compact).unwrap();
+    let pretty: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+    assert_eq!(pretty, compact);
+}
+
+#[tokio::test]
+async fn test_logprobs_pass_through() {
+    let logprobs = serde_json::json!({
+        "content": [{"token": "Hello", "logprob": -0.12, "top_logprobs": []}]
+    });
+    let mut backend_resp: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).unwrap();
+    backend_resp["choices"][0]["logprobs"] = logprobs.clone();
+    let mock = MockBackendServer::start(&backend_resp.to_string()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "logprobs": true,
+            "top_logprobs": 2
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["choices"][0]["logprobs"], logprobs);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["logprobs"], true);
+    assert_eq!(sent["top_logprobs"], 2);
+}
+
+#[tokio::test]
+async fn test_echo_suffix_forwarded() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "def add(a, b):"}],
+            "echo": true,
+            "suffix": "    return result"
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["echo"], true);
+    assert_eq!(sent["suffix"], "    return result");
+}
+
+#[tokio::test]
+async fn test_store_and_metadata_not_forwarded() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "store": true,
+            "metadata": {"session": "abc-123"}
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert!(sent.get("store").is_none());
+    assert!(sent.get("metadata").is_none());
+}
+
+async fn send_with_attribution(mode: AttributionHeaders) -> Option<reqwest::header::HeaderMap> {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            attribution_headers: mode,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("X-Title", "My App")
+        .header("HTTP-Referer", "https://app.example")
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    mock.last_request_headers()
+}
+
+#[tokio::test]
+async fn test_attribution_headers_forwarded() {
+    let sent = send_with_attribution(AttributionHeaders::Forward)
+        .await
+        .expect("backend was called");
+    assert_eq!(sent["x-title"], "My App");
+    assert_eq!(sent["http-referer"], "https://app.example");
+}
+
+#[tokio::test]
+async fn test_attribution_headers_not_forwarded_unless_enabled() {
+    for mode in [AttributionHeaders::Off, AttributionHeaders::Capture] {
+        let sent = send_with_attribution(mode)
+            .await
+            .expect("backend was called");
+        assert!(!sent.contains_key("x-title"));
+        assert!(!sent.contains_key("http-referer"));
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Authentication tests
 // ---------------------------------------------------------------------------
@@ -81,6 +245,52 @@ async fn test_auth_model_not_permitted() {
     assert_eq!(body["error"]["type"], "permission_error");
 }
 
+/// Sends one completion with `header: value` to a gateway accepting the
+/// `Bearer` and `Token` schemes, returning the status.
+async fn status_with_auth_header(header: &str, value: &str) -> u16 {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            auth_schemes: vec!["Bearer".to_owned(), "Token".to_owned()],
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header(header, value)
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed")
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn test_auth_configured_token_scheme() {
+    let value = format!("Token {TEST_API_KEY}");
+    assert_eq!(status_with_auth_header("Authorization", &value).await, 200);
+}
+
+#[tokio::test]
+async fn test_auth_x_api_key_fallback() {
+    assert_eq!(
+        status_with_auth_header("X-API-Key", TEST_API_KEY).await,
+        200
+    );
+}
+
+#[tokio::test]
+async fn test_auth_unsupported_scheme_401() {
+    let value = format!("Basic {TEST_API_KEY}");
+    assert_eq!(status_with_auth_header("Authorization", &value).await, 401);
+}
+
 // ---------------------------------------------------------------------------
 // Streaming tests
 // ---------------------------------------------------------------------------
@@ -121,6 +331,103 @@ async fn test_proxy_streaming_basic() {
     );
 }
 
+#[tokio::test]
+async fn test_sse_keepalive_interval_from_config() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    // Backend stalls well past the configured keep-alive before its first chunk.
+    let mock = MockBackendServer::start_sse_delayed(&chunk_refs, 2_500).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            sse_keepalive: std::time::Duration::from_secs(1),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    let first_data = body_text.find("data:").expect("should contain data lines");
+    let comments_before_data = body_text[..first_data]
+        .lines()
+        .filter(|line| line.starts_with(':'))
+        .count();
+    assert!(
+        comments_before_data >= 1,
+        "expected keep-alive comments before the first token, got: {body_text:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_stream_format_ndjson_when_requested() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .header("X-Stream-Format", "ndjson")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers()["content-type"].to_str().unwrap(),
+        "application/x-ndjson"
+    );
+    let body_text = resp.text().await.expect("read body");
+    assert!(!body_text.contains("data:"));
+    assert!(!body_text.contains("[DONE]"));
+    let lines: Vec<&str> = body_text.lines().collect();
+    assert!(!lines.is_empty());
+    for line in lines {
+        let chunk: serde_json::Value = serde_json::from_str(line).expect("each line is JSON");
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+    }
+
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(
+        resp.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    let body_text = resp.text().await.expect("read body");
+    assert!(body_text.contains("data: {"));
+    assert!(body_text.contains("data: [DONE]"));
+}
+
 #[tokio::test]
 async fn test_streaming_multiple_chunks() {
     let chunks = sample_sse_chunks();
@@ -216,6 +523,39 @@ async fn test_round_robin() {
     );
 }
 
+#[tokio::test]
+async fn test_verify_response_model_strict_rejects_mismatch() {
+    let mut response: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).expect("valid JSON");
+    response["model"] = "some-other-model".into();
+    let mock = MockBackendServer::start(&response.to_string()).await;
+
+    for (mode, expected_status) in [
+        (ResponseModelCheck::Warn, 200),
+        (ResponseModelCheck::Strict, 502),
+    ] {
+        let gw = TestGateway::start(
+            &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+            &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+            TestGatewayOptions {
+                verify_response_model: mode,
+                ..TestGatewayOptions::default()
+            },
+        )
+        .await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), expected_status, "mode {mode:?}");
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Rate limiting tests
 // ---------------------------------------------------------------------------
@@ -264,80 +604,3640 @@ async fn test_rate_limit_exceeded() {
     assert_eq!(body["error"]["type"], "rate_limit_error");
 }
 
-// ---------------------------------------------------------------------------
-// Error handling tests
-// ---------------------------------------------------------------------------
-
 #[tokio::test]
-async fn test_backend_error_502() {
-    let mock =
-        MockBackendServer::start_with_options(r#"{"error": "internal server error"}"#, 500, 0)
-            .await;
-    let gw = TestGateway::start_simple(&mock.url()).await;
+async fn test_model_rate_limit_below_client_limit() {
+    const OTHER_MODEL: &str = "qwen2.5-14b";
+    let models = vec![TEST_MODEL.to_owned(), OTHER_MODEL.to_owned()];
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), models.clone())],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, models)],
+        TestGatewayOptions {
+            rate_limit_rpm: 60,
+            model_rate_limits: HashMap::from([(TEST_MODEL.to_owned(), 2)]),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
 
     let client = reqwest::Client::new();
-    let resp = client
-        .post(format!("{}/v1/chat/completions", gw.url()))
-        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
-        .header("Content-Type", "application/json")
-        .body(sample_request_body())
-        .send()
-        .await
-        .expect("request should succeed");
-
-    assert_eq!(resp.status(), 502);
+    let send = |model: &'static str| {
+        let body = serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .json(&body)
+            .send()
+    };
 
+    for _ in 0..2 {
+        assert_eq!(send(TEST_MODEL).await.unwrap().status(), 200);
+    }
+    // The model cap trips although only 3 of 60 client RPM are used.
+    let resp = send(TEST_MODEL).await.unwrap();
+    assert_eq!(resp.status(), 429);
     let body: serde_json::Value = resp.json().await.expect("valid JSON");
-    assert_eq!(body["error"]["type"], "backend_error");
-}
-
-#[tokio::test]
-async fn test_malformed_request_400() {
-    let mock = MockBackendServer::start(&sample_openai_response()).await;
-    let gw = TestGateway::start_simple(&mock.url()).await;
+    assert_eq!(body["error"]["type"], "rate_limit_error");
 
-    let client = reqwest::Client::new();
+    // A dry run sees the exhausted cap without charging it further.
     let resp = client
-        .post(format!("{}/v1/chat/completions", gw.url()))
+        .post(format!("{}/v1/chat/completions?dry_run=1", gw.url()))
         .header("Authorization", format!("Bearer {TEST_API_KEY}"))
-        .header("Content-Type", "application/json")
-        .body("this is not valid json")
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "Hello"}]
+        }))
         .send()
         .await
-        .expect("request should succeed");
-
-    assert_eq!(resp.status(), 400);
+        .unwrap();
+    assert_eq!(resp.status(), 429);
 
-    let body: serde_json::Value = resp.json().await.expect("valid JSON");
-    assert_eq!(body["error"]["type"], "invalid_request_error");
+    // Other models are unaffected.
+    assert_eq!(send(OTHER_MODEL).await.unwrap().status(), 200);
 }
 
 #[tokio::test]
-async fn test_no_healthy_backend_503() {
-    // Start a mock but don't mark backends as healthy
+async fn test_token_rate_limit_exceeded() {
     let mock = MockBackendServer::start(&sample_openai_response()).await;
     let gw = TestGateway::start(
         &[(mock.url(), vec![TEST_MODEL.to_owned()])],
         &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
         TestGatewayOptions {
-            mark_healthy: false,
+            rate_limit_tpm: Some(1_200),
             ..TestGatewayOptions::default()
         },
     )
     .await;
 
+    // ~500 estimated input tokens per request, well under the 60 RPM limit.
+    let body = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "word ".repeat(400)}]
+    });
     let client = reqwest::Client::new();
-    let resp = client
-        .post(format!("{}/v1/chat/completions", gw.url()))
-        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
-        .header("Content-Type", "application/json")
-        .body(sample_request_body())
-        .send()
+    let send = || {
+        client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .json(&body)
+            .send()
+    };
+
+    for _ in 0..2 {
+        let resp = send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["x-ratelimit-limit-tokens"], "1200");
+    }
+    // Two prompts plus their completions leave too little for a third.
+    let resp = send().await.unwrap();
+    assert_eq!(resp.status(), 429);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "rate_limit_error");
+    assert_eq!(mock.completion_requests(), 2);
+}
+
+#[tokio::test]
+async fn test_rate_limit_headers() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            rate_limit_rpm: 5,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let header_u64 = |resp: &reqwest::Response, name: &str| -> u64 {
+        resp.headers()
+            .get(name)
+            .unwrap_or_else(|| panic!("missing {name} header"))
+            .to_str()
+            .expect("ascii header")
+            .parse()
+            .expect("numeric header")
+    };
+
+    let mut remaining = Vec::new();
+    for _ in 0..3 {
+        let resp = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), 200);
+
+        assert_eq!(header_u64(&resp, "x-ratelimit-limit"), 5);
+        let now_secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(
+            header_u64(&resp, "x-ratelimit-reset") > now_secs,
+            "reset should be in the future"
+        );
+        remaining.push(header_u64(&resp, "x-ratelimit-remaining"));
+    }
+
+    assert_eq!(remaining, vec![4, 3, 2]);
+}
+
+// ---------------------------------------------------------------------------
+// Error handling tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_backend_error_502() {
+    let mock =
+        MockBackendServer::start_with_options(r#"{"error": "internal server error"}"#, 500, 0)
+            .await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 502);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "backend_error");
+}
+
+#[tokio::test]
+async fn test_backend_429_retried_when_enabled() {
+    let mock = MockBackendServer::start_throttled(&sample_openai_response(), 1, Some("0")).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            retry_on_429: 2,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 200);
+    assert_eq!(mock.completion_requests(), 2);
+}
+
+#[tokio::test]
+async fn test_backend_429_not_retried_by_default_or_past_max_backoff() {
+    let mock = MockBackendServer::start_throttled(&sample_openai_response(), 1, None).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+    let send = |gw_url: String| async move {
+        reqwest::Client::new()
+            .post(format!("{gw_url}/v1/chat/completions"))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed")
+    };
+
+    assert_eq!(send(gw.url()).await.status(), 502);
+    assert_eq!(mock.completion_requests(), 1);
+
+    // Retry-After of a minute is beyond the default 2s backoff ceiling.
+    let mock = MockBackendServer::start_throttled(&sample_openai_response(), 1, Some("60")).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            retry_on_429: 2,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    assert_eq!(send(gw.url()).await.status(), 502);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+#[tokio::test]
+async fn test_empty_choices_502_unless_allowed() {
+    let mut backend_resp: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).unwrap();
+    backend_resp["choices"] = serde_json::json!([]);
+    let mock = MockBackendServer::start(&backend_resp.to_string()).await;
+
+    for (allow_empty_choices, expected_status) in [(false, 502), (true, 200)] {
+        let gw = TestGateway::start(
+            &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+            &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+            TestGatewayOptions {
+                allow_empty_choices,
+                ..TestGatewayOptions::default()
+            },
+        )
+        .await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), expected_status);
+
+        let body: serde_json::Value = resp.json().await.expect("valid JSON");
+        if allow_empty_choices {
+            assert_eq!(body["choices"], serde_json::json!([]));
+        } else {
+            assert_eq!(body["error"]["type"], "backend_error");
+            let message = body["error"]["message"].as_str().unwrap();
+            assert!(message.contains("no choices"), "{message}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_empty_choices_502_on_non_streaming_stream_fallback() {
+    let mut backend_resp: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).unwrap();
+    backend_resp["choices"] = serde_json::json!([]);
+    let mock = MockBackendServer::start(&backend_resp.to_string()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            non_streaming_backends: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 502);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "backend_error");
+}
+
+#[tokio::test]
+async fn test_non_utf8_backend_body_502() {
+    // Latin-1 "café" inside otherwise plausible JSON.
+    let mock = MockBackendServer::start_raw(b"{\"id\": \"caf\xe9\"}", 200, 0).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 502);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "backend_error");
+    let message = body["error"]["message"].as_str().unwrap_or_default();
+    assert!(message.contains("malformed response"), "{message}");
+    assert!(message.contains("not valid UTF-8"), "{message}");
+}
+
+/// Collects formatted tracing output for assertions on server-side logs.
+#[derive(Clone, Default)]
+struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_terse_errors_hide_backend_body() {
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+    // current_thread runtime: gateway tasks log on this thread.
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mock = MockBackendServer::start_with_options(
+        r#"{"error": "Traceback at /srv/internal/model_runner.py line 42"}"#,
+        500,
+        0,
+    )
+    .await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            error_verbosity: mb_server::config::ErrorVerbosity::Terse,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 502);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    let message = body["error"]["message"].as_str().expect("message");
+    assert!(!message.contains("/srv/internal"), "leaked: {message}");
+    assert_eq!(body["error"]["type"], "backend_error");
+
+    let correlation_id = body["error"]["correlation_id"]
+        .as_str()
+        .expect("correlation id");
+    assert!(message.contains(correlation_id));
+
+    let logged = String::from_utf8(logs.0.lock().unwrap().clone()).expect("utf-8 logs");
+    let line = logged
+        .lines()
+        .find(|line| line.contains(correlation_id))
+        .expect("correlation id should be logged");
+    assert!(line.contains("/srv/internal/model_runner.py"));
+}
+
+#[tokio::test]
+async fn test_redact_content_keeps_backend_text_out_of_logs() {
+    const SECRET: &str = "patient Jane Roe";
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    // Valid JSON of the wrong shape, so the body is logged as malformed.
+    let backend_body = serde_json::json!({"text": SECRET}).to_string();
+    let mock = MockBackendServer::start(&backend_body).await;
+    for redact in [false, true] {
+        let gw = TestGateway::start(
+            &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+            &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+            TestGatewayOptions {
+                redact_log_content: redact,
+                ..TestGatewayOptions::default()
+            },
+        )
+        .await;
+        logs.0.lock().unwrap().clear();
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), 502);
+
+        let logged = String::from_utf8(logs.0.lock().unwrap().clone()).expect("utf-8 logs");
+        assert!(logged.contains("malformed backend response"), "{logged}");
+        assert_eq!(
+            logged.contains(SECRET),
+            !redact,
+            "redact: {redact}\n{logged}"
+        );
+        if redact {
+            let expected = format!("[redacted: {} bytes]", backend_body.len());
+            assert!(logged.contains(&expected), "{logged}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_request_400() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body("this is not valid json")
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 400);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+}
+
+#[tokio::test]
+async fn test_utf16_request_body_transcoded() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    // UTF-16LE with a byte-order mark, as some Windows clients send it.
+    let body: Vec<u8> = "\u{FEFF}"
+        .chars()
+        .chain(sample_request_body().chars())
+        .collect::<String>()
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json; charset=UTF-16")
+        .body(body)
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 200);
+    let forwarded = mock.last_request_body().expect("backend was called");
+    assert_eq!(forwarded["model"], TEST_MODEL);
+}
+
+#[tokio::test]
+async fn test_unsupported_request_charset_400() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json; charset=\"shift_jis\"")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.contains("shift_jis"), "{message}");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+#[tokio::test]
+async fn test_missing_model_names_param() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}]}))
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["param"], "model");
+    assert_eq!(body["error"]["message"], "missing required field: model");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+#[tokio::test]
+async fn test_missing_model_uses_default_model() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            default_model: Some(TEST_MODEL.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}]}))
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["model"], TEST_MODEL);
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["model"], TEST_MODEL);
+}
+
+#[tokio::test]
+async fn test_empty_model_is_rejected_despite_default_model() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_with(
+        &mock.url(),
+        TestGatewayOptions {
+            default_model: Some(TEST_MODEL.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = gw
+        .post_chat(
+            &serde_json::json!({"model": "", "messages": [{"role": "user", "content": "hi"}]}),
+            &[],
+        )
+        .await;
+
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["param"], "model");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+#[tokio::test]
+async fn test_default_model_still_checks_permission() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(
+            mock.url(),
+            vec![TEST_MODEL.to_owned(), "other-model".to_owned()],
+        )],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec!["other-model".to_owned()])],
+        TestGatewayOptions {
+            default_model: Some(TEST_MODEL.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({"messages": [{"role": "user", "content": "hi"}]}))
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 403);
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+async fn send_with_temperature(
+    action: ForbiddenParamActionConfig,
+) -> (MockBackendServer, reqwest::Response) {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            forbidden_params: vec!["temperature".to_owned()],
+            forbidden_param_action: action,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "temperature": 1.8,
+            "max_tokens": 32
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    (mock, resp)
+}
+
+#[tokio::test]
+async fn test_forbidden_param_stripped() {
+    let (mock, resp) = send_with_temperature(ForbiddenParamActionConfig::Strip).await;
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert!(sent.get("temperature").is_none());
+    assert_eq!(sent["max_tokens"], 32);
+}
+
+#[tokio::test]
+async fn test_forbidden_param_rejected_when_strict() {
+    let (mock, resp) = send_with_temperature(ForbiddenParamActionConfig::Reject).await;
+    assert_eq!(resp.status(), 400);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["param"], "temperature");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+async fn send_with_penalties(
+    check: PenaltyRangeCheck,
+    presence_penalty: f64,
+) -> (MockBackendServer, reqwest::Response) {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        penalty_range: check,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+
+    let body = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Hello"}],
+        "presence_penalty": presence_penalty,
+        "frequency_penalty": -0.5
+    });
+    let resp = gw.post_chat(&body, &[]).await;
+    (mock, resp)
+}
+
+#[tokio::test]
+async fn test_out_of_range_penalty_clamped() {
+    let (mock, resp) = send_with_penalties(PenaltyRangeCheck::Clamp, 3.5).await;
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["presence_penalty"], 2.0);
+    assert_eq!(sent["frequency_penalty"], -0.5);
+}
+
+#[tokio::test]
+async fn test_in_range_penalty_passed_through() {
+    for check in [PenaltyRangeCheck::Clamp, PenaltyRangeCheck::Reject] {
+        let (mock, resp) = send_with_penalties(check, 1.25).await;
+        assert_eq!(resp.status(), 200);
+
+        let sent = mock.last_request_body().expect("backend was called");
+        assert_eq!(sent["presence_penalty"], 1.25);
+        assert_eq!(sent["frequency_penalty"], -0.5);
+    }
+
+    // Off forwards even out-of-range values untouched.
+    let (mock, resp) = send_with_penalties(PenaltyRangeCheck::Off, 3.5).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(mock.last_request_body().unwrap()["presence_penalty"], 3.5);
+}
+
+#[tokio::test]
+async fn test_out_of_range_penalty_rejected() {
+    let (mock, resp) = send_with_penalties(PenaltyRangeCheck::Reject, -2.5).await;
+    assert_eq!(resp.status(), 400);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["param"], "presence_penalty");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+fn request_with_tools(tool_count: usize) -> serde_json::Value {
+    let tools: Vec<serde_json::Value> = (0..tool_count)
+        .map(|i| {
+            serde_json::json!({
+                "type": "function",
+                "function": {"name": format!("tool_{i}"), "parameters": {"type": "object"}}
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Hello"}],
+        "tools": tools
+    })
+}
+
+#[tokio::test]
+async fn test_tool_count_at_limit_accepted() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        max_tools: Some(3),
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+
+    let resp = gw.post_chat(&request_with_tools(3), &[]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+#[tokio::test]
+async fn test_tool_count_over_limit_rejected() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        max_tools: Some(3),
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+
+    let resp = gw.post_chat(&request_with_tools(4), &[]).await;
+    assert_eq!(resp.status(), 400);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["param"], "tools");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+#[tokio::test]
+async fn test_client_max_tools_overrides_routing_limit() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        max_tools: Some(3),
+        client_max_tools: HashMap::from([(TEST_CLIENT_ID.to_owned(), 1)]),
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+
+    let resp = gw.post_chat(&request_with_tools(2), &[]).await;
+    assert_eq!(resp.status(), 400);
+    let resp = gw.post_chat(&request_with_tools(1), &[]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+#[tokio::test]
+async fn test_no_healthy_backend_503() {
+    // Start a mock but don't mark backends as healthy
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            mark_healthy: false,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 503);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "service_unavailable");
+    let message = body["error"]["message"].as_str().unwrap_or_default();
+    assert!(
+        message.contains("1 serving backend(s), none healthy"),
+        "{message}"
+    );
+}
+
+#[tokio::test]
+async fn test_request_timeout_504() {
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 2_000).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            request_timeout: Some(std::time::Duration::from_millis(200)),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 504);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "timeout_error");
+    assert_eq!(body["error"]["code"], 504);
+}
+
+#[tokio::test]
+async fn test_handler_panic_returns_500_envelope() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            request_timeout: Some(std::time::Duration::from_secs(5)),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/test/panic", gw.url()))
+        .send()
+        .await
+        .expect("connection should not be dropped");
+    assert_eq!(resp.status(), 500);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["type"], "server_error");
+    assert_eq!(body["error"]["code"], 500);
+    let message = body["error"]["message"].as_str().expect("message");
+    assert!(!message.contains("secret detail"), "leaked: {message}");
+    let correlation_id = body["error"]["correlation_id"]
+        .as_str()
+        .expect("correlation id");
+    assert!(message.contains(correlation_id));
+
+    // The gateway keeps serving after a panic.
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+}
+
+// ---------------------------------------------------------------------------
+// Test: per-IP rate limit keyed on X-Forwarded-For only when trusted
+// ---------------------------------------------------------------------------
+
+async fn statuses_for_forwarded_ips(trust_forwarded: bool, ips: &[&str]) -> Vec<u16> {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            trust_forwarded,
+            ip_rate_limit_rpm: Some(1),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let mut statuses = Vec::new();
+    for ip in ips {
+        let resp = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .header("X-Forwarded-For", *ip)
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        statuses.push(resp.status().as_u16());
+    }
+    statuses
+}
+
+#[tokio::test]
+async fn test_ip_rate_limit_trusts_forwarded_header() {
+    let statuses =
+        statuses_for_forwarded_ips(true, &["203.0.113.7", "203.0.113.8", "203.0.113.7"]).await;
+
+    // Distinct forwarded clients get separate buckets.
+    assert_eq!(statuses, vec![200, 200, 429]);
+}
+
+#[tokio::test]
+async fn test_ip_rate_limit_ignores_spoofed_leading_hops() {
+    let statuses = statuses_for_forwarded_ips(
+        true,
+        &["198.51.100.1, 203.0.113.7", "198.51.100.2, 203.0.113.7"],
+    )
+    .await;
+
+    // Only the proxy-appended hop counts, so rotating the spoofed one does
+    // not earn a fresh bucket.
+    assert_eq!(statuses, vec![200, 429]);
+}
+
+#[tokio::test]
+async fn test_ip_rate_limit_ignores_untrusted_forwarded_header() {
+    let statuses = statuses_for_forwarded_ips(false, &["203.0.113.7", "203.0.113.8"]).await;
+
+    // Both requests share the loopback peer's bucket despite the header.
+    assert_eq!(statuses, vec![200, 429]);
+}
+
+// ---------------------------------------------------------------------------
+// Test: startup warm-up sends one completion per model when enabled
+// ---------------------------------------------------------------------------
+
+async fn run_warmup(mock: &MockBackendServer, enabled: bool) -> mb_server::warmup::WarmupOutcome {
+    use mb_core::core::{BackendId, BackendInfo, BackendSpec, ModelId};
+
+    let models = vec![TEST_MODEL.to_owned(), "mistral-7b".to_owned()];
+    let gw = TestGateway::start(
+        &[(mock.url(), models.clone())],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, models.clone())],
+        TestGatewayOptions::default(),
+    )
+    .await;
+    let backend = BackendInfo {
+        id: BackendId::new("mock-0"),
+        spec: BackendSpec::OpenAiChat,
+        models: models.into_iter().map(ModelId::new).collect(),
+        max_concurrent: 4,
+        base_url: mock.url(),
+        model_map: std::collections::HashMap::new(),
+        tool_support: mb_core::core::ToolSupport::Native,
+        cost_weight: 0,
+    };
+    let warmup_ids = if enabled {
+        vec![backend.id.clone()]
+    } else {
+        Vec::new()
+    };
+    let probe = mb_server::health::HttpHealthProbe::new(std::time::Duration::from_secs(2))
+        .expect("build probe");
+
+    mb_server::warmup::warm_up_backends(&gw.state, &probe, &[backend], &warmup_ids).await
+}
+
+#[tokio::test]
+async fn test_warmup_one_request_per_model() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+
+    let outcome = run_warmup(&mock, true).await;
+
+    assert_eq!(outcome.succeeded, 2);
+    assert_eq!(outcome.failed, 0);
+    assert_eq!(mock.completion_requests(), 2);
+}
+
+#[tokio::test]
+async fn test_warmup_disabled_sends_nothing() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+
+    let outcome = run_warmup(&mock, false).await;
+
+    assert_eq!(outcome, mb_server::warmup::WarmupOutcome::default());
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+// ---------------------------------------------------------------------------
+// Test: health.verify_models flags a backend whose listing lacks a model
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_probe_flags_backend_missing_configured_model() {
+    use mb_core::core::{BackendId, BackendInfo, BackendSpec, HealthError, HealthProbe, ModelId};
+
+    // The mock lists only TEST_MODEL.
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let backend = BackendInfo {
+        id: BackendId::new("mock-0"),
+        spec: BackendSpec::OpenAiChat,
+        models: vec![ModelId::new(TEST_MODEL), ModelId::new("mistral-7b")],
+        max_concurrent: 4,
+        base_url: mock.url(),
+        model_map: std::collections::HashMap::new(),
+        tool_support: mb_core::core::ToolSupport::Native,
+        cost_weight: 0,
+    };
+    let probe = mb_server::health::HttpHealthProbe::new(std::time::Duration::from_secs(2))
+        .expect("build probe");
+
+    // Without verification a 2xx listing is enough.
+    assert!(probe.probe(&backend).await.is_ok());
+
+    let probe = probe.with_model_verification(true);
+    match probe.probe(&backend).await {
+        Err(HealthError::MissingModels(missing)) => assert_eq!(missing, ["mistral-7b"]),
+        other => panic!("expected MissingModels, got {other:?}"),
+    }
+
+    let manager = mb_server::health::HealthCheckManager::new(std::slice::from_ref(&backend));
+    let handle = manager.start_background_checks(
+        vec![backend],
+        std::time::Duration::from_millis(10),
+        1,
+        2000,
+        std::sync::Arc::new(probe),
+    );
+    let mut status = mb_core::core::BackendStatus::Unknown;
+    for _ in 0..100 {
+        status = manager.get_states().await[0].status;
+        if status == mb_core::core::BackendStatus::Unhealthy {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    handle.abort();
+    assert_eq!(status, mb_core::core::BackendStatus::Unhealthy);
+}
+
+// ---------------------------------------------------------------------------
+// Test: the health probe authenticates to protected backends
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_probe_sends_backend_api_key() {
+    use mb_core::core::{
+        ApiKey, BackendId, BackendInfo, BackendSpec, HealthError, HealthProbe, ModelId,
+    };
+
+    let mock =
+        MockBackendServer::start_with_models_key(&sample_openai_response(), "sk-backend").await;
+    let backend = BackendInfo {
+        id: BackendId::new("mock-0"),
+        spec: BackendSpec::OpenAiChat,
+        models: vec![ModelId::new(TEST_MODEL)],
+        max_concurrent: 4,
+        base_url: mock.url(),
+        model_map: std::collections::HashMap::new(),
+        tool_support: mb_core::core::ToolSupport::Native,
+        cost_weight: 0,
+    };
+    let probe = mb_server::health::HttpHealthProbe::new(std::time::Duration::from_secs(2))
+        .expect("build probe");
+
+    match probe.probe(&backend).await {
+        Err(HealthError::UnexpectedStatus(401)) => {}
+        other => panic!("expected a 401 without credentials, got {other:?}"),
+    }
+
+    let probe = probe.with_api_keys(std::collections::HashMap::from([(
+        backend.id.clone(),
+        ApiKey::new("sk-backend"),
+    )]));
+    assert!(probe.probe(&backend).await.is_ok());
+
+    let manager = mb_server::health::HealthCheckManager::new(std::slice::from_ref(&backend));
+    let handle = manager.start_background_checks(
+        vec![backend],
+        std::time::Duration::from_millis(10),
+        1,
+        2000,
+        std::sync::Arc::new(probe),
+    );
+    let mut status = mb_core::core::BackendStatus::Unknown;
+    for _ in 0..100 {
+        status = manager.get_states().await[0].status;
+        if status == mb_core::core::BackendStatus::Healthy {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    handle.abort();
+    assert_eq!(status, mb_core::core::BackendStatus::Healthy);
+}
+
+// ---------------------------------------------------------------------------
+// Latency tracking tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_request_latency_reported_in_health_and_metrics() {
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 50).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+    let client = reqwest::Client::new();
+
+    for _ in 0..3 {
+        let resp = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), 200);
+    }
+
+    let health: serde_json::Value = client
+        .get(format!("{}/health", gw.url()))
+        .send()
+        .await
+        .expect("request should succeed")
+        .json()
+        .await
+        .expect("valid JSON");
+    let latency = &health["backends"][0]["latency_ms"];
+    assert_eq!(latency["samples"], 3);
+    assert!(latency["p50"].as_u64().expect("p50") >= 50);
+
+    let metrics = client
+        .get(format!("{}/metrics", gw.url()))
+        .send()
+        .await
+        .expect("request should succeed")
+        .text()
+        .await
+        .expect("text body");
+    assert!(metrics.contains("mb_backend_latency_ms_count{backend=\"mock-0\"} 3"));
+}
+
+// ---------------------------------------------------------------------------
+// Routing tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_round_robin_is_independent_per_model() {
+    const OTHER_MODEL: &str = "qwen2.5-14b";
+    let models = vec![TEST_MODEL.to_owned(), OTHER_MODEL.to_owned()];
+    let mock_a = MockBackendServer::start(&sample_openai_response_with_id("resp-A")).await;
+    let mock_b = MockBackendServer::start(&sample_openai_response_with_id("resp-B")).await;
+
+    let gw = TestGateway::start(
+        &[
+            (mock_a.url(), models.clone()),
+            (mock_b.url(), models.clone()),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, models)],
+        TestGatewayOptions {
+            routing_strategy: RoutingStrategyConfig::RoundRobin,
+            cache_aware: false,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let mut picks: HashMap<&str, Vec<String>> = HashMap::new();
+
+    // Strictly interleaved traffic: with one shared counter each model would
+    // always land on the same backend.
+    for _ in 0..4 {
+        for model in [TEST_MODEL, OTHER_MODEL] {
+            let body: serde_json::Value = client
+                .post(format!("{}/v1/chat/completions", gw.url()))
+                .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+                .json(&serde_json::json!({
+                    "model": model,
+                    "messages": [{"role": "user", "content": "Hello"}]
+                }))
+                .send()
+                .await
+                .expect("request should succeed")
+                .json()
+                .await
+                .expect("valid JSON");
+            let id = body["id"].as_str().expect("response id").to_owned();
+            picks.entry(model).or_default().push(id);
+        }
+    }
+
+    for (model, ids) in &picks {
+        assert_eq!(ids.len(), 4);
+        assert!(
+            ids.windows(2).all(|pair| pair[0] != pair[1]),
+            "{model} should alternate backends, got: {ids:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_backend_max_rpm_sheds_traffic_then_503() {
+    let mock_a = MockBackendServer::start(&sample_openai_response_with_id("resp-A")).await;
+    let mock_b = MockBackendServer::start(&sample_openai_response_with_id("resp-B")).await;
+
+    let gw = TestGateway::start(
+        &[
+            (mock_a.url(), vec![TEST_MODEL.to_owned()]),
+            (mock_b.url(), vec![TEST_MODEL.to_owned()]),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            cache_aware: false,
+            backend_max_rpm: HashMap::from([("mock-0".to_owned(), 1), ("mock-1".to_owned(), 2)]),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let mut statuses = Vec::new();
+    for _ in 0..4 {
+        let resp = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        statuses.push(resp.status().as_u16());
+        if resp.status() == 503 {
+            let body: serde_json::Value = resp.json().await.expect("valid JSON");
+            assert_eq!(body["error"]["type"], "service_unavailable");
+        }
+    }
+
+    assert_eq!(statuses, [200, 200, 200, 503]);
+    assert_eq!(mock_a.completion_requests(), 1);
+    assert_eq!(mock_b.completion_requests(), 2);
+
+    // A dry run skips throttled backends the same way.
+    let resp = client
+        .post(format!("{}/v1/chat/completions?dry_run=1", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 503);
+}
+
+/// Requests a `json_schema` response format from a backend that always
+/// answers with `content`; returns the mock, status and body.
+async fn send_with_schema(
+    mode: JsonOutputValidation,
+    content: &str,
+) -> (MockBackendServer, u16, serde_json::Value) {
+    let mut response: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).expect("valid JSON");
+    response["choices"][0]["message"]["content"] = content.into();
+    let mock = MockBackendServer::start(&response.to_string()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            validate_json_output: mode,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "Describe a person."}],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "person",
+                    "schema": {
+                        "type": "object",
+                        "properties": {"name": {"type": "string"}, "age": {"type": "integer"}},
+                        "required": ["name", "age"]
+                    }
+                }
+            }
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    let status = resp.status().as_u16();
+    let body = resp.json().await.expect("valid JSON");
+    (mock, status, body)
+}
+
+#[tokio::test]
+async fn test_validate_json_output_passes_matching_output() {
+    let (mock, status, body) = send_with_schema(
+        JsonOutputValidation::Reject,
+        r#"{"name": "Ada", "age": 36}"#,
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["choices"][0]["message"]["content"],
+        r#"{"name": "Ada", "age": 36}"#
+    );
+    assert_eq!(mock.completion_requests(), 1);
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["response_format"]["type"], "json_schema");
+}
+
+#[tokio::test]
+async fn test_validate_json_output_rejects_or_retries_mismatch() {
+    let (mock, status, body) =
+        send_with_schema(JsonOutputValidation::Reject, r#"{"name": "Ada"}"#).await;
+    assert_eq!(status, 502);
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("does not match the requested json_schema")
+            && message.contains("missing required property `age`"),
+        "{message}"
+    );
+    assert_eq!(mock.completion_requests(), 1);
+
+    // The mock never improves, so the single retry fails the same way.
+    let (mock, status, body) = send_with_schema(JsonOutputValidation::Retry, "not json").await;
+    assert_eq!(status, 502);
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.contains("output is not valid JSON"), "{message}");
+    assert_eq!(mock.completion_requests(), 2);
+
+    // Unchecked when the option is off.
+    let (_, status, _) = send_with_schema(JsonOutputValidation::Off, "not json").await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_rejected_json_output_still_charges_quota() {
+    let mut response: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).expect("valid JSON");
+    response["choices"][0]["message"]["content"] = "not json".into();
+    let mock = MockBackendServer::start(&response.to_string()).await;
+    let gw = TestGateway::start_with(
+        &mock.url(),
+        TestGatewayOptions {
+            validate_json_output: JsonOutputValidation::Reject,
+            // The rejected reply reports 18 total tokens.
+            monthly_token_limit: Some(10),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+    let body = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Describe a person."}],
+        "response_format": {
+            "type": "json_schema",
+            "json_schema": {"name": "person", "schema": {"type": "object"}}
+        }
+    });
+
+    let resp = gw.post_chat(&body, &[]).await;
+    assert_eq!(resp.status(), 502);
+
+    let resp = gw.post_chat(&body, &[]).await;
+    assert_eq!(resp.status(), 402);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+/// Sends `n` requests for `model` and returns the distinct response ids seen,
+/// one per backend that served them.
+async fn response_ids_for(gw: &TestGateway, model: &str, n: usize) -> HashSet<String> {
+    let client = reqwest::Client::new();
+    let body = serde_json::json!({
+        "model": model,
+        "messages": [{"role": "user", "content": "Hello"}]
+    })
+    .to_string();
+
+    let mut seen_ids = HashSet::new();
+    for _ in 0..n {
+        let resp = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.expect("valid JSON");
+        if let Some(id) = body.get("id").and_then(|v| v.as_str()) {
+            seen_ids.insert(id.to_owned());
+        }
+    }
+    seen_ids
+}
+
+#[tokio::test]
+async fn test_per_model_strategy_override() {
+    const OVERRIDDEN_MODEL: &str = "qwen-cache";
+    let models = vec![TEST_MODEL.to_owned(), OVERRIDDEN_MODEL.to_owned()];
+    let mock_a = MockBackendServer::start(&sample_openai_response_with_id("resp-A")).await;
+    let mock_b = MockBackendServer::start(&sample_openai_response_with_id("resp-B")).await;
+
+    let gw = TestGateway::start(
+        &[
+            (mock_a.url(), models.clone()),
+            (mock_b.url(), models.clone()),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, models)],
+        TestGatewayOptions {
+            routing_strategy: RoutingStrategyConfig::LeastLoaded,
+            per_model: HashMap::from([(
+                OVERRIDDEN_MODEL.to_owned(),
+                RoutingStrategyConfig::RoundRobin,
+            )]),
+            cache_aware: false,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    // The override spreads requests across both backends...
+    let ids = response_ids_for(&gw, OVERRIDDEN_MODEL, 4).await;
+    assert_eq!(ids.len(), 2, "round-robin override expected, got: {ids:?}");
+
+    // ...while the default least-loaded strategy keeps idle traffic on one.
+    let ids = response_ids_for(&gw, TEST_MODEL, 4).await;
+    assert_eq!(ids.len(), 1, "least-loaded default expected, got: {ids:?}");
+}
+
+// ---------------------------------------------------------------------------
+// Request coalescing tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_coalesce_identical_concurrent_requests() {
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 300).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            coalesce: true,
+            rate_limit_rpm: 100,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let mut requests = tokio::task::JoinSet::new();
+    for _ in 0..5 {
+        let request = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body());
+        requests.spawn(async move {
+            let resp = request.send().await.expect("request should succeed");
+            let status = resp.status();
+            let body: serde_json::Value = resp.json().await.expect("valid JSON");
+            (status, body)
+        });
+    }
+
+    while let Some(result) = requests.join_next().await {
+        let (status, body) = result.expect("task should not panic");
+        assert_eq!(status, 200);
+        assert_eq!(body["model"], TEST_MODEL);
+    }
+    assert_eq!(
+        mock.completion_requests(),
+        1,
+        "identical concurrent requests should share one backend call"
+    );
+
+    // Once the call has finished, the next identical request goes out again.
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(mock.completion_requests(), 2);
+}
+
+#[tokio::test]
+async fn test_coalesce_keeps_requests_apart_across_param_policies() {
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 300).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[
+            ("client-strict", "sk-strict", vec![TEST_MODEL.to_owned()]),
+            ("client-open", "sk-open", vec![TEST_MODEL.to_owned()]),
+        ],
+        TestGatewayOptions {
+            coalesce: true,
+            client_forbidden_params: HashMap::from([(
+                "client-strict".to_owned(),
+                vec!["temperature".to_owned()],
+            )]),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    // Byte-identical bodies, but the strict client's policy strips
+    // `temperature`, so the two requests differ once it is applied.
+    let body = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Hello"}],
+        "temperature": 1.5
+    });
+    let client = reqwest::Client::new();
+    let mut requests = tokio::task::JoinSet::new();
+    for key in ["sk-strict", "sk-open"] {
+        let request = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {key}"))
+            .json(&body);
+        requests.spawn(async move { request.send().await.expect("request should succeed") });
+    }
+    while let Some(result) = requests.join_next().await {
+        assert_eq!(result.expect("task should not panic").status(), 200);
+    }
+
+    assert_eq!(
+        mock.completion_requests(),
+        2,
+        "requests that differ after policy must not share a backend call"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Fan-out tests
+// ---------------------------------------------------------------------------
+
+fn request_with_n(n: u32) -> serde_json::Value {
+    serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Hello"}],
+        "n": n
+    })
+}
+
+async fn fanout_gateway(
+    good: &MockBackendServer,
+    failing: &MockBackendServer,
+    fanout: FanoutMode,
+) -> TestGateway {
+    TestGateway::start(
+        &[
+            (good.url(), vec![TEST_MODEL.to_owned()]),
+            (failing.url(), vec![TEST_MODEL.to_owned()]),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            routing_strategy: RoutingStrategyConfig::RoundRobin,
+            fanout,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_fanout_serves_n_choices_with_summed_usage() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        fanout: FanoutMode::Strict,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+
+    let resp = gw.post_chat(&request_with_n(3), &[]).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get("x-fanout-failures").is_none());
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    let indices: Vec<u64> = body["choices"]
+        .as_array()
+        .expect("choices array")
+        .iter()
+        .map(|choice| choice["index"].as_u64().expect("index"))
+        .collect();
+    assert_eq!(indices, [0, 1, 2]);
+    assert_eq!(body["usage"]["total_tokens"], 54);
+
+    // Each backend call asks for a single choice.
+    assert_eq!(mock.completion_requests(), 3);
+    assert!(mock.last_request_body().expect("backend was called")["n"].is_null());
+}
+
+#[tokio::test]
+async fn test_fanout_partial_returns_surviving_choices() {
+    let good = MockBackendServer::start(&sample_openai_response()).await;
+    let failing = MockBackendServer::start_with_options("{}", 500, 0).await;
+    let gw = fanout_gateway(&good, &failing, FanoutMode::Partial).await;
+
+    let resp = gw.post_chat(&request_with_n(2), &[]).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["x-fanout-failures"], "1");
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["choices"].as_array().map(Vec::len), Some(1));
+    assert_eq!(body["usage"]["total_tokens"], 18);
+    assert_eq!(good.completion_requests(), 1);
+    assert_eq!(failing.completion_requests(), 1);
+}
+
+#[tokio::test]
+async fn test_fanout_strict_fails_on_any_error() {
+    let good = MockBackendServer::start(&sample_openai_response()).await;
+    let failing = MockBackendServer::start_with_options("{}", 500, 0).await;
+    let gw = fanout_gateway(&good, &failing, FanoutMode::Strict).await;
+
+    let resp = gw.post_chat(&request_with_n(2), &[]).await;
+    assert_eq!(resp.status(), 502);
+}
+
+#[tokio::test]
+async fn test_fanout_n_over_limit_400() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        fanout: FanoutMode::Partial,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+
+    let resp = gw.post_chat(&request_with_n(9), &[]).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["param"], "n");
+    assert_eq!(mock.completion_requests(), 0);
+}
+
+// ---------------------------------------------------------------------------
+// Response cache tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_response_cache_hit_skips_backend() {
+    let mock = MockBackendServer::start(&sample_openai_response_with_id("resp-cached")).await;
+    let options = TestGatewayOptions {
+        response_cache: true,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+    let request = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "What is 2 + 2?"}],
+        "temperature": 0
+    });
+
+    let first = gw.post_chat(&request, &[]).await;
+    assert_eq!(first.status(), 200);
+    assert_eq!(first.headers()["x-cache"], "MISS");
+    let first: serde_json::Value = first.json().await.expect("valid JSON");
+
+    let second = gw.post_chat(&request, &[]).await;
+    assert_eq!(second.status(), 200);
+    assert_eq!(second.headers()["x-cache"], "HIT");
+    let second: serde_json::Value = second.json().await.expect("valid JSON");
+
+    assert_eq!(second, first);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+#[tokio::test]
+async fn test_response_cache_misses_non_deterministic_request() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        response_cache: true,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+    let request = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Write a poem"}],
+        "temperature": 0.8
+    });
+
+    for _ in 0..2 {
+        let resp = gw.post_chat(&request, &[]).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["x-cache"], "MISS");
+    }
+    assert_eq!(mock.completion_requests(), 2);
+}
+
+// ---------------------------------------------------------------------------
+// Idempotency key tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_idempotency_key_replays_response() {
+    let mock = MockBackendServer::start(&sample_openai_response_with_id("resp-once")).await;
+    let options = TestGatewayOptions {
+        idempotency_ttl_secs: 60,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+    let request = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Write a poem"}],
+        "temperature": 0.8
+    });
+
+    let first = gw
+        .post_chat(&request, &[("Idempotency-Key", "order-42")])
+        .await;
+    assert_eq!(first.status(), 200);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    let first: serde_json::Value = first.json().await.expect("valid JSON");
+
+    let second = gw
+        .post_chat(&request, &[("Idempotency-Key", "order-42")])
+        .await;
+    assert_eq!(second.status(), 200);
+    assert_eq!(second.headers()["idempotent-replayed"], "true");
+    let second: serde_json::Value = second.json().await.expect("valid JSON");
+
+    assert_eq!(second, first);
+    assert_eq!(mock.completion_requests(), 1);
+
+    // A fresh key, or none at all, reaches the backend again.
+    let third = gw
+        .post_chat(&request, &[("Idempotency-Key", "order-43")])
+        .await;
+    assert_eq!(third.status(), 200);
+    assert_eq!(gw.post_chat(&request, &[]).await.status(), 200);
+    assert_eq!(mock.completion_requests(), 3);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_concurrent_retries_dispatch_once() {
+    // The delay keeps the first request in flight while the retries arrive.
+    let mock = MockBackendServer::start_with_options(
+        &sample_openai_response_with_id("resp-once"),
+        200,
+        300,
+    )
+    .await;
+    let options = TestGatewayOptions {
+        idempotency_ttl_secs: 60,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+    let request = serde_json::json!({
+        "model": TEST_MODEL,
+        "messages": [{"role": "user", "content": "Write a poem"}],
+        "temperature": 0.8
+    });
+
+    let responses = futures_util::future::join_all(
+        (0..3).map(|_| gw.post_chat(&request, &[("Idempotency-Key", "order-42")])),
+    )
+    .await;
+    let replayed = responses
+        .iter()
+        .filter(|resp| resp.headers().get("idempotent-replayed").is_some())
+        .count();
+    assert!(responses.iter().all(|resp| resp.status() == 200));
+    assert_eq!(replayed, 2);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+#[tokio::test]
+async fn test_idempotency_key_reused_with_other_body_400() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let options = TestGatewayOptions {
+        idempotency_ttl_secs: 60,
+        ..TestGatewayOptions::default()
+    };
+    let gw = TestGateway::start_with(&mock.url(), options).await;
+    let request = |text: &str| {
+        serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": text}]
+        })
+    };
+
+    let first = gw
+        .post_chat(&request("first"), &[("Idempotency-Key", "order-42")])
+        .await;
+    assert_eq!(first.status(), 200);
+
+    let reused = gw
+        .post_chat(&request("second"), &[("Idempotency-Key", "order-42")])
+        .await;
+    assert_eq!(reused.status(), 400);
+    let body: serde_json::Value = reused.json().await.expect("valid JSON");
+    assert_eq!(body["error"]["param"], "Idempotency-Key");
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+// ---------------------------------------------------------------------------
+// Model name mapping tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_model_map_renames_model_for_backend_only() {
+    const WIRE_MODEL: &str = "meta-llama/Llama-3-70B-Instruct";
+    let mut response: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).expect("valid JSON");
+    response["model"] = WIRE_MODEL.into();
+    let mock = MockBackendServer::start(&response.to_string()).await;
+
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            model_map: HashMap::from([(TEST_MODEL.to_owned(), WIRE_MODEL.to_owned())]),
+            // The backend's own name for the model is not a mismatch.
+            verify_response_model: ResponseModelCheck::Strict,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["model"], WIRE_MODEL);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["model"], TEST_MODEL);
+}
+
+// ---------------------------------------------------------------------------
+// Provider prefix tests
+// ---------------------------------------------------------------------------
+
+async fn provider_prefix_gateway(
+    openai: &MockBackendServer,
+    ollama: &MockBackendServer,
+    provider_prefix: bool,
+) -> TestGateway {
+    TestGateway::start(
+        &[
+            (openai.url(), vec![TEST_MODEL.to_owned()]),
+            (ollama.url(), vec![TEST_MODEL.to_owned()]),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            ollama_backends: vec!["mock-1".to_owned()],
+            provider_prefix,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await
+}
+
+fn chat_request(model: &str) -> serde_json::Value {
+    serde_json::json!({
+        "model": model,
+        "messages": [{"role": "user", "content": "Hello"}],
+    })
+}
+
+#[tokio::test]
+async fn test_provider_prefix_prefers_matching_backend_spec() {
+    let openai = MockBackendServer::start(&sample_openai_response()).await;
+    let ollama = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = provider_prefix_gateway(&openai, &ollama, true).await;
+
+    for (model, expected) in [
+        (format!("ollama/{TEST_MODEL}"), "mock-1"),
+        (format!("openai/{TEST_MODEL}"), "mock-0"),
+    ] {
+        let resp = gw
+            .post_chat(&chat_request(&model), &[("X-Dry-Run", "true")])
+            .await;
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.expect("valid JSON");
+        assert_eq!(body["model"], TEST_MODEL);
+        assert_eq!(body["backend"], expected, "routing {model}");
+    }
+
+    // A prefix naming no backend spec stays part of the model name.
+    let resp = gw
+        .post_chat(
+            &chat_request(&format!("acme/{TEST_MODEL}")),
+            &[("X-Dry-Run", "true")],
+        )
+        .await;
+    assert_eq!(resp.status(), 403);
+
+    // The backend only ever sees the bare model name.
+    let body = chat_request(&format!("openai/{TEST_MODEL}"));
+    let resp = gw.post_chat(&body, &[]).await;
+    assert_eq!(resp.status(), 200);
+    let sent = openai
+        .last_request_body()
+        .expect("openai backend was called");
+    assert_eq!(sent["model"], TEST_MODEL);
+    assert_eq!(ollama.completion_requests(), 0);
+}
+
+#[tokio::test]
+async fn test_provider_prefix_dispatches_to_ollama_backend() {
+    let openai = MockBackendServer::start(&sample_openai_response()).await;
+    let ollama = MockBackendServer::start(&sample_ollama_response()).await;
+    let gw = provider_prefix_gateway(&openai, &ollama, true).await;
+
+    let resp = gw
+        .post_chat(&chat_request(&format!("ollama/{TEST_MODEL}")), &[])
+        .await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["model"], TEST_MODEL);
+    assert_eq!(
+        body["choices"][0]["message"]["content"],
+        "Hello from Ollama!"
+    );
+    assert_eq!(body["usage"]["total_tokens"], 15);
+
+    assert_eq!(ollama.last_request_path().as_deref(), Some("/api/chat"));
+    let sent = ollama
+        .last_request_body()
+        .expect("ollama backend was called");
+    assert_eq!(sent["model"], TEST_MODEL);
+    assert_eq!(sent["stream"], false);
+    assert_eq!(openai.completion_requests(), 0);
+}
+
+#[tokio::test]
+async fn test_provider_prefix_ignored_unless_enabled() {
+    let openai = MockBackendServer::start(&sample_openai_response()).await;
+    let ollama = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = provider_prefix_gateway(&openai, &ollama, false).await;
+
+    let resp = gw
+        .post_chat(
+            &chat_request(&format!("ollama/{TEST_MODEL}")),
+            &[("X-Dry-Run", "true")],
+        )
+        .await;
+    assert_eq!(resp.status(), 403);
+}
+
+// ---------------------------------------------------------------------------
+// Test: queued requests take freed capacity in client priority order
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_high_priority_request_takes_next_freed_slot() {
+    const BATCH_KEY: &str = "mb-sk-batch00000000000000000000000";
+    const PROD_KEY: &str = "mb-sk-prod000000000000000000000000";
+
+    // One slot, held for 300ms by each request.
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 300).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[
+            ("batch", BATCH_KEY, vec![TEST_MODEL.to_owned()]),
+            ("prod", PROD_KEY, vec![TEST_MODEL.to_owned()]),
+        ],
+        TestGatewayOptions {
+            queue_when_saturated: true,
+            max_concurrent: 1,
+            client_priorities: HashMap::from([("prod".to_owned(), 10)]),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut requests = Vec::new();
+    // The first batch request occupies the slot; the second batch request
+    // queues before the prod one.
+    for (name, key) in [
+        ("batch-1", BATCH_KEY),
+        ("batch-2", BATCH_KEY),
+        ("prod", PROD_KEY),
+    ] {
+        let (url, done_tx) = (gw.url(), done_tx.clone());
+        requests.push(tokio::spawn(async move {
+            let resp = reqwest::Client::new()
+                .post(format!("{url}/v1/chat/completions"))
+                .header("Authorization", format!("Bearer {key}"))
+                .header("Content-Type", "application/json")
+                .body(sample_request_body())
+                .send()
+                .await
+                .expect("request should succeed");
+            assert_eq!(resp.status(), 200);
+            done_tx.send(name).unwrap();
+        }));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    for request in requests {
+        request.await.unwrap();
+    }
+
+    let mut order = Vec::new();
+    while let Ok(name) = done_rx.try_recv() {
+        order.push(name);
+    }
+    assert_eq!(order, ["batch-1", "prod", "batch-2"]);
+    assert_eq!(mock.completion_requests(), 3);
+}
+
+// ---------------------------------------------------------------------------
+// Test: X-Backend-Load reports in-flight requests against capacity
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_backend_load_header_reflects_in_flight_requests() {
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 400).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            max_concurrent: 4,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let send = |url: String| async move {
+        reqwest::Client::new()
+            .post(format!("{url}/v1/chat/completions"))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+            .await
+            .expect("request should succeed")
+    };
+    let load = |resp: &reqwest::Response| {
+        resp.headers()["x-backend-load"]
+            .to_str()
+            .unwrap()
+            .to_owned()
+    };
+
+    let idle = send(gw.url()).await;
+    assert_eq!(load(&idle), "0/4");
+
+    let busy: Vec<_> = (0..3).map(|_| tokio::spawn(send(gw.url()))).collect();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let resp = send(gw.url()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(load(&resp), "3/4");
+    // The count is reported, not fed back into the routing snapshot.
+    assert!(gw
+        .state
+        .backend_states
+        .read()
+        .await
+        .values()
+        .all(|s| s.active_requests == 0));
+
+    for request in busy {
+        assert_eq!(request.await.unwrap().status(), 200);
+    }
+    assert_eq!(load(&send(gw.url()).await), "0/4");
+}
+
+// ---------------------------------------------------------------------------
+// Test: X-Disable-Cache-Routing opts one request out of prefix affinity
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_disable_cache_routing_header_bypasses_affinity() {
+    use mb_core::core::{compute_prefix_hash, BackendId, Message, MessageContent, ModelId, Role};
+
+    let mock_a = MockBackendServer::start(&sample_openai_response_with_id("resp-A")).await;
+    let mock_b = MockBackendServer::start(&sample_openai_response_with_id("resp-B")).await;
+    let gw = TestGateway::start(
+        &[
+            (mock_a.url(), vec![TEST_MODEL.to_owned()]),
+            (mock_b.url(), vec![TEST_MODEL.to_owned()]),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            routing_strategy: RoutingStrategyConfig::RoundRobin,
+            cache_aware: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let model = ModelId::new(TEST_MODEL);
+    let prefix = compute_prefix_hash(
+        &[Message {
+            role: Role::User,
+            content: MessageContent::Text("Hello".to_owned()),
+            name: None,
+            tool_call_id: None,
+        }],
+        gw.state.cache_config.prefix_depth,
+    );
+    let send = |disable: bool| {
+        let mut req = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body());
+        if disable {
+            req = req.header("X-Disable-Cache-Routing", "true");
+        }
+        req.send()
+    };
+
+    // Not recorded: the map stays empty for this prefix.
+    assert_eq!(send(true).await.unwrap().status(), 200);
+    assert_eq!(gw.state.affinity_map.get(&model, prefix), None);
+
+    // Not consulted: with affinity pinned to mock-1, round-robin still
+    // reaches both backends.
+    gw.state
+        .affinity_map
+        .record(&model, prefix, &BackendId::new("mock-1"));
+    for _ in 0..3 {
+        assert_eq!(send(true).await.unwrap().status(), 200);
+    }
+    assert_eq!(mock_a.completion_requests(), 2);
+    assert_eq!(mock_b.completion_requests(), 2);
+
+    // Without the header the pinned backend wins.
+    assert_eq!(send(false).await.unwrap().status(), 200);
+    assert_eq!(mock_b.completion_requests(), 3);
+}
+
+// ---------------------------------------------------------------------------
+// Test: a contended affinity shard falls back to strategy routing
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_contended_affinity_skipped_when_enabled() {
+    use mb_core::core::{compute_prefix_hash, BackendId, Message, MessageContent, ModelId, Role};
+
+    let mock_a = MockBackendServer::start(&sample_openai_response_with_id("resp-A")).await;
+    let mock_b = MockBackendServer::start(&sample_openai_response_with_id("resp-B")).await;
+    let gw = TestGateway::start(
+        &[
+            (mock_a.url(), vec![TEST_MODEL.to_owned()]),
+            (mock_b.url(), vec![TEST_MODEL.to_owned()]),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            routing_strategy: RoutingStrategyConfig::RoundRobin,
+            cache_aware: true,
+            affinity_skip_on_contention: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let model = ModelId::new(TEST_MODEL);
+    let prefix = compute_prefix_hash(
+        &[Message {
+            role: Role::User,
+            content: MessageContent::Text("Hello".to_owned()),
+            name: None,
+            tool_call_id: None,
+        }],
+        gw.state.cache_config.prefix_depth,
+    );
+    gw.state
+        .affinity_map
+        .record(&model, prefix, &BackendId::new("mock-1"));
+    let send = || {
+        reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_request_body())
+            .send()
+    };
+
+    // Hold the request's affinity shard from another thread for the duration.
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let holder = {
+        let state = std::sync::Arc::clone(&gw.state);
+        let model = model.clone();
+        std::thread::spawn(move || {
+            let _held = state.affinity_map.lock_shard_of(&model, prefix);
+            locked_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+    };
+    locked_rx.recv().unwrap();
+
+    // The pinned backend is ignored: round-robin reaches both.
+    for _ in 0..2 {
+        assert_eq!(send().await.unwrap().status(), 200);
+    }
+    assert_eq!(mock_a.completion_requests(), 1);
+    assert_eq!(mock_b.completion_requests(), 1);
+    assert!(gw.state.affinity_map.skipped() >= 2);
+
+    release_tx.send(()).unwrap();
+    holder.join().unwrap();
+
+    // Once the shard is free the affinity hint applies again.
+    for _ in 0..2 {
+        assert_eq!(send().await.unwrap().status(), 200);
+    }
+    assert_eq!(mock_b.completion_requests(), 3);
+}
+
+// ---------------------------------------------------------------------------
+// Test: routing.model_capabilities gates features a model lacks
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_tools_request_to_non_tool_model() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let no_tools = ModelCapabilitiesConfig {
+        tools: false,
+        ..ModelCapabilitiesConfig::default()
+    };
+
+    for (mode, expected_status, expected_calls) in [
+        (CapabilityCheck::Lenient, 200, 1),
+        (CapabilityCheck::Strict, 400, 1),
+    ] {
+        let gw = TestGateway::start(
+            &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+            &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+            TestGatewayOptions {
+                model_capabilities: HashMap::from([(TEST_MODEL.to_owned(), no_tools)]),
+                capability_check: mode,
+                ..TestGatewayOptions::default()
+            },
+        )
+        .await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .json(&serde_json::json!({
+                "model": TEST_MODEL,
+                "messages": [{"role": "user", "content": "What's the weather?"}],
+                "tools": [{
+                    "type": "function",
+                    "function": {"name": "get_weather", "parameters": {"type": "object"}}
+                }]
+            }))
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), expected_status, "mode {mode:?}");
+        assert_eq!(mock.completion_requests(), expected_calls, "mode {mode:?}");
+
+        if mode == CapabilityCheck::Strict {
+            let body: serde_json::Value = resp.json().await.expect("valid JSON");
+            let message = body["error"]["message"].as_str().expect("message");
+            assert!(message.contains("tools"), "{message}");
+
+            // Plain requests to the same model are unaffected.
+            let resp = reqwest::Client::new()
+                .post(format!("{}/v1/chat/completions", gw.url()))
+                .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+                .header("Content-Type", "application/json")
+                .body(sample_request_body())
+                .send()
+                .await
+                .expect("request should succeed");
+            assert_eq!(resp.status(), 200);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Test: consecutive system messages merged before dispatch and hashing
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_merge_system_messages_before_dispatch_and_hashing() {
+    use mb_core::core::{compute_prefix_hash, BackendId, Message, MessageContent, ModelId, Role};
+
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            cache_aware: true,
+            merge_system_messages: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [
+                {"role": "system", "content": "Be terse."},
+                {"role": "system", "content": "Answer in French."},
+                {"role": "user", "content": "Hello"}
+            ]
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(
+        sent["messages"],
+        serde_json::json!([
+            {"role": "system", "content": "Be terse.\nAnswer in French."},
+            {"role": "user", "content": "Hello"}
+        ])
+    );
+
+    let message = |role: Role, text: &str| Message {
+        role,
+        content: MessageContent::Text(text.to_owned()),
+        name: None,
+        tool_call_id: None,
+    };
+    let merged = compute_prefix_hash(
+        &[
+            message(Role::System, "Be terse.\nAnswer in French."),
+            message(Role::User, "Hello"),
+        ],
+        gw.state.cache_config.prefix_depth,
+    );
+    assert_eq!(
+        gw.state.affinity_map.get(&ModelId::new(TEST_MODEL), merged),
+        Some(BackendId::new("mock-0"))
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Test: routing decisions counted in /metrics
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_routing_metrics_count_affinity_hits_and_misses() {
+    use mb_core::core::RoutingStrategy;
+
+    let mock_a = MockBackendServer::start(&sample_openai_response_with_id("resp-A")).await;
+    let mock_b = MockBackendServer::start(&sample_openai_response_with_id("resp-B")).await;
+    let gw = TestGateway::start(
+        &[
+            (mock_a.url(), vec![TEST_MODEL.to_owned()]),
+            (mock_b.url(), vec![TEST_MODEL.to_owned()]),
+        ],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            routing_strategy: RoutingStrategyConfig::RoundRobin,
+            cache_aware: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let send = |text: &'static str, disable_affinity: bool| {
+        let mut req = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .json(&serde_json::json!({
+                "model": TEST_MODEL,
+                "messages": [{"role": "user", "content": text}]
+            }));
+        if disable_affinity {
+            req = req.header("X-Disable-Cache-Routing", "true");
+        }
+        req.send()
+    };
+
+    // First sight of each prefix misses; repeats hit the recorded backend.
+    for (text, disable_affinity) in [
+        ("first", false),
+        ("first", false),
+        ("second", false),
+        ("first", false),
+        ("second", false),
+        ("first", true),
+    ] {
+        let resp = send(text, disable_affinity).await.expect("request");
+        assert_eq!(resp.status(), 200);
+    }
+
+    let metrics = &gw.state.routing_metrics;
+    assert_eq!(metrics.affinity_hits(), 3);
+    assert_eq!(metrics.affinity_misses(), 2);
+    assert_eq!(metrics.overload_fallbacks(), 0);
+    assert_eq!(metrics.selections(RoutingStrategy::RoundRobin), 6);
+    assert_eq!(metrics.selections(RoutingStrategy::LeastLoaded), 0);
+
+    let text = reqwest::get(format!("{}/metrics", gw.url()))
+        .await
+        .expect("metrics")
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains("mb_affinity_hits_total 3\n"), "{text}");
+    assert!(text.contains("mb_affinity_misses_total 2\n"), "{text}");
+    assert!(text.contains("mb_overload_fallbacks_total 0\n"), "{text}");
+    assert!(
+        text.contains("mb_routing_selections_total{strategy=\"round-robin\"} 6\n"),
+        "{text}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Test: routing.case_insensitive_models folds model name casing
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_case_insensitive_models_opt_in() {
+    let upper = TEST_MODEL.to_ascii_uppercase();
+    for (enabled, expected_status) in [(true, 200), (false, 403)] {
+        let mock = MockBackendServer::start(&sample_openai_response()).await;
+        let gw = TestGateway::start(
+            &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+            &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+            TestGatewayOptions {
+                case_insensitive_models: enabled,
+                ..TestGatewayOptions::default()
+            },
+        )
+        .await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .json(&serde_json::json!({
+                "model": upper,
+                "messages": [{"role": "user", "content": "Hello"}]
+            }))
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), expected_status, "enabled: {enabled}");
+
+        if enabled {
+            let sent = mock.last_request_body().expect("backend was called");
+            assert_eq!(sent["model"], TEST_MODEL);
+        } else {
+            assert_eq!(mock.completion_requests(), 0);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Streaming tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_streamed_chunks_share_id_and_requested_model() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    let events: Vec<serde_json::Value> = body_text
+        .lines()
+        .filter_map(|l| l.strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str(data).expect("chunk is JSON"))
+        .collect();
+    assert!(events.len() >= 2, "expected several chunks, got {events:?}");
+
+    let id = events[0]["id"].as_str().expect("chunk id");
+    assert!(id.starts_with("chatcmpl-"), "{id}");
+    let created = events[0]["created"].as_u64().expect("chunk created");
+    assert!(created > 0);
+    for event in &events {
+        assert_eq!(event["id"], id);
+        assert_eq!(event["created"], created);
+        assert_eq!(event["model"], TEST_MODEL);
+    }
+}
+
+/// Streams `chunks` through the gateway and returns the `delta` of every
+/// downstream chunk, in order.
+async fn streamed_deltas(chunks: &[String]) -> Vec<serde_json::Value> {
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    body_text
+        .lines()
+        .filter_map(|l| l.strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| *data != "[DONE]")
+        .map(|data| {
+            let chunk: serde_json::Value = serde_json::from_str(data).expect("chunk is JSON");
+            chunk["choices"][0]["delta"].clone()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_role_delta_synthesized_when_backend_omits_it() {
+    // Drop the leading role chunk so the backend opens with content.
+    let chunks: Vec<String> = sample_sse_chunks().into_iter().skip(1).collect();
+    let deltas = streamed_deltas(&chunks).await;
+
+    assert_eq!(deltas[0]["role"], "assistant", "got {deltas:?}");
+    assert_eq!(deltas[1]["content"], "Hello", "got {deltas:?}");
+    let roles = deltas.iter().filter(|d| d.get("role").is_some()).count();
+    assert_eq!(roles, 1, "got {deltas:?}");
+}
+
+#[tokio::test]
+async fn test_backend_role_delta_not_duplicated() {
+    let deltas = streamed_deltas(&sample_sse_chunks()).await;
+
+    assert_eq!(deltas[0]["role"], "assistant", "got {deltas:?}");
+    let roles = deltas.iter().filter(|d| d.get("role").is_some()).count();
+    assert_eq!(roles, 1, "got {deltas:?}");
+}
+
+#[tokio::test]
+async fn test_heartbeat_during_mid_stream_stall() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    // The first token arrives at once, then the backend stalls between chunks.
+    let mock = MockBackendServer::start_sse_paced(&chunk_refs, 1_500).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            sse_keepalive: std::time::Duration::from_secs(1),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    let first_data = body_text.find("data:").expect("should contain data lines");
+    let last_data = body_text.rfind("data:").expect("should contain data lines");
+    let comments_between_data = body_text[first_data..last_data]
+        .lines()
+        .filter(|line| line.starts_with(':'))
+        .count();
+    assert!(
+        comments_between_data >= 1,
+        "expected keep-alive comments between chunks, got: {body_text:?}"
+    );
+    assert!(body_text.contains("[DONE]"), "stream should still complete");
+}
+
+#[tokio::test]
+async fn test_mid_stream_backend_error_sends_error_frame() {
+    let chunks = sample_sse_chunks();
+    let mock = MockBackendServer::start_sse_then_error(&[chunks[0].as_str()]).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    let data: Vec<&str> = body_text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    assert!(
+        data.len() >= 3,
+        "expected content, error and [DONE]: {data:?}"
+    );
+    assert!(data[0].contains("chat.completion.chunk"), "{data:?}");
+    assert_eq!(data[data.len() - 1], "[DONE]");
+
+    let error: serde_json::Value =
+        serde_json::from_str(data[data.len() - 2]).expect("error frame is JSON");
+    assert_eq!(error["error"]["type"], "backend_error");
+    assert_eq!(error["error"]["code"], 502);
+}
+
+#[tokio::test]
+async fn test_finish_synthesized_when_ollama_stream_omits_done() {
+    let lines = [
+        serde_json::json!({"message": {"role": "assistant", "content": "Hello"}, "done": false})
+            .to_string(),
+        serde_json::json!({"message": {"role": "assistant", "content": " world"}, "done": false})
+            .to_string(),
+    ];
+    let line_refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+    // No `done: true` line; the mock's trailing `[DONE]` means nothing to
+    // the Ollama adapter and is skipped.
+    let mock = MockBackendServer::start_sse(&line_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ollama_backends: vec!["mock-0".to_owned()],
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    let data: Vec<&str> = body_text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    assert_eq!(data.last(), Some(&"[DONE]"), "{data:?}");
+
+    let chunks: Vec<serde_json::Value> = data[..data.len() - 1]
+        .iter()
+        .map(|d| serde_json::from_str(d).expect("chunk is JSON"))
+        .collect();
+    let content: String = chunks
+        .iter()
+        .filter_map(|c| c["choices"][0]["delta"]["content"].as_str())
+        .collect();
+    assert_eq!(content, "Hello world");
+    let finishes: Vec<&serde_json::Value> = chunks
+        .iter()
+        .map(|c| &c["choices"][0]["finish_reason"])
+        .filter(|f| !f.is_null())
+        .collect();
+    assert_eq!(finishes, [&serde_json::json!("stop")]);
+    assert_eq!(
+        chunks.last().unwrap()["choices"][0]["finish_reason"],
+        "stop"
+    );
+}
+
+#[tokio::test]
+async fn test_non_streaming_backend_replays_whole_response() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            non_streaming_backends: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(
+        sent["stream"], false,
+        "backend must get a non-streaming call"
+    );
+
+    let body_text = resp.text().await.expect("read body");
+    let chunks: Vec<serde_json::Value> = body_text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str(data).expect("chunk is JSON"))
+        .collect();
+    let text: String = chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect();
+    let expected: serde_json::Value =
+        serde_json::from_str(&sample_openai_response()).expect("sample is JSON");
+    assert_eq!(text, expected["choices"][0]["message"]["content"]);
+    assert_eq!(
+        chunks.last().expect("at least one chunk")["choices"][0]["finish_reason"],
+        "stop"
+    );
+    assert!(body_text.contains("data: [DONE]"));
+}
+
+#[tokio::test]
+async fn test_non_streaming_backend_charges_completion_tokens() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            non_streaming_backends: true,
+            rate_limit_tpm: Some(1_000),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let mut remaining = Vec::new();
+    for _ in 0..2 {
+        let resp = client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_stream_request_body())
+            .send()
+            .await
+            .expect("request should succeed");
+        assert_eq!(resp.status(), 200);
+        let value = resp.headers()["x-ratelimit-remaining-tokens"]
+            .to_str()
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        remaining.push(value);
+        resp.text().await.expect("read body");
+    }
+    // Between the two checks the budget lost the second prompt estimate
+    // plus the first reply's 8 completion tokens.
+    let prompt = 1_000 - remaining[0];
+    assert_eq!(remaining[0] - remaining[1], prompt + 8);
+}
+
+#[tokio::test]
+async fn test_non_streaming_backend_records_quota_usage() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            non_streaming_backends: true,
+            // The replayed response reports 18 total tokens.
+            monthly_token_limit: Some(10),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let send = || {
+        client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .body(sample_stream_request_body())
+            .send()
+    };
+    let resp = send().await.expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    resp.text().await.expect("read body");
+
+    let resp = send().await.expect("request should succeed");
+    assert_eq!(resp.status(), 402);
+}
+
+#[tokio::test]
+async fn test_streaming_uses_streaming_inference_path() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            split_stream_path_backends: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    let body_text = resp.text().await.expect("read body");
+    assert!(body_text.contains("[DONE]"));
+    assert_eq!(mock.completion_requests(), 1);
+
+    // The non-streaming path is not served by the mock.
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_ne!(resp.status(), 200);
+    assert_eq!(mock.completion_requests(), 1);
+}
+
+// ---------------------------------------------------------------------------
+// Backend connect / read timeouts
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_connect_timeout_fails_fast() {
+    let backend = UnacceptingBackend::start().await;
+    let gw = TestGateway::start(
+        &[(backend.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            connect_timeout: std::time::Duration::from_millis(200),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let started = std::time::Instant::now();
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+
+    assert_eq!(resp.status(), 502);
+    assert!(
+        started.elapsed() < std::time::Duration::from_secs(3),
+        "connect should give up after the connect timeout, took {:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn test_slow_stream_outlives_read_timeout() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    // Every gap between chunks exceeds the non-streaming read timeout.
+    let mock = MockBackendServer::start_sse_paced(&chunk_refs, 500).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            read_timeout: std::time::Duration::from_millis(200),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body_text = resp.text().await.expect("read body");
+    assert!(body_text.contains("[DONE]"), "stream should complete");
+}
+
+#[tokio::test]
+async fn test_read_timeout_applies_to_non_streaming() {
+    let mock = MockBackendServer::start_with_options(&sample_openai_response(), 200, 1_000).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            read_timeout: std::time::Duration::from_millis(200),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 502);
+}
+
+#[tokio::test]
+async fn test_collapse_stream_header_buffers_completion() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .header("X-Collapse-Stream", "true")
+        .body(sample_stream_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+
+    // The backend was still asked to stream.
+    let sent = mock.last_request_body().expect("backend was called");
+    assert_eq!(sent["stream"], true);
+
+    let body: serde_json::Value = resp.json().await.expect("a single JSON body");
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["model"], TEST_MODEL);
+    let choices = body["choices"].as_array().expect("choices");
+    assert_eq!(choices.len(), 1);
+    assert_eq!(choices[0]["message"]["role"], "assistant");
+    assert_eq!(choices[0]["message"]["content"], "Hello world");
+    assert_eq!(choices[0]["finish_reason"], "stop");
+
+    // The stream reports no usage, so it is estimated like a prompt.
+    let usage = &body["usage"];
+    assert_eq!(usage["completion_tokens"], 2);
+    let prompt_tokens = usage["prompt_tokens"].as_u64().expect("prompt_tokens");
+    assert!(prompt_tokens > 0);
+    assert_eq!(usage["total_tokens"], prompt_tokens + 2);
+}
+
+#[tokio::test]
+async fn test_collapse_stream_joins_logprobs() {
+    let chunk = |delta: serde_json::Value, logprobs: serde_json::Value| {
+        serde_json::json!({
+            "id": "chatcmpl-stream",
+            "object": "chat.completion.chunk",
+            "created": 1700000000,
+            "model": TEST_MODEL,
+            "choices": [{"index": 0, "delta": delta, "logprobs": logprobs, "finish_reason": null}]
+        })
+        .to_string()
+    };
+    let chunks = [
+        chunk(
+            serde_json::json!({"role": "assistant"}),
+            serde_json::Value::Null,
+        ),
+        chunk(
+            serde_json::json!({"content": "Hello"}),
+            serde_json::json!({"content": [{"token": "Hello", "logprob": -0.1}]}),
+        ),
+        chunk(
+            serde_json::json!({"content": " world"}),
+            serde_json::json!({"content": [{"token": " world", "logprob": -0.2}]}),
+        ),
+    ];
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("X-Collapse-Stream", "true")
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "stream": true,
+            "logprobs": true
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.expect("a single JSON body");
+    assert_eq!(body["choices"][0]["message"]["content"], "Hello world");
+    assert_eq!(
+        body["choices"][0]["logprobs"],
+        serde_json::json!({"content": [
+            {"token": "Hello", "logprob": -0.1},
+            {"token": " world", "logprob": -0.2}
+        ]})
+    );
+}
+
+#[tokio::test]
+async fn test_collapse_stream_records_quota_usage() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            monthly_token_limit: Some(1),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    let send = || {
+        client
+            .post(format!("{}/v1/chat/completions", gw.url()))
+            .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+            .header("Content-Type", "application/json")
+            .header("X-Collapse-Stream", "true")
+            .body(sample_stream_request_body())
+            .send()
+    };
+    assert_eq!(send().await.expect("request should succeed").status(), 200);
+    assert_eq!(send().await.expect("request should succeed").status(), 402);
+}
+
+#[tokio::test]
+async fn test_collapse_stream_rejects_tools() {
+    let chunks = sample_sse_chunks();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("X-Collapse-Stream", "true")
+        .json(&serde_json::json!({
+            "model": TEST_MODEL,
+            "messages": [{"role": "user", "content": "What's the weather?"}],
+            "stream": true,
+            "tools": [{
+                "type": "function",
+                "function": {"name": "get_weather", "parameters": {"type": "object"}}
+            }]
+        }))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 400);
+    assert!(mock.last_request_body().is_none());
+}
+
+#[tokio::test]
+async fn test_collapse_stream_caps_buffered_size() {
+    // Twenty 64 KiB deltas: each line fits the SSE parser, the total does not.
+    let text = "x".repeat(64 * 1024);
+    let chunks: Vec<String> = (0..20)
+        .map(|_| {
+            serde_json::json!({
+                "id": "chatcmpl-stream",
+                "object": "chat.completion.chunk",
+                "created": 1700000000,
+                "model": TEST_MODEL,
+                "choices": [{"index": 0, "delta": {"content": text}, "finish_reason": null}]
+            })
+            .to_string()
+        })
+        .collect();
+    let chunk_refs: Vec<&str> = chunks.iter().map(|s| s.as_str()).collect();
+    let mock = MockBackendServer::start_sse(&chunk_refs).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            enable_stream_dispatch: true,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .header("X-Collapse-Stream", "true")
+        .body(sample_stream_request_body())
+        .send()
         .await
         .expect("request should succeed");
+    assert_eq!(resp.status(), 502);
+}
 
-    assert_eq!(resp.status(), 503);
+// ---------------------------------------------------------------------------
+// Dry-run tests
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn test_dry_run_returns_decision_without_consuming_rate_limit() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            rate_limit_rpm: 1,
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let client = reqwest::Client::new();
 
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .header("X-Dry-Run", "true")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
     let body: serde_json::Value = resp.json().await.expect("valid JSON");
-    assert_eq!(body["error"]["type"], "service_unavailable");
+    assert_eq!(body["dry_run"], true);
+    assert_eq!(body["model"], TEST_MODEL);
+    assert_eq!(body["backend"], "mock-0");
+    assert_eq!(body["strategy"], "least-loaded");
+    assert_eq!(body["affinity"], "miss");
+    assert_eq!(body["saturated"], false);
+    assert!(body["estimated_input_tokens"].as_u64().unwrap() > 0);
+
+    let resp = client
+        .post(format!("{}/v1/chat/completions?dry_run=1", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        mock.completion_requests(),
+        0,
+        "dry run must not reach the backend"
+    );
+
+    // With RPM = 1, the real request only succeeds if neither dry run was counted.
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(mock.completion_requests(), 1);
+
+    // A dry run still reports that the next real request would be limited.
+    let resp = client
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .header("X-Dry-Run", "true")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 429);
+}
+
+/// Reports a fixed count so the test can tell which counter produced it.
+struct FixedCounter(u64);
+
+impl TokenCounter for FixedCounter {
+    fn count_text(&self, _text: &str) -> u64 {
+        self.0
+    }
+}
+
+#[tokio::test]
+async fn test_estimated_tokens_use_counter_for_model_family() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            token_counters: TokenCounterRegistry::new()
+                .with_family("llama3", Arc::new(FixedCounter(1_000)))
+                .with_family("gpt-", Arc::new(FixedCounter(7))),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions?dry_run=1", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    // TEST_MODEL is a llama3 model, so the heuristic and "gpt-" counters are skipped.
+    assert_eq!(body["estimated_input_tokens"], 1_000);
+}
+
+// ---------------------------------------------------------------------------
+// Quota reset tests
+// ---------------------------------------------------------------------------
+
+const ADMIN_KEY: &str = "mb-sk-admin0000000000000000000000";
+
+async fn post_completion_status(gw: &TestGateway) -> u16 {
+    reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {TEST_API_KEY}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed")
+        .status()
+        .as_u16()
+}
+
+async fn post_reset(gw: &TestGateway, client_id: &str, key: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!(
+            "{}/admin/clients/{client_id}/quota/reset",
+            gw.url()
+        ))
+        .header("Authorization", format!("Bearer {key}"))
+        .send()
+        .await
+        .expect("request should succeed")
+}
+
+#[tokio::test]
+async fn test_quota_reset_lets_over_quota_client_pass() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            // The mock response reports 18 total tokens, so one request
+            // exhausts a 10-token budget.
+            monthly_token_limit: Some(10),
+            admin_key: Some(ADMIN_KEY.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    assert_eq!(post_completion_status(&gw).await, 200);
+    assert_eq!(post_completion_status(&gw).await, 402);
+
+    let resp = post_reset(&gw, TEST_CLIENT_ID, ADMIN_KEY).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["client_id"], TEST_CLIENT_ID);
+    assert_eq!(body["tokens_cleared"], 18);
+
+    assert_eq!(post_completion_status(&gw).await, 200);
+}
+
+#[tokio::test]
+async fn test_quota_reset_requires_admin_key() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            admin_key: Some(ADMIN_KEY.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+
+    // A client key is not an admin key.
+    let resp = post_reset(&gw, TEST_CLIENT_ID, TEST_API_KEY).await;
+    assert_eq!(resp.status(), 401);
+
+    let resp = post_reset(&gw, "no-such-client", ADMIN_KEY).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_quota_reset_disabled_without_admin_key() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start_simple(&mock.url()).await;
+
+    let resp = post_reset(&gw, TEST_CLIENT_ID, ADMIN_KEY).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[tokio::test]
+async fn test_cache_stats_requires_admin_key() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[(TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()])],
+        TestGatewayOptions {
+            cache_aware: true,
+            admin_key: Some(ADMIN_KEY.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await;
+    assert_eq!(post_completion_status(&gw).await, 200);
+    assert_eq!(post_completion_status(&gw).await, 200);
+
+    let get_stats = |key: &'static str| {
+        reqwest::Client::new()
+            .get(format!("{}/cache/stats", gw.url()))
+            .header("Authorization", format!("Bearer {key}"))
+            .send()
+    };
+    let resp = get_stats(TEST_API_KEY)
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 401);
+
+    let resp = get_stats(ADMIN_KEY).await.expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["models"][TEST_MODEL]["samples"], 1);
+}
+
+// ---------------------------------------------------------------------------
+// Key revocation tests
+// ---------------------------------------------------------------------------
+
+const OTHER_CLIENT_ID: &str = "other-client";
+const OTHER_API_KEY: &str = "mb-sk-other0000000000000000000000";
+
+async fn post_completion_as(gw: &TestGateway, key: &str) -> u16 {
+    reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", gw.url()))
+        .header("Authorization", format!("Bearer {key}"))
+        .header("Content-Type", "application/json")
+        .body(sample_request_body())
+        .send()
+        .await
+        .expect("request should succeed")
+        .status()
+        .as_u16()
+}
+
+async fn post_revoke(gw: &TestGateway, client_id: &str, key: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/admin/clients/{client_id}/revoke", gw.url()))
+        .header("Authorization", format!("Bearer {key}"))
+        .send()
+        .await
+        .expect("request should succeed")
+}
+
+async fn start_two_clients(mock: &MockBackendServer) -> TestGateway {
+    TestGateway::start(
+        &[(mock.url(), vec![TEST_MODEL.to_owned()])],
+        &[
+            (TEST_CLIENT_ID, TEST_API_KEY, vec![TEST_MODEL.to_owned()]),
+            (OTHER_CLIENT_ID, OTHER_API_KEY, vec![TEST_MODEL.to_owned()]),
+        ],
+        TestGatewayOptions {
+            admin_key: Some(ADMIN_KEY.to_owned()),
+            ..TestGatewayOptions::default()
+        },
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_revoked_key_rejected_while_others_work() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = start_two_clients(&mock).await;
+
+    assert_eq!(post_completion_as(&gw, TEST_API_KEY).await, 200);
+
+    let resp = post_revoke(&gw, TEST_CLIENT_ID, ADMIN_KEY).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.expect("valid JSON");
+    assert_eq!(body["client_id"], TEST_CLIENT_ID);
+    assert_eq!(body["revoked"], true);
+
+    assert_eq!(post_completion_as(&gw, TEST_API_KEY).await, 401);
+    assert_eq!(post_completion_as(&gw, OTHER_API_KEY).await, 200);
+}
+
+#[tokio::test]
+async fn test_revoke_requires_admin_key_and_known_client() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = start_two_clients(&mock).await;
+
+    // A client cannot revoke anyone, itself included.
+    let resp = post_revoke(&gw, OTHER_CLIENT_ID, TEST_API_KEY).await;
+    assert_eq!(resp.status(), 401);
+    assert_eq!(post_completion_as(&gw, OTHER_API_KEY).await, 200);
+
+    let resp = post_revoke(&gw, "no-such-client", ADMIN_KEY).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_list_clients_reports_revocation() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let gw = start_two_clients(&mock).await;
+    post_revoke(&gw, TEST_CLIENT_ID, ADMIN_KEY).await;
+
+    let resp = reqwest::Client::new()
+        .get(format!("{}/admin/clients", gw.url()))
+        .header("Authorization", format!("Bearer {ADMIN_KEY}"))
+        .send()
+        .await
+        .expect("request should succeed");
+    assert_eq!(resp.status(), 200);
+    let text = resp.text().await.expect("body");
+    assert!(!text.contains(TEST_API_KEY));
+
+    let body: serde_json::Value = serde_json::from_str(&text).expect("valid JSON");
+    let clients = body["clients"].as_array().expect("clients array");
+    assert_eq!(clients.len(), 2);
+    let revoked = |id: &str| {
+        clients
+            .iter()
+            .find(|c| c["id"] == id)
+            .map(|c| c["revoked"].clone())
+    };
+    assert_eq!(revoked(TEST_CLIENT_ID), Some(serde_json::json!(true)));
+    assert_eq!(revoked(OTHER_CLIENT_ID), Some(serde_json::json!(false)));
+}
+
+// ---------------------------------------------------------------------------
+// `mb validate --check-backends` tests
+// ---------------------------------------------------------------------------
+
+/// Writes a config with one backend per URL and returns its path.
+fn write_config(backend_urls: &[String]) -> PathBuf {
+    let mut toml = format!(
+        "[[clients]]\n\
+         id = \"{TEST_CLIENT_ID}\"\n\
+         api_key = \"{TEST_API_KEY}\"\n\
+         allowed_models = \"*\"\n\
+         rate_limit_rpm = 60\n"
+    );
+    for (i, url) in backend_urls.iter().enumerate() {
+        toml.push_str(&format!(
+            "\n[[backends]]\n\
+             id = \"backend-{i}\"\n\
+             base_url = \"{url}\"\n\
+             spec = \"openai-chat\"\n\
+             models = [\"{TEST_MODEL}\"]\n"
+        ));
+    }
+    let path = std::env::temp_dir().join(format!("mb-validate-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, toml).expect("write temp config");
+    path
+}
+
+/// An address nothing listens on: bind an ephemeral port, then release it.
+fn unreachable_url() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind ephemeral port");
+    let addr = listener.local_addr().expect("local addr");
+    drop(listener);
+    format!("http://{addr}")
+}
+
+async fn run_validate(config: &PathBuf, check_backends: bool) -> std::process::Output {
+    let mut cmd = tokio::process::Command::new(env!("CARGO_BIN_EXE_mb"));
+    cmd.arg("-c").arg(config).arg("validate");
+    if check_backends {
+        cmd.arg("--check-backends");
+    }
+    cmd.output().await.expect("run mb validate")
+}
+
+#[tokio::test]
+async fn test_check_backends_passes_when_all_reachable() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let config = write_config(&[mock.url()]);
+
+    let output = run_validate(&config, true).await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("ok      backend-0"));
+    std::fs::remove_file(&config).ok();
+}
+
+#[tokio::test]
+async fn test_check_backends_fails_on_unreachable_backend() {
+    let mock = MockBackendServer::start(&sample_openai_response()).await;
+    let config = write_config(&[mock.url(), unreachable_url()]);
+
+    let output = run_validate(&config, true).await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "stdout: {stdout}");
+    assert!(stdout.contains("ok      backend-0"));
+    assert!(stdout.contains("FAILED  backend-1"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 of 2 backends unreachable"));
+
+    // Without the flag only the structure is checked.
+    let output = run_validate(&config, false).await;
+    assert!(output.status.success());
+    std::fs::remove_file(&config).ok();
 }